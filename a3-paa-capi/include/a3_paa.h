@@ -0,0 +1,51 @@
+/* C API of a3-paa: decode Bohemia Interactive PAA textures into caller
+ * buffers.  Every function returns PAA_OK (0) on success, or a negative
+ * PAA_ERROR_* code described by paa_error_message(). */
+
+#ifndef A3_PAA_H
+#define A3_PAA_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define PAA_OK 0
+#define PAA_ERROR_NULL_POINTER -1
+#define PAA_ERROR_INVALID_PATH -2
+#define PAA_ERROR_IO -3
+#define PAA_ERROR_PARSE -4
+#define PAA_ERROR_MIPMAP_INDEX -5
+#define PAA_ERROR_DECODE -6
+#define PAA_ERROR_BUFFER_TOO_SMALL -7
+#define PAA_ERROR_PANIC -8
+
+typedef struct PaaInfo {
+	/* PAA type magic, e.g. 0xFF01 for DXT1 or 0x8888 for ARGB8888 */
+	uint16_t paatype;
+	/* Dimensions of the first (largest) mipmap */
+	uint16_t width;
+	uint16_t height;
+	uint32_t mipmap_count;
+} PaaInfo;
+
+/* Decode mipmap #mip_index (0-based) of the PAA file at path (UTF-8) into
+ * out_buf, as width * height * 4 bytes of RGBA pixels.  out_w and out_h are
+ * set even if out_buf is NULL or smaller than needed, in which case
+ * PAA_ERROR_BUFFER_TOO_SMALL is returned. */
+int paa_decode_file(const char *path, uint32_t mip_index, uint8_t *out_buf, size_t out_len, uint32_t *out_w, uint32_t *out_h);
+
+/* Read the type, dimensions and mipmap count of the PAA file at path
+ * (UTF-8), without decoding it. */
+int paa_read_info(const char *path, PaaInfo *info);
+
+/* Static, NUL-terminated description of a return code; never to be freed. */
+const char *paa_error_message(int code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* A3_PAA_H */
@@ -0,0 +1,242 @@
+//! C API of [`a3_paa`], for decoding PAA files into caller buffers.  See
+//! `include/a3_paa.h` for the C declarations.
+//!
+//! Every function returns [`PAA_OK`] on success, or a negative `PAA_ERROR_*`
+//! code described by [`paa_error_message`].  Panics are caught at the FFI
+//! boundary and reported as [`PAA_ERROR_PANIC`].
+
+#![warn(missing_docs, unreachable_pub, clippy::all)]
+#![allow(deprecated)]
+#![warn(clippy::missing_safety_doc)]
+
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use a3_paa::{PaaDecoder, PaaImage, PaaReader, PaaType};
+
+
+/// Success.
+pub const PAA_OK: c_int = 0;
+/// A pointer argument was NULL.
+pub const PAA_ERROR_NULL_POINTER: c_int = -1;
+/// The path is not valid UTF-8.
+pub const PAA_ERROR_INVALID_PATH: c_int = -2;
+/// The file could not be opened.
+pub const PAA_ERROR_IO: c_int = -3;
+/// The file is not a PAA file, or its header is corrupted.
+pub const PAA_ERROR_PARSE: c_int = -4;
+/// The mipmap index is not below the mipmap count.
+pub const PAA_ERROR_MIPMAP_INDEX: c_int = -5;
+/// The mipmap could not be read or decoded.
+pub const PAA_ERROR_DECODE: c_int = -6;
+/// The output buffer is NULL or smaller than width * height * 4 bytes.
+pub const PAA_ERROR_BUFFER_TOO_SMALL: c_int = -7;
+/// a3-paa panicked; this is a bug.
+pub const PAA_ERROR_PANIC: c_int = -8;
+
+
+/// Header information of a PAA file, filled by [`paa_read_info`]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PaaInfo {
+	/// PAA type magic, e.g. 0xFF01 for [`PaaType::Dxt1`].
+	pub paatype: u16,
+	/// Width of the first (largest) mipmap.
+	pub width: u16,
+	/// Height of the first (largest) mipmap.
+	pub height: u16,
+	/// Number of mipmaps.
+	pub mipmap_count: u32,
+}
+
+
+/// Run `f`, turning a panic into [`PAA_ERROR_PANIC`].
+fn ffi_boundary(f: impl FnOnce() -> Result<(), c_int>) -> c_int {
+	match catch_unwind(AssertUnwindSafe(f)) {
+		Ok(Ok(())) => PAA_OK,
+		Ok(Err(code)) => code,
+		Err(_) => PAA_ERROR_PANIC,
+	}
+}
+
+
+/// # Safety
+/// `path` is NULL or a valid NUL-terminated string.
+unsafe fn open_paa(path: *const c_char) -> Result<PaaReader<BufReader<File>>, c_int> {
+	if path.is_null() {
+		return Err(PAA_ERROR_NULL_POINTER);
+	};
+
+	let path = CStr::from_ptr(path).to_str().map_err(|_| PAA_ERROR_INVALID_PATH)?;
+	let file = File::open(path).map_err(|_| PAA_ERROR_IO)?;
+
+	PaaReader::new(BufReader::new(file)).map_err(|_| PAA_ERROR_PARSE)
+}
+
+
+fn paatype_magic(paatype: PaaType) -> u16 {
+	u16::from_le_bytes(paatype.magic())
+}
+
+
+/// Decode mipmap #`mip_index` (0-based) of the PAA file at `path` into
+/// `out_buf`, as `width * height * 4` bytes of RGBA pixels.  `out_w` and
+/// `out_h` are set even if `out_buf` is NULL or smaller than needed, in which
+/// case [`PAA_ERROR_BUFFER_TOO_SMALL`] is returned.
+///
+/// # Safety
+/// - `path` is NULL or a valid NUL-terminated string.
+/// - `out_buf` is NULL or valid for writes of `out_len` bytes.
+/// - `out_w` and `out_h` are valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn paa_decode_file(
+	path: *const c_char,
+	mip_index: u32,
+	out_buf: *mut u8,
+	out_len: usize,
+	out_w: *mut u32,
+	out_h: *mut u32,
+) -> c_int {
+	ffi_boundary(|| {
+		if out_w.is_null() || out_h.is_null() {
+			return Err(PAA_ERROR_NULL_POINTER);
+		};
+
+		let mut reader = open_paa(path)?;
+		let index = mip_index as usize;
+
+		if index >= reader.mipmap_count() {
+			return Err(PAA_ERROR_MIPMAP_INDEX);
+		};
+
+		let (width, height) = reader.mipmap_dimensions(index).map_err(|_| PAA_ERROR_DECODE)?;
+		*out_w = width.into();
+		*out_h = height.into();
+
+		let size = usize::from(width) * usize::from(height) * 4;
+
+		if out_buf.is_null() || out_len < size {
+			return Err(PAA_ERROR_BUFFER_TOO_SMALL);
+		};
+
+		let mipmap = reader.read_mipmap(index).map_err(|_| PAA_ERROR_DECODE)?;
+		let single = PaaImage { paatype: reader.paatype(), taggs: vec![], palette: None, mipmaps: vec![Ok(mipmap)], trailer: vec![] };
+		let image = PaaDecoder::with_paa(single).decode_first().map_err(|_| PAA_ERROR_DECODE)?;
+
+		if image.as_raw().len() != size {
+			return Err(PAA_ERROR_DECODE);
+		};
+
+		std::slice::from_raw_parts_mut(out_buf, size).copy_from_slice(image.as_raw());
+
+		Ok(())
+	})
+}
+
+
+/// Read the type, dimensions and mipmap count of the PAA file at `path`,
+/// without decoding it.
+///
+/// # Safety
+/// - `path` is NULL or a valid NUL-terminated string.
+/// - `info` is NULL or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn paa_read_info(path: *const c_char, info: *mut PaaInfo) -> c_int {
+	ffi_boundary(|| {
+		if info.is_null() {
+			return Err(PAA_ERROR_NULL_POINTER);
+		};
+
+		let mut reader = open_paa(path)?;
+		let (width, height) = reader.mipmap_dimensions(0).map_err(|_| PAA_ERROR_PARSE)?;
+
+		*info = PaaInfo {
+			paatype: paatype_magic(reader.paatype()),
+			width,
+			height,
+			mipmap_count: reader.mipmap_count().try_into().map_err(|_| PAA_ERROR_PARSE)?,
+		};
+
+		Ok(())
+	})
+}
+
+
+/// Static, NUL-terminated description of a return code of this API; it must
+/// not be freed.
+#[no_mangle]
+pub extern "C" fn paa_error_message(code: c_int) -> *const c_char {
+	let message: &'static [u8] = match code {
+		PAA_OK => b"Success\0",
+		PAA_ERROR_NULL_POINTER => b"A pointer argument was NULL\0",
+		PAA_ERROR_INVALID_PATH => b"Path is not valid UTF-8\0",
+		PAA_ERROR_IO => b"Could not open file\0",
+		PAA_ERROR_PARSE => b"Could not read PAA header\0",
+		PAA_ERROR_MIPMAP_INDEX => b"Mipmap index out of range\0",
+		PAA_ERROR_DECODE => b"Could not read or decode mipmap\0",
+		PAA_ERROR_BUFFER_TOO_SMALL => b"Output buffer is NULL or too small\0",
+		PAA_ERROR_PANIC => b"a3-paa panicked\0",
+		_ => b"Unknown error code\0",
+	};
+
+	message.as_ptr().cast()
+}
+
+
+#[test]
+fn capi() {
+	use std::ffi::CString;
+	use std::ptr::{null, null_mut};
+	use a3_paa::{PaaEncoder, TextureEncodingSettings};
+
+	let dir = std::env::temp_dir().join(format!("a3-paa-capi-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let path = dir.join("gradient.paa");
+
+	let image = image::RgbaImage::from_fn(16, 8, |x, y| image::Rgba([x as u8 * 16, y as u8 * 32, 0x40, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).build();
+	let paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+	std::fs::write(&path, paa.to_bytes().unwrap()).unwrap();
+
+	let c_path = CString::new(path.to_str().unwrap()).unwrap();
+	let (mut width, mut height) = (0u32, 0u32);
+	let mut info = PaaInfo::default();
+
+	unsafe {
+		assert_eq!(paa_read_info(c_path.as_ptr(), &mut info), PAA_OK);
+		assert_eq!(info, PaaInfo { paatype: 0x8888, width: 16, height: 8, mipmap_count: 5 });
+
+		// Query the dimensions first, then decode
+		assert_eq!(paa_decode_file(c_path.as_ptr(), 0, null_mut(), 0, &mut width, &mut height), PAA_ERROR_BUFFER_TOO_SMALL);
+		assert_eq!((width, height), (16, 8));
+
+		let mut buffer = vec![0u8; 16 * 8 * 4];
+		assert_eq!(paa_decode_file(c_path.as_ptr(), 0, buffer.as_mut_ptr(), buffer.len(), &mut width, &mut height), PAA_OK);
+		assert_eq!(buffer, image.into_raw());
+
+		assert_eq!(paa_decode_file(c_path.as_ptr(), 1, buffer.as_mut_ptr(), buffer.len(), &mut width, &mut height), PAA_OK);
+		assert_eq!((width, height), (8, 4));
+
+		assert_eq!(paa_decode_file(c_path.as_ptr(), 5, buffer.as_mut_ptr(), buffer.len(), &mut width, &mut height), PAA_ERROR_MIPMAP_INDEX);
+		assert_eq!(paa_read_info(null(), &mut info), PAA_ERROR_NULL_POINTER);
+		assert_eq!(paa_read_info(c_path.as_ptr(), null_mut()), PAA_ERROR_NULL_POINTER);
+
+		let missing = CString::new(dir.join("missing.paa").to_str().unwrap()).unwrap();
+		assert_eq!(paa_read_info(missing.as_ptr(), &mut info), PAA_ERROR_IO);
+
+		std::fs::write(dir.join("garbage.paa"), b"not a PAA file").unwrap();
+		let garbage = CString::new(dir.join("garbage.paa").to_str().unwrap()).unwrap();
+		assert_eq!(paa_read_info(garbage.as_ptr(), &mut info), PAA_ERROR_PARSE);
+
+		assert_eq!(CStr::from_ptr(paa_error_message(PAA_ERROR_MIPMAP_INDEX)).to_str(), Ok("Mipmap index out of range"));
+		assert_eq!(CStr::from_ptr(paa_error_message(-100)).to_str(), Ok("Unknown error code"));
+	};
+
+	assert_eq!(ffi_boundary(|| panic!("Test panic")), PAA_ERROR_PANIC);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,109 @@
+//! Benchmarks of the hot paths of reading, decoding and encoding PAAs.  Run
+//! with `cargo bench -p a3-paa --features bench`.  Textures are generated
+//! from [`ValueNoise`], so no fixtures are needed.
+
+use std::io::Cursor;
+
+use a3_paa::*;
+use a3_paa::imageops::noise::{noise_u8, ValueNoise};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use image::RgbaImage;
+
+
+/// `size`x`size` texture of smooth color noise with some grain, and alpha
+/// varying independently.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, clippy::cast_sign_loss)]
+fn texture(size: u32) -> RgbaImage {
+	let noise = ValueNoise::new(42, (16, 16));
+	let alpha = ValueNoise::new(7, (4, 4));
+	let cell = size as f32 / 16.0;
+
+	RgbaImage::from_fn(size, size, |x, y| {
+		let (fx, fy) = (x as f32 / cell, y as f32 / cell);
+		let value = (noise.sample(fx, fy) * 224.0) as u8;
+		let grain = noise_u8(x, y, 0, 42) / 8;
+		image::Rgba([value + grain, value / 2 + grain, 0xFF - value, (alpha.sample(fx / 4.0, fy / 4.0) * 255.0) as u8])
+	})
+}
+
+
+fn encode(image: RgbaImage, paatype: PaaType, quality: DxtQuality) -> PaaImage {
+	let settings = TextureEncodingSettings::builder().format(paatype).dxt_quality(quality).build();
+	PaaEncoder::with_image_and_settings(image, settings).encode().unwrap()
+}
+
+
+/// `paa` with only its first mipmap.
+fn first_mipmap(paa: &PaaImage) -> PaaImage {
+	PaaImage { mipmaps: paa.mipmaps[..1].to_vec(), ..paa.clone() }
+}
+
+
+fn bench_read_from(c: &mut Criterion) {
+	// Mipmaps of 256x256 and above are LZO-compressed
+	let bytes = encode(texture(1024), PaaType::Dxt5, DxtQuality::Fast).to_bytes().unwrap();
+
+	let mut group = c.benchmark_group("PaaImage::read_from");
+	let _ = group.throughput(Throughput::Bytes(bytes.len() as u64));
+	let _ = group.bench_function("Dxt5 LZO 1024", |b| b.iter(|| PaaImage::read_from(&mut Cursor::new(&bytes)).unwrap()));
+	group.finish();
+}
+
+
+fn bench_decode(c: &mut Criterion) {
+	let mut group = c.benchmark_group("PaaMipmap::decode");
+	let _ = group.throughput(Throughput::Elements(512 * 512));
+
+	for paatype in [PaaType::Dxt5, PaaType::Argb4444] {
+		let decoder = PaaDecoder::with_paa(first_mipmap(&encode(texture(512), paatype, DxtQuality::Fast)));
+		let _ = group.bench_function(BenchmarkId::new(format!("{paatype:?}"), 512), |b| b.iter(|| decoder.decode_first().unwrap()));
+	};
+
+	group.finish();
+}
+
+
+fn bench_encode(c: &mut Criterion) {
+	let mut group = c.benchmark_group("PaaEncoder::encode");
+	let _ = group.sample_size(10);
+
+	for size in [512, 2048] {
+		let image = texture(size);
+		let _ = group.throughput(Throughput::Elements(u64::from(size) * u64::from(size)));
+
+		for quality in [DxtQuality::Fast, DxtQuality::Best] {
+			let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).dxt_quality(quality).build();
+			let encoder = PaaEncoder::with_image_and_settings(image.clone(), settings);
+			let _ = group.bench_function(BenchmarkId::new(format!("Dxt5 {quality:?}"), size), |b| b.iter(|| encoder.encode().unwrap()));
+		};
+	};
+
+	group.finish();
+}
+
+
+fn bench_swizzle(c: &mut Criterion) {
+	let image = texture(1024);
+	let swizzle = "1-R, 1-A, G, B".parse::<ArgbSwizzle>().unwrap();
+
+	let mut group = c.benchmark_group("ArgbSwizzle::apply_to_image");
+	let _ = group.throughput(Throughput::Elements(1024 * 1024));
+	let _ = group.bench_function("1024", |b| b.iter_batched_ref(|| image.clone(), |i| swizzle.apply_to_image(i), BatchSize::LargeInput));
+	group.finish();
+}
+
+
+fn bench_to_bytes(c: &mut Criterion) {
+	let mut group = c.benchmark_group("PaaImage::to_bytes");
+
+	for paatype in [PaaType::Dxt5, PaaType::Argb4444] {
+		let paa = encode(texture(1024), paatype, DxtQuality::Fast);
+		let _ = group.bench_function(BenchmarkId::new(format!("{paatype:?}"), 1024), |b| b.iter(|| paa.to_bytes().unwrap()));
+	};
+
+	group.finish();
+}
+
+
+criterion_group!(benches, bench_read_from, bench_decode, bench_encode, bench_swizzle, bench_to_bytes);
+criterion_main!(benches);
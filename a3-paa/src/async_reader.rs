@@ -0,0 +1,394 @@
+use std::io::SeekFrom;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::{CompressionPolicy, PaaError, PaaImage, PaaLimits, PaaMipmap, PaaMipmapCompression, PaaPalette, PaaResult, PaaType, Tagg};
+use crate::PaaError::*;
+use crate::macros;
+use crate::sansio::{self, MipmapHead, ReadBudget, WalkFrom};
+
+
+/// Lazy reader of PAA files over [`tokio::io`], as [`PaaReader`][`crate::PaaReader`]
+///
+/// The header is parsed up front, but mipmaps are only read on demand.
+/// Parsing is shared with the blocking readers, so both read the same files
+/// the same way.
+#[cfg_attr(doc, doc(cfg(feature = "tokio")))]
+#[derive(Debug)]
+pub struct AsyncPaaReader<R> {
+	input: R,
+	paatype: PaaType,
+	taggs: Vec<Tagg>,
+	palette: Option<PaaPalette>,
+	offsets: Vec<u32>,
+	policy: CompressionPolicy,
+}
+
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncPaaReader<R> {
+	/// Read the header of a PAA file from `input`, and locate its mipmaps as
+	/// [`PaaReader::new`][`crate::PaaReader::new`].
+	///
+	/// # Errors
+	/// - As [`PaaReader::new`][`crate::PaaReader::new`].
+	///
+	/// # Panics
+	/// - As [`PaaReader::new`][`crate::PaaReader::new`].
+	pub async fn new(mut input: R) -> PaaResult<Self> {
+		let (paatype, taggs, palette) = read_header(&mut input, PaaLimits::default()).await?;
+		let start = position(&mut input).await?;
+		let (mut offsets, walk) = sansio::lazy_offsets(&taggs);
+
+		match walk {
+			WalkFrom::Nowhere => (),
+			WalkFrom::HeaderEnd => offsets.extend(walk_mipmaps(&mut input, paatype, start).await?),
+			WalkFrom::Offset(offset) => offsets.extend(walk_mipmaps(&mut input, paatype, offset.into()).await?),
+		};
+
+		Ok(AsyncPaaReader { input, paatype, taggs, palette, offsets, policy: CompressionPolicy::default() })
+	}
+
+
+	/// Format of all mipmaps.
+	pub fn paatype(&self) -> PaaType {
+		self.paatype
+	}
+
+
+	/// Taggs of the PAA header.
+	pub fn taggs(&self) -> &[Tagg] {
+		&self.taggs
+	}
+
+
+	/// Palette of [`PaaType::IndexPalette`] mipmaps, if any.
+	pub fn palette(&self) -> Option<&PaaPalette> {
+		self.palette.as_ref()
+	}
+
+
+	/// Number of mipmaps.
+	pub fn mipmap_count(&self) -> usize {
+		self.offsets.len()
+	}
+
+
+	/// Read the (possibly compressed) mipmap #`index` (0-based) without
+	/// decompressing it, e.g. to [`decompress`][`CompressedMipmap::decompress`]
+	/// it in [`tokio::task::spawn_blocking`] while reading on.
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: `index` is not below
+	///   [`mipmap_count`][`Self::mipmap_count`].
+	/// - [`EmptyMipmap`]: Width or height of the mipmap is 0.
+	/// - [`UnexpectedEof`]: The mipmap is cut short by the end of file.
+	/// - [`UnexpectedIoError`]: Unexpected read or seek error.
+	pub async fn fetch_mipmap(&mut self, index: usize) -> PaaResult<CompressedMipmap> {
+		let offset = self.offsets.get(index).ok_or(MipmapIndexOutOfRange)?;
+		let _ = self.input.seek(SeekFrom::Start((*offset).into())).await?;
+		Ok(fetch_mipmap(&mut self.input, self.paatype, self.policy).await?.0)
+	}
+
+
+	/// Read and decompress mipmap #`index` (0-based), as
+	/// [`PaaReader::read_mipmap`][`crate::PaaReader::read_mipmap`].
+	///
+	/// # Errors
+	/// - As [`PaaReader::read_mipmap`][`crate::PaaReader::read_mipmap`].
+	///
+	/// # Panics
+	/// - As [`PaaReader::read_mipmap`][`crate::PaaReader::read_mipmap`].
+	pub async fn read_mipmap(&mut self, index: usize) -> PaaResult<PaaMipmap> {
+		self.fetch_mipmap(index).await?.decompress()
+	}
+
+
+	/// Tell the compression of mipmaps apart as set by `policy`, rather than
+	/// [`CompressionPolicy::default`].
+	pub fn with_policy(self, policy: CompressionPolicy) -> Self {
+		Self { policy, ..self }
+	}
+
+
+	/// Return the underlying reader.
+	pub fn into_inner(self) -> R {
+		self.input
+	}
+}
+
+
+/// Mipmap read by [`AsyncPaaReader::fetch_mipmap`], not yet decompressed
+#[cfg_attr(doc, doc(cfg(feature = "tokio")))]
+#[derive(Debug, Clone)]
+pub struct CompressedMipmap {
+	head: MipmapHead,
+	payload: Vec<u8>,
+}
+
+
+impl CompressedMipmap {
+	/// Width in pixels.
+	pub fn width(&self) -> u16 {
+		self.head.width
+	}
+
+
+	/// Height in pixels.
+	pub fn height(&self) -> u16 {
+		self.head.height
+	}
+
+
+	/// Compression of the data.
+	pub fn compression(&self) -> PaaMipmapCompression {
+		self.head.compression
+	}
+
+
+	/// Decompress the mipmap, as [`PaaMipmap::read_from_with_policy`] would
+	/// have.
+	///
+	/// # Errors
+	/// - [`LzoError`]: Failed to decompress LZO data.
+	/// - [`LzssDecompressError`]: LZSS data did not expand to the length
+	///   computed by [`PaaType::predict_size`].
+	/// - [`RleError`]: Failed to decompress RLE data.
+	/// - [`ArithmeticOverflow`]: LZSS data did not have enough space for the
+	///   checksum.
+	///
+	/// # Panics
+	/// - As [`PaaMipmap::read_from`].
+	pub fn decompress(self) -> PaaResult<PaaMipmap> {
+		PaaMipmap::from_head(self.head, self.payload)
+	}
+}
+
+
+impl PaaImage {
+	/// [`read_from`][`Self::read_from`] over [`tokio::io`].
+	///
+	/// Mipmaps are decompressed as they are read, within the default
+	/// [`PaaLimits`]; use [`AsyncPaaReader`] to decompress them apart.
+	///
+	/// # Errors
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Panics
+	/// - As [`read_from`][`Self::read_from`].
+	#[cfg_attr(doc, doc(cfg(feature = "tokio")))]
+	#[cfg_attr(not(feature = "log"), allow(unused_variables))]
+	pub async fn read_from_async<R: AsyncRead + AsyncSeek + Unpin>(input: &mut R) -> PaaResult<Self> {
+		let policy = CompressionPolicy::default();
+		let limits = PaaLimits::default();
+		let (paatype, taggs, palette) = read_header(input, limits).await?;
+		let header_end = position(input).await?;
+		let offsets = Self::offsets_in(&taggs);
+		let (mut mipmaps, mut end) = read_mipmaps_at(input, paatype, policy, &mut ReadBudget::new(limits), &offsets, header_end).await?;
+
+		if !offsets.is_empty() && mipmaps.iter().all(Result::is_err) {
+			let mut search = sansio::RepairSearch::default();
+
+			for candidate in sansio::repair_candidates(&offsets, header_end)? {
+				let (mipmaps, _) = read_mipmaps_at(input, paatype, policy, &mut ReadBudget::new(limits), &candidate.1, header_end).await?;
+				search.record(candidate, mipmaps.iter().filter(|m| m.is_ok()).count());
+			};
+
+			if let Some((repair, repaired)) = search.best() {
+				macros::log!(warn, "No mipmap could be read at the offsets of OFFSTAGG; read them with {:?} offsets instead", repair);
+				(mipmaps, end) = read_mipmaps_at(input, paatype, policy, &mut ReadBudget::new(limits), &repaired, header_end).await?;
+			};
+		};
+
+		let _ = input.seek(SeekFrom::Start(end)).await?;
+		let mut trailer = Vec::with_capacity(Self::TERMINATOR.len());
+		let _ = (&mut *input).take(Self::MAX_TRAILER_LEN as u64).read_to_end(&mut trailer).await?;
+
+		Ok(PaaImage { paatype, taggs, palette, mipmaps, trailer })
+	}
+}
+
+
+async fn position<R: AsyncSeek + Unpin>(input: &mut R) -> PaaResult<u64> {
+	Ok(input.seek(SeekFrom::Current(0)).await?)
+}
+
+
+/// Read the header within `limits` as [`PaaImage::read_header_from`], leaving
+/// `input` at the first mipmap.
+async fn read_header<R: AsyncRead + AsyncSeek + Unpin>(input: &mut R, limits: PaaLimits) -> PaaResult<(PaaType, Vec<Tagg>, Option<PaaPalette>)> {
+	let (paatype, _) = sansio::drive_async(input, &mut vec![], |buf, _| sansio::parse_magic(buf)).await?;
+	let start = position(input).await?;
+
+	let mut parser = sansio::TaggsParser::new(limits);
+	let (taggs, consumed) = sansio::drive_async(input, &mut vec![], |buf, eof| parser.parse(buf, eof)).await?;
+
+	// Looking for one more tagg read past the last one
+	let _ = input.seek(SeekFrom::Start(start + consumed as u64)).await?;
+
+	let taggs = taggs.into_iter().map(|(tagg, _)| tagg).collect();
+
+	let (palette, _) = sansio::drive_async(input, &mut vec![], |buf, _| sansio::parse_palette(buf, paatype)).await?;
+
+	Ok((paatype, taggs, palette))
+}
+
+
+/// Read the mipmap at the position of `input`, without decompressing it, and
+/// return it with the bytes it takes.
+async fn fetch_mipmap<R: AsyncRead + Unpin>(input: &mut R, paatype: PaaType, policy: CompressionPolicy) -> PaaResult<(CompressedMipmap, usize)> {
+	let mut buf = vec![];
+	let ((head, start), end) = sansio::drive_async(input, &mut buf, |buf, eof| sansio::parse_mipmap(buf, eof, paatype, policy)).await?;
+	buf.truncate(end);
+	let payload = buf.split_off(start);
+
+	Ok((CompressedMipmap { head, payload }, end))
+}
+
+
+/// Read the header of the mipmap at the position of `input`, as
+/// [`PaaReader`][`crate::PaaReader`] does, and return it with the bytes it
+/// takes.
+async fn read_mipmap_head<R: AsyncRead + AsyncSeek + Unpin>(input: &mut R, paatype: PaaType) -> PaaResult<(MipmapHead, usize)> {
+	let start = position(input).await?;
+	let policy = CompressionPolicy::default();
+	let (head, consumed) = sansio::drive_async(input, &mut vec![], |buf, _| sansio::parse_mipmap_head(buf, paatype, policy)).await?;
+
+	if (head.paatype, head.compression) != (PaaType::IndexPalette, PaaMipmapCompression::Lzss) {
+		return Ok((head, consumed));
+	};
+
+	let _ = input.seek(SeekFrom::Start(start)).await?;
+	let ((head, consumed), _) = sansio::drive_async(input, &mut vec![], |buf, eof| sansio::parse_mipmap(buf, eof, paatype, policy)).await?;
+
+	Ok((head, consumed))
+}
+
+
+/// Read the mipmaps as [`PaaImage::read_mipmaps_at`].
+#[allow(clippy::type_complexity)]
+async fn read_mipmaps_at<R: AsyncRead + AsyncSeek + Unpin>(input: &mut R, paatype: PaaType, policy: CompressionPolicy, budget: &mut ReadBudget, offsets: &[u32], header_end: u64) -> PaaResult<(Vec<PaaResult<PaaMipmap>>, u64)> {
+	let mut walk = sansio::MipmapWalk::new(offsets, header_end);
+	let mut mipmaps = Vec::with_capacity(offsets.len().max(8));
+	let mut current = None;
+
+	while let Some(offset) = walk.next_offset() {
+		// Sequential mipmaps follow each other without seeking
+		let seeked = match current {
+			Some(p) if p == offset => Ok(offset),
+			_ => input.seek(SeekFrom::Start(offset)).await.map_err(PaaError::from),
+		};
+
+		let fetched = match seeked {
+			Ok(_) => fetch_mipmap(input, paatype, policy).await,
+			Err(e) => Err(e),
+		};
+
+		let mut after = position(input).await?;
+
+		// Telling a legacy LZSS mipmap apart may have read past it
+		if let Ok((_, size)) = &fetched {
+			let end = offset + *size as u64;
+
+			if after != end {
+				after = input.seek(SeekFrom::Start(end)).await?;
+			};
+		};
+
+		let index = mipmaps.len();
+		let mipmap = fetched.and_then(|(mipmap, _)| {
+			budget.charge(index, &mipmap.head)?;
+			mipmap.decompress()
+		});
+		let exceeded = matches!(mipmap, Err(ReadBudgetExceeded(..)));
+
+		walk.record(&mipmap, after);
+		current = Some(after);
+		mipmaps.push(mipmap);
+
+		if exceeded {
+			break;
+		};
+	};
+
+	Ok((mipmaps, walk.end()))
+}
+
+
+/// Collect the offsets of sequential mipmaps from `offset` on, as
+/// [`PaaReader::new`][`crate::PaaReader::new`].
+async fn walk_mipmaps<R: AsyncRead + AsyncSeek + Unpin>(input: &mut R, paatype: PaaType, offset: u64) -> PaaResult<Vec<u32>> {
+	let mut offsets = vec![];
+	let mut offset = offset;
+	let _ = input.seek(SeekFrom::Start(offset)).await?;
+
+	loop {
+		let (head, consumed) = match read_mipmap_head(input, paatype).await {
+			Ok(head) => head,
+			Err(_) => break,
+		};
+
+		offsets.push(offset.try_into().map_err(|_| ArithmeticOverflow)?);
+		offset += (consumed + head.payload_len) as u64;
+		let _ = input.seek(SeekFrom::Start(offset)).await?;
+	};
+
+	Ok(offsets)
+}
+
+
+#[tokio::test]
+async fn async_matches_sync() {
+	use std::io::Cursor;
+	use byteorder::{ByteOrder, LittleEndian};
+	use crate::PaaReader;
+
+	let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+	let mut files = vec![std::fs::read(root.join("tests/reference/fixtures/unpaired/lzss_dxt5.paa")).unwrap()];
+
+	for name in ["checker_dxt1", "gradient_argb8888", "ramp_ai88"] {
+		files.push(std::fs::read(root.join("tests/reference/fixtures").join(name).with_extension("paa")).unwrap());
+	};
+
+	// Offsets zeroed or shifted, and mipmaps read one after the other
+	let mipmap = |size: u16| PaaMipmap {
+		width: size,
+		height: size,
+		paatype: PaaType::Argb8888,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0x40; usize::from(size).pow(2) * 4],
+		payload: None,
+	};
+	let image = PaaImage { paatype: PaaType::Argb8888, mipmaps: vec![Ok(mipmap(4)), Ok(mipmap(2)), Ok(mipmap(1))], ..Default::default() };
+	let bytes = image.to_bytes().unwrap();
+	let offsets = PaaImage::offsets_in(&PaaImage::from_bytes(&bytes).unwrap().taggs);
+
+	for changed in [vec![offsets[0], 0], offsets.iter().map(|o| o - 60).collect(), vec![20, 24, 28], vec![0; 16]] {
+		let mut bytes = bytes.clone();
+		LittleEndian::write_u32_into(&changed, &mut bytes[2 + 12..2 + 12 + changed.len() * 4]);
+		files.push(bytes);
+	};
+
+	files.push(bytes);
+
+	for (i, data) in files.iter().enumerate() {
+		let sync = PaaImage::from_bytes(data).unwrap();
+		let parsed = PaaImage::read_from_async(&mut Cursor::new(data)).await.unwrap();
+		assert_eq!(format!("{parsed:?}"), format!("{sync:?}"), "file #{i}");
+
+		let mut sync = PaaReader::new(Cursor::new(data)).unwrap();
+		let mut lazy = AsyncPaaReader::new(Cursor::new(data)).await.unwrap();
+		assert_eq!((lazy.paatype(), lazy.taggs()), (sync.paatype(), sync.taggs()), "file #{i}");
+		assert_eq!(format!("{:?}", lazy.palette()), format!("{:?}", sync.palette()), "file #{i}");
+		assert_eq!(lazy.mipmap_count(), sync.mipmap_count(), "file #{i}");
+
+		for index in 0..=sync.mipmap_count() {
+			assert_eq!(format!("{:?}", lazy.read_mipmap(index).await), format!("{:?}", sync.read_mipmap(index)), "file #{i}, mipmap #{index}");
+		};
+	};
+
+	// Lenient reading of LZSS-compressed DXTn mipmaps
+	let mut lazy = AsyncPaaReader::new(Cursor::new(&files[0])).await.unwrap().with_policy(CompressionPolicy::LENIENT);
+	let fetched = lazy.fetch_mipmap(0).await.unwrap();
+	assert_eq!(fetched.compression(), PaaMipmapCompression::Lzss);
+	assert_eq!(fetched.decompress().unwrap().data.len(), PaaType::Dxt5.predict_size(8, 8));
+}
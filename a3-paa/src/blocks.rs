@@ -0,0 +1,262 @@
+use crate::{PaaMipmap, PaaResult, PaaType};
+use crate::PaaError::*;
+
+
+impl PaaMipmap {
+	/// Size in bytes of a 4x4 block: 8 for [`PaaType::Dxt1`] (BC1), and 16
+	/// for other DXTn types (BC2 and BC3).
+	///
+	/// # Errors
+	/// - [`NotBlockCompressed`]: The mipmap is not of a DXTn type.
+	pub fn block_size(&self) -> PaaResult<usize> {
+		match self.paatype {
+			PaaType::Dxt1 => Ok(8),
+			t if t.is_dxtn() => Ok(16),
+			t => Err(NotBlockCompressed(t)),
+		}
+	}
+
+
+	/// Number of 4x4 blocks across and down.  Dimensions are rounded up to a
+	/// multiple of 4, as blocks on the right and bottom edges may be partial.
+	pub fn block_dimensions(&self) -> (usize, usize) {
+		((usize::from(self.width) + 3) / 4, (usize::from(self.height) + 3) / 4)
+	}
+
+
+	/// Iterate over the 4x4 blocks of a DXTn mipmap in row-major order,
+	/// without decoding them.
+	///
+	/// # Errors
+	/// - [`NotBlockCompressed`]: The mipmap is not of a DXTn type.
+	/// - [`UnexpectedMipmapDataSize`]: Mipmap data is not of
+	///   [`PaaType::predict_size`].
+	pub fn blocks(&self) -> PaaResult<impl Iterator<Item = &[u8]>> {
+		let size = self.checked_block_size()?;
+		Ok(self.data.chunks_exact(size))
+	}
+
+
+	/// [`blocks`][`Self::blocks`], mutably.
+	///
+	/// # Errors
+	/// - As [`blocks`][`Self::blocks`].
+	pub fn blocks_mut(&mut self) -> PaaResult<impl Iterator<Item = &mut [u8]>> {
+		let size = self.checked_block_size()?;
+		Ok(self.data.chunks_exact_mut(size))
+	}
+
+
+	/// Return the block in column `bx` and row `by` (in blocks, 0-based).
+	///
+	/// # Errors
+	/// - [`BlockIndexOutOfRange`]: The block is outside of
+	///   [`block_dimensions`][`Self::block_dimensions`].
+	/// - As [`blocks`][`Self::blocks`].
+	pub fn block_at(&self, bx: usize, by: usize) -> PaaResult<&[u8]> {
+		let size = self.checked_block_size()?;
+		let (columns, rows) = self.block_dimensions();
+
+		if bx >= columns || by >= rows {
+			return Err(BlockIndexOutOfRange(bx, by));
+		};
+
+		let start = (by * columns + bx) * size;
+		Ok(&self.data[start..start + size])
+	}
+
+
+	/// Flip the mipmap upside down without decoding it, by reversing the
+	/// order of block rows and of the texel rows within each block, as when
+	/// converting between DDS and OpenGL conventions.
+	///
+	/// # Errors
+	/// - [`DxtMipmapDimensionsNotMultipleOf4`]: The height is larger than 4
+	///   and not a multiple of 4, so texels would move across blocks.
+	/// - As [`blocks`][`Self::blocks`].
+	pub fn flip_vertical_in_place(&mut self) -> PaaResult<()> {
+		let size = self.checked_block_size()?;
+		let (columns, rows) = self.block_dimensions();
+
+		// A single partial block row only flips its texel rows in the image
+		let texel_rows = match usize::from(self.height) {
+			h if h % 4 == 0 => 4,
+			h if h < 4 => h,
+			_ => return Err(DxtMipmapDimensionsNotMultipleOf4(self.width, self.height)),
+		};
+
+		let row_len = columns * size;
+
+		for by in 0..rows / 2 {
+			let (upper, lower) = self.data.split_at_mut((rows - 1 - by) * row_len);
+			upper[by * row_len..(by + 1) * row_len].swap_with_slice(&mut lower[..row_len]);
+		};
+
+		let paatype = self.paatype;
+
+		for block in self.data.chunks_exact_mut(size) {
+			flip_block_rows(paatype, block, texel_rows);
+		};
+
+		Ok(())
+	}
+
+
+	fn checked_block_size(&self) -> PaaResult<usize> {
+		let size = self.block_size()?;
+
+		if self.data.len() != self.paatype.predict_size(self.width, self.height) {
+			return Err(UnexpectedMipmapDataSize(self.width, self.height, self.data.len()));
+		};
+
+		Ok(size)
+	}
+}
+
+
+/// Reverse the first `rows` texel rows of a DXTn `block`
+fn flip_block_rows(paatype: PaaType, block: &mut [u8], rows: usize) {
+	let (alpha, color) = block.split_at_mut(block.len() - 8);
+
+	// BC1 color: two 16-bit endpoints, then a byte of 2-bit indices per row
+	color[4..4 + rows].reverse();
+
+	match paatype {
+		// BC2 alpha: 16 bits of 4-bit alpha per row
+		PaaType::Dxt2 | PaaType::Dxt3 => {
+			for row in 0..rows / 2 {
+				let (upper, lower) = alpha.split_at_mut(2 * (rows - 1 - row));
+				upper[2 * row..2 * row + 2].swap_with_slice(&mut lower[..2]);
+			};
+		},
+
+		// BC3 alpha: two 8-bit endpoints, then 48 bits of 3-bit indices, 12
+		// bits per row
+		PaaType::Dxt4 | PaaType::Dxt5 => {
+			let mut bytes = [0u8; 8];
+			bytes[..6].copy_from_slice(&alpha[2..8]);
+			let indices = u64::from_le_bytes(bytes);
+
+			let flipped = (0..4)
+				.map(|row| if row < rows { rows - 1 - row } else { row })
+				.enumerate()
+				.fold(0u64, |acc, (row, source)| acc | ((indices >> (12 * source)) & 0xFFF) << (12 * row));
+
+			alpha[2..8].copy_from_slice(&flipped.to_le_bytes()[..6]);
+		},
+
+		_ => (),
+	};
+}
+
+
+/// Decode a DXTn `block` into its 16 texels in row-major order, at 16 bits
+/// per channel.  Endpoints are widened to 16 bits before interpolating.
+#[cfg(feature = "image")]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn decode_block_rgba16(paatype: PaaType, block: &[u8]) -> [[u16; 4]; 16] {
+	use crate::pixel::widen_channel;
+
+	// `(a * wa + b * wb) / (wa + wb)`, rounded
+	let mix = |a: u16, b: u16, wa: u32, wb: u32| ((u32::from(a) * wa + u32::from(b) * wb + (wa + wb) / 2) / (wa + wb)) as u16;
+
+	let (alpha, color) = block.split_at(block.len() - 8);
+
+	let endpoint = |c: u16| [widen_channel((c >> 11) as u8, 5), widen_channel((c >> 5) as u8, 6), widen_channel(c as u8, 5), u16::MAX];
+
+	let (c0, c1) = (u16::from_le_bytes([color[0], color[1]]), u16::from_le_bytes([color[2], color[3]]));
+	let (e0, e1) = (endpoint(c0), endpoint(c1));
+	let lerp = |wa: u32, wb: u32| [0, 1, 2, 3].map(|i| mix(e0[i], e1[i], wa, wb));
+
+	// BC1 switches to three colors and transparent black if c0 <= c1
+	let colors = if paatype != PaaType::Dxt1 || c0 > c1 {
+		[e0, e1, lerp(2, 1), lerp(1, 2)]
+	} else {
+		[e0, e1, lerp(1, 1), [0; 4]]
+	};
+
+	let color_indices = u32::from_le_bytes([color[4], color[5], color[6], color[7]]);
+	let mut texels = [[0u16; 4]; 16];
+
+	for (i, texel) in texels.iter_mut().enumerate() {
+		*texel = colors[(color_indices >> (2 * i)) as usize & 3];
+	};
+
+	match paatype {
+		PaaType::Dxt2 | PaaType::Dxt3 => {
+			for (i, texel) in texels.iter_mut().enumerate() {
+				texel[3] = widen_channel(alpha[i / 2] >> (4 * (i % 2)), 4);
+			};
+		},
+
+		PaaType::Dxt4 | PaaType::Dxt5 => {
+			let (a0, a1) = (u16::from(alpha[0]) * 257, u16::from(alpha[1]) * 257);
+
+			// Eight alphas if a0 > a1, otherwise six, then 0 and 1
+			let alphas: [u16; 8] = if alpha[0] > alpha[1] {
+				[a0, a1, mix(a0, a1, 6, 1), mix(a0, a1, 5, 2), mix(a0, a1, 4, 3), mix(a0, a1, 3, 4), mix(a0, a1, 2, 5), mix(a0, a1, 1, 6)]
+			} else {
+				[a0, a1, mix(a0, a1, 4, 1), mix(a0, a1, 3, 2), mix(a0, a1, 2, 3), mix(a0, a1, 1, 4), 0, u16::MAX]
+			};
+
+			let mut bytes = [0u8; 8];
+			bytes[..6].copy_from_slice(&alpha[2..8]);
+			let alpha_indices = u64::from_le_bytes(bytes);
+
+			for (i, texel) in texels.iter_mut().enumerate() {
+				texel[3] = alphas[(alpha_indices >> (3 * i)) as usize & 7];
+			};
+		},
+
+		_ => (),
+	};
+
+	texels
+}
+
+
+#[test]
+fn blocks() {
+	let data = (0..64).collect::<Vec<u8>>();
+	let mipmap = PaaMipmap { width: 8, height: 8, paatype: PaaType::Dxt1, data, ..Default::default() };
+
+	assert_eq!(mipmap.block_dimensions(), (2, 2));
+	assert_eq!(mipmap.blocks().unwrap().count(), 4);
+	assert_eq!(mipmap.block_at(1, 1).unwrap(), &mipmap.data[24..32]);
+	assert!(matches!(mipmap.block_at(2, 0), Err(BlockIndexOutOfRange(2, 0))));
+
+	let mut odd = PaaMipmap { width: 6, height: 2, ..mipmap.clone() };
+	odd.data.truncate(16);
+	assert_eq!(odd.block_dimensions(), (2, 1));
+	assert_eq!(odd.blocks_mut().unwrap().count(), 2);
+
+	assert!(matches!(PaaMipmap { paatype: PaaType::Dxt5, ..mipmap }.blocks(), Err(UnexpectedMipmapDataSize(8, 8, 64))));
+	assert!(matches!(PaaMipmap { paatype: PaaType::Argb8888, ..Default::default() }.block_size(), Err(NotBlockCompressed(PaaType::Argb8888))));
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn flip_vertical() {
+	let image = image::RgbaImage::from_fn(16, 8, |x, y| image::Rgba([x as u8 * 16, y as u8 * 32, 0x80, 0xFF - y as u8 * 24 - x as u8]));
+
+	for paatype in [PaaType::Dxt1, PaaType::Dxt3, PaaType::Dxt5] {
+		let original = PaaMipmap::encode(paatype, &image).unwrap();
+		let mut flipped = original.clone();
+		flipped.flip_vertical_in_place().unwrap();
+
+		let expected = image::imageops::flip_vertical(&original.decode().unwrap());
+		assert_eq!(flipped.decode().unwrap(), expected, "{paatype:?}");
+
+		flipped.flip_vertical_in_place().unwrap();
+		assert_eq!(flipped, original);
+	};
+
+	// Only the two rows of a 4x2 mipmap are swapped
+	let mut short = PaaMipmap { width: 4, height: 2, paatype: PaaType::Dxt1, data: vec![0, 0, 0, 0, 1, 2, 3, 4], ..Default::default() };
+	short.flip_vertical_in_place().unwrap();
+	assert_eq!(short.data, [0, 0, 0, 0, 2, 1, 3, 4]);
+
+	let mut uneven = PaaMipmap { width: 4, height: 6, paatype: PaaType::Dxt1, data: vec![0; 16], ..Default::default() };
+	assert!(matches!(uneven.flip_vertical_in_place(), Err(DxtMipmapDimensionsNotMultipleOf4(4, 6))));
+}
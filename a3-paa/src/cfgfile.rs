@@ -1,19 +1,22 @@
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use derive_more::Display;
 use unicode_xid::UnicodeXID;
 use nom::{
 	IResult,
 	branch::alt,
-	bytes::complete::{tag, take_until},
-	character::complete::{anychar, i32, multispace1, newline, not_line_ending},
-	combinator::{all_consuming, map, opt, value, verify},
-	error::{VerboseError, context, convert_error},
-	multi::many0,
+	bytes::complete::{tag, take_until, take_while},
+	character::complete::{multispace1, newline, not_line_ending, satisfy},
+	combinator::{all_consuming, cut, map, map_res, opt, recognize, value},
+	error::{VerboseError, VerboseErrorKind, ErrorKind, context},
+	multi::{many0, separated_list0},
+	number::complete::recognize_float,
 	sequence::{delimited, pair, preceded, terminated, tuple},
 };
 
-use crate::{PaaError, PaaError::*, PaaResult, PaaType, ArgbSwizzle, TextureEncodingSettings, TextureErrorMetrics, TextureMipmapFilter};
+use crate::{ErrorSource, PaaError, PaaError::*, PaaResult, PaaType, ArgbSwizzle, TextureEncodingSettings, TextureErrorMetrics, TextureMipmapFilter};
+#[cfg(test)] use crate::TextureHints;
 
 
 fn parse_single_line_comment(i: &str) -> IResult<&str, (), VerboseError<&str>> {
@@ -26,6 +29,11 @@ fn parse_multi_line_comment(i: &str) -> IResult<&str, (), VerboseError<&str>> {
 }
 
 
+fn parse_preprocessor_line(i: &str) -> IResult<&str, (), VerboseError<&str>> {
+	value((), pair(tag("#"), context("preprocessor directive", tuple((not_line_ending, opt(newline))))))(i)
+}
+
+
 #[test]
 fn comments() {
 	assert_eq!(parse_single_line_comment("// Good"), Ok(("", ())));
@@ -37,50 +45,113 @@ fn comments() {
 }
 
 
-fn construct_ident(args: (char, Vec<char>)) -> ConfigIdent {
-	let (start, cont) = args;
-	let mut inner = String::new();
-	inner.push(start);
-	inner.extend(cont);
-	ConfigIdent::from(&inner)
+/// Maximum size of a `TexConvert.cfg` file accepted by
+/// [`try_parse_texconvert`].
+const MAX_INPUT_SIZE: usize = 1024 * 1024;
+
+
+/// Maximum nesting of classes and arrays; keeps the recursive descent parser
+/// from overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 32;
+
+
+/// Context pushed by [`check_depth`]; [`describe_context`] adds the limit.
+const NESTING_CONTEXT: &str = "nested classes and arrays";
+
+
+/// Fail without backtracking once `depth` exceeds [`MAX_NESTING_DEPTH`].
+fn check_depth(i: &str, depth: usize) -> IResult<&str, (), VerboseError<&str>> {
+	if depth < MAX_NESTING_DEPTH {
+		Ok((i, ()))
+	}
+	else {
+		let errors = vec![(i, VerboseErrorKind::Context(NESTING_CONTEXT))];
+		Err(nom::Err::Failure(VerboseError { errors }))
+	}
+}
+
+
+fn describe_context(c: &str) -> String {
+	if c == NESTING_CONTEXT {
+		format!("at most {} levels of {}", MAX_NESTING_DEPTH, c)
+	}
+	else {
+		String::from(c)
+	}
 }
 
 
 fn parse_ident(i: &str) -> IResult<&str, ConfigIdent, VerboseError<&str>> {
 	map(
-		pair(
-			verify(anychar, |c: &char| UnicodeXID::is_xid_start(*c)),
-			many0(verify(anychar, |c: &char| UnicodeXID::is_xid_continue(*c)))),
-	construct_ident)(i)
+		recognize(pair(
+			satisfy(|c| c.is_xid_start()),
+			take_while(|c: char| c.is_xid_continue()))),
+		ConfigIdent::from)(i)
+}
+
+
+fn parse_number(i: &str) -> IResult<&str, ConfigValue, VerboseError<&str>> {
+	map_res(recognize_float, |s: &str| {
+		if s.contains(|c: char| matches!(c, '.' | 'e' | 'E')) {
+			s.parse::<f64>().map(ConfigValue::Float)
+		}
+		else {
+			// Integers overflowing an i32 are kept as floats
+			s.parse::<i32>().map(ConfigValue::Integer).or_else(|_| s.parse::<f64>().map(ConfigValue::Float))
+		}
+	})(i)
+}
+
+
+fn parse_array(i: &str, depth: usize) -> IResult<&str, ConfigValue, VerboseError<&str>> {
+	let (i, _) = tag("{")(i)?;
+	let (i, _) = check_depth(i, depth)?;
+
+	map(
+		terminated(
+			separated_list0(tag(","), with_ws_or_comments(move |i| parse_nested_value(i, depth + 1))),
+			context("'}' closing array", cut(preceded(wscom0, tag("}"))))),
+		ConfigValue::Array)(i)
 }
 
 
 fn parse_value(i: &str) -> IResult<&str, ConfigValue, VerboseError<&str>> {
+	parse_nested_value(i, 0)
+}
+
+
+fn parse_nested_value(i: &str, depth: usize) -> IResult<&str, ConfigValue, VerboseError<&str>> {
 	alt((
-		map(i32, ConfigValue::Integer),
+		parse_number,
 		map(delimited(tag("\""), take_until("\""), tag("\"")), |s: &str| ConfigValue::String(String::from(s))),
 		map(parse_ident, ConfigValue::Ident),
+		move |i| parse_array(i, depth),
 	))(i)
 }
 
 
 fn parse_property(i: &str) -> IResult<&str, ConfigProperty, VerboseError<&str>> {
+	parse_nested_property(i, 0)
+}
+
+
+fn parse_nested_property(i: &str, depth: usize) -> IResult<&str, ConfigProperty, VerboseError<&str>> {
 	tuple((
 			parse_ident,
+			context("array brackets", opt(pair(with_ws_or_comments(tag("[")), tag("]")))),
 			context("equals sign", with_ws_or_comments(tag("="))),
-			context("property value", with_ws_or_comments(parse_value)),))
+			context("property value", cut(move |i| parse_nested_value(i, depth))),))
 		(i)
-		.map(|args: (&str, (ConfigIdent, &str, ConfigValue))| {
-			let (left, (ident, _, value)) = args;
+		.map(|args: (&str, (ConfigIdent, Option<(&str, &str)>, &str, ConfigValue))| {
+			let (left, (ident, _, _, value)) = args;
 			(left, ConfigProperty { ident, value })
 		})
 }
 
 
-fn parse_class(i: &str) -> IResult<&str, ConfigClass, VerboseError<&str>> {
+fn parse_class(i: &str, depth: usize) -> IResult<&str, ConfigClass, VerboseError<&str>> {
 	let class_name = context("class name", with_ws_or_comments(parse_ident));
 	let parent_class_name = context("parent class name", opt(preceded(with_ws_or_comments(tag(":")), with_ws_or_comments(parse_ident))));
-	let children = context("children", terminated_list(parse_item, ";"));
 
 	#[allow(clippy::type_complexity)]
 	tuple((
@@ -88,8 +159,8 @@ fn parse_class(i: &str) -> IResult<&str, ConfigClass, VerboseError<&str>> {
 		class_name,
 		parent_class_name,
 		context("opening brace", with_ws_or_comments(tag("{"))),
-		children,
-		context("closing brace", tag("}")),))
+		move |i| parse_items(i, depth + 1),
+		context("'}' closing class", cut(preceded(wscom0, tag("}")))),))
 	(i)
 	.map(|args: (&str, (&str, ConfigIdent, Option<ConfigIdent>, &str, Vec<ConfigItem>, &str))| {
 		let (left, (_, classname, parent_class, _, children, _)) = args;
@@ -99,14 +170,25 @@ fn parse_class(i: &str) -> IResult<&str, ConfigClass, VerboseError<&str>> {
 }
 
 
-fn parse_item(i: &str) -> IResult<&str, ConfigItem, VerboseError<&str>> {
+fn expect_semicolon<'a>(what: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, VerboseError<&'a str>> {
+	context(what, cut(preceded(wscom0, tag(";"))))
+}
+
+
+fn parse_item(i: &str, depth: usize) -> IResult<&str, ConfigItem, VerboseError<&str>> {
 	alt((
-		map(parse_property, ConfigItem::Property),
-		map(parse_class, ConfigItem::Class)
+		terminated(map(move |i| parse_nested_property(i, depth), ConfigItem::Property), expect_semicolon("';' after property")),
+		terminated(map(move |i| parse_class(i, depth), ConfigItem::Class), expect_semicolon("';' after class")),
 	))(i)
 }
 
 
+fn parse_items(i: &str, depth: usize) -> IResult<&str, Vec<ConfigItem>, VerboseError<&str>> {
+	let (i, _) = check_depth(i, depth)?;
+	many0(preceded(wscom0, move |i| parse_item(i, depth)))(i)
+}
+
+
 #[test]
 fn property() {
 	assert_eq!(parse_ident("dynRange").unwrap(), ("", ConfigIdent::from("dynRange")));
@@ -116,8 +198,44 @@ fn property() {
 }
 
 
+#[test]
+fn float_and_array_values() {
+	assert_eq!(parse_value("0.5").unwrap(), ("", ConfigValue::Float(0.5)));
+	assert_eq!(parse_value("-1.25e2").unwrap(), ("", ConfigValue::Float(-125.0)));
+	assert_eq!(parse_value("3E-1").unwrap(), ("", ConfigValue::Float(0.3)));
+	assert_eq!(parse_value("{}").unwrap(), ("", ConfigValue::Array(vec![])));
+	assert_eq!(
+		parse_property("colors[] = { 1, -0.5, \"x\", {2} }").unwrap(),
+		("", ConfigProperty {
+			ident: ConfigIdent::from("colors"),
+			value: ConfigValue::Array(vec![
+				ConfigValue::Integer(1),
+				ConfigValue::Float(-0.5),
+				ConfigValue::String(String::from("x")),
+				ConfigValue::Array(vec![ConfigValue::Integer(2)]),
+			]),
+		}));
+
+	let config = r#"
+		class TextureHints {
+			class normalmap {
+				name = "*_nohq.*";
+				format = "DXT5";
+				errorPenality = 0.5;
+				gamma = 2.2e0;
+				offset = -3;
+				colors[] = {1, 0, 0, 1};
+			};
+		};
+	"#;
+
+	let hints = TextureHints::try_parse_from_str(config).unwrap();
+	assert_eq!(hints.get("NOHQ").map(|s| s.format), Some(PaaType::Dxt5));
+}
+
+
 fn wscom0(i: &str) -> IResult<&str, (), VerboseError<&str>> {
-	value((), many0(alt((parse_single_line_comment, parse_multi_line_comment, value((), multispace1)))))(i)
+	value((), many0(alt((parse_single_line_comment, parse_multi_line_comment, parse_preprocessor_line, value((), multispace1)))))(i)
 }
 
 
@@ -135,11 +253,118 @@ fn with_whitespace() {
 }
 
 
-fn terminated_list<'a, F: 'a, O>(inner: F, delimiter: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>, VerboseError<&'a str>>
-where
-	F: FnMut(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
-{
-	many0(terminated(with_ws_or_comments(inner), with_ws_or_comments(tag(delimiter))))
+/// Maximum length of [`ParseDiagnostic::snippet`].
+const MAX_SNIPPET_CHARS: usize = 200;
+
+
+/// Location and description of a syntax error in a `TexConvert.cfg` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+	/// 1-based line of the error.
+	pub line: usize,
+	/// 1-based column (in characters) of the error.
+	pub column: usize,
+	/// Human-readable description of the error, e.g. "expected ';' after
+	/// property".
+	pub message: String,
+	/// Parser contexts and error kinds reported at the error, innermost first.
+	pub context_stack: Vec<String>,
+	/// The line of input containing the error (truncated to 200 characters).
+	pub snippet: String,
+}
+
+
+impl std::fmt::Display for ParseDiagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "error at line {}, column {}: {}", self.line, self.column, self.message)
+	}
+}
+
+
+impl ParseDiagnostic {
+	fn from_nom(input: &str, error: nom::Err<VerboseError<&str>>) -> Self {
+		let errors = match error {
+			nom::Err::Error(e) | nom::Err::Failure(e) => e.errors,
+			nom::Err::Incomplete(_) => vec![],
+		};
+
+		let context_stack = errors.iter()
+			.map(|(_, kind)| match kind {
+				VerboseErrorKind::Context(c) => describe_context(c),
+				VerboseErrorKind::Char(c) => format!("'{}'", c),
+				VerboseErrorKind::Nom(k) => format!("{:?}", k),
+			})
+			.collect::<Vec<String>>();
+
+		let innermost_context = errors.iter()
+			.find(|(_, kind)| matches!(kind, VerboseErrorKind::Context(_)));
+
+		let (remaining, message) = match (innermost_context, errors.first()) {
+			(Some((r, VerboseErrorKind::Context(c))), _) => (*r, format!("expected {}", describe_context(c))),
+			(_, Some((r, VerboseErrorKind::Char(c)))) => (*r, format!("expected '{}'", c)),
+			(_, Some((r, VerboseErrorKind::Nom(ErrorKind::Eof)))) => (*r, String::from("unexpected input")),
+			(_, Some((r, _))) => (*r, String::from("invalid syntax")),
+			(_, None) => ("", String::from("unexpected end of input")),
+		};
+
+		Self::at(input, remaining, message, context_stack)
+	}
+
+
+	/// Construct a diagnostic pointing at `remaining`, a suffix of `input`.
+	fn at(input: &str, remaining: &str, message: String, context_stack: Vec<String>) -> Self {
+		let offset = input.len().saturating_sub(remaining.len());
+		let consumed = input.get(..offset).unwrap_or(input);
+		let line_start = consumed.rfind('\n').map_or(0, |p| p + 1);
+		let line_end = input[line_start..].find('\n').map_or(input.len(), |p| line_start + p);
+
+		let line = consumed.matches('\n').count() + 1;
+		let column = consumed[line_start..].chars().count() + 1;
+		let snippet = input[line_start..line_end].trim_end().chars().take(MAX_SNIPPET_CHARS).collect();
+
+		Self { line, column, message, context_stack, snippet }
+	}
+}
+
+
+#[test]
+fn diagnostic_missing_semicolon() {
+	let config = "class TextureHints {\n\tclass sky {\n\t\tname = \"*_sky.*\";\n\t\tformat = \"DXT1\"\n\t\tautoreduce = 1;\n\t};\n};\n";
+
+	match try_parse_texconvert(config) {
+		Err(TexconvertParseError(d)) => {
+			assert_eq!(d.line, 4);
+			assert_eq!(d.message, "expected ';' after property");
+			assert_eq!(d.snippet, "\t\tformat = \"DXT1\"");
+			assert_eq!(d.to_string(), "error at line 4, column 18: expected ';' after property");
+		},
+		r => panic!("Unexpected result: {:?}", r),
+	};
+}
+
+
+#[test]
+fn pathological_inputs() {
+	let nested_classes = format!("class TextureHints {{ {}{} }};", "class a { ".repeat(64), "}; ".repeat(64));
+	match try_parse_texconvert(&nested_classes) {
+		Err(TexconvertParseError(d)) => assert_eq!(d.message, format!("expected at most {} levels of nested classes and arrays", MAX_NESTING_DEPTH)),
+		r => panic!("Unexpected result: {:?}", r),
+	};
+
+	let nested_arrays = format!("class TextureHints {{ class a {{ name = \"*_a.*\"; x[] = {}{}; }}; }};", "{".repeat(100_000), "}".repeat(100_000));
+	assert!(matches!(try_parse_texconvert(&nested_arrays), Err(TexconvertParseError(_))));
+
+	let long_ident = format!("class TextureHints {{ class a {{ name = \"*_a.*\"; {} = 1; }}; }};", "x".repeat(MAX_INPUT_SIZE / 2));
+	assert_eq!(TextureHints::try_parse_from_str(&long_ident).unwrap().unknown_properties()[0].1.len(), 1);
+
+	let too_large = "x".repeat(MAX_INPUT_SIZE + 1);
+	match try_parse_texconvert(&too_large) {
+		Err(TexconvertParseError(d)) => assert_eq!((d.line, d.column, d.snippet.len()), (1, 1, MAX_SNIPPET_CHARS)),
+		r => panic!("Unexpected result: {:?}", r),
+	};
+
+	let unterminated = "class TextureHints { class a { name = \"*_a.*; }; };";
+	assert!(matches!(try_parse_texconvert(unterminated), Err(TexconvertParseError(_))));
 }
 
 
@@ -168,7 +393,7 @@ impl ConfigIdent {
 }
 
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 enum ConfigItem {
 	Property(ConfigProperty),
 	Class(ConfigClass),
@@ -193,7 +418,7 @@ impl ConfigItem {
 }
 
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 struct ConfigClass {
 	classname: ConfigIdent,
 	inherit_classname: Option<ConfigIdent>,
@@ -219,28 +444,30 @@ impl ConfigClass {
 	}
 
 
-	fn into_settings(self, siblings: &HashMap<String, TextureEncodingSettings>) -> PaaResult<(String, TextureEncodingSettings)> {
+	fn has_property(&self, ident: &str) -> bool {
+		self.children.iter().any(|i| matches!(i, ConfigItem::Property(_)) && *i.get_ident() == ident)
+	}
+
+
+	/// Convert the class into a `name` pattern, settings, and the properties
+	/// that were not used for the settings.  Inherited properties must already
+	/// be merged into `self.children` (see [`resolve_inheritance`]).
+	fn into_settings(self) -> PaaResult<HintPattern> {
 		let prop = |ident| self.children.iter()
 			.find(|i| matches!(i, ConfigItem::Property(_)) && *i.get_ident() == ident)
 			.map(|p| match p { ConfigItem::Property(p) => p.clone(), _ => unreachable!() } );
 
 		let mut settings = TextureEncodingSettings::default();
 
-		if let Some(parent) = self.inherit_classname {
-			settings = *siblings.get(&parent.inner).ok_or(TexconvertInvalidInherit(parent.inner))?;
-		};
-
-		let suffix = prop("name")
+		let pattern = prop("name")
 			.and_then(ConfigProperty::try_into_string)
-			.and_then(ConfigClass::name_to_suffix).ok_or(TexconvertNoName)?;
+			.ok_or(TexconvertNoName)?;
 
 		let enable_dxt = prop("enableDXT")
-			.and_then(ConfigProperty::try_into_bool)
-			.map(|e| if e { String::from("DXT5") } else { String::from("ARGB8888") });
+			.and_then(ConfigProperty::try_into_bool);
 
 		let format = prop("format")
-			.and_then(ConfigProperty::try_into_string)
-			.or(enable_dxt)
+			.and_then(ConfigProperty::try_into_word)
 			.and_then(|s| s.parse::<PaaType>().ok());
 
 		let dynrange = prop("dynrange")
@@ -250,26 +477,56 @@ impl ConfigClass {
 			.and_then(ConfigProperty::try_into_bool);
 
 		let mipmap_filter = prop("mipmapFilter")
-			.and_then(ConfigProperty::try_into_ident)
-			.and_then(|i| i.inner.parse::<TextureMipmapFilter>().ok());
+			.and_then(ConfigProperty::try_into_word)
+			.and_then(|i| i.parse::<TextureMipmapFilter>().ok());
 
 		let error_metrics = prop("errorMetrics")
-			.and_then(ConfigProperty::try_into_ident)
-			.and_then(|i| i.inner.parse::<TextureErrorMetrics>().ok());
-
-		let swizzle = {
-			let swiz_a = prop("channelSwizzleA").and_then(|p| p.try_into_string())
-				.unwrap_or_else(|| "A".into());
-			let swiz_r = prop("channelSwizzleR").and_then(|p| p.try_into_string())
-				.unwrap_or_else(|| "R".into());
-			let swiz_g = prop("channelSwizzleG").and_then(|p| p.try_into_string())
-				.unwrap_or_else(|| "G".into());
-			let swiz_b = prop("channelSwizzleB").and_then(|p| p.try_into_string())
-				.unwrap_or_else(|| "B".into());
-			ArgbSwizzle::parse_argb(&swiz_a, &swiz_r, &swiz_g, &swiz_b)?
-		};
-
-		if let Some(format) = format {
+			.and_then(ConfigProperty::try_into_word)
+			.and_then(|i| i.parse::<TextureErrorMetrics>().ok());
+
+		let max_size = prop("maxSize")
+			.and_then(ConfigProperty::try_into_int)
+			.and_then(|i| u32::try_from(i).ok())
+			.filter(|s| *s > 0);
+
+		let swizzle_channel = |ident| prop(ident).and_then(ConfigProperty::try_into_string);
+		let swiz_a = swizzle_channel("channelSwizzleA");
+		let swiz_r = swizzle_channel("channelSwizzleR");
+		let swiz_g = swizzle_channel("channelSwizzleG");
+		let swiz_b = swizzle_channel("channelSwizzleB");
+
+		let swizzle = ArgbSwizzle::parse_argb(
+			swiz_a.as_deref().unwrap_or("A"),
+			swiz_r.as_deref().unwrap_or("R"),
+			swiz_g.as_deref().unwrap_or("G"),
+			swiz_b.as_deref().unwrap_or("B"),
+		)?;
+
+		// Properties that are unknown, or whose value could not be used
+		let recognized = [
+			("name", true),
+			("enableDXT", enable_dxt.is_some()),
+			("format", format.is_some()),
+			("dynRange", dynrange.is_some()),
+			("autoreduce", autoreduce.is_some()),
+			("mipmapFilter", mipmap_filter.is_some()),
+			("errorMetrics", error_metrics.is_some()),
+			("maxSize", max_size.is_some()),
+			("channelSwizzleA", swiz_a.is_some()),
+			("channelSwizzleR", swiz_r.is_some()),
+			("channelSwizzleG", swiz_g.is_some()),
+			("channelSwizzleB", swiz_b.is_some()),
+		];
+
+		let unknown = self.children.iter()
+			.filter_map(|i| match i { ConfigItem::Property(p) => Some(p), _ => None })
+			.filter(|p| !recognized.iter().any(|(ident, used)| *used && p.ident == *ident))
+			.map(|p| (p.ident.to_string(), p.value.to_string()))
+			.collect::<Vec<_>>();
+
+		let enable_dxt = enable_dxt.map(|e| if e { PaaType::Dxt5 } else { PaaType::Argb8888 });
+
+		if let Some(format) = format.or(enable_dxt) {
 			settings = TextureEncodingSettings { format, ..settings };
 		};
 
@@ -289,24 +546,18 @@ impl ConfigClass {
 			settings = TextureEncodingSettings { error_metrics: Some(error_metrics), ..settings };
 		};
 
-		settings = TextureEncodingSettings { swizzle, ..settings };
-
-		Ok((suffix, settings))
-	}
+		if let Some(max_size) = max_size {
+			settings = TextureEncodingSettings { max_size: Some(max_size), ..settings };
+		};
 
+		settings = TextureEncodingSettings { swizzle, ..settings };
 
-	fn name_to_suffix(name: String) -> Option<String> {
-		if name.starts_with("*_") && name.ends_with(".*") {
-			Some(String::from(&name[2..name.len()-2]).to_uppercase())
-		}
-		else {
-			None
-		}
+		Ok((pattern, settings, unknown))
 	}
 }
 
 
-#[derive(Debug, Display, PartialEq, Eq, Clone)]
+#[derive(Debug, Display, PartialEq, Clone)]
 #[display(fmt = "{} = {};", ident, value)]
 struct ConfigProperty {
 	ident: ConfigIdent,
@@ -324,15 +575,26 @@ impl ConfigProperty {
 
 	fn try_into_string(self) -> Option<String> {
 		match self.value {
-			ConfigValue::String(ref s) => Some(s.clone()),
+			ConfigValue::String(ref s) => Some(s.trim().to_owned()),
 			_ => None,
 		}
 	}
 
 
-	fn try_into_ident(self) -> Option<ConfigIdent> {
+	/// A string or a bare identifier, e.g. `format = "DXT5"` or
+	/// `mipmapFilter = NormalizeNormalMapAlpha`.
+	fn try_into_word(self) -> Option<String> {
 		match self.value {
-			ConfigValue::Ident(ref i) => Some(i.clone()),
+			ConfigValue::String(ref s) => Some(s.trim().to_owned()),
+			ConfigValue::Ident(ref i) => Some(i.inner.clone()),
+			_ => None,
+		}
+	}
+
+
+	fn try_into_int(self) -> Option<i32> {
+		match self.value {
+			ConfigValue::Integer(i) => Some(i),
 			_ => None,
 		}
 	}
@@ -347,10 +609,14 @@ impl ConfigProperty {
 }
 
 
-#[derive(Debug, Display, PartialEq, Eq, Clone)]
+#[derive(Debug, Display, PartialEq, Clone)]
 enum ConfigValue {
 	#[display(fmt = "{}", _0)]
 	Integer(i32),
+	#[display(fmt = "{}", _0)]
+	Float(f64),
+	#[display(fmt = "{{{}}}", "_0.iter().map(ToString::to_string).collect::<Vec<_>>().join(\", \")")]
+	Array(Vec<ConfigValue>),
 	#[display(fmt = "\"{}\"", _0)]
 	String(String),
 	#[display(fmt = "{}", _0)]
@@ -363,7 +629,7 @@ impl std::str::FromStr for ConfigValue {
 
 	fn from_str(input: &str) -> PaaResult<Self> {
 		let (_, result) = parse_value(input)
-			.map_err(|e| TexconvertParseError(e.map(|e| convert_error(input, e))))?;
+			.map_err(|e| TexconvertParseError(ParseDiagnostic::from_nom(input, e)))?;
 		Ok(result)
 	}
 }
@@ -373,18 +639,33 @@ impl ConfigValue {
 	fn normalized(self) -> Self {
 		match self {
 			ConfigValue::Ident(i) => ConfigValue::Ident(i.normalized()),
+			ConfigValue::Array(a) => ConfigValue::Array(a.into_iter().map(ConfigValue::normalized).collect()),
 			s => s,
 		}
 	}
 }
 
 
-pub(crate) fn try_parse_texconvert(input: &str) -> PaaResult<HashMap<String, TextureEncodingSettings>> {
-	let (_, items) = all_consuming(terminated_list(parse_item, ";"))(input)
-		.map_err(|e| TexconvertParseError(e.map(|e| {eprintln!("{:?}", e); convert_error(input, e)})))?;
+/// A `name` glob pattern, its settings, and the names and values of the
+/// class properties that were not used for the settings.
+pub(crate) type HintPattern = (String, TextureEncodingSettings, Vec<(String, String)>);
+
+
+/// [`HintPattern`]s in declaration order.
+pub(crate) type HintPatterns = Vec<HintPattern>;
+
+
+pub(crate) fn try_parse_texconvert(input: &str) -> PaaResult<HintPatterns> {
+	if input.len() > MAX_INPUT_SIZE {
+		let message = format!("input exceeds the limit of {} bytes", MAX_INPUT_SIZE);
+		return Err(TexconvertParseError(ParseDiagnostic::at(input, input, message, vec![])));
+	};
+
+	let (_, items) = all_consuming(terminated(|i| parse_items(i, 0), wscom0))(input)
+		.map_err(|e| TexconvertParseError(ParseDiagnostic::from_nom(input, e)))?;
 
 	let mut hints: Option<ConfigClass> = None;
-	let mut result: HashMap<String, TextureEncodingSettings> = HashMap::new();
+	let mut result: HintPatterns = vec![];
 
 	for i in items {
 		if let ConfigItem::Class(c) = i {
@@ -394,19 +675,321 @@ pub(crate) fn try_parse_texconvert(input: &str) -> PaaResult<HashMap<String, Tex
 		};
 	};
 
-	let hints = if let Some(hints) = hints { hints } else { return Ok(HashMap::new()); };
-
-	let mut classname_map: HashMap<String, TextureEncodingSettings> = HashMap::new();
+	let hints = if let Some(hints) = hints { hints } else { return Ok(vec![]); };
 
 	let child_classes = hints.children.into_iter()
-		.filter_map(|c| if let ConfigItem::Class(c) = c { Some(c.normalized()) } else { None });
+		.filter_map(|c| if let ConfigItem::Class(c) = c { Some(c.normalized()) } else { None })
+		.collect::<Vec<ConfigClass>>();
+
+	let parents: HashSet<ConfigIdent> = child_classes.iter()
+		.filter_map(|c| c.inherit_classname.clone())
+		.collect();
+
+	for c in resolve_inheritance(child_classes)? {
+		// Nameless base classes only provide properties to their children
+		if !c.has_property("name") && parents.contains(&c.classname) {
+			continue;
+		};
+
+		result.push(c.into_settings()?);
+	};
+
+	Ok(result)
+}
+
+
+/// Merge inherited properties into every class of `classes`, resolving
+/// parents regardless of declaration order and through any number of levels.
+/// Properties of a class override those of the same name in its ancestors.
+fn resolve_inheritance(classes: Vec<ConfigClass>) -> PaaResult<Vec<ConfigClass>> {
+	let index: HashMap<String, usize> = classes.iter()
+		.enumerate()
+		.map(|(i, c)| (c.classname.inner.clone(), i))
+		.collect();
+	let mut resolved: Vec<Option<Vec<ConfigItem>>> = vec![None; classes.len()];
+
+	for start in 0..classes.len() {
+		// Walk up to the first resolved ancestor (or the root)...
+		let mut chain: Vec<usize> = vec![];
+		let mut on_chain: HashSet<usize> = HashSet::new();
+		let mut current = Some(start);
+
+		while let Some(i) = current {
+			if resolved[i].is_some() {
+				break;
+			};
+
+			if !on_chain.insert(i) {
+				let pos = chain.iter().position(|c| *c == i).unwrap_or_default();
+				let names = chain[pos..].iter()
+					.chain(std::iter::once(&i))
+					.map(|c| classes[*c].classname.to_string())
+					.collect::<Vec<String>>()
+					.join(" -> ");
+				return Err(TexconvertInheritCycle(names));
+			};
+
+			chain.push(i);
+
+			current = match &classes[i].inherit_classname {
+				Some(parent) => Some(*index.get(&parent.inner).ok_or_else(|| TexconvertInvalidInherit(parent.to_string()))?),
+				None => None,
+			};
+		};
+
+		// ...then resolve the chain back down
+		for i in chain.into_iter().rev() {
+			let mut properties = classes[i].inherit_classname.as_ref()
+				.and_then(|p| index.get(&p.inner))
+				.and_then(|p| resolved[*p].clone())
+				.unwrap_or_default();
+
+			let own = classes[i].children.iter()
+				.filter(|c| matches!(c, ConfigItem::Property(_)))
+				.collect::<Vec<&ConfigItem>>();
+			let own_idents = own.iter()
+				.map(|c| c.get_ident())
+				.collect::<HashSet<&ConfigIdent>>();
+
+			properties.retain(|x| !own_idents.contains(x.get_ident()));
+			properties.extend(own.into_iter().cloned());
+
+			resolved[i] = Some(properties);
+		};
+	};
+
+	let result = classes.into_iter()
+		.zip(resolved)
+		.map(|(c, properties)| ConfigClass { children: properties.unwrap_or_default(), ..c })
+		.collect();
+
+	Ok(result)
+}
+
+
+#[test]
+fn inheritance() {
+	let config = r#"
+		class TextureHints {
+			class child : parent {
+				name = "*_ch.*";
+				channelSwizzleA = "1";
+			};
+			class grandchild : child {
+				name = "*_gc.*";
+				format = "DXT1";
+			};
+			class parent : base {
+				name = "*_pa.*";
+				autoreduce = 1;
+			};
+			class base {
+				format = "ARGB4444";
+				channelSwizzleR = "1-R";
+			};
+		};
+	"#;
+
+	let hints = TextureHints::try_parse_from_str(config).unwrap();
+	assert_eq!(hints.len(), 3);
 
-	for c in child_classes {
-		let classname = c.classname.clone().normalized().to_string();
-		let (suffix, settings) = c.into_settings(&classname_map)?;
-		let _ = classname_map.insert(classname, settings);
-		let _ = result.insert(suffix, settings);
+	let parent = hints.get("PA").unwrap();
+	assert_eq!(parent.format, PaaType::Argb4444);
+	assert!(parent.autoreduce);
+	assert_eq!(parent.swizzle, ArgbSwizzle::parse_argb("A", "1-R", "G", "B").unwrap());
+
+	let child = hints.get("CH").unwrap();
+	assert_eq!(child.format, PaaType::Argb4444);
+	assert!(child.autoreduce);
+	assert_eq!(child.swizzle, ArgbSwizzle::parse_argb("1", "1-R", "G", "B").unwrap());
+
+	let grandchild = hints.get("GC").unwrap();
+	assert_eq!(grandchild.format, PaaType::Dxt1);
+	assert!(grandchild.autoreduce);
+	assert_eq!(grandchild.swizzle, child.swizzle);
+
+	let cycle = "class TextureHints { class a : c { name = \"*_a.*\"; }; class b : a { name = \"*_b.*\"; }; class c : b { name = \"*_c.*\"; }; };";
+
+	match try_parse_texconvert(cycle) {
+		Err(TexconvertInheritCycle(names)) => assert_eq!(names, "A -> C -> B -> A"),
+		r => panic!("Unexpected result: {:?}", r),
 	};
 
+	let missing = "class TextureHints { class a : b { name = \"*_a.*\"; }; };";
+	assert!(matches!(try_parse_texconvert(missing), Err(TexconvertInvalidInherit(_))));
+}
+
+
+#[test]
+fn unknown_properties() {
+	let config = r#"class TextureHints {
+		class normalmap {
+			name = "*_nohq.*";
+			format = " DXT5 ";
+			maxSize = 1024;
+			mipmapFilter = NormalizeNormalMapAlpha ;
+			errorPenality = 0.5;
+			mipmapFliter = FadeOut;
+			errorMetrics = "Manhattan";
+		};
+	};"#;
+
+	let hints = TextureHints::try_parse_from_str(config).unwrap();
+	let settings = hints.get("NOHQ").unwrap();
+	assert_eq!(settings.format, PaaType::Dxt5);
+	assert_eq!(settings.max_size, Some(1024));
+	assert_eq!(settings.mipmap_filter, Some(TextureMipmapFilter::NormalizeNormalMapAlpha));
+
+	let expected = vec![
+		("ERRORPENALITY".to_owned(), "0.5".to_owned()),
+		("MIPMAPFLITER".to_owned(), "FADEOUT".to_owned()),
+		("ERRORMETRICS".to_owned(), "\"Manhattan\"".to_owned()),
+	];
+	assert_eq!(hints.unknown_properties(), [("*_nohq.*".to_owned(), expected)]);
+}
+
+
+/// Maximum nesting of `#include` directives followed by
+/// [`try_parse_texconvert_path`].
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+
+/// Replace `//` and `/* */` comments in `input` with whitespace, keeping
+/// line breaks and string literals intact.
+fn strip_comments(input: &str) -> String {
+	let mut result = String::with_capacity(input.len());
+	let mut chars = input.chars().peekable();
+	let mut in_string = false;
+
+	while let Some(c) = chars.next() {
+		match (c, chars.peek().copied()) {
+			('"', _) => {
+				in_string = !in_string;
+				result.push(c);
+			},
+			('\n', _) => {
+				in_string = false;
+				result.push(c);
+			},
+			('/', Some('/')) if !in_string => {
+				while chars.peek().map_or(false, |&c| c != '\n') {
+					let _ = chars.next();
+				};
+			},
+			('/', Some('*')) if !in_string => {
+				let _ = chars.next();
+				let mut previous = ' ';
+				for c in chars.by_ref() {
+					if c == '\n' {
+						result.push(c);
+					};
+					if previous == '*' && c == '/' {
+						break;
+					};
+					previous = c;
+				};
+				result.push(' ');
+			},
+			_ => result.push(c),
+		};
+	};
+
+	result
+}
+
+
+/// Return the paths named by `#include "..."` (or `#include <...>`)
+/// directives in `input`, in order of appearance. Directives inside comments
+/// are ignored.
+fn find_includes(input: &str) -> Vec<String> {
+	strip_comments(input).lines()
+		.filter_map(|l| l.trim_start().strip_prefix('#'))
+		.filter_map(|l| l.trim_start().strip_prefix("include"))
+		.filter_map(|l| {
+			let l = l.trim();
+			let (open, close) = match l.chars().next()? {
+				'"' => ('"', '"'),
+				'<' => ('<', '>'),
+				_ => return None,
+			};
+			l.strip_prefix(open)?.split(close).next().map(String::from)
+		})
+		.collect()
+}
+
+
+pub(crate) fn try_parse_texconvert_path(path: &Path) -> PaaResult<HintPatterns> {
+	let mut stack: Vec<PathBuf> = vec![];
+	try_parse_texconvert_path_nested(path, &mut stack)
+}
+
+
+fn try_parse_texconvert_path_nested(path: &Path, stack: &mut Vec<PathBuf>) -> PaaResult<HintPatterns> {
+	let io_error = |e: std::io::Error| TexconvertIoError(path.display().to_string(), e.kind(), ErrorSource::new(e));
+	let canonical = path.canonicalize().map_err(io_error)?;
+
+	if stack.contains(&canonical) {
+		let chain = stack.iter()
+			.chain(std::iter::once(&canonical))
+			.map(|p| p.display().to_string())
+			.collect::<Vec<_>>()
+			.join(" -> ");
+		return Err(TexconvertIncludeCycle(chain));
+	};
+
+	if stack.len() >= MAX_INCLUDE_DEPTH {
+		return Err(TexconvertIncludeTooDeep(canonical.display().to_string()));
+	};
+
+	let input = std::fs::read_to_string(&canonical).map_err(io_error)?;
+	let base = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+	let mut result: HintPatterns = vec![];
+
+	stack.push(canonical);
+
+	for include in find_includes(&input) {
+		let included = try_parse_texconvert_path_nested(&base.join(include), stack)?;
+		result.extend(included);
+	};
+
+	// Hints defined in the including file are declared last, and take
+	// precedence over included hints with the same pattern
+	result.extend(try_parse_texconvert(&input)?);
+
+	let _ = stack.pop();
+
 	Ok(result)
 }
+
+
+#[test]
+fn preprocessor_directives() {
+	let config = "#define FOO 1\n#include \"missing.cfg\"\nclass TextureHints {\n#ifdef FOO\nclass sky { name = \"*_sky.*\"; format = \"DXT1\"; };\n#endif\n};\n";
+	let hints = TextureHints::try_parse_from_str(config).unwrap();
+	assert_eq!(hints.get("SKY").map(|s| s.format), Some(PaaType::Dxt1));
+	assert_eq!(find_includes(config), vec!["missing.cfg"]);
+
+	let commented = "// #include \"a.cfg\"\n/* #include \"b.cfg\"\n#include \"c.cfg\" */\nx = \"//\"; /**/\n#include <e.cfg> // f.cfg\n";
+	assert_eq!(find_includes(commented), vec!["e.cfg"]);
+}
+
+
+#[test]
+fn include_files() {
+	let dir = std::env::temp_dir().join(format!("a3-paa-include-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let main = dir.join("TexConvert.cfg");
+	let other = dir.join("other.cfg");
+	std::fs::write(&main, "#include \"other.cfg\"\nclass TextureHints { class sky { name = \"*_sky.*\"; format = \"DXT1\"; }; };\n").unwrap();
+	std::fs::write(&other, "class TextureHints { class sky { name = \"*_sky.*\"; format = \"DXT5\"; }; class ca { name = \"*_ca.*\"; format = \"ARGB4444\"; }; };\n").unwrap();
+
+	let hints = TextureHints::try_parse_from_path(&main).unwrap();
+	assert_eq!(hints.get("SKY").map(|s| s.format), Some(PaaType::Dxt1));
+	assert_eq!(hints.get("CA").map(|s| s.format), Some(PaaType::Argb4444));
+
+	std::fs::write(&other, "#include \"TexConvert.cfg\"\n").unwrap();
+	assert!(matches!(try_parse_texconvert_path(&main), Err(TexconvertIncludeCycle(_))));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,317 @@
+//! Pluggable DXTn (BC1-BC3) block compression, selected with
+//! [`PaaEncoder::with_codec`] or [`PaaMipmap::encode_with`].
+
+use std::borrow::Cow;
+#[cfg(test)] use std::sync::Arc;
+#[cfg(test)] use std::sync::atomic::{AtomicUsize, Ordering};
+
+use texpresso::Format as TextureFormat;
+
+#[cfg(doc)] use crate::{PaaMipmap, PaaEncoder};
+
+
+/// Speed/quality trade-off of DXTn compression, mapped to each
+/// [`DxtCodec`]'s own settings.  The default is [`Best`][`Self::Best`], which
+/// is what [`TexpressoCodec`] always used before codecs were selectable.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DxtQuality {
+	/// Fastest compression, lowest quality.
+	Fast,
+	/// Balance of speed and quality.
+	Normal,
+	/// Slowest compression, highest quality.
+	Best,
+}
+
+
+impl Default for DxtQuality {
+	fn default() -> Self {
+		Self::Best
+	}
+}
+
+
+/// DXTn block compressor and decompressor.
+///
+/// Pixels are 8-bit RGBA, `stride` bytes per row (at least `width * 4`).
+/// Buffers are at least as large as `width` and `height` require, and
+/// images being compressed are a multiple of 4 pixels in both dimensions;
+/// [`PaaMipmap`] checks both before calling a codec.
+pub trait DxtCodec: Send + Sync {
+	/// Compress `rgba` to BC1 (DXT1) blocks in `blocks`.
+	fn compress_bc1(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]);
+	/// Compress `rgba` to BC2 (DXT2, DXT3) blocks in `blocks`.
+	fn compress_bc2(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]);
+	/// Compress `rgba` to BC3 (DXT4, DXT5) blocks in `blocks`.
+	fn compress_bc3(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]);
+	/// Decompress BC1 (DXT1) `blocks` to `rgba`.
+	fn decompress_bc1(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]);
+	/// Decompress BC2 (DXT2, DXT3) `blocks` to `rgba`.
+	fn decompress_bc2(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]);
+	/// Decompress BC3 (DXT4, DXT5) `blocks` to `rgba`.
+	fn decompress_bc3(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]);
+}
+
+
+/// [`DxtCodec`] backed by `texpresso`, a pure-Rust port of libsquish; the
+/// default codec.
+///
+/// [`DxtQuality::Fast`] uses range fit, [`DxtQuality::Normal`] cluster fit,
+/// and [`DxtQuality::Best`] iterative cluster fit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TexpressoCodec;
+
+
+impl TexpressoCodec {
+	fn compress(format: TextureFormat, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		let algorithm = match quality {
+			DxtQuality::Fast => texpresso::Algorithm::RangeFit,
+			DxtQuality::Normal => texpresso::Algorithm::ClusterFit,
+			DxtQuality::Best => texpresso::Algorithm::IterativeClusterFit,
+		};
+
+		let params = texpresso::Params { algorithm, ..Default::default() };
+		format.compress(&packed_rows(rgba, width, height, stride), width, height, params, blocks);
+	}
+
+
+	fn decompress(format: TextureFormat, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		if stride == width * 4 {
+			format.decompress(blocks, width, height, rgba);
+			return;
+		};
+
+		let mut packed = vec![0; width * height * 4];
+		format.decompress(blocks, width, height, &mut packed);
+
+		for (row, packed_row) in rgba.chunks_mut(stride).zip(packed.chunks_exact(width * 4)) {
+			row[..width * 4].copy_from_slice(packed_row);
+		};
+	}
+}
+
+
+impl DxtCodec for TexpressoCodec {
+	fn compress_bc1(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		Self::compress(TextureFormat::Bc1, rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn compress_bc2(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		Self::compress(TextureFormat::Bc2, rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn compress_bc3(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		Self::compress(TextureFormat::Bc3, rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn decompress_bc1(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		Self::decompress(TextureFormat::Bc1, blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc2(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		Self::decompress(TextureFormat::Bc2, blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc3(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		Self::decompress(TextureFormat::Bc3, blocks, width, height, stride, rgba);
+	}
+}
+
+
+/// [`DxtCodec`] backed by Intel's ISPC Texture Compressor (`intel_tex_2`),
+/// several times faster than [`TexpressoCodec`] on large mipmaps.
+///
+/// ISPC has a single BC1 and BC3 mode, so [`DxtQuality`] is ignored for them.
+/// ISPC has no BC2 compressor and no decompressor at all: BC2 compression
+/// and all decompression use [`TexpressoCodec`].
+#[cfg(feature = "intel-tex")]
+#[cfg_attr(doc, doc(cfg(feature = "intel-tex")))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IntelTexCodec;
+
+
+#[cfg(feature = "intel-tex")]
+impl DxtCodec for IntelTexCodec {
+	#[allow(clippy::cast_possible_truncation)]
+	fn compress_bc1(&self, rgba: &[u8], width: usize, height: usize, stride: usize, _quality: DxtQuality, blocks: &mut [u8]) {
+		let surface = intel_tex_2::RgbaSurface { data: rgba, width: width as u32, height: height as u32, stride: stride as u32 };
+		intel_tex_2::bc1::compress_blocks_into(&surface, blocks);
+	}
+
+
+	fn compress_bc2(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		TexpressoCodec.compress_bc2(rgba, width, height, stride, quality, blocks);
+	}
+
+
+	#[allow(clippy::cast_possible_truncation)]
+	fn compress_bc3(&self, rgba: &[u8], width: usize, height: usize, stride: usize, _quality: DxtQuality, blocks: &mut [u8]) {
+		let surface = intel_tex_2::RgbaSurface { data: rgba, width: width as u32, height: height as u32, stride: stride as u32 };
+		intel_tex_2::bc3::compress_blocks_into(&surface, blocks);
+	}
+
+
+	fn decompress_bc1(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		TexpressoCodec.decompress_bc1(blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc2(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		TexpressoCodec.decompress_bc2(blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc3(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		TexpressoCodec.decompress_bc3(blocks, width, height, stride, rgba);
+	}
+}
+
+
+/// `rgba` without the padding at the end of each row, if any.
+fn packed_rows(rgba: &[u8], width: usize, height: usize, stride: usize) -> Cow<'_, [u8]> {
+	if stride == width * 4 {
+		return Cow::Borrowed(&rgba[..width * height * 4]);
+	};
+
+	Cow::Owned(rgba.chunks(stride).take(height).flat_map(|row| &row[..width * 4]).copied().collect())
+}
+
+
+/// [`TexpressoCodec`] counting the calls made to it, for tests of what
+/// encoders and decoders compress and decompress
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CountingCodec(pub(crate) Arc<AtomicUsize>);
+
+
+#[cfg(test)]
+impl CountingCodec {
+	fn count(&self) {
+		let _ = self.0.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+
+#[cfg(test)]
+impl DxtCodec for CountingCodec {
+	fn compress_bc1(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		self.count();
+		TexpressoCodec.compress_bc1(rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn compress_bc2(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		self.count();
+		TexpressoCodec.compress_bc2(rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn compress_bc3(&self, rgba: &[u8], width: usize, height: usize, stride: usize, quality: DxtQuality, blocks: &mut [u8]) {
+		self.count();
+		TexpressoCodec.compress_bc3(rgba, width, height, stride, quality, blocks);
+	}
+
+
+	fn decompress_bc1(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		self.count();
+		TexpressoCodec.decompress_bc1(blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc2(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		self.count();
+		TexpressoCodec.decompress_bc2(blocks, width, height, stride, rgba);
+	}
+
+
+	fn decompress_bc3(&self, blocks: &[u8], width: usize, height: usize, stride: usize, rgba: &mut [u8]) {
+		self.count();
+		TexpressoCodec.decompress_bc3(blocks, width, height, stride, rgba);
+	}
+}
+
+
+#[cfg(test)]
+fn codec_roundtrip_psnr(codec: &dyn DxtCodec, quality: DxtQuality) -> [f64; 3] {
+	use crate::{PaaMipmap, PaaType};
+	use crate::imageops::image_difference;
+
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, (0xFF - y * 2) as u8]));
+
+	[PaaType::Dxt1, PaaType::Dxt3, PaaType::Dxt5].map(|paatype| {
+		let mipmap = PaaMipmap::encode_with(paatype, &image, codec, quality).unwrap();
+		let decoded = mipmap.decode_with(codec).unwrap();
+		let mut expected = image.clone();
+
+		// DXT1 alpha is binary
+		if paatype == PaaType::Dxt1 {
+			expected.pixels_mut().for_each(|p| p.0[3] = 0xFF);
+		};
+
+		image_difference(&decoded, &expected).unwrap().total_psnr()
+	})
+}
+
+
+#[test]
+fn texpresso_codec_roundtrip() {
+	for quality in [DxtQuality::Fast, DxtQuality::Normal, DxtQuality::Best] {
+		for psnr in codec_roundtrip_psnr(&TexpressoCodec, quality) {
+			assert!(psnr > 35.0, "{quality:?}: PSNR {psnr}");
+		};
+	};
+}
+
+
+#[cfg(feature = "intel-tex")]
+#[test]
+fn intel_tex_codec_roundtrip() {
+	for quality in [DxtQuality::Fast, DxtQuality::Normal, DxtQuality::Best] {
+		for psnr in codec_roundtrip_psnr(&IntelTexCodec, quality) {
+			assert!(psnr > 35.0, "{quality:?}: PSNR {psnr}");
+		};
+	};
+}
+
+
+#[test]
+fn texpresso_codec_default_unchanged() {
+	use crate::{PaaMipmap, PaaType};
+
+	let image = image::RgbaImage::from_fn(32, 16, |x, y| image::Rgba([(x * 8) as u8, (y * 16) as u8, 0x40, (0xFF - x * 4) as u8]));
+
+	for (paatype, format) in [(PaaType::Dxt1, TextureFormat::Bc1), (PaaType::Dxt3, TextureFormat::Bc2), (PaaType::Dxt5, TextureFormat::Bc3)] {
+		let mut expected = vec![0; format.compressed_size(32, 16)];
+		let params = texpresso::Params { algorithm: texpresso::Algorithm::IterativeClusterFit, ..Default::default() };
+		format.compress(image.as_raw(), 32, 16, params, &mut expected);
+
+		let mipmap = PaaMipmap::encode(paatype, &image).unwrap();
+		assert_eq!(mipmap.data, expected);
+
+		let mut decoded = vec![0; 32 * 16 * 4];
+		format.decompress(&expected, 32, 16, &mut decoded);
+		assert_eq!(mipmap.decode().unwrap().into_raw(), decoded);
+	};
+
+	// Padded rows give the same blocks and pixels
+	let stride = 32 * 4 + 12;
+	let padded = image.rows().flat_map(|row| row.flat_map(|p| p.0).chain([0xAA; 12])).collect::<Vec<u8>>();
+	let mut tight_blocks = vec![0; 32 * 16];
+	let mut padded_blocks = vec![0; 32 * 16];
+	TexpressoCodec.compress_bc3(image.as_raw(), 32, 16, 32 * 4, DxtQuality::Best, &mut tight_blocks);
+	TexpressoCodec.compress_bc3(&padded, 32, 16, stride, DxtQuality::Best, &mut padded_blocks);
+	assert_eq!(tight_blocks, padded_blocks);
+
+	let mut rgba = vec![0; stride * 16];
+	TexpressoCodec.decompress_bc3(&padded_blocks, 32, 16, stride, &mut rgba);
+	let unpadded = rgba.chunks(stride).flat_map(|row| &row[..32 * 4]).copied().collect::<Vec<u8>>();
+	let mut expected = vec![0; 32 * 16 * 4];
+	TextureFormat::Bc3.decompress(&tight_blocks, 32, 16, &mut expected);
+	assert_eq!(unpadded, expected);
+}
@@ -0,0 +1,282 @@
+use ddsfile::{Caps2, Dds, D3DFormat, DxgiFormat, MiscFlag, NewD3dParams};
+use image::RgbaImage;
+
+use crate::{imageops, PaaImage, PaaMipmap, PaaResult, PaaType};
+use crate::PaaError::*;
+
+
+/// Pixel data of a DDS file that can be converted to PAA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DdsSource {
+	/// DXTn blocks, copied to PAA as is.
+	Dxt(PaaType),
+	/// 32-bit pixels, in BGR(A/X) order if `bgr` is set, or RGB(A/X) order
+	/// otherwise; the fourth byte is ignored unless `alpha` is set.
+	Uncompressed { bgr: bool, alpha: bool },
+}
+
+
+impl DdsSource {
+	fn of(dds: &Dds) -> PaaResult<Self> {
+		let source = match (dds.get_d3d_format(), dds.get_dxgi_format()) {
+			(Some(D3DFormat::DXT1), _) | (_, Some(DxgiFormat::BC1_UNorm | DxgiFormat::BC1_UNorm_sRGB)) => DdsSource::Dxt(PaaType::Dxt1),
+			(Some(D3DFormat::DXT2), _) => DdsSource::Dxt(PaaType::Dxt2),
+			(Some(D3DFormat::DXT3), _) | (_, Some(DxgiFormat::BC2_UNorm | DxgiFormat::BC2_UNorm_sRGB)) => DdsSource::Dxt(PaaType::Dxt3),
+			(Some(D3DFormat::DXT4), _) => DdsSource::Dxt(PaaType::Dxt4),
+			(Some(D3DFormat::DXT5), _) | (_, Some(DxgiFormat::BC3_UNorm | DxgiFormat::BC3_UNorm_sRGB)) => DdsSource::Dxt(PaaType::Dxt5),
+			(Some(D3DFormat::A8R8G8B8), _) | (_, Some(DxgiFormat::B8G8R8A8_UNorm | DxgiFormat::B8G8R8A8_UNorm_sRGB)) =>
+				DdsSource::Uncompressed { bgr: true, alpha: true },
+			(Some(D3DFormat::X8R8G8B8), _) | (_, Some(DxgiFormat::B8G8R8X8_UNorm | DxgiFormat::B8G8R8X8_UNorm_sRGB)) =>
+				DdsSource::Uncompressed { bgr: true, alpha: false },
+			(Some(D3DFormat::A8B8G8R8), _) | (_, Some(DxgiFormat::R8G8B8A8_UNorm | DxgiFormat::R8G8B8A8_UNorm_sRGB)) =>
+				DdsSource::Uncompressed { bgr: false, alpha: true },
+			(Some(D3DFormat::X8B8G8R8), _) => DdsSource::Uncompressed { bgr: false, alpha: false },
+			(d3d, dxgi) => return Err(DdsFormatUnsupported(format!("{d3d:?}/{dxgi:?}"))),
+		};
+
+		Ok(source)
+	}
+
+
+	/// Size in bytes of a `width`x`height` mipmap, with uncompressed rows
+	/// padded to a multiple of `row_alignment` bytes.
+	fn level_size(self, (width, height): (u32, u32), row_alignment: u32) -> usize {
+		let size = match self {
+			DdsSource::Dxt(t) => {
+				let block_size = if t == PaaType::Dxt1 { 8 } else { 16 };
+				((width + 3) / 4).max(1) * ((height + 3) / 4).max(1) * block_size
+			},
+
+			DdsSource::Uncompressed { .. } => row_pitch(width, row_alignment) * height,
+		};
+
+		size as usize
+	}
+}
+
+
+/// Bytes per row of a 32-bit `width` pixels wide mipmap, padded to a multiple
+/// of `row_alignment`.
+fn row_pitch(width: u32, row_alignment: u32) -> u32 {
+	let tight = width * 4;
+
+	match tight % row_alignment {
+		0 => tight,
+		r => tight + (row_alignment - r),
+	}
+}
+
+
+/// Find the row alignment of an uncompressed DDS file from the pitch of its
+/// first mipmap.
+fn row_alignment(width: u32, pitch: Option<u32>) -> PaaResult<u32> {
+	match pitch {
+		Some(pitch) if pitch > width * 4 => (2..=12)
+			.map(|e| 1u32 << e)
+			.find(|a| row_pitch(width, *a) == pitch)
+			.ok_or_else(|| DdsFormatUnsupported(format!("row pitch {pitch} for a width of {width} pixels"))),
+		_ => Ok(1),
+	}
+}
+
+
+/// Number of 2D layers of `dds`, counting every cube map face, and whether
+/// they are cube map faces.  Layers are laid out in DDS order, so cube map
+/// faces go +X, -X, +Y, -Y, +Z, -Z.
+#[cfg_attr(doc, doc(cfg(feature = "dds")))]
+pub fn dds_layer_count(dds: &Dds) -> (u32, bool) {
+	match &dds.header10 {
+		// DX10 headers count cubes rather than faces
+		Some(h) if h.misc_flag.contains(MiscFlag::TEXTURECUBE) => (h.array_size * 6, true),
+		_ => (dds.get_num_array_layers(), dds.header.caps2.contains(Caps2::CUBEMAP)),
+	}
+}
+
+
+impl PaaImage {
+	/// Convert array layer or cube map face #`layer` (0-based) of `dds` to a
+	/// [`PaaImage`][Self].  DXTn blocks are copied as is, down to the 4x4
+	/// mipmap; 32-bit pixels are converted to [`PaaType::Argb8888`].  Mipmaps
+	/// missing from the DDS file are
+	/// [generated][`Self::generate_missing_mipmaps`], and AVGC, MAXC and FLAG
+	/// taggs are [computed][`Self::recompute_taggs`].
+	///
+	/// # Errors
+	/// - [`DdsFormatUnsupported`]: The DDS pixel format has no PAA
+	///   counterpart, or its row pitch is not understood.
+	/// - [`DdsLayerOutOfRange`]: `layer` is not below [`dds_layer_count`].
+	/// - [`MipmapDataBeyondEof`]: The DDS data of the layer is truncated.
+	/// - [`MipmapTooLarge`]: The DDS dimensions overflow a [`u16`].
+	/// - [`DxtMipmapDimensionsNotMultipleOf4`]: A DXTn mipmap of at least 4x4
+	///   has dimensions that are not multiples of 4.
+	/// - Any error of [`Self::generate_missing_mipmaps`] or
+	///   [`Self::recompute_taggs`].
+	#[cfg_attr(doc, doc(cfg(feature = "dds")))]
+	pub fn from_dds(dds: &Dds, layer: u32) -> PaaResult<Self> {
+		let source = DdsSource::of(dds)?;
+		let (width, height) = (dds.get_width(), dds.get_height());
+		let (layers, _) = dds_layer_count(dds);
+
+		if layer >= layers {
+			return Err(DdsLayerOutOfRange(layer, layers));
+		};
+
+		let (paatype, alignment) = match source {
+			DdsSource::Dxt(paatype) => (paatype, 1),
+			DdsSource::Uncompressed { .. } => (PaaType::Argb8888, row_alignment(width, dds.header.pitch)?),
+		};
+
+		// DXTn dimensions stop halving at 4 in PAA, but not in DDS; both hold a
+		// single block at that point
+		let min_dimension = if paatype.is_dxtn() { 4 } else { 1 };
+		let dimensions = imageops::mipmap_dimensions((width, height), 1)
+			.take(dds.get_num_mipmap_levels() as usize)
+			.zip(imageops::mipmap_dimensions((width, height), min_dimension))
+			.collect::<Vec<_>>();
+
+		let layer_size: usize = dimensions.iter().map(|(d, _)| source.level_size(*d, alignment)).sum();
+		let layer_offset = layer_size * layer as usize;
+		let data = dds.data.get(layer_offset..layer_offset + layer_size).ok_or(MipmapDataBeyondEof)?;
+
+		let mut cursor: usize = 0;
+		let mut mipmaps = vec![];
+
+		for ((w, h), (paa_width, paa_height)) in dimensions {
+			let level = &data[cursor..cursor + source.level_size((w, h), alignment)];
+			cursor += level.len();
+
+			let width: u16 = paa_width.try_into().map_err(|_| MipmapTooLarge)?;
+			let height: u16 = paa_height.try_into().map_err(|_| MipmapTooLarge)?;
+
+			let mipmap = match source {
+				DdsSource::Dxt(_) => {
+					if width % 4 != 0 || height % 4 != 0 {
+						return Err(DxtMipmapDimensionsNotMultipleOf4(width, height));
+					};
+
+					let compression = PaaMipmap::suggest_compression(paatype, width, height);
+					PaaMipmap { width, height, compression, paatype, data: level.to_owned(), payload: None }
+				},
+
+				DdsSource::Uncompressed { bgr, alpha } => {
+					let pitch = row_pitch(w, alignment);
+					let image = RgbaImage::from_fn(w, h, |x, y| {
+						let start = (y * pitch + x * 4) as usize;
+						let p = &level[start..start + 4];
+						let (r, b) = if bgr { (p[2], p[0]) } else { (p[0], p[2]) };
+						image::Rgba([r, p[1], b, if alpha { p[3] } else { 0xFF }])
+					});
+
+					PaaMipmap::encode(paatype, &image)?
+				},
+			};
+
+			mipmaps.push(Ok(mipmap));
+		};
+
+		let mut paa = PaaImage { paatype, taggs: vec![], palette: None, mipmaps, trailer: vec![] };
+		let _ = paa.generate_missing_mipmaps()?;
+		paa.recompute_taggs()?;
+
+		Ok(paa)
+	}
+
+
+	/// Convert the image to a DDS texture with every mipmap.  DXTn blocks are
+	/// copied as is, as are the pixels of uncompressed ARGB and AI88 types.
+	///
+	/// # Errors
+	/// - [`DdsFormatUnsupported`]: The [`PaaType`] has no DDS counterpart,
+	///   or `ddsfile` cannot lay out the texture.
+	/// - Any error of [`Self::validate`]; DDS files cannot skip mipmap levels.
+	#[cfg_attr(doc, doc(cfg(feature = "dds")))]
+	pub fn to_dds(&self) -> PaaResult<Dds> {
+		let format = match self.paatype {
+			PaaType::Dxt1 => D3DFormat::DXT1,
+			PaaType::Dxt2 => D3DFormat::DXT2,
+			PaaType::Dxt3 => D3DFormat::DXT3,
+			PaaType::Dxt4 => D3DFormat::DXT4,
+			PaaType::Dxt5 => D3DFormat::DXT5,
+			PaaType::Argb8888 => D3DFormat::A8R8G8B8,
+			PaaType::Argb1555 => D3DFormat::A1R5G5B5,
+			PaaType::Argb4444 => D3DFormat::A4R4G4B4,
+			PaaType::Ai88 => D3DFormat::A8L8,
+			t => return Err(DdsFormatUnsupported(format!("{t:?}"))),
+		};
+
+		self.validate()?;
+
+		let mipmaps = self.mipmaps.iter().map_while(|m| m.as_ref().ok()).collect::<Vec<_>>();
+		let (width, height) = (mipmaps[0].width, mipmaps[0].height);
+
+		let params = NewD3dParams {
+			height: height.into(),
+			width: width.into(),
+			depth: None,
+			format,
+			mipmap_levels: Some(mipmaps.len().try_into()?),
+			caps2: None,
+		};
+		let mut dds = Dds::new_d3d(params).map_err(|e| DdsFormatUnsupported(format!("{format:?}: {e}")))?;
+		let data = mipmaps.iter().flat_map(|m| m.data.iter().copied()).collect::<Vec<u8>>();
+
+		if data.len() != dds.data.len() {
+			return Err(DdsFormatUnsupported(format!("{format:?} mipmaps of {} bytes, instead of {}", data.len(), dds.data.len())));
+		};
+
+		dds.data = data;
+
+		Ok(dds)
+	}
+}
+
+
+#[cfg(test)]
+use crate::{PaaEncoder, TextureEncodingSettings};
+
+
+#[cfg(test)]
+fn dds_roundtrip(paatype: PaaType) {
+	let image = RgbaImage::from_fn(32, 16, |x, y| image::Rgba([x as u8 * 8, y as u8 * 16, 0x80, (x * y) as u8]));
+	let settings = TextureEncodingSettings::builder().format(paatype).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	let dds = paa.to_dds().unwrap();
+	assert_eq!((dds.get_width(), dds.get_height()), (32, 16));
+
+	let mut bytes = vec![];
+	dds.write(&mut bytes).unwrap();
+	let dds = Dds::read(std::io::Cursor::new(bytes)).unwrap();
+	assert_eq!(dds_layer_count(&dds), (1, false));
+
+	let roundtrip = PaaImage::from_dds(&dds, 0).unwrap();
+	assert_eq!(roundtrip.paatype, paatype);
+	assert_eq!(roundtrip.mipmaps.len(), paa.mipmaps.len());
+
+	for (a, b) in roundtrip.mipmaps.iter().zip(&paa.mipmaps) {
+		let (a, b) = (a.as_ref().unwrap(), b.as_ref().unwrap());
+		assert_eq!((a.width, a.height, &a.data), (b.width, b.height, &b.data));
+	};
+
+	assert!(matches!(PaaImage::from_dds(&dds, 1), Err(DdsLayerOutOfRange(1, 1))));
+}
+
+
+#[test]
+fn dds_roundtrip_dxt1() {
+	dds_roundtrip(PaaType::Dxt1);
+}
+
+
+#[test]
+fn dds_roundtrip_dxt5() {
+	dds_roundtrip(PaaType::Dxt5);
+}
+
+
+#[test]
+fn dds_format_unsupported() {
+	let params = NewD3dParams { height: 4, width: 4, depth: None, format: D3DFormat::R5G6B5, mipmap_levels: None, caps2: None };
+	let dds = Dds::new_d3d(params).unwrap();
+	assert!(matches!(PaaImage::from_dds(&dds, 0), Err(DdsFormatUnsupported(_))));
+}
@@ -1,26 +1,132 @@
-use crate::PaaImage;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{ArgbSwizzle, ChannelSwizzle, ChannelSwizzleData, ChannelSwizzleFill, DxtCodec, PaaImage, PaaType, Tagg, TexpressoCodec, Transparency};
 use crate::PaaResult;
 use crate::PaaError::*;
+#[cfg(test)] use crate::codec::CountingCodec;
 
-use image::RgbaImage;
+use image::{ImageBuffer, Rgba, RgbaImage};
 
 
 /// Wrapper around [`PaaImage`] that decodes mipmaps into [`image::RgbaImage`]
+///
+/// DXTn mipmaps are decompressed with [`TexpressoCodec`] unless another
+/// [`DxtCodec`] is set with [`with_codec`][`Self::with_codec`].  Decoded
+/// mipmaps are not kept unless [`with_cache`][`Self::with_cache`] is used.
 #[allow(missing_debug_implementations)]
 #[derive(Clone)]
 pub struct PaaDecoder {
 	paa: PaaImage,
+	options: DecodeOptions,
+	codec: Arc<dyn DxtCodec>,
+	cache: Option<Arc<Mutex<DecodeCache>>>,
+}
+
+
+/// Post-processing of decoded mipmaps by [`PaaDecoder`], applied in field
+/// order
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+	/// Apply the transparency of [`Tagg::Flag`]: alpha is made opaque for
+	/// [`Transparency::None`], and either transparent or opaque for
+	/// [`Transparency::AlphaNotInterpolated`].
+	pub respect_flag: bool,
+	/// Restore the channels of the original image by inverting the swizzle
+	/// of [`Tagg::Swiz`], if any (see [`ArgbSwizzle::inverse`]).
+	pub unswizzle: bool,
+	/// Multiply color channels by alpha.  Mipmaps of every type are stored
+	/// with straight alpha, [`PaaType::Dxt2`] and [`PaaType::Dxt4`] included,
+	/// as [`PaaMipmap::encode`][`crate::PaaMipmap::encode`] writes them.
+	pub premultiply_alpha: bool,
+	/// Make alpha opaque.
+	pub opaque: bool,
+}
+
+
+/// Mipmaps decoded by a [`PaaDecoder`], before post-processing
+#[derive(Default)]
+struct DecodeCache {
+	max_bytes: usize,
+	images: BTreeMap<usize, RgbaImage>,
+}
+
+
+impl DecodeCache {
+	fn bytes(&self) -> usize {
+		self.images.values().map(|i| i.as_raw().len()).sum()
+	}
+
+
+	/// Keep `image` as mipmap #`index`, dropping the largest mipmaps until
+	/// the cache fits in `max_bytes`.
+	fn insert(&mut self, index: usize, image: RgbaImage) {
+		if image.as_raw().len() > self.max_bytes {
+			return;
+		};
+
+		let _ = self.images.insert(index, image);
+
+		while self.bytes() > self.max_bytes {
+			let largest = self.images.iter()
+				.filter(|(i, _)| **i != index)
+				.max_by_key(|(_, image)| image.as_raw().len())
+				.map(|(i, _)| *i);
+
+			match largest {
+				Some(largest) => { let _ = self.images.remove(&largest); },
+				None => break,
+			};
+		};
+	}
 }
 
 
 impl PaaDecoder {
 	/// Create an instance of `Self` from a [`PaaImage`].
 	pub fn with_paa(paa: PaaImage) -> Self {
-		Self { paa }
+		Self { paa, options: DecodeOptions::default(), codec: Arc::new(TexpressoCodec), cache: None }
+	}
+
+
+	/// Set the post-processing of decoded mipmaps.
+	pub fn with_options(self, options: DecodeOptions) -> Self {
+		Self { options, ..self }
+	}
+
+
+	/// Decompress DXTn mipmaps with `codec`.
+	pub fn with_codec(self, codec: impl DxtCodec + 'static) -> Self {
+		Self { codec: Arc::new(codec), ..self }
 	}
 
 
-	/// Decode mipmap at [`PaaImage::mipmaps`]`[index]`.
+	/// Keep decoded mipmaps, up to `max_bytes` of pixels, so that decoding
+	/// one again only applies the [`DecodeOptions`].  The largest mipmaps are
+	/// dropped first when the cache is full.  Clones of `self` share the
+	/// cache.
+	pub fn with_cache(self, max_bytes: usize) -> Self {
+		let cache = DecodeCache { max_bytes, ..Default::default() };
+		Self { cache: Some(Arc::new(Mutex::new(cache))), ..self }
+	}
+
+
+	/// Drop all cached mipmaps, if caching.
+	pub fn clear_cache(&self) {
+		if let Some(cache) = &self.cache {
+			cache.lock().unwrap_or_else(PoisonError::into_inner).images.clear();
+		};
+	}
+
+
+	/// Swizzle of the [`Tagg::Swiz`] of the PAA, if any.
+	pub fn swizzle(&self) -> Option<ArgbSwizzle> {
+		self.paa.taggs.iter().find_map(|t| if let Tagg::Swiz { swizzle } = t { Some(*swizzle) } else { None })
+	}
+
+
+	/// Decode mipmap at [`PaaImage::mipmaps`]`[index]`, and apply the
+	/// [`DecodeOptions`].
 	///
 	/// # Errors
 	/// - [`MipmapIndexOutOfRange`]: `index` is outside of bounds of [`PaaImage::mipmaps`].
@@ -29,13 +135,87 @@ impl PaaDecoder {
 	/// # Panics
 	/// - If [`image::RgbaImage::from_vec`] fails.
 	pub fn decode_nth(&self, index: usize) -> PaaResult<RgbaImage> {
+		let cache = match &self.cache {
+			Some(cache) => cache,
+			None => {
+				let mut image = self.decode_uncached(index)?;
+				self.postprocess(&mut image);
+				return Ok(image);
+			},
+		};
+
+		let cached = cache.lock().unwrap_or_else(PoisonError::into_inner).images.get(&index).cloned();
+
+		let mut image = match cached {
+			Some(image) => image,
+			None => {
+				let image = self.decode_uncached(index)?;
+				cache.lock().unwrap_or_else(PoisonError::into_inner).insert(index, image.clone());
+				image
+			},
+		};
+
+		self.postprocess(&mut image);
+
+		Ok(image)
+	}
+
+
+	/// Decode every mipmap, as [`iter`][`Self::iter`].
+	pub fn decode_all(&self) -> Vec<PaaResult<RgbaImage>> {
+		self.iter().map(|(_, image)| image).collect()
+	}
+
+
+	/// Iterate over the indices and [decoded][`Self::decode_nth`] images of
+	/// all mipmaps, decoding each one only as the iterator reaches it.
+	/// Mipmaps that contain errors are yielded as such, but a trailing
+	/// [`EmptyMipmap`] (the terminator of a PAA without [`Tagg::Offs`]) is
+	/// not.
+	pub fn iter(&self) -> DecodeIter<'_> {
+		let count = match self.paa.mipmaps.as_slice() {
+			[rest @ .., Err(EmptyMipmap)] => rest.len(),
+			all => all.len(),
+		};
+
+		DecodeIter { decoder: self, index: 0, count }
+	}
+
+
+	/// Decode the smallest mipmap at least `target_w` wide and `target_h`
+	/// high, or the largest mipmap if none is, skipping mipmaps that contain
+	/// errors.  Return its index along with the image.
+	///
+	/// # Errors
+	/// - [`NoMipmaps`]: No mipmap was read successfully.
+	/// - Any error of [`decode_nth`][`Self::decode_nth`].
+	///
+	/// # Panics
+	/// - As [`decode_nth`][`Self::decode_nth`].
+	pub fn decode_nearest(&self, target_w: u32, target_h: u32) -> PaaResult<(usize, RgbaImage)> {
+		let levels = self.paa.mipmaps
+			.iter()
+			.enumerate()
+			.filter_map(|(index, m)| m.as_ref().ok().map(|m| (index, u32::from(m.width), u32::from(m.height))));
+
+		let (index, _, _) = levels.clone()
+			.filter(|(_, w, h)| *w >= target_w && *h >= target_h)
+			.min_by_key(|(_, w, h)| w * h)
+			.or_else(|| levels.max_by_key(|(_, w, h)| w * h))
+			.ok_or(NoMipmaps)?;
+
+		Ok((index, self.decode_nth(index)?))
+	}
+
+
+	fn decode_uncached(&self, index: usize) -> PaaResult<RgbaImage> {
 		let mipmap = self.paa.mipmaps
 			.get(index)
 			.ok_or(MipmapIndexOutOfRange)?
 			.as_ref()
 			.map_err(Clone::clone)?;
 
-		mipmap.decode()
+		mipmap.decode_with(self.codec.as_ref())
 	}
 
 
@@ -50,4 +230,417 @@ impl PaaDecoder {
 	pub fn decode_first(&self) -> PaaResult<RgbaImage> {
 		self.decode_nth(0)
 	}
+
+
+	/// Decode mipmap at [`PaaImage::mipmaps`]`[index]` at 16 bits per
+	/// channel (see [`PaaMipmap::decode_rgba16`][`crate::PaaMipmap::decode_rgba16`]),
+	/// and apply the [`DecodeOptions`].  The cache and codec of `self` are
+	/// not used.
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: `index` is outside of bounds of [`PaaImage::mipmaps`].
+	/// - other: [`PaaResult<PaaMipmap>`] at given index contains an error, or
+	///   it fails to decode.
+	pub fn decode_nth_rgba16(&self, index: usize) -> PaaResult<ImageBuffer<Rgba<u16>, Vec<u16>>> {
+		let mut image = self.paa.mipmaps
+			.get(index)
+			.ok_or(MipmapIndexOutOfRange)?
+			.as_ref()
+			.map_err(Clone::clone)?
+			.decode_rgba16()?;
+
+		self.postprocess(&mut image);
+
+		Ok(image)
+	}
+
+
+	fn postprocess<C: Channel>(&self, image: &mut ImageBuffer<Rgba<C>, Vec<C>>) {
+		let set_alpha = |image: &mut ImageBuffer<Rgba<C>, Vec<C>>, f: fn(C) -> C| {
+			for pixel in image.pixels_mut() {
+				pixel.0[3] = f(pixel.0[3]);
+			};
+		};
+
+		if self.options.respect_flag {
+			let transparency = self.paa.taggs.iter()
+				.find_map(|t| if let Tagg::Flag { transparency } = t { Some(*transparency) } else { None });
+
+			match transparency {
+				Some(Transparency::None) => set_alpha(image, |_| C::MAX),
+				Some(Transparency::AlphaNotInterpolated) => set_alpha(image, |a| if a.widen() <= C::MAX.widen() / 2 { C::ZERO } else { C::MAX }),
+				Some(Transparency::AlphaInterpolated) | None => (),
+			};
+		};
+
+		if let Some(swizzle) = self.swizzle().filter(|_| self.options.unswizzle) {
+			let inverse = swizzle.inverse();
+
+			for pixel in image.pixels_mut() {
+				let src = pixel.0;
+
+				for channel in [inverse.a, inverse.r, inverse.g, inverse.b] {
+					pixel.0[channel.target as usize] = swizzle_channel(channel, &src);
+				};
+			};
+		};
+
+		if self.options.premultiply_alpha {
+			for pixel in image.pixels_mut() {
+				let [r, g, b, a] = pixel.0;
+				pixel.0 = [premultiply(r, a), premultiply(g, a), premultiply(b, a), a];
+			};
+		};
+
+		if self.options.opaque {
+			set_alpha(image, |_| C::MAX);
+		};
+	}
+}
+
+
+/// Channel of the images [`PaaDecoder`] decodes to
+trait Channel: image::Primitive + 'static {
+	const ZERO: Self;
+	const MAX: Self;
+
+	fn widen(self) -> u32;
+	fn narrow(value: u32) -> Self;
+}
+
+
+impl Channel for u8 {
+	const ZERO: Self = 0;
+	const MAX: Self = u8::MAX;
+
+	fn widen(self) -> u32 { u32::from(self) }
+	fn narrow(value: u32) -> Self { u8::try_from(value).unwrap_or(u8::MAX) }
+}
+
+
+impl Channel for u16 {
+	const ZERO: Self = 0;
+	const MAX: Self = u16::MAX;
+
+	fn widen(self) -> u32 { u32::from(self) }
+	fn narrow(value: u32) -> Self { u16::try_from(value).unwrap_or(u16::MAX) }
+}
+
+
+/// Value of the target channel of `channel` for the pixel `src`, as
+/// [`ChannelSwizzle::to_subpixel_map`] at any depth
+fn swizzle_channel<C: Channel>(channel: ChannelSwizzle, src: &[C; 4]) -> C {
+	match channel.data {
+		ChannelSwizzleData::Source { neg_flag: false, source } => src[source as usize],
+		ChannelSwizzleData::Source { neg_flag: true, source } => C::narrow(C::MAX.widen() - src[source as usize].widen()),
+		ChannelSwizzleData::Fill { value: ChannelSwizzleFill::FillFF } => C::MAX,
+		ChannelSwizzleData::Fill { value: ChannelSwizzleFill::Fill00 } => C::ZERO,
+	}
+}
+
+
+/// `channel * alpha / max`, rounded
+fn premultiply<C: Channel>(channel: C, alpha: C) -> C {
+	let max = C::MAX.widen();
+	C::narrow((channel.widen() * alpha.widen() + max / 2) / max)
+}
+
+
+/// Iterator over the decoded mipmaps of a [`PaaDecoder`], returned by
+/// [`PaaDecoder::iter`]
+#[allow(missing_debug_implementations)]
+pub struct DecodeIter<'a> {
+	decoder: &'a PaaDecoder,
+	index: usize,
+	count: usize,
+}
+
+
+impl Iterator for DecodeIter<'_> {
+	type Item = (usize, PaaResult<RgbaImage>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.index >= self.count {
+			return None;
+		};
+
+		let index = self.index;
+		self.index += 1;
+
+		Some((index, self.decoder.decode_nth(index)))
+	}
+
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let remaining = self.count - self.index;
+		(remaining, Some(remaining))
+	}
+}
+
+
+impl ExactSizeIterator for DecodeIter<'_> {}
+
+
+impl<'a> IntoIterator for &'a PaaDecoder {
+	type Item = (usize, PaaResult<RgbaImage>);
+	type IntoIter = DecodeIter<'a>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+
+#[test]
+fn decode_options() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([x as u8 * 32, y as u8 * 32, 0xC0, 0x70]));
+	let swizzle = ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap();
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).swizzle(swizzle).build();
+	let mut paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+	assert!(paa.taggs.contains(&Tagg::Swiz { swizzle }));
+
+	let unswizzle = DecodeOptions { unswizzle: true, ..Default::default() };
+	assert_ne!(PaaDecoder::with_paa(paa.clone()).decode_first().unwrap(), image);
+	assert_eq!(PaaDecoder::with_paa(paa.clone()).with_options(unswizzle).decode_first().unwrap(), image);
+
+	let opaque = PaaDecoder::with_paa(paa.clone()).with_options(DecodeOptions { opaque: true, ..unswizzle }).decode_first().unwrap();
+	assert!(opaque.pixels().all(|p| p.0[3] == 0xFF));
+
+	// The flag applies to the stored alpha, i.e. the swizzled red channel
+	paa.taggs.push(Tagg::Flag { transparency: Transparency::AlphaNotInterpolated });
+	let binary = PaaDecoder::with_paa(paa).with_options(DecodeOptions { respect_flag: true, ..unswizzle }).decode_first().unwrap();
+	assert!(binary.pixels().all(|p| p.0[0] == 0x00 || p.0[0] == 0xFF));
+	assert_eq!(binary.get_pixel(0, 0).0, [0x00, 0x00, 0xC0, 0x70]);
+	assert_eq!(binary.get_pixel(7, 0).0, [0xFF, 0x00, 0xC0, 0x70]);
+}
+
+
+#[test]
+fn decode_concurrently() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let encode = |paatype| {
+		let image = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+		let settings = TextureEncodingSettings::builder().format(paatype).build();
+		PaaEncoder::with_image_and_settings(image, settings).encode().unwrap()
+	};
+
+	let decoders = [PaaDecoder::with_paa(encode(PaaType::Dxt1)), PaaDecoder::with_paa(encode(PaaType::Argb4444))];
+	let expected = decoders.iter().map(|d| d.decode_first().unwrap()).collect::<Vec<_>>();
+
+	let decoded = std::thread::scope(|scope| {
+		let handles = decoders.iter().map(|d| scope.spawn(move || d.decode_first().unwrap())).collect::<Vec<_>>();
+		handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+	});
+
+	assert_eq!(decoded, expected);
+}
+
+
+#[test]
+fn decode_cache() {
+	use std::sync::atomic::Ordering;
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 0x20, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	let codec = CountingCodec::default();
+	let count = Arc::clone(&codec.0);
+	let uncached = PaaDecoder::with_paa(paa.clone()).with_codec(codec);
+	let expected = uncached.decode_first().unwrap();
+	assert_eq!(uncached.decode_first().unwrap(), expected);
+	assert_eq!(count.load(Ordering::Relaxed), 2);
+
+	let codec = CountingCodec::default();
+	let count = Arc::clone(&codec.0);
+	let cached = PaaDecoder::with_paa(paa).with_codec(codec).with_cache(32 * 32 * 4 + 16 * 16 * 4);
+	assert_eq!(cached.decode_first().unwrap(), expected);
+	assert_eq!(cached.decode_first().unwrap(), expected);
+	assert_eq!(count.load(Ordering::Relaxed), 1);
+
+	// Options apply to cached mipmaps too
+	let opaque = cached.clone().with_options(DecodeOptions { opaque: true, ..Default::default() });
+	assert_eq!(opaque.decode_first().unwrap(), expected);
+	assert_eq!(count.load(Ordering::Relaxed), 1);
+
+	// Mipmaps #1 and #2 do not fit with #0, which is dropped as the largest
+	let _ = cached.decode_nth(1).unwrap();
+	let _ = cached.decode_nth(2).unwrap();
+	let _ = cached.decode_nth(1).unwrap();
+	assert_eq!(count.load(Ordering::Relaxed), 3);
+	let _ = cached.decode_first().unwrap();
+	assert_eq!(count.load(Ordering::Relaxed), 4);
+
+	cached.clear_cache();
+	let _ = cached.decode_nth(1).unwrap();
+	assert_eq!(count.load(Ordering::Relaxed), 5);
+}
+
+
+#[test]
+fn decode_nearest() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let mut paa = PaaEncoder::with_image_and_settings(RgbaImage::new(1024, 1024), settings).encode().unwrap();
+	assert_eq!(paa.mipmaps.len(), 9);
+
+	let decoder = PaaDecoder::with_paa(paa.clone());
+	let nearest = |w, h| decoder.decode_nearest(w, h).map(|(index, image)| (index, image.dimensions())).unwrap();
+	assert_eq!(nearest(1024, 1024), (0, (1024, 1024)));
+	assert_eq!(nearest(256, 256), (2, (256, 256)));
+	assert_eq!(nearest(200, 100), (2, (256, 256)));
+	assert_eq!(nearest(100, 200), (2, (256, 256)));
+	assert_eq!(nearest(4, 4), (8, (4, 4)));
+	assert_eq!(nearest(1, 1), (8, (4, 4)));
+	assert_eq!(nearest(4096, 16), (0, (1024, 1024)));
+
+	// Errored mipmaps are skipped
+	paa.mipmaps[2] = Err(MipmapDataBeyondEof);
+	paa.mipmaps[0] = Err(MipmapDataBeyondEof);
+	let decoder = PaaDecoder::with_paa(paa.clone());
+	assert_eq!(decoder.decode_nearest(200, 200).unwrap().0, 1);
+	assert_eq!(decoder.decode_nearest(2000, 2000).unwrap().0, 1);
+
+	paa.mipmaps.iter_mut().for_each(|m| *m = Err(EmptyMipmap));
+	assert!(matches!(PaaDecoder::with_paa(paa).decode_nearest(1, 1), Err(NoMipmaps)));
+}
+
+
+#[test]
+fn decode_iter() {
+	use std::sync::atomic::Ordering;
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 0x20, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let mut paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	paa.mipmaps[2] = Err(MipmapDataBeyondEof);
+	paa.mipmaps.push(Err(EmptyMipmap));
+
+	let codec = CountingCodec::default();
+	let count = Arc::clone(&codec.0);
+	let decoder = PaaDecoder::with_paa(paa).with_codec(codec);
+
+	let all = decoder.decode_all();
+	assert_eq!(all.len(), 4);
+	assert!(matches!(all[2], Err(MipmapDataBeyondEof)));
+	assert_eq!(all.iter().filter(|i| i.is_ok()).count(), 3);
+	assert_eq!(all[1].as_ref().unwrap(), &decoder.decode_nth(1).unwrap());
+	assert_eq!(count.load(Ordering::Relaxed), 4);
+
+	// Breaking early leaves the remaining mipmaps undecoded
+	let mut iter = decoder.iter();
+	assert_eq!(iter.len(), 4);
+	assert_eq!(iter.next().unwrap().1.unwrap().dimensions(), (32, 32));
+	assert_eq!(count.load(Ordering::Relaxed), 5);
+	assert_eq!(iter.len(), 3);
+
+	for (index, image) in &decoder {
+		assert_eq!(image.is_err(), index == 2);
+	};
+
+	assert_eq!(count.load(Ordering::Relaxed), 8);
+}
+
+
+#[test]
+fn decode_premultiplied() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([0xF0 - x as u8 * 8, y as u8 * 16, 0xA0, x as u8 * 16 + y as u8]));
+	let premultiplied = DecodeOptions { premultiply_alpha: true, ..Default::default() };
+
+	for paatype in [PaaType::Dxt1, PaaType::Dxt2, PaaType::Dxt3, PaaType::Dxt4, PaaType::Dxt5, PaaType::Argb4444, PaaType::Argb1555, PaaType::Argb8888, PaaType::Ai88] {
+		let settings = TextureEncodingSettings::builder().format(paatype).build();
+		let paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+
+		let mut expected = PaaDecoder::with_paa(paa.clone()).decode_first().unwrap();
+
+		for pixel in expected.pixels_mut() {
+			let a = f32::from(pixel.0[3]);
+			for c in &mut pixel.0[..3] {
+				*c = (f32::from(*c) * a / 255.0).round() as u8;
+			};
+		};
+
+		assert_eq!(PaaDecoder::with_paa(paa).with_options(premultiplied).decode_first().unwrap(), expected, "{paatype:?}");
+	};
+}
+
+
+#[test]
+fn decode_rgba16() {
+	use crate::PaaMipmap;
+
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 17, y as u8 * 17, 0xFF - x as u8 * 16, (x + y) as u8 * 8]));
+
+	// 4-bit channels widen to multiples of 0x1111, the 8-bit decode being
+	// their high byte: ARGB4444 loses nothing at 8 bits either
+	let argb4444 = PaaMipmap::encode(PaaType::Argb4444, &image).unwrap();
+	let wide = argb4444.decode_rgba16().unwrap();
+	assert!(wide.as_raw().iter().all(|c| c % 0x1111 == 0));
+	assert!(wide.pixels().zip(argb4444.decode().unwrap().pixels()).all(|(w, n)| w.0.map(|c| (c >> 8) as u8) == n.0));
+	assert_eq!(wide.get_pixel(15, 0).0[0], u16::MAX);
+
+	let float = argb4444.decode_rgba32f().unwrap();
+	assert_eq!(float.get_pixel(15, 0).0[0], 1.0);
+	assert!((float.get_pixel(3, 0).0[0] - 0x3333 as f32 / 65535.0).abs() < 1e-6);
+
+	let argb1555 = PaaMipmap::encode(PaaType::Argb1555, &image).unwrap().decode_rgba16().unwrap();
+	assert_eq!(argb1555.get_pixel(15, 0).0[0], u16::MAX);
+	assert!(argb1555.pixels().all(|p| p.0[3] == 0 || p.0[3] == u16::MAX));
+
+	// DXTn interpolates at 16 bits, and rounds differently from 8 bits
+	for paatype in [PaaType::Dxt1, PaaType::Dxt3, PaaType::Dxt5] {
+		let mipmap = PaaMipmap::encode(paatype, &image).unwrap();
+		let wide = mipmap.decode_rgba16().unwrap();
+
+		for (w, n) in wide.as_raw().iter().zip(mipmap.decode().unwrap().as_raw()) {
+			assert!(((w >> 8) as i32 - i32::from(*n)).abs() <= 1, "{paatype:?}: {w:#06X} vs {n:#04X}");
+		};
+	};
+
+	// A BC3 alpha ramp from 1/255 to 0 collapses to two values at 8 bits
+	let alpha_indices = (0..16u64).fold(0, |acc, i| acc | (i % 8) << (3 * i));
+	let mut data = vec![1, 0];
+	data.extend(&alpha_indices.to_le_bytes()[..6]);
+	data.extend([0; 8]);
+	let ramp = PaaMipmap { width: 4, height: 4, paatype: PaaType::Dxt5, data, ..Default::default() };
+
+	let narrow_alphas = ramp.decode().unwrap().pixels().map(|p| p.0[3]).collect::<std::collections::BTreeSet<_>>();
+	let wide_alphas = ramp.decode_rgba16().unwrap().pixels().map(|p| p.0[3]).collect::<std::collections::BTreeSet<_>>();
+	assert_eq!(narrow_alphas.len(), 2);
+	assert_eq!(wide_alphas.len(), 8);
+	assert_eq!(wide_alphas.iter().max(), Some(&257));
+
+	assert!(matches!(PaaMipmap { data: vec![0; 8], ..ramp }.decode_rgba16(), Err(UnexpectedMipmapDataSize(4, 4, 8))));
+}
+
+
+#[test]
+fn decode_nth_rgba16() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([x as u8 * 32, y as u8 * 32, 0xC0, 0x70]));
+	let swizzle = ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap();
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).swizzle(swizzle).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	let options = DecodeOptions { unswizzle: true, premultiply_alpha: true, ..Default::default() };
+	let decoder = PaaDecoder::with_paa(paa).with_options(options);
+
+	// Widened 8-bit data is post-processed as at 8 bits
+	let narrow = decoder.decode_first().unwrap();
+	let wide = decoder.decode_nth_rgba16(0).unwrap();
+
+	for (w, n) in wide.as_raw().iter().zip(narrow.as_raw()) {
+		assert!(((w >> 8) as i32 - i32::from(*n)).abs() <= 1, "{w:#06X} vs {n:#04X}");
+	};
+
+	assert!(matches!(decoder.decode_nth_rgba16(99), Err(MipmapIndexOutOfRange)));
 }
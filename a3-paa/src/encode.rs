@@ -1,132 +1,1289 @@
 use crate::macros;
 use crate::imageops;
 use crate::cfgfile;
+use crate::progress::{self, ProgressEvent};
 
-use crate::{PaaResult, PaaType, PaaImage, Tagg, PaaMipmap, ArgbSwizzle};
+use crate::{PaaResult, PaaType, PaaImage, Tagg, PaaMipmap, Alpha1Mode, ArgbSwizzle, DxtCodec, DxtQuality, TexpressoCodec, Transparency};
+#[cfg(test)] use crate::Bgra8888Pixel;
+use crate::{ChannelSwizzle, ChannelSwizzleId, ChannelSwizzleData};
+#[cfg(feature = "builtin-hints")] use crate::ChannelSwizzleFill;
+use crate::PaaError::Cancelled;
 #[cfg(doc)] use crate::PaaError::*;
 
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::ops::Deref;
+use std::ops::{ControlFlow, Deref};
+use std::sync::Arc;
 
-use image::RgbaImage;
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+use image::imageops::FilterType;
 
 
 /// Wrapper around [`TextureEncodingSettings`] that encodes an
 /// [`image::RgbaImage`] into a [`PaaImage`]
 ///
+/// DXTn mipmaps are compressed with [`TexpressoCodec`] unless another
+/// [`DxtCodec`] is set with [`with_codec`][`Self::with_codec`].
+///
+/// # Determinism
+///
+/// The same image and settings encode to the same bytes on every run, thread
+/// and platform, for a given version of this crate and set of features, so
+/// that build systems can cache PAAs by content hash.  Mipmaps are encoded
+/// one after the other, and nothing depends on time or hashing order.
+/// [`TexpressoCodec`] and LZSS are pure Rust, and LZO uses an exact version of
+/// minilzo.  Without the `minilzo` feature (or on wasm32), LZO mipmaps are
+/// written as literal runs, so their bytes differ from those of other
+/// builds.  Other [`DxtCodec`]s are as deterministic as they are themselves.
+///
 /// [`RgbaImage`]: [image::RgbaImage]
 #[allow(missing_debug_implementations)]
 #[derive(Clone)]
 pub struct PaaEncoder {
 	image: RgbaImage,
 	settings: TextureEncodingSettings,
+	codec: Arc<dyn DxtCodec>,
+	progress: Option<Arc<dyn Fn(ProgressEvent) -> ControlFlow<()> + Send + Sync>>,
+	/// The source image has no alpha channel
+	opaque_source: bool,
+}
+
+
+impl PaaEncoder {
+	/// Largest width or height of an image that the engine loads in practice.
+	pub const MAX_DIMENSION: u32 = crate::MAX_DIMENSION;
+
+
+	/// Creates a new encoder from an [`image::RgbaImage`] and
+	/// [`TextureEncodingSettings`].
+	pub fn with_image_and_settings(image: RgbaImage, settings: TextureEncodingSettings) -> Self {
+		Self { image, settings, codec: Arc::new(TexpressoCodec), progress: None, opaque_source: false }
+	}
+
+
+	/// Creates a new encoder from an [`image::DynamicImage`] of any color
+	/// type and [`TextureEncodingSettings`].
+	///
+	/// Gray is copied to every color channel, which [`PaaType::Ai88`] stores
+	/// unchanged.  Sources of more than 8 bits per channel are rounded, or
+	/// dithered if [`TextureEncodingSettings::dither`].  Sources without
+	/// alpha are made opaque, and get a [`Tagg::Flag`] of
+	/// [`Transparency::None`] unless the swizzle or alpha threshold makes
+	/// them transparent.
+	pub fn with_dynamic_image(image: DynamicImage, settings: TextureEncodingSettings) -> Self {
+		let opaque_source = !image.color().has_alpha();
+
+		let image = match image {
+			DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgb8(_) => image.into_rgba8(),
+			DynamicImage::ImageRgba8(image) => image,
+			deep if settings.dither => dither_to_rgba8(&deep.into_rgba16()),
+			deep => {
+				let image = deep.into_rgba16();
+				#[allow(clippy::cast_possible_truncation)]
+				let data = image.as_raw().iter().map(|c| ((u32::from(*c) + 128) / 257) as u8).collect();
+				RgbaImage::from_vec(image.width(), image.height(), data).unwrap()
+			},
+		};
+
+		Self { opaque_source, ..Self::with_image_and_settings(image, settings) }
+	}
+
+
+	/// Compress DXTn mipmaps with `codec`, at
+	/// [`TextureEncodingSettings::dxt_quality`].
+	pub fn with_codec(self, codec: impl DxtCodec + 'static) -> Self {
+		Self { codec: Arc::new(codec), ..self }
+	}
+
+
+	/// Call `progress` with a [`ProgressEvent::MipmapEncodeStarted`] and a
+	/// [`ProgressEvent::MipmapEncoded`] for every mipmap encoded.
+	pub fn with_progress(self, progress: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+		self.with_progress_control(move |event| {
+			progress(event);
+			ControlFlow::Continue(())
+		})
+	}
+
+
+	/// As [`with_progress`][`Self::with_progress`], but encoding stops with
+	/// [`Cancelled`] as soon as `progress` returns [`ControlFlow::Break`].
+	/// Mipmaps are encoded one at a time, so cancelling waits for the current
+	/// one at most.
+	pub fn with_progress_control(self, progress: impl Fn(ProgressEvent) -> ControlFlow<()> + Send + Sync + 'static) -> Self {
+		Self { progress: Some(Arc::new(progress)), ..self }
+	}
+
+
+	fn report(&self, event: ProgressEvent) -> PaaResult<()> {
+		match &self.progress {
+			Some(progress) if progress(event).is_break() => Err(Cancelled),
+			_ => Ok(()),
+		}
+	}
+
+
+	/// Encode the image into a [`PaaImage`] with a mipmap chain.
+	///
+	/// The engine requires power-of-two dimensions for DXTn, and loads nothing
+	/// larger than [`MAX_DIMENSION`][`Self::MAX_DIMENSION`]; images that break
+	/// either rule are fixed as set by
+	/// [`TextureEncodingSettings::resize_to_fit`], or rejected.
+	///
+	/// # Errors
+	/// - [`DimensionsTooLarge`]: The image is larger than
+	///   [`MAX_DIMENSION`][`Self::MAX_DIMENSION`], and not to be resized.
+	/// - [`DimensionsNotPowerOfTwo`]: The image is to be encoded to DXTn, is
+	///   not a power of two, and is not to be resized.
+	/// - [`Cancelled`]: The callback of
+	///   [`with_progress_control`][`Self::with_progress_control`] returned
+	///   [`ControlFlow::Break`].
+	///
+	/// Errors encoding each mipmap are returned in [`PaaImage::mipmaps`].
+	///
+	/// # Panics
+	/// - If `self.image.width * self.image.height` overflows a [`u64`].
+	pub fn encode(&self) -> PaaResult<PaaImage> {
+		let mut img = self.image.clone();
+		let tolerance = self.settings.autoreduce_tolerance;
+		let paatype = self.settings.format;
+		let filter = self.settings.mipmap_resize_filter.unwrap_or(FilterType::Triangle);
+		// DXTn mipmaps are not reduced below a single 4x4 block
+		let min_dimension = if paatype.is_dxtn() { 4 } else { 1 };
+
+		self.settings.swizzle.apply_to_image(&mut img);
+
+		if let Some(threshold) = self.settings.alpha_threshold {
+			for pixel in img.pixels_mut() {
+				pixel.0[3] = if pixel.0[3] >= threshold { 0xFF } else { 0 };
+			};
+		};
+
+		if self.settings.autoreduce && imageops::is_solid_color(&img, tolerance) {
+			let pixel = if tolerance > 0 {
+				let (average, _) = imageops::get_avgc_maxc(&img, AvgcWeighting::Uniform);
+				average.into()
+			}
+			else {
+				*img.get_pixel(0, 0)
+			};
+
+			img = RgbaImage::from_pixel(1, 1, pixel);
+		};
+
+		if let Some(max_size) = self.settings.max_size {
+			// Halve rather than scale to `max_size`, keeping powers of two
+			let fitting = imageops::mipmap_dimensions(img.dimensions(), min_dimension)
+				.reduce(|d, next| if d.0.max(d.1) > max_size { next } else { d });
+
+			if let Some((width, height)) = fitting.filter(|d| *d != img.dimensions()) {
+				macros::log!(debug, "PaaEncoder::encode: downscaled {}x{} to {}x{} (max_size={})", img.width(), img.height(), width, height, max_size);
+				img = image::imageops::resize(&img, width, height, filter);
+			};
+		};
+
+		if let Err(e) = check_dimensions(paatype, img.width(), img.height()) {
+			let mode = self.settings.resize_to_fit.ok_or(e)?;
+			img = fit_dimensions(&img, paatype, mode, filter);
+
+			macros::log!(debug, "PaaEncoder::encode: fitted {}x{} to {}x{} ({:?})", self.image.width(), self.image.height(), img.width(), img.height(), mode);
+		};
+
+		// Unless set otherwise, AVGC and MAXC describe the stored (swizzled)
+		// texture, as PaaImage::recompute_taggs does
+		let (avgc, maxc) = if self.settings.swizzle_affects_taggs.unwrap_or(true) {
+			imageops::get_avgc_maxc(&img, self.settings.avgc_weighting)
+		}
+		else {
+			imageops::get_avgc_maxc(&self.image, self.settings.avgc_weighting)
+		};
+
+		macros::log!(trace, "PaaEncoder::encode: AVGC={}, MAXC={}", avgc, maxc);
+
+		let avgc_tagg = Tagg::Avgc { rgba: avgc };
+		let maxc_tagg = Tagg::Maxc { rgba: maxc };
+		let mut taggs = vec![avgc_tagg, maxc_tagg];
+
+		let transparency = self.settings.transparency
+			.or_else(|| (self.opaque_source && img.pixels().all(|p| p.0[3] == 0xFF)).then(|| Transparency::None));
+
+		if let Some(transparency) = transparency {
+			taggs.push(Tagg::Flag { transparency });
+		};
+
+		if !self.settings.swizzle.is_noop() {
+			taggs.push(Tagg::Swiz { swizzle: self.settings.swizzle });
+		};
+
+		// Sharpening would denormalize the vectors of normal maps
+		let normal_map = self.settings.mipmap_filter.map_or(false, TextureMipmapFilter::is_normal_map);
+		let sharpen = self.settings.mipmap_sharpen.filter(|_| !normal_map);
+
+		// Compression quality is invisible on the smallest mipmaps
+		let quality = |level| if level < 3 || self.settings.full_quality_mipmaps {
+			self.settings.dxt_quality
+		}
+		else {
+			DxtQuality::Fast
+		};
+
+		let alpha1 = if self.settings.alpha1_preserve_coverage {
+			Alpha1Mode::PreserveCoverage
+		}
+		else {
+			self.settings.alpha_threshold.map_or_else(Alpha1Mode::default, Alpha1Mode::Threshold)
+		};
+
+		let max_mipmaps = self.settings.max_mipmaps.unwrap_or(usize::MAX).min(PaaImage::MAX_MIPMAPS.into());
+
+		// A single mipmap needs no downscaled ones
+		let series = if max_mipmaps == 1 {
+			vec![img]
+		}
+		else {
+			let options = imageops::MipmapChainOptions { min_dimension, filter, sharpen, ..Default::default() };
+			imageops::mipmap_chain(img, options)
+		};
+
+		let series = &series[..series.len().min(max_mipmaps)];
+		let count = series.len();
+
+		let mipmaps = series
+			.iter()
+			.enumerate()
+			.map(|(index, i)| {
+				self.report(ProgressEvent::MipmapEncodeStarted { index, count, width: i.width(), height: i.height() })?;
+				let timer = progress::start_timer(self.progress.is_some());
+				let mipmap = PaaMipmap::encode_with_alpha1(paatype, i, self.codec.as_ref(), quality(index), alpha1);
+				let elapsed = progress::elapsed(timer);
+
+				macros::log!(debug, "PaaEncoder::encode: mipmap #{} ({}x{}) encoded in {:?}", index, i.width(), i.height(), elapsed);
+				self.report(ProgressEvent::MipmapEncoded { index, count, elapsed })?;
+				Ok(mipmap)
+			})
+			.collect::<PaaResult<Vec<PaaResult<PaaMipmap>>>>()?;
+
+		let image = PaaImage { paatype, taggs, palette: None, mipmaps, trailer: vec![] };
+
+		Ok(image)
+	}
+}
+
+
+/// Reduce `image` to 8 bits per channel with a 4x4 ordered (Bayer) dither,
+/// which keeps the average of smooth gradients instead of banding them
+fn dither_to_rgba8(image: &ImageBuffer<Rgba<u16>, Vec<u16>>) -> RgbaImage {
+	const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+	RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+		// Threshold in 0..257, centered in its sixteenth
+		let threshold = (BAYER[y as usize % 4][x as usize % 4] * 2 + 1) * 257 / 32;
+		#[allow(clippy::cast_possible_truncation)]
+		let channel = |c: u16| ((u32::from(c) + threshold) / 257).min(0xFF) as u8;
+		Rgba(image.get_pixel(x, y).0.map(channel))
+	})
+}
+
+
+/// Nearest dimensions not larger and not smaller than `dimension` (up to
+/// [`PaaEncoder::MAX_DIMENSION`]) that the engine accepts for `paatype`
+pub(crate) fn nearest_dimensions(paatype: PaaType, dimension: u32) -> (u32, u32) {
+	let clamped = dimension.min(PaaEncoder::MAX_DIMENSION);
+
+	if !paatype.is_dxtn() || clamped.is_power_of_two() {
+		return (clamped, clamped);
+	};
+
+	let smaller = 1 << (u32::BITS - 1 - clamped.max(1).leading_zeros());
+	(smaller, smaller * 2)
+}
+
+
+/// Check `width` and `height` of the top mipmap against the engine's rules
+/// for `paatype`, suggesting the nearest valid dimensions if they break them.
+fn check_dimensions(paatype: PaaType, width: u32, height: u32) -> PaaResult<()> {
+	use crate::PaaError::*;
+
+	let (w_smaller, w_larger) = nearest_dimensions(paatype, width);
+	let (h_smaller, h_larger) = nearest_dimensions(paatype, height);
+
+	if width > PaaEncoder::MAX_DIMENSION || height > PaaEncoder::MAX_DIMENSION {
+		return Err(DimensionsTooLarge(width, height, (w_smaller, h_smaller)));
+	};
+
+	if (w_smaller, h_smaller) != (width, height) {
+		return Err(DimensionsNotPowerOfTwo(width, height, (w_larger, h_larger), (w_smaller, h_smaller)));
+	};
+
+	Ok(())
+}
+
+
+/// Fix the dimensions of `image` for `paatype` as set by `mode`, resizing with
+/// `filter` if stretched.
+fn fit_dimensions(image: &RgbaImage, paatype: PaaType, mode: FitMode, filter: FilterType) -> RgbaImage {
+	let (width, height) = image.dimensions();
+	let (w_smaller, w_larger) = nearest_dimensions(paatype, width);
+	let (h_smaller, h_larger) = nearest_dimensions(paatype, height);
+
+	// Nearer by ratio: above the geometric mean of the two, round up
+	let nearest = |d: u32, smaller: u32, larger: u32| {
+		if u64::from(d).pow(2) < u64::from(smaller) * u64::from(larger) { smaller } else { larger }
+	};
+
+	let (fit_width, fit_height) = match mode {
+		FitMode::Stretch => {
+			let (w, h) = (nearest(width, w_smaller, w_larger), nearest(height, h_smaller, h_larger));
+			return image::imageops::resize(image, w, h, filter);
+		},
+		FitMode::PadTransparent => (w_larger, h_larger),
+		FitMode::Crop => (w_smaller, h_smaller),
+	};
+
+	// Crop the center of anything too large, then center it on the canvas
+	let (crop_width, crop_height) = (width.min(fit_width), height.min(fit_height));
+	let cropped = image::imageops::crop_imm(image, (width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height);
+
+	let mut canvas = RgbaImage::new(fit_width, fit_height);
+	let (x, y) = ((fit_width - crop_width) / 2, (fit_height - crop_height) / 2);
+	image::imageops::overlay(&mut canvas, &cropped.to_image(), x.into(), y.into());
+	canvas
+}
+
+
+/// Steps applied to an RGBA image when converting to PAA
+///
+/// Use [`builder`][`Self::builder`] to construct settings without spelling out
+/// every field.  With the `serde` feature, settings can be (de)serialized;
+/// missing fields take their [`Default`] values, and [`ArgbSwizzle`] is
+/// represented as a string (e.g. `"1-R, 1-A, G, B"`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TextureEncodingSettings {
+	/// [`PaaImage::paatype`] of the output PAA.
+	pub format: PaaType,
+	/// `[TODO]`
+	pub dynrange: Option<bool>,
+	/// Crop the texture to 1x1 if solid color.
+	pub autoreduce: bool,
+	/// Largest difference between pixels, per channel, for which
+	/// [`autoreduce`][`Self::autoreduce`] still considers the texture solid
+	/// color.  If nonzero, the reduced pixel is the average color.
+	pub autoreduce_tolerance: u8,
+	/// `[TODO]`
+	pub mipmap_filter: Option<TextureMipmapFilter>,
+	/// Subpixel mapping applied to the input image.
+	pub swizzle: ArgbSwizzle,
+	/// `[TODO]`
+	pub error_metrics: Option<TextureErrorMetrics>,
+	/// Largest allowed width or height of the output (`maxSize` in
+	/// TexConvert.cfg); larger inputs are halved until they fit, with
+	/// [`mipmap_resize_filter`][`Self::mipmap_resize_filter`], before
+	/// computing mipmaps and AVGC.
+	pub max_size: Option<u32>,
+	/// How pixels contribute to the AVGC and MAXC taggs.
+	pub avgc_weighting: AvgcWeighting,
+	/// Amount of unsharp mask applied to downscaled mipmaps (see
+	/// [`MipmapChainOptions::sharpen`][`imageops::MipmapChainOptions::sharpen`]).
+	/// Ignored for normal map [`mipmap_filter`][`Self::mipmap_filter`]s.
+	pub mipmap_sharpen: Option<f32>,
+	/// Filter used to downscale each mipmap from the previous one; triangle
+	/// if [`None`].  Not (de)serialized.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	pub mipmap_resize_filter: Option<FilterType>,
+	/// Largest number of mipmaps.
+	pub max_mipmaps: Option<usize>,
+	/// Make alpha binary after swizzling: opaque at or above the threshold,
+	/// transparent below it.  Also the alpha at or above which
+	/// [`PaaType::Argb1555`] and punch-through [`PaaType::Dxt1`] pixels of
+	/// each mipmap are opaque, 128 if [`None`].
+	pub alpha_threshold: Option<u8>,
+	/// Rather than [`alpha_threshold`][`Self::alpha_threshold`], pick the
+	/// single-bit alpha threshold of each mipmap to keep its alpha coverage
+	/// (see [`Alpha1Mode::PreserveCoverage`]).
+	pub alpha1_preserve_coverage: bool,
+	/// Speed/quality trade-off of the [`DxtCodec`] compressing DXTn mipmaps.
+	/// Unless [`full_quality_mipmaps`][`Self::full_quality_mipmaps`], only
+	/// applies to the first three mipmaps; the rest use [`DxtQuality::Fast`].
+	pub dxt_quality: DxtQuality,
+	/// Compress every mipmap at [`dxt_quality`][`Self::dxt_quality`].
+	pub full_quality_mipmaps: bool,
+	/// Fix images of dimensions that the engine does not accept, rather than
+	/// fail to encode them.
+	pub resize_to_fit: Option<FitMode>,
+	/// Dither sources of more than 8 bits per channel down to 8 bits, rather
+	/// than round them (see [`PaaEncoder::with_dynamic_image`]).
+	pub dither: bool,
+	/// Compute the AVGC and MAXC taggs from the swizzled texture as stored,
+	/// if [`None`] or true, or from the input image as given, before
+	/// swizzling, alpha threshold and resizing, if false.
+	pub swizzle_affects_taggs: Option<bool>,
+	/// Transparency of the [`Tagg::Flag`] to write.  If [`None`], FLAG is
+	/// only written for opaque sources of
+	/// [`PaaEncoder::with_dynamic_image`].
+	pub transparency: Option<Transparency>,
+}
+
+
+impl Default for TextureEncodingSettings {
+	fn default() -> Self {
+		Self {
+			format: PaaType::default(),
+			dynrange: None,
+			autoreduce: false,
+			autoreduce_tolerance: 0,
+			mipmap_filter: None,
+			swizzle: ArgbSwizzle::default(),
+			error_metrics: None,
+			max_size: None,
+			avgc_weighting: AvgcWeighting::default(),
+			mipmap_sharpen: None,
+			mipmap_resize_filter: None,
+			max_mipmaps: None,
+			alpha_threshold: None,
+			alpha1_preserve_coverage: false,
+			dxt_quality: DxtQuality::default(),
+			full_quality_mipmaps: false,
+			resize_to_fit: None,
+			dither: false,
+			swizzle_affects_taggs: None,
+			transparency: None,
+		}
+	}
+}
+
+
+impl std::fmt::Display for TextureEncodingSettings {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let mut segments: Vec<String> = vec![];
+		segments.push(format!("{:?}", self.format));
+
+		if let Some(r) = self.dynrange {
+			segments.push(format!("dynRange={}", r));
+		};
+
+		if self.autoreduce && self.autoreduce_tolerance > 0 {
+			segments.push(format!("autoreduce(\u{b1}{})", self.autoreduce_tolerance));
+		}
+		else if self.autoreduce {
+			segments.push("autoreduce".into());
+		};
+
+		if let Some(f) = self.mipmap_filter {
+			segments.push(format!("{}", f));
+		};
+
+		if !self.swizzle.is_noop() {
+			segments.push(format!("swizzle=<{}>", self.swizzle));
+		};
+
+		if let Some(m) = self.error_metrics {
+			segments.push(format!("errorMetrics={}", m));
+		};
+
+		if let Some(m) = self.max_size {
+			segments.push(format!("maxSize={}", m));
+		};
+
+		if self.avgc_weighting != AvgcWeighting::default() {
+			segments.push(format!("avgc={:?}", self.avgc_weighting));
+		};
+
+		if let Some(s) = self.mipmap_sharpen {
+			segments.push(format!("sharpen={}", s));
+		};
+
+		if let Some(f) = self.mipmap_resize_filter {
+			segments.push(format!("resize={:?}", f));
+		};
+
+		if let Some(m) = self.max_mipmaps {
+			segments.push(format!("maxMips={}", m));
+		};
+
+		if let Some(t) = self.alpha_threshold {
+			segments.push(format!("alphaThreshold={}", t));
+		};
+
+		if self.alpha1_preserve_coverage {
+			segments.push("alpha1Coverage".into());
+		};
+
+		if self.dxt_quality != DxtQuality::default() {
+			segments.push(format!("quality={:?}", self.dxt_quality));
+		};
+
+		if self.full_quality_mipmaps {
+			segments.push("fullQualityMips".into());
+		};
+
+		if let Some(m) = self.resize_to_fit {
+			segments.push(format!("fit={:?}", m));
+		};
+
+		if self.dither {
+			segments.push("dither".into());
+		};
+
+		if let Some(a) = self.swizzle_affects_taggs {
+			segments.push(format!("swizzleAffectsTaggs={}", a));
+		};
+
+		if let Some(t) = self.transparency {
+			segments.push(format!("flag={}", t.as_str()));
+		};
+
+		write!(f, "<{}>", segments.join(", "))
+	}
+}
+
+
+impl TextureEncodingSettings {
+	/// Return a [`TextureEncodingSettingsBuilder`] starting from the default
+	/// settings.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureEncodingSettings, PaaType, ArgbSwizzle};
+	/// let swizzle = ArgbSwizzle::parse_argb("1", "R", "G", "B").unwrap();
+	/// let settings = TextureEncodingSettings::builder()
+	///     .format(PaaType::Dxt1)
+	///     .autoreduce(true)
+	///     .swizzle(swizzle)
+	///     .build();
+	/// assert_eq!(settings.format, PaaType::Dxt1);
+	/// assert_eq!(settings.mipmap_filter, None);
+	/// ```
+	pub fn builder() -> TextureEncodingSettingsBuilder {
+		TextureEncodingSettingsBuilder::default()
+	}
+
+
+	/// Settings for user interface textures (loading screens, insignia,
+	/// icons) of `format`, usually [`PaaType::Argb8888`] or
+	/// [`PaaType::Dxt5`]: the engine shows them at their full size, so they
+	/// have a single mipmap, and keep their alpha as
+	/// [`Transparency::AlphaInterpolated`].  They are neither swizzled nor
+	/// autoreduced.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureEncodingSettings, PaaType};
+	/// let settings = TextureEncodingSettings::ui_texture(PaaType::Dxt5);
+	/// assert_eq!(settings.max_mipmaps, Some(1));
+	/// assert!(!settings.autoreduce);
+	/// ```
+	pub fn ui_texture(format: PaaType) -> Self {
+		Self::builder()
+			.format(format)
+			.autoreduce(false)
+			.max_mipmaps(1)
+			.transparency(Transparency::AlphaInterpolated)
+			.build()
+	}
+
+
+	/// Settings for opaque color textures (`_co`): [`PaaType::Dxt5`],
+	/// [autoreduced][`Self::autoreduce`] when solid color.
+	pub fn co() -> Self {
+		Self::builder().format(PaaType::Dxt5).autoreduce(true).build()
+	}
+
+
+	/// Settings for color textures with alpha (`_ca`): [`PaaType::Dxt5`] with
+	/// [`Transparency::AlphaInterpolated`], and
+	/// [`TextureMipmapFilter::AlphaNoise`] so that alpha-tested foliage does
+	/// not thin out in the smaller mipmaps.
+	pub fn ca() -> Self {
+		Self::builder()
+			.format(PaaType::Dxt5)
+			.transparency(Transparency::AlphaInterpolated)
+			.mipmap_filter(TextureMipmapFilter::AlphaNoise)
+			.build()
+	}
+
+
+	/// Settings for normal maps (`_nohq`): [`PaaType::Dxt5`] swizzled
+	/// `1-R, 1-A, G, B` (the X component moves to alpha, where DXT5 keeps it
+	/// at higher precision), with [`TextureErrorMetrics::Distance`] and
+	/// [`TextureMipmapFilter::NormalizeNormalMapAlpha`].
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureEncodingSettings, ArgbSwizzle};
+	/// let settings = TextureEncodingSettings::nohq();
+	/// assert_eq!(settings.swizzle, ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap());
+	/// ```
+	pub fn nohq() -> Self {
+		use ChannelSwizzleId::*;
+
+		let swizzle = |target, neg_flag, source| ChannelSwizzle { target, data: ChannelSwizzleData::Source { neg_flag, source } };
+
+		Self {
+			format: PaaType::Dxt5,
+			dynrange: Some(false),
+			swizzle: ArgbSwizzle {
+				a: swizzle(Alpha, true, Red),
+				r: swizzle(Red, true, Alpha),
+				g: swizzle(Green, false, Green),
+				b: swizzle(Blue, false, Blue),
+			},
+			error_metrics: Some(TextureErrorMetrics::Distance),
+			mipmap_filter: Some(TextureMipmapFilter::NormalizeNormalMapAlpha),
+			..Default::default()
+		}
+	}
+
+
+	/// Settings for specular maps (`_smdi`): [`PaaType::Dxt1`].
+	pub fn smdi() -> Self {
+		Self::builder().format(PaaType::Dxt1).build()
+	}
+
+
+	/// Settings for masks and other textures that must not suffer compression
+	/// artifacts (`_mask`, `_lco`): [`PaaType::Argb8888`].
+	pub fn mask_lco() -> Self {
+		Self::builder().format(PaaType::Argb8888).build()
+	}
+}
+
+
+/// Fluent builder for [`TextureEncodingSettings`], created by
+/// [`TextureEncodingSettings::builder`]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct TextureEncodingSettingsBuilder {
+	settings: TextureEncodingSettings,
+}
+
+
+impl TextureEncodingSettingsBuilder {
+	/// Set [`TextureEncodingSettings::format`].
+	pub fn format(self, format: PaaType) -> Self {
+		Self { settings: TextureEncodingSettings { format, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::dynrange`].
+	pub fn dynrange(self, dynrange: bool) -> Self {
+		Self { settings: TextureEncodingSettings { dynrange: Some(dynrange), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::autoreduce`].
+	pub fn autoreduce(self, autoreduce: bool) -> Self {
+		Self { settings: TextureEncodingSettings { autoreduce, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::autoreduce_tolerance`].
+	pub fn autoreduce_tolerance(self, autoreduce_tolerance: u8) -> Self {
+		Self { settings: TextureEncodingSettings { autoreduce_tolerance, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::mipmap_filter`].
+	pub fn mipmap_filter(self, mipmap_filter: TextureMipmapFilter) -> Self {
+		Self { settings: TextureEncodingSettings { mipmap_filter: Some(mipmap_filter), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::swizzle`].
+	pub fn swizzle(self, swizzle: ArgbSwizzle) -> Self {
+		Self { settings: TextureEncodingSettings { swizzle, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::error_metrics`].
+	pub fn error_metrics(self, error_metrics: TextureErrorMetrics) -> Self {
+		Self { settings: TextureEncodingSettings { error_metrics: Some(error_metrics), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::max_size`].
+	pub fn max_size(self, max_size: u32) -> Self {
+		Self { settings: TextureEncodingSettings { max_size: Some(max_size), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::avgc_weighting`].
+	pub fn avgc_weighting(self, avgc_weighting: AvgcWeighting) -> Self {
+		Self { settings: TextureEncodingSettings { avgc_weighting, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::mipmap_sharpen`].
+	pub fn mipmap_sharpen(self, mipmap_sharpen: f32) -> Self {
+		Self { settings: TextureEncodingSettings { mipmap_sharpen: Some(mipmap_sharpen), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::mipmap_resize_filter`].
+	pub fn mipmap_resize_filter(self, mipmap_resize_filter: FilterType) -> Self {
+		Self { settings: TextureEncodingSettings { mipmap_resize_filter: Some(mipmap_resize_filter), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::max_mipmaps`].
+	pub fn max_mipmaps(self, max_mipmaps: usize) -> Self {
+		Self { settings: TextureEncodingSettings { max_mipmaps: Some(max_mipmaps), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::alpha_threshold`].
+	pub fn alpha_threshold(self, alpha_threshold: u8) -> Self {
+		Self { settings: TextureEncodingSettings { alpha_threshold: Some(alpha_threshold), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::alpha1_preserve_coverage`].
+	pub fn alpha1_preserve_coverage(self, alpha1_preserve_coverage: bool) -> Self {
+		Self { settings: TextureEncodingSettings { alpha1_preserve_coverage, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::dxt_quality`].
+	pub fn dxt_quality(self, dxt_quality: DxtQuality) -> Self {
+		Self { settings: TextureEncodingSettings { dxt_quality, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::full_quality_mipmaps`].
+	pub fn full_quality_mipmaps(self, full_quality_mipmaps: bool) -> Self {
+		Self { settings: TextureEncodingSettings { full_quality_mipmaps, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::resize_to_fit`].
+	pub fn resize_to_fit(self, resize_to_fit: FitMode) -> Self {
+		Self { settings: TextureEncodingSettings { resize_to_fit: Some(resize_to_fit), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::dither`].
+	pub fn dither(self, dither: bool) -> Self {
+		Self { settings: TextureEncodingSettings { dither, ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::swizzle_affects_taggs`].
+	pub fn swizzle_affects_taggs(self, swizzle_affects_taggs: bool) -> Self {
+		Self { settings: TextureEncodingSettings { swizzle_affects_taggs: Some(swizzle_affects_taggs), ..self.settings } }
+	}
+
+
+	/// Set [`TextureEncodingSettings::transparency`].
+	pub fn transparency(self, transparency: Transparency) -> Self {
+		Self { settings: TextureEncodingSettings { transparency: Some(transparency), ..self.settings } }
+	}
+
+
+	/// Return the built [`TextureEncodingSettings`].
+	pub fn build(self) -> TextureEncodingSettings {
+		self.settings
+	}
+}
+
+
+#[test]
+fn autoreduce_tolerance() {
+	let image = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([0x80 + ((x + y) % 3) as u8, 0x40, 0x20, 0xFF]));
+	let encode = |tolerance| {
+		let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).autoreduce(true).autoreduce_tolerance(tolerance).build();
+		PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap()
+	};
+
+	let paa = encode(0);
+	let mipmap = paa.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.width, mipmap.height), (8, 8));
+
+	let paa = encode(2);
+	let mipmap = paa.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.width, mipmap.height, paa.mipmaps.len()), (1, 1, 1));
+
+	let average = Bgra8888Pixel { b: 0x20, g: 0x40, r: 0x81, a: 0xFF };
+	assert!(paa.taggs.contains(&Tagg::Avgc { rgba: average }));
+	assert!(paa.taggs.contains(&Tagg::Maxc { rgba: average }));
+}
+
+
+#[test]
+fn dimension_rules() {
+	use crate::PaaError::*;
+
+	let encode = |width, height, format, fit: Option<FitMode>| {
+		let image = RgbaImage::from_pixel(width, height, image::Rgba([0x80, 0x40, 0x20, 0xFF]));
+		let settings = TextureEncodingSettings { format, resize_to_fit: fit, ..Default::default() };
+		PaaEncoder::with_image_and_settings(image, settings).encode()
+	};
+
+	let error = encode(1000, 1000, PaaType::Dxt5, None).unwrap_err();
+	assert!(matches!(error, DimensionsNotPowerOfTwo(1000, 1000, (1024, 1024), (512, 512))));
+	assert_eq!(error.to_string(), "1000x1000 is not a power of two; nearest valid sizes are 1024x1024 or 512x512");
+	assert!(matches!(encode(1024, 600, PaaType::Dxt1, None), Err(DimensionsNotPowerOfTwo(1024, 600, (1024, 1024), (1024, 512)))));
+	assert!(matches!(encode(20000, 4, PaaType::Dxt1, None), Err(DimensionsTooLarge(20000, 4, (16384, 4)))));
+	assert!(matches!(encode(16385, 5, PaaType::Argb8888, None), Err(DimensionsTooLarge(16385, 5, (16384, 5)))));
+	assert_eq!(encode(16385, 5, PaaType::Argb8888, None).unwrap_err().to_string(), "16385x5 is larger than 16384x16384; nearest valid size is 16384x5");
+
+	// Only DXTn needs powers of two, and images that break no rule are not fitted
+	let paa = encode(12, 5, PaaType::Argb8888, Some(FitMode::Stretch)).unwrap();
+	let mipmap = paa.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.width, mipmap.height), (12, 5));
+
+	let paa = encode(12, 5, PaaType::Dxt1, Some(FitMode::PadTransparent)).unwrap();
+	let mipmap = paa.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.width, mipmap.height), (16, 8));
+}
+
+
+#[test]
+fn fit_modes() {
+	let red = image::Rgba([0xFF, 0, 0, 0xFF]);
+	let blue = image::Rgba([0, 0, 0xFF, 0xFF]);
+	let image = RgbaImage::from_fn(6, 3, |x, _| if x < 3 { red } else { blue });
+	let fit = |mode| fit_dimensions(&image, PaaType::Dxt1, mode, FilterType::Nearest);
+
+	// 6x3 is nearer to 8x4 than to 4x2
+	let stretched = fit(FitMode::Stretch);
+	assert_eq!(stretched.dimensions(), (8, 4));
+	assert_eq!((stretched.get_pixel(3, 0), stretched.get_pixel(4, 3)), (&red, &blue));
+
+	let padded = fit(FitMode::PadTransparent);
+	assert_eq!(padded.dimensions(), (8, 4));
+	assert_eq!((padded.get_pixel(1, 0), padded.get_pixel(6, 2)), (&red, &blue));
+	assert!([(0, 0), (7, 1), (3, 3)].iter().all(|(x, y)| padded.get_pixel(*x, *y).0 == [0; 4]));
+
+	let cropped = fit(FitMode::Crop);
+	assert_eq!(cropped.dimensions(), (4, 2));
+	assert_eq!(cropped.pixels().collect::<Vec<_>>(), [&red, &red, &blue, &blue, &red, &red, &blue, &blue]);
+
+	// Too large dimensions are cropped, even when padding
+	let wide = RgbaImage::from_fn(PaaEncoder::MAX_DIMENSION + 2, 3, |x, _| image::Rgba([0, 0, 0, (x % 256) as u8]));
+	let padded = fit_dimensions(&wide, PaaType::Dxt1, FitMode::PadTransparent, FilterType::Nearest);
+	assert_eq!(padded.dimensions(), (PaaEncoder::MAX_DIMENSION, 4));
+	assert_eq!(padded.get_pixel(0, 0).0[3], 1);
+}
+
+
+#[test]
+fn max_mipmaps_and_alpha_threshold() {
+	let image = RgbaImage::from_fn(32, 32, |x, _| image::Rgba([0x80, 0x40, 0x20, x as u8 * 8]));
+	let settings = TextureEncodingSettings::builder()
+		.format(PaaType::Argb8888)
+		.max_mipmaps(3)
+		.alpha_threshold(0x80)
+		.mipmap_resize_filter(FilterType::Nearest)
+		.build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	assert_eq!(paa.mipmaps.len(), 3);
+
+	for mipmap in paa.mipmaps.iter().flatten() {
+		assert!(mipmap.decode().unwrap().pixels().all(|p| p.0[3] == 0 || p.0[3] == 0xFF));
+	};
+
+	// The threshold also makes alpha binary again in each ARGB1555 mipmap,
+	// where downscaling averages it
+	let checker = RgbaImage::from_fn(8, 8, |x, y| image::Rgba([0x80, 0x40, 0x20, if (x + y) % 2 == 0 { 0xFF } else { 0 }]));
+
+	for (threshold, alpha) in [(0x20, 0xFF), (0xE0, 0)] {
+		let settings = TextureEncodingSettings::builder().format(PaaType::Argb1555).alpha_threshold(threshold).build();
+		let paa = PaaEncoder::with_image_and_settings(checker.clone(), settings).encode().unwrap();
+		let second = paa.mipmaps[1].as_ref().unwrap().decode().unwrap();
+		assert!(second.pixels().all(|p| p.0[3] == alpha), "{threshold}");
+	};
+}
+
+
+#[test]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn alpha1_coverage_per_mipmap() {
+	// Foliage-like: leaves with soft edges, fading out over a few pixels
+	let image = RgbaImage::from_fn(256, 256, |x, y| {
+		let (x, y) = (x as f32, y as f32);
+		let leaves = (x * 0.37).sin() * (y * 0.29).sin() + 0.4 * ((x + y) * 0.11).sin();
+		image::Rgba([0x30, 0x60, 0x20, (leaves * 400.0 + 64.0).clamp(0.0, 255.0) as u8])
+	});
+
+	let options = imageops::MipmapChainOptions { min_dimension: 1, ..Default::default() };
+	let sources = imageops::mipmap_chain(image.clone(), options);
+
+	for paatype in [PaaType::Argb1555, PaaType::Dxt1] {
+		let settings = TextureEncodingSettings::builder().format(paatype).alpha1_preserve_coverage(true).build();
+		let paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+
+		for (mipmap, source) in paa.mipmaps.iter().map(|m| m.as_ref().unwrap()).zip(&sources) {
+			let decoded = mipmap.decode().unwrap();
+			let pixels = decoded.pixels().len() as f64;
+
+			// Below 16x16, a single pixel is more than 1% of coverage
+			if pixels < 256.0 {
+				continue;
+			};
+
+			let opaque = decoded.pixels().filter(|p| p.0[3] == 0xFF).count() as f64 / pixels;
+			let coverage = source.pixels().map(|p| f64::from(p.0[3])).sum::<f64>() / 255.0 / pixels;
+			assert!((opaque - coverage).abs() < 0.01, "{paatype:?} {}x{}: {opaque} opaque for {coverage} coverage", mipmap.width, mipmap.height);
+		};
+	};
+}
+
+
+#[test]
+fn max_size_downscales_input() {
+	let image = RgbaImage::from_fn(4096, 4096, |x, _| image::Rgba([if x < 2048 { 0xFF } else { 0 }, 0, 0, 0xFF]));
+	let settings = TextureEncodingSettings::builder()
+		.format(PaaType::Argb8888)
+		.max_size(1024)
+		.max_mipmaps(2)
+		.mipmap_resize_filter(FilterType::Nearest)
+		.build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	let mipmaps = paa.mipmaps.iter().map(|m| m.as_ref().unwrap()).collect::<Vec<_>>();
+	assert_eq!((mipmaps[0].width, mipmaps[0].height), (1024, 1024));
+	assert_eq!((mipmaps[1].width, mipmaps[1].height), (512, 512));
+	assert!(matches!(paa.taggs[0], Tagg::Avgc { rgba: Bgra8888Pixel { r, g: 0, b: 0, a: 0xFF } } if r.abs_diff(0x80) <= 1));
+
+	// Smaller inputs are left alone
+	let paa = PaaEncoder::with_image_and_settings(RgbaImage::new(64, 32), settings).encode().unwrap();
+	assert_eq!(paa.mipmaps[0].as_ref().unwrap().width, 64);
+}
+
+
+#[test]
+fn swizzle_affects_taggs() {
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, 0x40, y as u8 * 16, 0xFF]));
+	let swizzle = ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap();
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).swizzle(swizzle).build();
+
+	let mut swizzled = image.clone();
+	swizzle.apply_to_image(&mut swizzled);
+	let (stored_avgc, stored_maxc) = imageops::get_avgc_maxc(&swizzled, AvgcWeighting::default());
+	let (source_avgc, source_maxc) = imageops::get_avgc_maxc(&image, AvgcWeighting::default());
+	assert_ne!(stored_avgc, source_avgc);
+
+	// Pinned: the taggs describe the swizzled texture by default, as
+	// recompute_taggs does
+	let mut paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+	assert_eq!(paa.taggs[..2], [Tagg::Avgc { rgba: stored_avgc }, Tagg::Maxc { rgba: stored_maxc }]);
+	paa.recompute_taggs().unwrap();
+	assert_eq!(paa.taggs[..2], [Tagg::Avgc { rgba: stored_avgc }, Tagg::Maxc { rgba: stored_maxc }]);
+
+	let unswizzled = TextureEncodingSettings { swizzle_affects_taggs: Some(false), ..settings };
+	let paa = PaaEncoder::with_image_and_settings(image, unswizzled).encode().unwrap();
+	assert_eq!(paa.taggs[..2], [Tagg::Avgc { rgba: source_avgc }, Tagg::Maxc { rgba: source_maxc }]);
+
+	// Autoreduced textures agree with the others
+	let solid = RgbaImage::from_pixel(8, 8, image::Rgba([0x20, 0x40, 0x60, 0xFF]));
+	let autoreduce = TextureEncodingSettings { autoreduce: true, ..settings };
+	let paa = PaaEncoder::with_image_and_settings(solid.clone(), autoreduce).encode().unwrap();
+	assert_eq!(paa.taggs[0], Tagg::Avgc { rgba: Bgra8888Pixel { r: 0x00, g: 0x40, b: 0x60, a: 0xDF } });
+	let paa = PaaEncoder::with_image_and_settings(solid, TextureEncodingSettings { swizzle_affects_taggs: Some(false), ..autoreduce }).encode().unwrap();
+	assert_eq!(paa.taggs[0], Tagg::Avgc { rgba: Bgra8888Pixel { r: 0x20, g: 0x40, b: 0x60, a: 0xFF } });
+}
+
+
+#[test]
+fn ui_texture() {
+	let image = RgbaImage::from_fn(64, 32, |x, y| image::Rgba([x as u8 * 4, y as u8 * 8, 0x80, (x + y) as u8 * 2]));
+
+	for format in [PaaType::Argb8888, PaaType::Dxt5] {
+		let settings = TextureEncodingSettings::ui_texture(format);
+		let paa = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+		assert_eq!(paa.mipmaps.len(), 1);
+		assert!(paa.taggs.contains(&Tagg::Flag { transparency: Transparency::AlphaInterpolated }));
+		assert!(!paa.taggs.iter().any(|t| matches!(t, Tagg::Swiz { .. })));
+
+		// Identical to keeping the first of a full chain
+		let chained = TextureEncodingSettings { max_mipmaps: None, ..settings };
+		let mut truncated = PaaEncoder::with_image_and_settings(image.clone(), chained).encode().unwrap();
+		assert!(truncated.mipmaps.len() > 1);
+		truncated.mipmaps.truncate(1);
+		assert_eq!(paa.to_bytes().unwrap(), truncated.to_bytes().unwrap(), "{format:?}");
+	};
+}
+
+
+#[test]
+fn texture_class_presets() {
+	use TextureEncodingSettings as S;
+
+	assert_eq!(S::co(), S { format: PaaType::Dxt5, autoreduce: true, ..Default::default() });
+	assert!(S::co().swizzle.is_noop());
+	assert_eq!(S::ca(), S {
+		format: PaaType::Dxt5,
+		transparency: Some(Transparency::AlphaInterpolated),
+		mipmap_filter: Some(TextureMipmapFilter::AlphaNoise),
+		..Default::default()
+	});
+	assert_eq!(S::smdi(), S { format: PaaType::Dxt1, ..Default::default() });
+	assert_eq!(S::mask_lco(), S { format: PaaType::Argb8888, ..Default::default() });
+
+	let nohq = S::nohq();
+	assert_eq!(nohq.format, PaaType::Dxt5);
+	assert_eq!(nohq.swizzle, ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap());
+	assert_eq!(nohq.error_metrics, Some(TextureErrorMetrics::Distance));
+	assert_eq!(nohq.mipmap_filter, Some(TextureMipmapFilter::NormalizeNormalMapAlpha));
+
+	// SWIZTAGG values: source channel (A=0, R=1, G=2, B=3), plus 4 if negated
+	let image = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0xFF, 0xFF]));
+	let paa = PaaEncoder::with_image_and_settings(image, nohq).encode().unwrap();
+	let swiz = paa.taggs.iter().find(|t| matches!(t, Tagg::Swiz { .. })).unwrap();
+	assert_eq!(swiz.to_bytes().unwrap()[8..], [4, 0, 0, 0, 0x05, 0x04, 0x02, 0x03]);
+}
+
+
+#[test]
+fn encoder_codec_and_quality() {
+	use std::sync::atomic::Ordering;
+	use crate::codec::CountingCodec;
+
+	let image = RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 0x20, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).build();
+
+	let codec = CountingCodec::default();
+	let count = Arc::clone(&codec.0);
+	let paa = PaaEncoder::with_image_and_settings(image.clone(), settings).with_codec(codec).encode().unwrap();
+	assert_eq!(count.load(Ordering::Relaxed), 4);
+	let expected = PaaEncoder::with_image_and_settings(image.clone(), settings).encode().unwrap();
+	assert!(paa.mipmaps.iter().flatten().map(|m| &m.data).eq(expected.mipmaps.iter().flatten().map(|m| &m.data)));
+
+	let fast = TextureEncodingSettings { dxt_quality: DxtQuality::Fast, ..settings };
+	assert_eq!(fast.to_string(), "<Dxt5, quality=Fast>");
+	let paa = PaaEncoder::with_image_and_settings(image, fast).encode().unwrap();
+	assert_eq!(paa.mipmaps.len(), 4);
 }
 
 
-impl PaaEncoder {
-	/// Creates a new encoder from an [`image::RgbaImage`] and
-	/// [`TextureEncodingSettings`].
-	pub fn with_image_and_settings(image: RgbaImage, settings: TextureEncodingSettings) -> Self {
-		Self { image, settings }
-	}
+#[test]
+fn encoder_progress() {
+	use std::sync::Mutex;
 
+	let events = Arc::new(Mutex::new(vec![]));
+	let recorded = Arc::clone(&events);
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, y as u8 * 16, 0x80, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings)
+		.with_progress(move |e| recorded.lock().unwrap().push(e))
+		.encode()
+		.unwrap();
+	assert_eq!(paa.mipmaps.len(), 3);
 
-	/// # Panics
-	/// - If `self.image.width * self.image.height` overflows a [`u64`].
-	#[allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
-	pub fn encode(&self) -> PaaResult<PaaImage> {
-		use image::GenericImageView;
+	let events = events.lock().unwrap();
+	let sequence = events.iter().map(|e| match e {
+		ProgressEvent::MipmapEncodeStarted { index, count: 3, width, height } => format!("start {index} {width}x{height}"),
+		ProgressEvent::MipmapEncoded { index, count: 3, .. } => format!("done {index}"),
+		e => panic!("Unexpected event {e:?}"),
+	}).collect::<Vec<_>>();
+	assert_eq!(sequence, ["start 0 16x16", "done 0", "start 1 8x8", "done 1", "start 2 4x4", "done 2"]);
+}
 
-		let mut img = self.image.clone();
 
-		// [TODO] It would seem that AVGC and MAXC are computed from the texture
-		// *before* swizzling, although this needs testing.
-		let (mut avgc, mut maxc) = imageops::get_avgc_maxc(&img);
+#[test]
+fn encoder_cancel() {
+	use std::sync::Mutex;
 
-		self.settings.swizzle.apply_to_image(&mut img);
+	let events = Arc::new(Mutex::new(vec![]));
+	let recorded = Arc::clone(&events);
+	let image = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).build();
+	let result = PaaEncoder::with_image_and_settings(image.clone(), settings)
+		.with_progress_control(move |e| {
+			let done = matches!(e, ProgressEvent::MipmapEncoded { index: 0, .. });
+			recorded.lock().unwrap().push(e);
+			if done { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+		})
+		.encode();
+	assert!(matches!(result, Err(Cancelled)));
 
-		if self.settings.autoreduce && imageops::is_solid_color(&img) {
-			img = img.view(0, 0, 1, 1).to_image();
-		}
-		else {
-			img = img.view(0, 0, self.image.width(), self.image.height()).to_image();
-			(avgc, maxc) = imageops::get_avgc_maxc(&img);
+	// No mipmap is started after the break
+	assert_eq!(events.lock().unwrap().len(), 2);
+
+	let paa = PaaEncoder::with_image_and_settings(image, settings)
+		.with_progress_control(|_| ControlFlow::Continue(()))
+		.encode()
+		.unwrap();
+	assert_eq!(paa.mipmaps.len(), 5);
+}
+
+
+#[test]
+fn dxt_quality_levels() {
+	use crate::imageops::image_difference;
+
+	let image = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8, 0xFF]));
+	let encode = |settings: TextureEncodingSettingsBuilder| PaaEncoder::with_image_and_settings(image.clone(), settings.format(PaaType::Dxt1).build())
+		.encode()
+		.unwrap()
+		.mipmaps
+		.into_iter()
+		.map(|m| m.unwrap().data)
+		.collect::<Vec<_>>();
+
+	let fast = encode(TextureEncodingSettings::builder().dxt_quality(DxtQuality::Fast));
+	let best = encode(TextureEncodingSettings::builder().dxt_quality(DxtQuality::Best));
+	let full = encode(TextureEncodingSettings::builder().dxt_quality(DxtQuality::Best).full_quality_mipmaps(true));
+	assert_ne!(fast[0], best[0]);
+
+	// From the fourth mipmap on, Fast unless opted out
+	assert_eq!(best.len(), 5);
+	assert_eq!(fast[3..], best[3..]);
+	assert_eq!(best[..3], full[..3]);
+
+	let large = RgbaImage::from_fn(1024, 1024, |x, y| image::Rgba([(x / 4) as u8, (y / 4) as u8, ((x + y) / 8) as u8, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).dxt_quality(DxtQuality::Fast).build();
+	let paa = PaaEncoder::with_image_and_settings(large.clone(), settings).encode().unwrap();
+	let decoded = paa.mipmaps[0].as_ref().unwrap().decode().unwrap();
+	assert!(image_difference(&decoded, &large).unwrap().total_psnr() > 35.0);
+}
+
+
+#[test]
+fn encode_deterministic() {
+	// Noise, so that the 256x256 DXTn mipmap is LZO-compressed and the
+	// Argb4444 ones LZSS-compressed
+	let image = RgbaImage::from_fn(256, 256, |x, y| image::Rgba((x.wrapping_mul(2_654_435_761) ^ y.wrapping_mul(40_503)).rotate_left(13).to_le_bytes()));
+
+	for paatype in [PaaType::Dxt1, PaaType::Dxt5, PaaType::Argb4444, PaaType::Ai88] {
+		let settings = TextureEncodingSettings::builder().format(paatype).dxt_quality(DxtQuality::Fast).build();
+		let encoder = PaaEncoder::with_image_and_settings(image.clone(), settings);
+		let bytes = encoder.encode().unwrap().to_bytes().unwrap();
+
+		// Again, and on other threads at the same time
+		let threads: Vec<_> = (0..4)
+			.map(|_| {
+				let encoder = encoder.clone();
+				std::thread::spawn(move || encoder.encode().unwrap().to_bytes().unwrap())
+			})
+			.collect();
+
+		for thread in threads {
+			assert_eq!(thread.join().unwrap(), bytes, "{paatype:?}");
 		};
+	};
+}
 
-		macros::log!(trace, "PaaEncoder::encode: AVGC={}, MAXC={}", avgc, maxc);
 
-		let paatype = self.settings.format;
+#[test]
+fn encode_dynamic_image() {
+	// 16-bit gray is rounded and stored as AI88 intensity, and made opaque
+	let luma16 = image::ImageBuffer::from_fn(16, 16, |x, y| image::Luma([((y * 16 + x) as u16 * 257).saturating_sub(100)]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Ai88).build();
+	let paa = PaaEncoder::with_dynamic_image(DynamicImage::ImageLuma16(luma16), settings).encode().unwrap();
+	let data = &paa.mipmaps[0].as_ref().unwrap().data;
+	assert!(data.chunks_exact(2).enumerate().all(|(i, p)| p == [i as u8, 0xFF]));
+	assert!(paa.taggs.contains(&Tagg::Flag { transparency: Transparency::None }));
 
-		let avgc_tagg = Tagg::Avgc { rgba: avgc };
-		let maxc_tagg = Tagg::Maxc { rgba: maxc };
-		let taggs = vec![avgc_tagg, maxc_tagg];
+	// Dithering keeps the average of 127.75 that rounding loses
+	let flat = DynamicImage::ImageLuma16(image::ImageBuffer::from_pixel(16, 16, image::Luma([127 * 257 + 193])));
+	let intensity_sum = |paa: PaaImage| paa.mipmaps[0].as_ref().unwrap().data.iter().step_by(2).map(|i| u32::from(*i)).sum::<u32>();
+	let rounded = PaaEncoder::with_dynamic_image(flat.clone(), settings).encode().unwrap();
+	let dithered = PaaEncoder::with_dynamic_image(flat, TextureEncodingSettings { dither: true, ..settings }).encode().unwrap();
+	assert_eq!(intensity_sum(rounded), 128 * 256);
+	assert_eq!(intensity_sum(dithered), 127 * 256 + 192);
 
-		let mut mipmaps = imageops
-			::construct_mipmap_series(img, 1, image::imageops::FilterType::Triangle)
-			.iter()
-			.map(|i| PaaMipmap::encode(paatype, i))
-			.collect::<Vec<PaaResult<PaaMipmap>>>();
-		mipmaps.truncate(<u8 as Into<usize>>::into(PaaImage::MAX_MIPMAPS));
+	// RGB gets opaque alpha and FLAG
+	let rgb = image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 0x80]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_dynamic_image(DynamicImage::ImageRgb8(rgb.clone()), settings).encode().unwrap();
+	assert!(paa.taggs.contains(&Tagg::Flag { transparency: Transparency::None }));
+	assert!(paa.mipmaps[0].as_ref().unwrap().decode().unwrap().pixels().all(|p| p.0[3] == 0xFF));
 
-		let image = PaaImage { paatype, taggs, palette: None, mipmaps };
+	// ...unless alpha is swizzled from a color channel
+	let swizzled = TextureEncodingSettings { swizzle: ArgbSwizzle::parse_argb("R", "R", "G", "B").unwrap(), ..settings };
+	let paa = PaaEncoder::with_dynamic_image(DynamicImage::ImageRgb8(rgb.clone()), swizzled).encode().unwrap();
+	assert!(!paa.taggs.iter().any(|t| matches!(t, Tagg::Flag { .. })));
 
-		Ok(image)
-	}
+	// RgbaImage sources are not flagged, as before
+	let paa = PaaEncoder::with_image_and_settings(DynamicImage::ImageRgb8(rgb).into_rgba8(), settings).encode().unwrap();
+	assert!(!paa.taggs.iter().any(|t| matches!(t, Tagg::Flag { .. })));
 }
 
 
-/// Steps applied to an RGBA image when converting to PAA
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
-pub struct TextureEncodingSettings {
-	/// [`PaaImage::paatype`] of the output PAA.
-	pub format: PaaType,
-	/// `[TODO]`
-	pub dynrange: Option<bool>,
-	/// Crop the texture to 1x1 if solid color.
-	pub autoreduce: bool,
-	/// `[TODO]`
-	pub mipmap_filter: Option<TextureMipmapFilter>,
-	/// Subpixel mapping applied to the input image.
-	pub swizzle: ArgbSwizzle,
-	/// `[TODO]`
-	pub error_metrics: Option<TextureErrorMetrics>,
+#[test]
+fn settings_builder() {
+	let swizzle = ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap();
+	let settings = TextureEncodingSettings::builder()
+		.format(PaaType::Dxt5)
+		.dynrange(false)
+		.mipmap_filter(TextureMipmapFilter::NormalizeNormalMapAlpha)
+		.swizzle(swizzle)
+		.error_metrics(TextureErrorMetrics::Distance)
+		.build();
+
+	let expected = TextureEncodingSettings {
+		format: PaaType::Dxt5,
+		dynrange: Some(false),
+		mipmap_filter: Some(TextureMipmapFilter::NormalizeNormalMapAlpha),
+		swizzle,
+		error_metrics: Some(TextureErrorMetrics::Distance),
+		..Default::default()
+	};
+	assert_eq!(settings, expected);
+	assert_eq!(TextureEncodingSettings::builder().build(), TextureEncodingSettings::default());
 }
 
 
-impl std::fmt::Display for TextureEncodingSettings {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		let mut segments: Vec<String> = vec![];
-		segments.push(format!("{:?}", self.format));
+#[cfg(feature = "serde")]
+#[test]
+fn settings_serde_roundtrip() {
+	let settings = TextureEncodingSettings::builder()
+		.format(PaaType::Dxt1)
+		.autoreduce(true)
+		.swizzle(ArgbSwizzle::parse_argb("1", "1", "G", "1-B").unwrap())
+		.max_size(512)
+		.build();
 
-		if let Some(r) = self.dynrange {
-			segments.push(format!("dynRange={}", r));
-		};
+	let json = serde_json::to_string(&settings).unwrap();
+	assert!(json.contains(r#""swizzle":"1, 1, g, 1-b""#));
+	assert_eq!(serde_json::from_str::<TextureEncodingSettings>(&json).unwrap(), settings);
 
-		if self.autoreduce {
-			segments.push("autoreduce".into());
-		};
+	let partial = serde_json::from_str::<TextureEncodingSettings>(r#"{ "format": "Dxt5" }"#).unwrap();
+	assert_eq!(partial, TextureEncodingSettings::builder().format(PaaType::Dxt5).build());
 
-		if let Some(f) = self.mipmap_filter {
-			segments.push(format!("{:?}", f));
-		};
+	let transparency = serde_json::to_string(&crate::Transparency::AlphaNotInterpolated).unwrap();
+	assert_eq!(serde_json::from_str::<crate::Transparency>(&transparency).unwrap(), crate::Transparency::AlphaNotInterpolated);
+}
 
-		if !self.swizzle.is_noop() {
-			segments.push(format!("swizzle=<{}>", self.swizzle));
-		};
 
-		if let Some(m) = self.error_metrics {
-			segments.push(format!("errorMetrics={:?}", m));
-		};
+/// Weighting of pixels when computing the average color ([`Tagg::Avgc`]) and
+/// maximum color ([`Tagg::Maxc`]) of a texture
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AvgcWeighting {
+	/// Weight colors by pixel alpha, and ignore fully transparent pixels for
+	/// MAXC, as ImageToPAA does.
+	AlphaWeighted,
+	/// Average colors of all pixels uniformly.
+	Uniform,
+}
 
-		write!(f, "<{}>", segments.join(", "))
+
+impl Default for AvgcWeighting {
+	fn default() -> Self {
+		AvgcWeighting::AlphaWeighted
 	}
 }
 
 
+/// How [`PaaEncoder::encode`] fixes an image of dimensions that the engine
+/// does not accept (see [`TextureEncodingSettings::resize_to_fit`])
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FitMode {
+	/// Resize to the nearest valid dimensions.
+	Stretch,
+	/// Center on a transparent canvas of the next larger valid dimensions.
+	PadTransparent,
+	/// Crop the center to the next smaller valid dimensions.
+	Crop,
+}
+
+
 /// `[TODO]`
 #[allow(missing_docs)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextureMipmapFilter {
 	AlphaNoise,
 	FadeOut,
@@ -138,6 +1295,30 @@ pub enum TextureMipmapFilter {
 }
 
 
+impl TextureMipmapFilter {
+	/// Every [`TextureMipmapFilter`], in declaration order.
+	pub const ALL: &'static [TextureMipmapFilter] = {
+		use TextureMipmapFilter::*;
+		&[AlphaNoise, FadeOut, AddAlphaNoise, NormalizeNormalMap, NormalizeNormalMapAlpha, NormalizeNormalMapNoise, NormalizeNormalMapFade]
+	};
+
+
+	/// Return true if the filter is meant for normal maps.
+	pub fn is_normal_map(self) -> bool {
+		use TextureMipmapFilter::*;
+		matches!(self, NormalizeNormalMap | NormalizeNormalMapAlpha | NormalizeNormalMapNoise | NormalizeNormalMapFade)
+	}
+}
+
+
+impl std::fmt::Display for TextureMipmapFilter {
+	/// Writes the name used in TexConvert.cfg, which [`FromStr`] parses back.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		std::fmt::Debug::fmt(self, f)
+	}
+}
+
+
 impl FromStr for TextureMipmapFilter {
 	type Err = ();
 
@@ -163,11 +1344,26 @@ impl FromStr for TextureMipmapFilter {
 /// `[TODO]`
 #[allow(missing_docs)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextureErrorMetrics {
 	Distance,
 }
 
 
+impl TextureErrorMetrics {
+	/// Every [`TextureErrorMetrics`], in declaration order.
+	pub const ALL: &'static [TextureErrorMetrics] = &[TextureErrorMetrics::Distance];
+}
+
+
+impl std::fmt::Display for TextureErrorMetrics {
+	/// Writes the name used in TexConvert.cfg, which [`FromStr`] parses back.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		std::fmt::Debug::fmt(self, f)
+	}
+}
+
+
 impl FromStr for TextureErrorMetrics {
 	type Err = ();
 
@@ -184,6 +1380,26 @@ impl FromStr for TextureErrorMetrics {
 }
 
 
+#[test]
+fn texconvert_names() {
+	for filter in TextureMipmapFilter::ALL {
+		assert_eq!(filter.to_string().parse::<TextureMipmapFilter>(), Ok(*filter));
+		assert_eq!(filter.to_string().to_uppercase().parse::<TextureMipmapFilter>(), Ok(*filter));
+	};
+
+	for metrics in TextureErrorMetrics::ALL {
+		assert_eq!(metrics.to_string().parse::<TextureErrorMetrics>(), Ok(*metrics));
+	};
+
+	let settings = TextureEncodingSettings::builder()
+		.mipmap_filter(TextureMipmapFilter::NormalizeNormalMapAlpha)
+		.error_metrics(TextureErrorMetrics::Distance)
+		.transparency(Transparency::AlphaNotInterpolated)
+		.build();
+	assert_eq!(settings.to_string(), "<Dxt5, NormalizeNormalMapAlpha, errorMetrics=Distance, flag=noninterpolated>");
+}
+
+
 /// The file `TexConvert.cfg` from Arma's TexView2, represented as a
 /// [suffix string][`String`] &#x21A6; [Settings][`TextureEncodingSettings`] map
 ///
@@ -211,11 +1427,19 @@ impl FromStr for TextureErrorMetrics {
 /// ```
 ///
 /// The config entry `name` contains the texture suffix in the form of a glob
-/// pattern; all entries are of this specific form and can be simplified as the
-/// suffix.
+/// pattern.  Entries of the usual `*_suffix.*` form are available by suffix
+/// through [`Deref`]; all patterns, including those of other forms, are matched
+/// by [`settings_for_filename`][`Self::settings_for_filename`].
+///
+/// Properties of a `TexConvert.cfg` entry that are unknown, or have values that
+/// could not be used, are reported by
+/// [`unknown_properties`][`Self::unknown_properties`].
 #[derive(Debug)]
 pub struct TextureHints {
 	hints: HashMap<String, TextureEncodingSettings>,
+	patterns: Vec<(String, TextureEncodingSettings)>,
+	unknown_properties: Vec<(String, Vec<(String, String)>)>,
+	default_suffix: Option<String>,
 }
 
 
@@ -240,7 +1464,137 @@ impl TextureHints {
 	/// let tc = TextureHints::with_hints(hints);
 	/// ```
 	pub fn with_hints(hints: HashMap<String, TextureEncodingSettings>) -> Self {
-		Self { hints }
+		// Sorted before lowercasing, so that of suffixes differing only in
+		// case, the same one wins whatever the order of the map
+		let mut hints = hints.into_iter().collect::<Vec<_>>();
+		hints.sort_by(|(x, _), (y, _)| x.to_lowercase().cmp(&y.to_lowercase()).then_with(|| x.cmp(y)));
+
+		let patterns = hints.into_iter()
+			.map(|(suffix, settings)| (format!("*_{}.*", suffix.to_lowercase()), settings))
+			.collect();
+		Self::with_patterns(patterns)
+	}
+
+
+	/// Constructs an instance of [`Self`] from `name` glob patterns (as in
+	/// `TexConvert.cfg`) and their settings, in declaration order.  A pattern
+	/// repeated later replaces the settings of its earlier declaration.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureHints, PaaType::*, TextureEncodingSettings};
+	/// let dxt1 = TextureEncodingSettings { format: Dxt1, ..Default::default() };
+	/// let tc = TextureHints::with_patterns(vec![("*_co.*".to_owned(), dxt1), ("*detail*.*".to_owned(), dxt1)]);
+	/// assert!(tc.get("CO").is_some());
+	/// assert!(tc.settings_for_filename("rock_detail_a.paa").is_some());
+	/// ```
+	pub fn with_patterns(patterns: Vec<(String, TextureEncodingSettings)>) -> Self {
+		Self::from_parsed(patterns.into_iter().map(|(p, s)| (p, s, vec![])).collect())
+	}
+
+
+	fn from_parsed(patterns: cfgfile::HintPatterns) -> Self {
+		let mut result = Self {
+			hints: HashMap::new(),
+			patterns: Vec::with_capacity(patterns.len()),
+			unknown_properties: vec![],
+			default_suffix: None,
+		};
+
+		for (pattern, settings, unknown) in patterns {
+			result.insert_pattern(pattern, settings, unknown);
+		};
+
+		result
+	}
+
+
+	fn insert_pattern(&mut self, pattern: String, settings: TextureEncodingSettings, unknown: Vec<(String, String)>) {
+		if let Some(suffix) = Self::pattern_to_suffix(&pattern) {
+			let _ = self.hints.insert(suffix, settings);
+		};
+
+		self.unknown_properties.retain(|(p, _)| !p.eq_ignore_ascii_case(&pattern));
+		if !unknown.is_empty() {
+			self.unknown_properties.push((pattern.clone(), unknown));
+		};
+
+		let existing = self.patterns.iter_mut()
+			.find(|(p, _)| p.eq_ignore_ascii_case(&pattern));
+
+		match existing {
+			Some((_, s)) => *s = settings,
+			None => self.patterns.push((pattern, settings)),
+		};
+	}
+
+
+	/// Return the suffix of a pattern of the form `*_suffix.*`.
+	fn pattern_to_suffix(pattern: &str) -> Option<String> {
+		let suffix = pattern.strip_prefix("*_")?.strip_suffix(".*")?;
+
+		if suffix.is_empty() || suffix.contains(|c: char| matches!(c, '*' | '?')) {
+			return None;
+		};
+
+		Some(suffix.to_uppercase())
+	}
+
+
+	/// Return the `name` glob patterns and their settings, in declaration
+	/// order.
+	pub fn patterns(&self) -> &[(String, TextureEncodingSettings)] {
+		&self.patterns
+	}
+
+
+	/// Return the properties of `TexConvert.cfg` entries that were ignored,
+	/// either because they are unknown (e.g. a misspelled name) or because
+	/// their value could not be used, as `(name pattern, [(property, value)])`
+	/// in declaration order.  Property names are uppercase, and values are in
+	/// config syntax.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::TextureHints;
+	/// let tc = TextureHints::try_parse_from_str("class TextureHints { class co { name = \"*_co.*\"; errorPenality = 2; }; };").unwrap();
+	/// assert_eq!(tc.unknown_properties(), [("*_co.*".to_owned(), vec![("ERRORPENALITY".to_owned(), "2".to_owned())])]);
+	/// ```
+	pub fn unknown_properties(&self) -> &[(String, Vec<(String, String)>)] {
+		&self.unknown_properties
+	}
+
+
+	/// Return the settings for a texture file name (or path; only the file
+	/// name is matched) by matching it against all `name` glob patterns.
+	///
+	/// Matching is case-insensitive and supports `*` and `?`.  If several
+	/// patterns match, the most specific one (with the most non-wildcard
+	/// characters) wins; ties are resolved in favor of the earliest declared
+	/// pattern.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureHints, PaaType::*, TextureEncodingSettings};
+	/// let dxt1 = TextureEncodingSettings { format: Dxt1, ..Default::default() };
+	/// let dxt5 = TextureEncodingSettings { format: Dxt5, ..Default::default() };
+	/// let tc = TextureHints::with_patterns(vec![("*_co.*".to_owned(), dxt1), ("*_co.paa".to_owned(), dxt5)]);
+	/// assert_eq!(tc.settings_for_filename("data/wall_CO.paa").map(|s| s.format), Some(Dxt5));
+	/// assert_eq!(tc.settings_for_filename("wall_co.png").map(|s| s.format), Some(Dxt1));
+	/// ```
+	pub fn settings_for_filename(&self, name: &str) -> Option<&TextureEncodingSettings> {
+		let file_name = std::path::Path::new(name)
+			.file_name()
+			.and_then(|n| n.to_str())
+			.unwrap_or(name);
+
+		let specificity = |pattern: &str| pattern.chars().filter(|c| !matches!(c, '*' | '?')).count();
+
+		self.patterns.iter()
+			.enumerate()
+			.filter(|(_, (p, _))| glob_matches(p, file_name))
+			.max_by_key(|(i, (p, _))| (specificity(p), std::cmp::Reverse(*i)))
+			.map(|(_, (_, s))| s)
 	}
 
 
@@ -258,12 +1612,202 @@ impl TextureHints {
 	/// # Ok(()) }
 	/// ```
 	pub fn try_parse_from_str(input: &str) -> PaaResult<Self> {
-		let hints = cfgfile::try_parse_texconvert(input)?;
-		let result = TextureHints { hints };
+		let patterns = cfgfile::try_parse_texconvert(input)?;
+		let result = TextureHints::from_parsed(patterns);
+		Ok(result)
+	}
+
+
+	/// Construct an instance of [`Self`] by reading a `TexConvert.cfg` file
+	/// from `path`.
+	///
+	/// Unlike [`try_parse_from_str`][`Self::try_parse_from_str`], which skips
+	/// all preprocessor directives, this follows `#include "file"` directives
+	/// (relative to the including file) up to 16 levels deep.  Hints from the
+	/// including file take precedence over included ones.
+	///
+	/// # Errors
+	/// - [`TexconvertIoError`]: Could not read `path` or an included file.
+	/// - [`TexconvertIncludeCycle`]: A file (indirectly) includes itself.
+	/// - [`TexconvertIncludeTooDeep`]: `#include`s are nested too deeply.
+	/// - [`TexconvertParseError`]: Could not parse a list of config items.
+	pub fn try_parse_from_path<P: AsRef<std::path::Path>>(path: P) -> PaaResult<Self> {
+		let patterns = cfgfile::try_parse_texconvert_path(path.as_ref())?;
+		let result = TextureHints::from_parsed(patterns);
+		Ok(result)
+	}
+
+
+	/// Layer `overrides` on top of `self`: every `name` pattern of `overrides`
+	/// replaces the settings of the same pattern (and therefore suffix) in
+	/// `self`, and patterns not yet in `self` are appended.  All other hints
+	/// of `self` are kept.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureHints, PaaType::*, TextureEncodingSettings};
+	/// let dxt1 = TextureEncodingSettings { format: Dxt1, ..Default::default() };
+	/// let dxt5 = TextureEncodingSettings { format: Dxt5, ..Default::default() };
+	/// let base = TextureHints::with_patterns(vec![("*_co.*".to_owned(), dxt1), ("*_ca.*".to_owned(), dxt1)]);
+	/// let overrides = TextureHints::with_patterns(vec![("*_CA.*".to_owned(), dxt5)]);
+	/// let tc = base.merge(overrides);
+	/// assert_eq!(tc.get("CO").map(|s| s.format), Some(Dxt1));
+	/// assert_eq!(tc.get("CA").map(|s| s.format), Some(Dxt5));
+	/// ```
+	pub fn merge(mut self, overrides: TextureHints) -> TextureHints {
+		let TextureHints { patterns, unknown_properties, default_suffix, .. } = overrides;
+
+		for (pattern, settings) in patterns {
+			let unknown = unknown_properties.iter()
+				.find(|(p, _)| p.eq_ignore_ascii_case(&pattern))
+				.map(|(_, u)| u.clone())
+				.unwrap_or_default();
+			self.insert_pattern(pattern, settings, unknown);
+		};
+
+		if default_suffix.is_some() {
+			self.default_suffix = default_suffix;
+		};
+
+		self
+	}
+
+
+	/// Construct an instance of [`Self`] from the contents of several
+	/// `TexConvert.cfg` files, each [merged][`Self::merge`] on top of the
+	/// previous ones.
+	///
+	/// # Errors
+	/// - [`TexconvertParseError`]: Could not parse a list of config items.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureHints, PaaType};
+	/// let base = "class TextureHints { class co { name = \"*_co.*\"; format = \"DXT1\"; }; };";
+	/// let project = "class TextureHints { class co { name = \"*_co.*\"; format = \"DXT5\"; }; };";
+	/// let tc = TextureHints::try_parse_many([base, project]).unwrap();
+	/// assert_eq!(tc.get("CO").map(|s| s.format), Some(PaaType::Dxt5));
+	/// ```
+	pub fn try_parse_many<'a, I: IntoIterator<Item=&'a str>>(configs: I) -> PaaResult<Self> {
+		let mut result = TextureHints::with_patterns(vec![]);
+
+		for config in configs {
+			result = result.merge(TextureHints::try_parse_from_str(config)?);
+		};
+
 		Ok(result)
 	}
 
 
+	/// Return the texture hints of the stock Arma 3 Tools `TexConvert.cfg`,
+	/// for use when no config file is available.
+	///
+	/// The table is built in code and covers the suffixes CO, CA, NOHQ,
+	/// NOVHQ, SMDI, AS, MC, DT, DTSMDI, MASK, TI, LCO and SKY.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{TextureHints, PaaType};
+	/// let hints = TextureHints::builtin();
+	/// assert_eq!(hints.get("CO").map(|s| s.format), Some(PaaType::Dxt5));
+	/// ```
+	#[cfg(feature = "builtin-hints")]
+	#[cfg_attr(doc, doc(cfg(feature = "builtin-hints")))]
+	pub fn builtin() -> Self {
+		use TextureMipmapFilter::*;
+		use ChannelSwizzleId::*;
+		use ChannelSwizzleData::*;
+
+		const fn src(source: ChannelSwizzleId) -> ChannelSwizzleData { Source { neg_flag: false, source } }
+		const fn neg(source: ChannelSwizzleId) -> ChannelSwizzleData { Source { neg_flag: true, source } }
+		const ONE: ChannelSwizzleData = Fill { value: ChannelSwizzleFill::FillFF };
+
+		let swizzle = |a, r, g, b| ArgbSwizzle {
+			a: ChannelSwizzle { target: Alpha, data: a },
+			r: ChannelSwizzle { target: Red, data: r },
+			g: ChannelSwizzle { target: Green, data: g },
+			b: ChannelSwizzle { target: Blue, data: b },
+		};
+
+		// Formats follow `PaaType::default_for_suffix`, as do the presets
+		let format = |suffix| PaaType::default_for_suffix(suffix).expect("builtin suffixes have a default format");
+		let plain = |suffix| TextureEncodingSettings { format: format(suffix), ..Default::default() };
+		let reduced = |suffix| TextureEncodingSettings { format: format(suffix), autoreduce: true, ..Default::default() };
+		let mask_lco = TextureEncodingSettings::mask_lco();
+		let nohq = TextureEncodingSettings::nohq();
+		let detail = TextureEncodingSettings { dynrange: Some(false), mipmap_filter: Some(FadeOut), ..plain("DT") };
+
+		let hints = [
+			("CO", TextureEncodingSettings::co()),
+			("CA", TextureEncodingSettings::ca()),
+			("NOHQ", nohq),
+			("NOVHQ", TextureEncodingSettings {
+				swizzle: swizzle(neg(Red), ONE, src(Green), ONE),
+				mipmap_filter: Some(NormalizeNormalMap),
+				..nohq
+			}),
+			("SMDI", TextureEncodingSettings { swizzle: swizzle(ONE, ONE, src(Green), src(Blue)), ..TextureEncodingSettings::smdi() }),
+			("AS", TextureEncodingSettings { swizzle: swizzle(ONE, ONE, src(Green), ONE), ..reduced("AS") }),
+			("MC", reduced("MC")),
+			("DT", detail),
+			("DTSMDI", detail),
+			("MASK", mask_lco),
+			("TI", reduced("TI")),
+			("LCO", mask_lco),
+			("SKY", TextureEncodingSettings { swizzle: swizzle(neg(Green), src(Red), neg(Alpha), src(Blue)), ..plain("SKY") }),
+		];
+
+		let patterns = hints.into_iter()
+			.map(|(suffix, settings)| (format!("*_{}.*", suffix.to_lowercase()), settings))
+			.collect::<Vec<_>>();
+
+		Self::with_patterns(patterns)
+	}
+
+
+	/// Set the suffix returned by [`suffix_for_path`][`Self::suffix_for_path`]
+	/// when no other candidate is a known suffix (commonly `"CO"`).
+	pub fn with_default_suffix(self, suffix: &str) -> Self {
+		Self { default_suffix: Some(suffix.to_uppercase()), ..self }
+	}
+
+
+	/// Find the known texture type suffix of a texture path.
+	///
+	/// Candidates are tried in order against the suffixes of `self`: the full
+	/// file stem, then progressively longer `'_'`-separated tails of the stem
+	/// (e.g. `"DIRTY"`, `"CO_DIRTY"`), then every `'_'`-separated part from the
+	/// right (e.g. `"CO"`), and finally the
+	/// [default suffix][`Self::with_default_suffix`].
+	///
+	/// # Example
+	/// ```
+	/// # use std::collections::HashMap;
+	/// # use a3_paa::{TextureHints, TextureEncodingSettings};
+	/// let hints = HashMap::from([("CO".to_owned(), TextureEncodingSettings::default())]);
+	/// let tc = TextureHints::with_hints(hints).with_default_suffix("co");
+	/// assert_eq!(tc.suffix_for_path("soldier_co_dirty.paa"), Some("CO"));
+	/// assert_eq!(tc.suffix_for_path("detailmap.paa"), Some("CO"));
+	/// ```
+	pub fn suffix_for_path<P: AsRef<std::path::Path>>(&self, path: P) -> Option<&str> {
+		let stem = path.as_ref()
+			.file_stem()?
+			.to_str()?
+			.to_uppercase();
+
+		let tails = stem.match_indices('_')
+			.rev()
+			.map(|(i, _)| &stem[i + 1..]);
+
+		std::iter::once(stem.as_str())
+			.chain(tails)
+			.chain(stem.rsplit('_'))
+			.chain(self.default_suffix.as_deref())
+			.find_map(|candidate| self.hints.get_key_value(candidate))
+			.map(|(suffix, _)| suffix.as_str())
+	}
+
+
 	/// Get the PAA texture type suffix from a PAA path.
 	///
 	/// # Example
@@ -279,3 +1823,150 @@ impl TextureHints {
 		Some(rsplit.to_uppercase())
 	}
 }
+
+
+/// Case-insensitive glob match of `name` against `pattern`, supporting `*`
+/// (any run of characters) and `?` (any single character).
+fn glob_matches(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+	let name: Vec<char> = name.to_lowercase().chars().collect();
+
+	let (mut p, mut n) = (0usize, 0usize);
+	let mut backtrack: Option<(usize, usize)> = None;
+
+	while n < name.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				backtrack = Some((p, n));
+				p += 1;
+			},
+
+			Some(c) if *c == '?' || *c == name[n] => {
+				p += 1;
+				n += 1;
+			},
+
+			_ => match backtrack {
+				// Let the last `*` consume one more character
+				Some((star_p, star_n)) => {
+					backtrack = Some((star_p, star_n + 1));
+					p = star_p + 1;
+					n = star_n + 1;
+				},
+
+				None => return false,
+			},
+		};
+	};
+
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+
+#[test]
+fn test_glob_matches() {
+	assert!(glob_matches("*_co.*", "wall_co.paa"));
+	assert!(glob_matches("*_CO.*", "Wall_co.PNG"));
+	assert!(!glob_matches("*_co.*", "wall_ca.paa"));
+	assert!(glob_matches("*detail*.*", "rock_detail_nohq.paa"));
+	assert!(glob_matches("tex?.paa", "tex1.paa"));
+	assert!(!glob_matches("tex?.paa", "tex12.paa"));
+	assert!(glob_matches("*", ""));
+	assert!(!glob_matches("a*b*c", "abca"));
+}
+
+
+#[test]
+fn settings_for_filename_precedence() {
+	use PaaType::*;
+
+	let with_format = |format| TextureEncodingSettings { format, ..Default::default() };
+	let hints = TextureHints::with_patterns(vec![
+		(String::from("*_co.*"), with_format(Dxt1)),
+		(String::from("*detail*.*"), with_format(Dxt5)),
+		(String::from("*_co.paa"), with_format(Argb4444)),
+		(String::from("*_ca.*"), with_format(Argb1555)),
+		(String::from("*_ca.*"), with_format(Argb8888)),
+		(String::from("*_?a.*"), with_format(Ai88)),
+	]);
+
+	assert_eq!(hints.settings_for_filename("wall_co.png").map(|s| s.format), Some(Dxt1));
+	assert_eq!(hints.settings_for_filename("wall_co.paa").map(|s| s.format), Some(Argb4444));
+	assert_eq!(hints.settings_for_filename("P:/a3/rock_detail_co.png").map(|s| s.format), Some(Dxt5));
+	assert_eq!(hints.settings_for_filename("rock_detail.png").map(|s| s.format), Some(Dxt5));
+	assert_eq!(hints.settings_for_filename("glass_ca.paa").map(|s| s.format), Some(Argb8888));
+	assert_eq!(hints.settings_for_filename("glass_xa.paa").map(|s| s.format), Some(Ai88));
+	assert_eq!(hints.settings_for_filename("glass.paa"), None);
+	assert_eq!(hints.patterns().len(), 5);
+	assert_eq!(hints.get("CA").map(|s| s.format), Some(Argb8888));
+}
+
+
+#[test]
+fn suffix_for_path() {
+	let hints = ["CO", "DT", "DTSMDI", "SMDI"]
+		.map(|s| (s.to_owned(), TextureEncodingSettings::default()));
+	let hints = TextureHints::with_hints(HashMap::from(hints));
+
+	assert_eq!(hints.suffix_for_path("rock_dtsmdi.paa"), Some("DTSMDI"));
+	assert_eq!(hints.suffix_for_path("data/soldier_co_dirty.paa"), Some("CO"));
+	assert_eq!(hints.suffix_for_path("detailmap.paa"), None);
+
+	let hints = hints.with_default_suffix("CO");
+	assert_eq!(hints.suffix_for_path("detailmap.paa"), Some("CO"));
+	assert_eq!(hints.suffix_for_path("Rock_SMDI.png"), Some("SMDI"));
+
+	// Of suffixes differing only in case, the last in byte order wins, whatever
+	// the order of the map
+	let settings = |format| TextureEncodingSettings { format, ..Default::default() };
+
+	for _ in 0..8 {
+		let hints = TextureHints::with_hints(HashMap::from([("CO".to_owned(), settings(PaaType::Dxt5)), ("co".to_owned(), settings(PaaType::Dxt1))]));
+		assert_eq!(hints.settings_for_filename("wall_co.paa").map(|s| s.format), Some(PaaType::Dxt1));
+	};
+}
+
+
+#[test]
+fn merge_hints() {
+	let base = r#"class TextureHints {
+		class co { name = "*_co.*"; format = "DXT1"; };
+		class ca { name = "*_ca.*"; format = "DXT5"; autoreduce = 1; };
+		class nohq { name = "*_nohq.*"; format = "DXT5"; channelSwizzleA = "1-R"; channelSwizzleR = "1-A"; };
+	};"#;
+	let project = r#"class TextureHints {
+		class normalmap { name = "*_nohq.*"; format = "ARGB8888"; channelSwizzleA = "1-R"; channelSwizzleR = "1-A"; };
+	};"#;
+
+	let base_hints = TextureHints::try_parse_from_str(base).unwrap();
+	let hints = TextureHints::try_parse_many([base, project]).unwrap();
+
+	assert_eq!(hints.len(), 3);
+	assert_eq!(hints.get("NOHQ").map(|s| s.format), Some(PaaType::Argb8888));
+	assert_eq!(hints.get("NOHQ").map(|s| s.swizzle), base_hints.get("NOHQ").map(|s| s.swizzle));
+	assert_eq!(hints.get("CO"), base_hints.get("CO"));
+	assert_eq!(hints.get("CA"), base_hints.get("CA"));
+
+	let patterns = hints.patterns().iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>();
+	assert_eq!(patterns, ["*_co.*", "*_ca.*", "*_nohq.*"]);
+}
+
+
+#[cfg(feature = "builtin-hints")]
+#[test]
+fn builtin_hints() {
+	type S = TextureEncodingSettings;
+
+	let hints = TextureHints::builtin();
+	assert_eq!(hints.get("CO"), Some(&S::co()));
+	assert_eq!(hints.get("CA"), Some(&S::ca()));
+	assert_eq!(hints.get("NOHQ"), Some(&S::nohq()));
+	assert_eq!(hints.get("SMDI").map(|s| s.format), Some(S::smdi().format));
+	assert_eq!(hints.get("MASK"), Some(&S::mask_lco()));
+	assert_eq!(hints.get("LCO"), Some(&S::mask_lco()));
+
+	for suffix in ["CO", "CA", "NOHQ", "NOVHQ", "SMDI", "AS", "MC", "DT", "DTSMDI", "MASK", "TI", "LCO", "SKY"] {
+		assert!(hints.contains_key(suffix), "Missing builtin hint: {}", suffix);
+		assert_eq!(hints.get(suffix).map(|s| s.format), PaaType::default_for_suffix(suffix), "{}", suffix);
+	};
+}
@@ -0,0 +1,237 @@
+use std::io::{Cursor, Read, Seek, Write};
+
+use image::{ColorType, DynamicImage, ImageBuffer, ImageDecoder, ImageEncoder, ImageError, ImageResult, RgbaImage};
+use image::error::{DecodingError, EncodingError, ImageFormatHint, ParameterError, ParameterErrorKind, UnsupportedError, UnsupportedErrorKind};
+
+use crate::{PaaEncoder, PaaError, PaaReader, PaaType, TextureEncodingSettings};
+
+
+/// [`image::ImageDecoder`] of the first (largest) mipmap of a PAA file, as
+/// [`ColorType::Rgba8`] pixels.  Mipmaps are decoded as by
+/// [`PaaDecoder::decode_first`][`crate::PaaDecoder::decode_first`], without
+/// [`DecodeOptions`][`crate::DecodeOptions`].
+#[derive(Debug)]
+#[cfg_attr(doc, doc(cfg(feature = "image-format")))]
+pub struct PaaImageDecoder<R> {
+	reader: PaaReader<R>,
+	dimensions: (u32, u32),
+}
+
+
+impl<R: Read + Seek> PaaImageDecoder<R> {
+	/// Read the PAA header from `input`, and the dimensions of its first
+	/// mipmap.
+	///
+	/// # Errors
+	/// - [`ImageError::Decoding`]: Any error of [`PaaReader::new`] or
+	///   [`PaaReader::mipmap_dimensions`], as source.
+	///
+	/// # Panics
+	/// - As [`PaaReader::new`].
+	pub fn new(input: R) -> ImageResult<Self> {
+		let mut reader = PaaReader::new(input).map_err(decoding_error)?;
+		let (width, height) = reader.mipmap_dimensions(0).map_err(decoding_error)?;
+
+		Ok(Self { reader, dimensions: (width.into(), height.into()) })
+	}
+}
+
+
+impl<'a, R: Read + Seek + 'a> ImageDecoder<'a> for PaaImageDecoder<R> {
+	type Reader = Cursor<Vec<u8>>;
+
+
+	fn dimensions(&self) -> (u32, u32) {
+		self.dimensions
+	}
+
+
+	fn color_type(&self) -> ColorType {
+		ColorType::Rgba8
+	}
+
+
+	fn into_reader(self) -> ImageResult<Self::Reader> {
+		let size = usize::try_from(self.total_bytes())
+			.map_err(|_| ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)))?;
+		let mut buffer = vec![0; size];
+		self.read_image(&mut buffer)?;
+
+		Ok(Cursor::new(buffer))
+	}
+
+
+	fn read_image(mut self, buf: &mut [u8]) -> ImageResult<()> {
+		if u64::try_from(buf.len()).ok() != Some(self.total_bytes()) {
+			return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+		};
+
+		let image = self.reader.read_mipmap(0).and_then(|m| m.decode()).map_err(decoding_error)?;
+
+		// The mipmap header was read twice, and might have been corrupted
+		if image.dimensions() != self.dimensions {
+			return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+		};
+
+		buf.copy_from_slice(image.as_raw());
+
+		Ok(())
+	}
+}
+
+
+/// Decode the first (largest) mipmap of a PAA file with [`PaaImageDecoder`].
+///
+/// # Errors
+/// - Any error of [`PaaImageDecoder::new`] or
+///   [`ImageDecoder::read_image`].
+///
+/// # Panics
+/// - As [`PaaImageDecoder::new`].
+#[cfg_attr(doc, doc(cfg(feature = "image-format")))]
+pub fn load_paa<R: Read + Seek>(reader: R) -> ImageResult<DynamicImage> {
+	DynamicImage::from_decoder(PaaImageDecoder::new(reader)?)
+}
+
+
+/// [`image::ImageEncoder`] of PAA files, [`PaaType::Dxt5`] unless set
+/// otherwise.  The image is encoded with a full mipmap chain and AVGC, MAXC
+/// and FLAG taggs, as by [`PaaEncoder::encode`]; 8-bit color types other
+/// than [`ColorType::Rgba8`] are converted to it.
+#[derive(Debug)]
+#[cfg_attr(doc, doc(cfg(feature = "image-format")))]
+pub struct PaaImageEncoder<W> {
+	writer: W,
+	paatype: PaaType,
+}
+
+
+impl<W: Write> PaaImageEncoder<W> {
+	/// Create an encoder of [`PaaType::Dxt5`] PAA files, writing to `writer`.
+	pub fn new(writer: W) -> Self {
+		Self { writer, paatype: PaaType::Dxt5 }
+	}
+
+
+	/// Set the type of the PAA mipmaps.
+	pub fn with_paatype(self, paatype: PaaType) -> Self {
+		Self { paatype, ..self }
+	}
+}
+
+
+impl<W: Write> ImageEncoder for PaaImageEncoder<W> {
+	fn write_image(mut self, buf: &[u8], width: u32, height: u32, color_type: ColorType) -> ImageResult<()> {
+		let unsupported = |kind| ImageError::Unsupported(UnsupportedError::from_format_and_kind(ImageFormatHint::Name("PAA".into()), kind));
+
+		if self.paatype == PaaType::IndexPalette {
+			return Err(unsupported(UnsupportedErrorKind::GenericFeature("encoding to IndexPalette".into())));
+		};
+
+		let dimension_mismatch = || ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch));
+		let expected_len = u64::from(width) * u64::from(height) * u64::from(color_type.bytes_per_pixel());
+
+		// ImageBuffer::from_raw accepts oversized buffers
+		if u64::try_from(buf.len()).ok() != Some(expected_len) {
+			return Err(dimension_mismatch());
+		};
+
+		let buf = buf.to_vec();
+		let image = match color_type {
+			ColorType::Rgba8 => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8),
+			ColorType::Rgb8 => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8),
+			ColorType::La8 => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8),
+			ColorType::L8 => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8),
+			c => return Err(unsupported(UnsupportedErrorKind::Color(c.into()))),
+		};
+		let image: RgbaImage = image
+			.ok_or_else(dimension_mismatch)?
+			.into_rgba8();
+
+		let settings = TextureEncodingSettings::builder().format(self.paatype).build();
+		let paa = PaaEncoder::with_image_and_settings(image, settings).encode().map_err(encoding_error)?;
+		let bytes = paa.to_bytes().map_err(encoding_error)?;
+		self.writer.write_all(&bytes)?;
+
+		Ok(())
+	}
+}
+
+
+fn decoding_error(error: PaaError) -> ImageError {
+	ImageError::Decoding(DecodingError::new(ImageFormatHint::Name("PAA".into()), error))
+}
+
+
+fn encoding_error(error: PaaError) -> ImageError {
+	ImageError::Encoding(EncodingError::new(ImageFormatHint::Name("PAA".into()), error))
+}
+
+
+#[test]
+fn paa_image_decoder() {
+	use std::error::Error;
+	use crate::{PaaDecoder, PaaEncoder, PaaType, TextureEncodingSettings};
+
+	let image = image::RgbaImage::from_fn(32, 16, |x, y| image::Rgba([x as u8 * 8, y as u8 * 16, 0x40, 0xFF - x as u8]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	let bytes = paa.to_bytes().unwrap();
+
+	let decoder = PaaImageDecoder::new(Cursor::new(&bytes)).unwrap();
+	assert_eq!(decoder.dimensions(), (32, 16));
+	assert_eq!(decoder.total_bytes(), 32 * 16 * 4);
+
+	let mut short = vec![0; 32 * 16 * 4 - 1];
+	assert!(matches!(decoder.read_image(&mut short), Err(ImageError::Parameter(_))));
+
+	let loaded = load_paa(Cursor::new(&bytes)).unwrap();
+	assert_eq!(loaded.color(), ColorType::Rgba8);
+	assert_eq!(loaded.into_rgba8(), PaaDecoder::with_paa(paa).decode_first().unwrap());
+
+	match load_paa(Cursor::new(b"not a PAA file")) {
+		Err(ImageError::Decoding(e)) => assert!(e.source().unwrap().downcast_ref::<PaaError>().is_some()),
+		r => panic!("Unexpected result: {r:?}"),
+	};
+}
+
+
+#[test]
+fn paa_image_encoder() {
+	use crate::{PaaDecoder, PaaImage, Tagg};
+
+	let image = image::RgbImage::from_fn(64, 32, |x, y| image::Rgb([x as u8 * 4, y as u8 * 8, 0x80]));
+	let mut bytes = vec![];
+	PaaImageEncoder::new(&mut bytes).write_image(image.as_raw(), 64, 32, ColorType::Rgb8).unwrap();
+
+	let paa = PaaImage::from_bytes(&bytes).unwrap();
+	assert_eq!(paa.paatype, PaaType::Dxt5);
+	assert_eq!(paa.mipmaps.iter().filter(|m| m.is_ok()).count(), 5);
+	assert!(paa.taggs.iter().any(|t| matches!(t, Tagg::Avgc { .. })));
+
+	let decoded = PaaDecoder::with_paa(paa).decode_first().unwrap();
+	assert_eq!(decoded.dimensions(), (64, 32));
+
+	for (a, b) in decoded.pixels().zip(image.pixels()) {
+		assert!((0..3).all(|c| (i16::from(a.0[c]) - i16::from(b.0[c])).abs() <= 16));
+		assert_eq!(a.0[3], 0xFF);
+	};
+
+	let mut bytes = vec![];
+	let rgba = image::RgbaImage::from_fn(4, 4, |x, y| image::Rgba([x as u8, y as u8, 0x10, 0x20]));
+	PaaImageEncoder::new(&mut bytes).with_paatype(PaaType::Argb8888).write_image(rgba.as_raw(), 4, 4, ColorType::Rgba8).unwrap();
+	assert_eq!(PaaDecoder::with_paa(PaaImage::from_bytes(&bytes).unwrap()).decode_first().unwrap(), rgba);
+
+	// AI88 stores gray and alpha unchanged
+	let mut bytes = vec![];
+	let luma_alpha = image::GrayAlphaImage::from_fn(4, 4, |x, y| image::LumaA([x as u8 * 0x40 + y as u8, 0xFF - y as u8 * 0x30]));
+	PaaImageEncoder::new(&mut bytes).with_paatype(PaaType::Ai88).write_image(luma_alpha.as_raw(), 4, 4, ColorType::La8).unwrap();
+	let decoded = PaaDecoder::with_paa(PaaImage::from_bytes(&bytes).unwrap()).decode_first().unwrap();
+	assert_eq!(DynamicImage::ImageRgba8(decoded).into_luma_alpha8(), luma_alpha);
+
+	let encoder = || PaaImageEncoder::new(vec![]);
+	assert!(matches!(encoder().write_image(&[0; 16 * 8], 4, 4, ColorType::Rgba16), Err(ImageError::Unsupported(_))));
+	assert!(matches!(encoder().write_image(&[0; 15], 2, 2, ColorType::Rgba8), Err(ImageError::Parameter(_))));
+	assert!(matches!(encoder().write_image(&[0; 16 * 3], 4, 4, ColorType::L8), Err(ImageError::Parameter(_))));
+	assert!(encoder().write_image(&[0; 16], 4, 4, ColorType::L8).is_ok());
+}
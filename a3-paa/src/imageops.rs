@@ -1,73 +1,506 @@
+//! Image operations used by [`PaaEncoder`][`crate::PaaEncoder`], for
+//! pipelines that prepare textures themselves
+//!
+//! # Example
+//! ```
+//! # use a3_paa::imageops::{mipmap_chain, MipmapChainOptions};
+//! let image = image::RgbaImage::new(64, 16);
+//! let options = MipmapChainOptions { min_dimension: 4, ..Default::default() };
+//! let sizes = mipmap_chain(image, options).iter().map(|i| i.dimensions()).collect::<Vec<_>>();
+//! assert_eq!(sizes, [(64, 16), (32, 8), (16, 4), (8, 4), (4, 4)]);
+//! ```
+
+pub mod color;
+pub mod noise;
+
+use image::{RgbaImage, Rgba32FImage, imageops::FilterType};
 use surety::Ensure;
 
-use crate::Bgra8888Pixel;
+use crate::{Bgra8888Pixel, AvgcWeighting};
+use color::{linear_to_srgb_image, srgb_to_linear_image};
 type ImageBuffer = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
 
 
-pub(crate) fn is_solid_color(image: &ImageBuffer) -> bool {
-	use image::Pixel;
-	let mut pixels = image.pixels();
+/// Return true if no channel differs by more than `tolerance` between any two
+/// pixels of `image`.
+///
+/// # Example
+/// ```
+/// # use a3_paa::imageops::is_solid_color;
+/// let image = image::RgbaImage::from_fn(4, 4, |x, _| image::Rgba([0x80 + x as u8 % 2, 0, 0, 0xFF]));
+/// assert!(!is_solid_color(&image, 0));
+/// assert!(is_solid_color(&image, 1));
+/// ```
+pub fn is_solid_color(image: &RgbaImage, tolerance: u8) -> bool {
+	let mut pixels = image.pixels().map(|p| Bgra8888Pixel::from(*p));
 	let first = if let Some(p) = pixels.next() { p } else { return true; };
-	pixels.all(|p| p.channels() == first.channels())
+	let (mut lo, mut hi) = (first, first);
+
+	pixels.all(|p| {
+		lo = lo.min_channels(p);
+		hi = hi.max_channels(p);
+		[hi.b - lo.b, hi.g - lo.g, hi.r - lo.r, hi.a - lo.a].iter().all(|d| *d <= tolerance)
+	})
+}
+
+
+#[test]
+fn test_is_solid_color() {
+	let noisy = ImageBuffer::from_fn(16, 16, |x, y| image::Rgba::<u8>([0x7F + ((x * 7 + y) % 3) as u8, 0x40, 0x40, 0xFF]));
+	assert!(!is_solid_color(&noisy, 0));
+	assert!(!is_solid_color(&noisy, 1));
+	assert!(is_solid_color(&noisy, 2));
+	assert!(is_solid_color(&ImageBuffer::from_pixel(4, 4, image::Rgba::<u8>([1, 2, 3, 4])), 0));
+}
+
+
+/// Per-channel difference between two images, in R, G, B, A order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDifference {
+	/// Largest absolute difference of each channel.
+	pub max_error: [u8; 4],
+	/// Mean absolute difference of each channel.
+	pub mean_error: [f64; 4],
+	/// Mean squared difference of each channel.
+	pub mean_squared_error: [f64; 4],
+}
+
+
+impl ImageDifference {
+	/// Mean absolute difference over all channels.
+	pub fn total_mean_error(&self) -> f64 {
+		self.mean_error.iter().sum::<f64>() / 4.0
+	}
+
+
+	/// Peak signal-to-noise ratio of each channel, in decibels; infinite for
+	/// identical channels.
+	pub fn psnr(&self) -> [f64; 4] {
+		self.mean_squared_error.map(psnr)
+	}
+
+
+	/// Peak signal-to-noise ratio over all channels, in decibels; infinite for
+	/// identical images.
+	pub fn total_psnr(&self) -> f64 {
+		psnr(self.mean_squared_error.iter().sum::<f64>() / 4.0)
+	}
+}
+
+
+fn psnr(mean_squared_error: f64) -> f64 {
+	10.0 * (255.0 * 255.0 / mean_squared_error).log10()
+}
+
+
+/// Compare `a` against `b` channel by channel, or return [`None`] if their
+/// dimensions differ.
+///
+/// # Example
+/// ```
+/// # use a3_paa::imageops::image_difference;
+/// let a = image::RgbaImage::from_pixel(2, 2, image::Rgba([0x80, 0x80, 0x80, 0xFF]));
+/// let b = image::RgbaImage::from_fn(2, 2, |x, y| image::Rgba([0x80 + (x + y) as u8, 0x80, 0x80, 0xFF]));
+/// let diff = image_difference(&a, &b).unwrap();
+/// assert_eq!(diff.max_error, [2, 0, 0, 0]);
+/// assert_eq!(diff.mean_error, [1.0, 0.0, 0.0, 0.0]);
+/// assert!(image_difference(&a, &image::RgbaImage::new(2, 1)).is_none());
+/// ```
+pub fn image_difference(a: &RgbaImage, b: &RgbaImage) -> Option<ImageDifference> {
+	if a.dimensions() != b.dimensions() {
+		return None;
+	};
+
+	let mut max_error = [0u8; 4];
+	let mut sum = [0u64; 4];
+	let mut sum_squared = [0u64; 4];
+
+	for (pa, pb) in a.pixels().zip(b.pixels()) {
+		for c in 0..4 {
+			let error = pa.0[c].abs_diff(pb.0[c]);
+			max_error[c] = max_error[c].max(error);
+			sum[c] += u64::from(error);
+			sum_squared[c] += u64::from(error) * u64::from(error);
+		};
+	};
+
+	#[allow(clippy::cast_precision_loss)]
+	let mean = |sums: [u64; 4]| {
+		let count = (u64::from(a.width()) * u64::from(a.height())).max(1) as f64;
+		sums.map(|s| s as f64 / count)
+	};
+
+	Some(ImageDifference { max_error, mean_error: mean(sum), mean_squared_error: mean(sum_squared) })
+}
+
+
+#[test]
+fn test_image_difference() {
+	let a = RgbaImage::from_fn(4, 4, |x, y| image::Rgba([(x * 16) as u8, (y * 16) as u8, 0x80, 0xFF]));
+	let same = image_difference(&a, &a).unwrap();
+	assert_eq!(same.max_error, [0; 4]);
+	assert_eq!(same.total_mean_error(), 0.0);
+	assert!(same.total_psnr().is_infinite());
+
+	let mut b = a.clone();
+	b.get_pixel_mut(1, 2).0[0] += 20;
+	b.get_pixel_mut(3, 3).0[3] -= 4;
+	let diff = image_difference(&a, &b).unwrap();
+	assert_eq!(diff.max_error, [20, 0, 0, 4]);
+	assert_eq!(diff.mean_error, [1.25, 0.0, 0.0, 0.25]);
+	assert_eq!(diff.mean_squared_error, [25.0, 0.0, 0.0, 1.0]);
+	assert!((diff.psnr()[0] - 34.151).abs() < 0.001);
+	assert!(diff.psnr()[1].is_infinite());
+	assert!((diff.total_psnr() - 40.002).abs() < 0.001);
 }
 
 
-pub(crate) fn get_avgc_maxc(image: &ImageBuffer) -> (Bgra8888Pixel, Bgra8888Pixel) {
-	if image.dimensions() == (0, 0) {
+/// Compute the average ([`Tagg::Avgc`][`crate::Tagg::Avgc`]) and maximum
+/// ([`Tagg::Maxc`][`crate::Tagg::Maxc`]) colors of `image`, weighted by
+/// [`AvgcWeighting::default`].
+///
+/// # Panics
+/// - If the pixel count of `image` overflows a [`u64`].
+///
+/// # Example
+/// ```
+/// # use a3_paa::{Bgra8888Pixel, imageops::average_and_max_color};
+/// let image = image::RgbaImage::from_fn(2, 1, |x, _| image::Rgba([0x10 * x as u8, 0x20, 0x40, 0xFF]));
+/// let (avgc, maxc) = average_and_max_color(&image);
+/// assert_eq!(avgc, Bgra8888Pixel { b: 0x40, g: 0x20, r: 0x08, a: 0xFF });
+/// assert_eq!(maxc, Bgra8888Pixel { b: 0x40, g: 0x20, r: 0x10, a: 0xFF });
+/// ```
+pub fn average_and_max_color(image: &RgbaImage) -> (Bgra8888Pixel, Bgra8888Pixel) {
+	get_avgc_maxc(image, AvgcWeighting::default())
+}
+
+
+/// Compute the AVGC and MAXC colors of `image`.
+///
+/// With [`AvgcWeighting::AlphaWeighted`], the color channels of AVGC are
+/// weighted by pixel alpha, and MAXC only considers pixels with nonzero alpha;
+/// a fully transparent image is averaged uniformly.  The alpha channel of AVGC
+/// is always the plain average.
+pub(crate) fn get_avgc_maxc(image: &ImageBuffer, weighting: AvgcWeighting) -> (Bgra8888Pixel, Bgra8888Pixel) {
+	if image.width() == 0 || image.height() == 0 {
 		return (Default::default(), Default::default());
 	};
 
+	let total_alpha: u64 = image.pixels().map(|p| p.0[3] as u64).sum();
+	let weighted = weighting == AvgcWeighting::AlphaWeighted && total_alpha > 0;
+
 	let mut pix_count = 0u64.checked();
 	let mut avgc: [u64; 4] = [0; 4];
-	let mut maxc: [u8; 4] = [0; 4];
+	let mut maxc = Bgra8888Pixel::default();
 
 	for pixel in image.pixels() {
-		for (i, c) in pixel.0.iter().enumerate() {
-			avgc[i] += *c as u64;
-			maxc[i] = std::cmp::max(maxc[i], *c);
+		let alpha = pixel.0[3];
+		let weight = if weighted { alpha as u64 } else { 1 };
+
+		for (i, c) in pixel.0[..3].iter().enumerate() {
+			avgc[i] += *c as u64 * weight;
+		};
+		avgc[3] += alpha as u64;
+
+		if !weighted || alpha > 0 {
+			maxc = maxc.max_channels((*pixel).into());
 		};
 
 		pix_count += 1;
 	};
 
 	let pix_count = pix_count.expect("Pixel count overflows a u64");
+	let color_divisor = if weighted { total_alpha } else { pix_count };
 
 	#[allow(clippy::cast_possible_truncation)]
-	let avgc = avgc.map(|c: u64| (c / pix_count) as u8);
+	let avgc = [avgc[0] / color_divisor, avgc[1] / color_divisor, avgc[2] / color_divisor, avgc[3] / pix_count]
+		.map(|c: u64| c as u8);
+
+	(image::Rgba::<u8>(avgc).into(), maxc)
+}
 
-	(image::Rgba::<u8>(avgc).into(), image::Rgba::<u8>(maxc).into())
+
+#[test]
+fn test_get_avgc_maxc() {
+	let image = ImageBuffer::from_fn(4, 2, |x, y| image::Rgba::<u8>([(x * 60) as u8, (y * 200) as u8, 0x10, 0xFF]));
+	let (avgc, maxc) = get_avgc_maxc(&image, AvgcWeighting::Uniform);
+
+	let sum = image.pixels()
+		.map(|p| Bgra8888Pixel::from(*p).to_rgba_f32())
+		.fold([0f32; 4], |acc, p| [acc[0] + p[0], acc[1] + p[1], acc[2] + p[2], acc[3] + p[3]]);
+	let mean = sum.map(|c| c / 8.0);
+
+	for (helper, tagg) in mean.iter().zip(avgc.to_rgba_f32()) {
+		assert!((helper - tagg).abs() <= 1.0 / 255.0);
+	};
+
+	assert_eq!(maxc, Bgra8888Pixel { b: 0x10, g: 200, r: 180, a: 0xFF });
+	assert!(image.pixels().all(|p| maxc.dominates(&(*p).into())));
 }
 
+#[test]
+fn test_get_avgc_maxc_weighted() {
+	let red = image::Rgba::<u8>([0xFF, 0x00, 0x00, 0x80]);
+	let blue = image::Rgba::<u8>([0x00, 0x00, 0xFF, 0xFF]);
+	let image = ImageBuffer::from_fn(2, 1, |x, _| if x == 0 { red } else { blue });
+
+	let (weighted, _) = get_avgc_maxc(&image, AvgcWeighting::AlphaWeighted);
+	let (uniform, _) = get_avgc_maxc(&image, AvgcWeighting::Uniform);
+	assert_eq!((weighted.r, weighted.b), (0x55, 0xA9));
+	assert_eq!((uniform.r, uniform.b), (0x7F, 0x7F));
+	assert_eq!(weighted.a, uniform.a);
 
-pub(crate) fn hint_mipmap_count((w, h): (u32, u32), min_dimension: u32) -> usize {
-	let smaller = std::cmp::min(w, h) as f64;
-	let hint = (smaller.log2() - (min_dimension as f64).log2()).ceil() as usize;
-	std::cmp::max(hint, 1usize)
+	let padding = image::Rgba::<u8>([0xFF, 0x00, 0x00, 0x00]);
+	let image = ImageBuffer::from_fn(4, 1, |x, _| if x == 0 { blue } else { padding });
+	let (avgc, maxc) = get_avgc_maxc(&image, AvgcWeighting::AlphaWeighted);
+	assert_eq!((avgc.r, avgc.b), (0x00, 0xFF));
+	assert_eq!(maxc, Bgra8888Pixel { b: 0xFF, g: 0x00, r: 0x00, a: 0xFF });
+
+	let transparent = ImageBuffer::from_pixel(2, 2, padding);
+	assert_eq!(get_avgc_maxc(&transparent, AvgcWeighting::AlphaWeighted), get_avgc_maxc(&transparent, AvgcWeighting::Uniform));
+}
+
+
+
+/// Dimensions of the mipmap following one of `(w, h)`, or [`None`] if `(w, h)`
+/// ends the chain.
+fn next_mipmap_dimensions((w, h): (u32, u32), min_dimension: u32) -> Option<(u32, u32)> {
+	let min_dimension = min_dimension.max(1);
+
+	if w <= min_dimension && h <= min_dimension {
+		return None;
+	};
+
+	// Halve each dimension, but never below the minimum (or the dimension
+	// itself, if it already is below the minimum)
+	let halve = |d: u32| (d / 2).max(d.min(min_dimension));
+	Some((halve(w), halve(h)))
+}
+
+
+/// Dimensions of every mipmap of a `(w, h)` texture, largest first.  Each
+/// dimension is halved down to `min_dimension`, and the chain continues until
+/// both dimensions reach it.
+///
+/// # Example
+/// ```
+/// # use a3_paa::imageops::mipmap_dimensions;
+/// let chain = mipmap_dimensions((8, 2), 1).collect::<Vec<_>>();
+/// assert_eq!(chain, [(8, 2), (4, 1), (2, 1), (1, 1)]);
+/// ```
+pub fn mipmap_dimensions(dimensions: (u32, u32), min_dimension: u32) -> impl Iterator<Item=(u32, u32)> {
+	let first = Some(dimensions).filter(|(w, h)| *w > 0 && *h > 0);
+	std::iter::successors(first, move |d| next_mipmap_dimensions(*d, min_dimension))
+}
+
+
+pub(crate) fn hint_mipmap_count(dimensions: (u32, u32), min_dimension: u32) -> usize {
+	mipmap_dimensions(dimensions, min_dimension).count()
 }
 
 
 #[test]
 fn test_hint_mipmap_count() {
-	assert_eq!(hint_mipmap_count((800, 1000), 6), 8);
-	assert_eq!(hint_mipmap_count((1080, 2160), 30), 6);
+	assert_eq!(hint_mipmap_count((800, 1000), 6), 9);
+	assert_eq!(hint_mipmap_count((1080, 2160), 30), 8);
+	assert_eq!(hint_mipmap_count((3, 2), 4), 1);
+	assert_eq!(hint_mipmap_count((0, 16), 1), 0);
+}
+
+
+/// Color space in which mipmaps are filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaMode {
+	/// Filter the stored channel values as they are.
+	Linear,
+	/// Treat the color channels as sRGB-encoded, and filter them in linear
+	/// light; this keeps high-contrast detail from darkening in smaller
+	/// mipmaps.  Alpha is filtered as is.
+	Srgb,
+}
+
+
+/// Rules for [`mipmap_chain`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MipmapChainOptions {
+	/// Smallest dimension mipmaps are reduced to (see [`mipmap_dimensions`]);
+	/// 4 for DXTn, 1 otherwise.
+	pub min_dimension: u32,
+	/// Maximum number of mipmaps, including the original image.
+	pub max_levels: Option<usize>,
+	/// Filter used to downscale each mipmap from the previous one.
+	pub filter: FilterType,
+	/// Color space of filtering.
+	pub gamma: GammaMode,
+	/// Amount of 3x3 unsharp mask applied to every mipmap but the first,
+	/// after downscaling; [`None`] or a non-positive amount leaves mipmaps
+	/// as downscaled.
+	pub sharpen: Option<f32>,
+}
+
+
+impl Default for MipmapChainOptions {
+	fn default() -> Self {
+		Self { min_dimension: 1, max_levels: None, filter: FilterType::Triangle, gamma: GammaMode::Linear, sharpen: None }
+	}
 }
 
 
-pub(crate) fn construct_mipmap_series(image: ImageBuffer, min_dimension: u32, filter: image::imageops::FilterType) -> Vec<ImageBuffer> {
-	let mut result = Vec::with_capacity(hint_mipmap_count(image.dimensions(), min_dimension));
-	let mut current = image;
+/// Construct the mipmaps of `image` (see [`mipmap_dimensions`]), starting
+/// with `image` itself.  Each mipmap is downscaled from the previous one,
+/// before sharpening.
+///
+/// # Example
+/// ```
+/// # use a3_paa::imageops::{mipmap_chain, MipmapChainOptions};
+/// let image = image::RgbaImage::new(256, 256);
+/// let chain = mipmap_chain(image, MipmapChainOptions { max_levels: Some(3), ..Default::default() });
+/// assert_eq!(chain.last().map(|i| i.dimensions()), Some((64, 64)));
+/// ```
+pub fn mipmap_chain(image: RgbaImage, options: MipmapChainOptions) -> Vec<RgbaImage> {
+	let MipmapChainOptions { min_dimension, max_levels, filter, gamma, sharpen } = options;
+	let max_levels = max_levels.unwrap_or(usize::MAX);
+
+	let mut dimensions = mipmap_dimensions(image.dimensions(), min_dimension).take(max_levels);
+
+	if dimensions.next().is_none() {
+		return vec![];
+	};
+
+	let mut result: Vec<RgbaImage> = Vec::with_capacity(hint_mipmap_count(image.dimensions(), min_dimension).min(max_levels));
+	result.push(image);
+
+	// With GammaMode::Srgb, the previous mipmap in linear light
+	let mut linear: Option<Rgba32FImage> = None;
 
-	loop {
-		let (width, height) = current.dimensions();
+	for (width, height) in dimensions {
+		let previous = &result[result.len() - 1];
 
-		if width < min_dimension || height < min_dimension {
-			break;
+		let level = match gamma {
+			GammaMode::Linear => image::imageops::resize(previous, width, height, filter),
+			GammaMode::Srgb => {
+				let source = linear.take().unwrap_or_else(|| srgb_to_linear_image(previous));
+				let resized = image::imageops::resize(&source, width, height, filter);
+				let level = linear_to_srgb_image(&resized);
+				linear = Some(resized);
+				level
+			},
 		};
 
-		result.push(current.clone());
+		result.push(level);
+	};
 
-		current = image::imageops::resize(&current, width / 2, height / 2, filter);
+	if let Some(amount) = sharpen.filter(|a| *a > 0.0) {
+		for level in result.iter_mut().skip(1) {
+			*level = sharpen_image(level, amount);
+		};
 	};
 
 	result
 }
+
+
+/// Apply an unsharp mask to the color channels of `image`, using a 3x3
+/// binomial blur with edge pixels repeated.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn sharpen_image(image: &RgbaImage, amount: f32) -> RgbaImage {
+	const WEIGHTS: [f32; 3] = [1.0, 2.0, 1.0];
+	let (width, height) = image.dimensions();
+
+	RgbaImage::from_fn(width, height, |x, y| {
+		let mut blurred = [0f32; 3];
+
+		for (dy, wy) in WEIGHTS.iter().enumerate() {
+			for (dx, wx) in WEIGHTS.iter().enumerate() {
+				let sx = (x + dx as u32).saturating_sub(1).min(width - 1);
+				let sy = (y + dy as u32).saturating_sub(1).min(height - 1);
+				let neighbour = image.get_pixel(sx, sy);
+
+				for (b, c) in blurred.iter_mut().zip(neighbour.0) {
+					*b += f32::from(c) * wx * wy / 16.0;
+				};
+			};
+		};
+
+		let mut pixel = *image.get_pixel(x, y);
+
+		for (c, b) in pixel.0.iter_mut().zip(blurred) {
+			let value = f32::from(*c);
+			*c = (value + amount * (value - b)).round().clamp(0.0, 255.0) as u8;
+		};
+
+		pixel
+	})
+}
+
+
+#[test]
+fn test_mipmap_dimensions() {
+	let chain = |w, h, min| mipmap_dimensions((w, h), min).collect::<Vec<_>>();
+
+	assert_eq!(chain(1024, 256, 1), [(1024, 256), (512, 128), (256, 64), (128, 32), (64, 16), (32, 8), (16, 4), (8, 2), (4, 1), (2, 1), (1, 1)]);
+	assert_eq!(chain(1024, 256, 4), [(1024, 256), (512, 128), (256, 64), (128, 32), (64, 16), (32, 8), (16, 4), (8, 4), (4, 4)]);
+	assert_eq!(chain(4, 1024, 1), [(4, 1024), (2, 512), (1, 256), (1, 128), (1, 64), (1, 32), (1, 16), (1, 8), (1, 4), (1, 2), (1, 1)]);
+	assert_eq!(chain(4, 1024, 4), [(4, 1024), (4, 512), (4, 256), (4, 128), (4, 64), (4, 32), (4, 16), (4, 8), (4, 4)]);
+	assert_eq!(chain(5, 5, 1), [(5, 5), (2, 2), (1, 1)]);
+	assert_eq!(chain(5, 5, 4), [(5, 5), (4, 4)]);
+
+	let series = mipmap_chain(RgbaImage::new(5, 5), MipmapChainOptions { filter: FilterType::Nearest, ..Default::default() });
+	assert_eq!(series.iter().map(RgbaImage::dimensions).collect::<Vec<_>>(), chain(5, 5, 1));
+}
+
+
+#[test]
+fn test_mipmap_chain_gradient() {
+	let gradient = RgbaImage::from_fn(64, 16, |x, _| image::Rgba([(x * 4) as u8, 0x80, (0xFF - x * 4) as u8, 0xFF]));
+	let (avgc, _) = average_and_max_color(&gradient);
+
+	let chain = mipmap_chain(gradient.clone(), MipmapChainOptions::default());
+	assert_eq!(chain.len(), 7);
+	assert_eq!(chain[0], gradient);
+
+	for level in &chain {
+		let (level_avgc, _) = average_and_max_color(level);
+		assert!(level_avgc.r.abs_diff(avgc.r) <= 3 && level_avgc.b.abs_diff(avgc.b) <= 3);
+		assert!(level.pixels().all(|p| p.0[1] == 0x80));
+	};
+
+	let limited = mipmap_chain(gradient, MipmapChainOptions { min_dimension: 4, max_levels: Some(2), ..Default::default() });
+	assert_eq!(limited.iter().map(RgbaImage::dimensions).collect::<Vec<_>>(), [(64, 16), (32, 8)]);
+
+	// Black and white average to 50% linear intensity, i.e. ~0xBC in sRGB
+	let checker = RgbaImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { image::Rgba([0, 0, 0, 0xFF]) } else { image::Rgba([0xFF, 0xFF, 0xFF, 0xFF]) });
+	let srgb = mipmap_chain(checker.clone(), MipmapChainOptions { gamma: GammaMode::Srgb, ..Default::default() });
+	let plain = mipmap_chain(checker, MipmapChainOptions::default());
+	assert!(srgb[1].get_pixel(0, 0).0[0].abs_diff(0xBC) <= 1);
+	assert!(plain[1].get_pixel(0, 0).0[0].abs_diff(0x80) <= 1);
+	assert_eq!(srgb[1].get_pixel(0, 0).0[3], 0xFF);
+}
+
+
+#[test]
+fn test_mipmap_chain_sharpen() {
+	let edge = RgbaImage::from_fn(64, 64, |x, _| if x < 30 { image::Rgba([0x40, 0x40, 0x40, 0xFF]) } else { image::Rgba([0xC0, 0xC0, 0xC0, 0xFF]) });
+	let chain = |sharpen| mipmap_chain(edge.clone(), MipmapChainOptions { sharpen, ..Default::default() });
+
+	// Largest difference between horizontally adjacent pixels
+	let contrast = |image: &RgbaImage| (1..image.width())
+		.map(|x| image.get_pixel(x, 0).0[0].abs_diff(image.get_pixel(x - 1, 0).0[0]))
+		.max()
+		.unwrap_or(0);
+
+	let plain = chain(None);
+	assert_eq!(chain(Some(0.0)), plain);
+
+	let mut previous = contrast(&plain[2]);
+
+	for amount in [0.5, 1.0, 2.0] {
+		let sharpened = chain(Some(amount));
+		assert_eq!(sharpened[0], edge);
+		assert!(sharpened[2].pixels().all(|p| p.0[3] == 0xFF));
+
+		let current = contrast(&sharpened[2]);
+		assert!(current > previous);
+		previous = current;
+	};
+}
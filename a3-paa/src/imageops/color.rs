@@ -0,0 +1,88 @@
+//! sRGB transfer functions, for pipelines that need linear light
+//!
+//! Arma color textures are stored sRGB-encoded; normal maps and other data
+//! textures hold values that must not be converted.
+//!
+//! # Example
+//! ```
+//! # use a3_paa::imageops::color::{linear_to_srgb, srgb_to_linear};
+//! assert!((srgb_to_linear(0.5) - 0.214).abs() < 0.001);
+//! assert!((linear_to_srgb(srgb_to_linear(0.5)) - 0.5).abs() < 1e-6);
+//! ```
+
+use image::{RgbaImage, Rgba32FImage};
+
+
+/// Suffixes (see
+/// [`TextureHints::texture_filename_to_suffix`][`crate::TextureHints::texture_filename_to_suffix`])
+/// of normal maps, whose channels hold vectors rather than sRGB colors.
+pub const NORMAL_MAP_SUFFIXES: &[&str] = &["NO", "NOHQ", "NOF", "NOPX", "NOVHQ", "NS", "NSHQ"];
+
+
+/// Return true if `suffix` (in any case) is one of [`NORMAL_MAP_SUFFIXES`].
+///
+/// # Example
+/// ```
+/// # use a3_paa::imageops::color::is_normal_map_suffix;
+/// assert!(is_normal_map_suffix("nohq"));
+/// assert!(!is_normal_map_suffix("CO"));
+/// ```
+pub fn is_normal_map_suffix(suffix: &str) -> bool {
+	NORMAL_MAP_SUFFIXES.iter().any(|s| s.eq_ignore_ascii_case(suffix))
+}
+
+
+/// Decode an sRGB-encoded channel value in `0.0..=1.0` to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+	if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+
+/// Encode a linear channel value in `0.0..=1.0` to sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+	if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+
+/// Convert the color channels of `image` from sRGB to linear light, with
+/// channels in `0.0..=1.0`.  Alpha is only rescaled.
+pub fn srgb_to_linear_image(image: &RgbaImage) -> Rgba32FImage {
+	let to_linear = |c: u8| srgb_to_linear(f32::from(c) / 255.0);
+
+	Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+		let [r, g, b, a] = image.get_pixel(x, y).0;
+		image::Rgba([to_linear(r), to_linear(g), to_linear(b), f32::from(a) / 255.0])
+	})
+}
+
+
+/// Convert the color channels of `image` from linear light to sRGB, the
+/// inverse of [`srgb_to_linear_image`] up to rounding.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn linear_to_srgb_image(image: &Rgba32FImage) -> RgbaImage {
+	let to_u8 = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+
+	RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+		let [r, g, b, a] = image.get_pixel(x, y).0;
+		image::Rgba([to_u8(linear_to_srgb(r)), to_u8(linear_to_srgb(g)), to_u8(linear_to_srgb(b)), to_u8(a)])
+	})
+}
+
+
+#[test]
+fn known_values() {
+	// sRGB and linear values of IEC 61966-2-1
+	let pairs = [(0.0, 0.0), (0.04045, 0.003_130_8), (0.5, 0.214_041), (0.735_357, 0.5), (1.0, 1.0)];
+
+	for (srgb, linear) in pairs {
+		assert!((srgb_to_linear(srgb) - linear).abs() < 1e-5, "{srgb} to linear");
+		assert!((linear_to_srgb(linear) - srgb).abs() < 1e-5, "{linear} to sRGB");
+	};
+
+	// Every 8-bit value survives the roundtrip
+	let image = RgbaImage::from_fn(256, 1, |x, _| image::Rgba([x as u8, 255 - x as u8, x as u8 / 2, x as u8]));
+	let linear = srgb_to_linear_image(&image);
+	assert!((linear.get_pixel(128, 0).0[0] - 0.215_861).abs() < 1e-5);
+	assert_eq!(linear.get_pixel(128, 0).0[3], 128.0 / 255.0);
+	assert_eq!(linear_to_srgb_image(&linear), image);
+}
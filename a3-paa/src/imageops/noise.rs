@@ -0,0 +1,107 @@
+//! Deterministic noise for mipmap filters and procedural textures
+//!
+//! All values are pure functions of their inputs, so encoding the same texture
+//! twice produces identical bytes.  The exact output of every function is part
+//! of the encoder's behavior, and must not change between versions.
+//!
+//! # Example
+//! ```
+//! # use a3_paa::imageops::noise::{noise_u8, ValueNoise};
+//! assert_eq!(noise_u8(3, 4, 0, 42), noise_u8(3, 4, 0, 42));
+//! assert_ne!(noise_u8(3, 4, 0, 42), noise_u8(3, 4, 1, 42));
+//!
+//! let noise = ValueNoise::new(42, (8, 8));
+//! assert_eq!(noise.sample(1.5, 2.25), noise.sample(9.5, -5.75));
+//! ```
+
+
+/// Permute `input` with the PCG-RXS-M-XS output function.
+fn pcg(input: u32) -> u32 {
+	let state = input.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+	let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+	(word >> 22) ^ word
+}
+
+
+/// Hash a pixel position, mipmap level and seed into a pseudorandom [`u32`].
+pub fn hash(x: u32, y: u32, mip_level: u32, seed: u32) -> u32 {
+	pcg(x ^ pcg(y ^ pcg(mip_level ^ pcg(seed))))
+}
+
+
+/// Pseudorandom byte for a pixel position, mipmap level and seed.
+#[allow(clippy::cast_possible_truncation)]
+pub fn noise_u8(x: u32, y: u32, mip_level: u32, seed: u32) -> u8 {
+	(hash(x, y, mip_level, seed) >> 24) as u8
+}
+
+
+/// Pseudorandom value in `[0, 1)` for a pixel position, mipmap level and seed.
+#[allow(clippy::cast_precision_loss)]
+pub fn noise_f32(x: u32, y: u32, mip_level: u32, seed: u32) -> f32 {
+	(hash(x, y, mip_level, seed) >> 8) as f32 / (1u32 << 24) as f32
+}
+
+
+/// Smooth noise interpolated between pseudorandom values on an integer
+/// lattice, repeating every `period` units in each direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueNoise {
+	seed: u32,
+	period: (u32, u32),
+}
+
+
+impl ValueNoise {
+	/// Create a noise generator repeating every `period` lattice cells; zero
+	/// periods are treated as 1.
+	pub fn new(seed: u32, period: (u32, u32)) -> Self {
+		Self { seed, period: (period.0.max(1), period.1.max(1)) }
+	}
+
+
+	/// Sample the noise at `(x, y)`, in lattice units.  The result is in
+	/// `[0, 1)`.
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	pub fn sample(&self, x: f32, y: f32) -> f32 {
+		let (x0, y0) = (x.floor(), y.floor());
+		let smoothstep = |t: f32| t * t * (3.0 - 2.0 * t);
+		let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+
+		let lattice = |dx: i64, dy: i64| {
+			let lx = (x0 as i64 + dx).rem_euclid(self.period.0.into()) as u32;
+			let ly = (y0 as i64 + dy).rem_euclid(self.period.1.into()) as u32;
+			noise_f32(lx, ly, 0, self.seed)
+		};
+
+		let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+		let top = lerp(lattice(0, 0), lattice(1, 0), tx);
+		let bottom = lerp(lattice(0, 1), lattice(1, 1), tx);
+		lerp(top, bottom, ty)
+	}
+}
+
+
+#[test]
+fn test_noise_values() {
+	assert_eq!(hash(0, 0, 0, 0), 0x8D32_4821);
+	assert_eq!(hash(1, 2, 3, 4), 0xFDBF_7848);
+	assert_eq!([0, 1, 2, 3].map(|x| noise_u8(x, 7, 0, 1234)), [131, 89, 83, 94]);
+	assert_eq!(noise_f32(5, 9, 2, 99), 0.364_542_07);
+}
+
+
+#[test]
+fn test_value_noise() {
+	let noise = ValueNoise::new(7, (4, 2));
+	assert_eq!(noise.sample(0.0, 0.0), noise_f32(0, 0, 0, 7));
+	assert_eq!(noise.sample(2.0, 1.0), noise_f32(2, 1, 0, 7));
+	assert_eq!(noise.sample(1.25, 0.5), 0.252_125_83);
+
+	// Tileable in both directions
+	for (x, y) in [(0.25, 0.75), (3.875, 1.125), (2.5, 0.0)] {
+		assert_eq!(noise.sample(x, y), noise.sample(x + 4.0, y - 2.0));
+	};
+
+	assert!((0..64).map(|i| noise.sample(i as f32 * 0.37, i as f32 * 0.11)).all(|v| (0.0..1.0).contains(&v)));
+}
@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::{ArgbSwizzle, Bgra8888Pixel, PaaImage, PaaMipmap, PaaMipmapCompression, PaaReader, PaaResult, PaaType, Tagg, Transparency};
+#[cfg(test)] use crate::{Bgr888Pixel, PaaPalette};
+
+
+/// Summary of a PAA file, small enough to keep for many textures, e.g. in an
+/// asset database
+///
+/// Unlike [`PaaImage`], it holds no mipmap data.  [`Display`][std::fmt::Display]
+/// renders it on one line, as `paatool info` prints it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaaInfo {
+	/// Format of all mipmaps.
+	pub paatype: PaaType,
+	/// Width of the first mipmap, or 0 if it cannot be read.
+	pub width: u16,
+	/// Height of the first mipmap, or 0 if it cannot be read.
+	pub height: u16,
+	/// Number of mipmaps, including those that cannot be read.
+	pub mipmap_count: usize,
+	/// Some pixel of the smallest mipmap is not fully opaque.  If it cannot be
+	/// decoded (or without the `image` feature), whether
+	/// [`paatype`][`Self::paatype`] stores alpha at all.
+	pub has_alpha_content: bool,
+	/// Transparency of [`Tagg::Flag`], if any.
+	pub transparency: Option<Transparency>,
+	/// Color of [`Tagg::Avgc`], if any.
+	pub avgc: Option<Bgra8888Pixel>,
+	/// Color of [`Tagg::Maxc`], if any.
+	pub maxc: Option<Bgra8888Pixel>,
+	/// Swizzle of [`Tagg::Swiz`], if any.
+	pub swizzle: Option<ArgbSwizzle>,
+	/// Size of the file in bytes, or [`None`] if built
+	/// [`from_image`][`Self::from_image`].
+	pub file_size: Option<u64>,
+	/// Compression of each mipmap whose header can be read, in order.
+	pub compressions: Vec<PaaMipmapCompression>,
+}
+
+
+impl PaaInfo {
+	/// Summarize `image`, which has no [`file_size`][`Self::file_size`].
+	pub fn from_image(image: &PaaImage) -> Self {
+		let (width, height) = match image.mipmaps.first() {
+			Some(Ok(m)) => (m.width, m.height),
+			_ => (0, 0),
+		};
+
+		let readable = image.mipmaps.iter().filter_map(|m| m.as_ref().ok());
+		let smallest = readable.clone().last();
+
+		Self {
+			width,
+			height,
+			mipmap_count: image.mipmaps.len(),
+			has_alpha_content: has_alpha_content(image.paatype, smallest),
+			file_size: None,
+			compressions: readable.map(|m| m.compression).collect(),
+			..Self::from_header(image.paatype, &image.taggs)
+		}
+	}
+
+
+	/// Summarize the PAA file at `path`, reading its header and only the
+	/// smallest mipmap.
+	///
+	/// # Errors
+	/// - [`UnexpectedIoError`][`crate::PaaError::UnexpectedIoError`]: The
+	///   file could not be opened or read.
+	/// - Any error of [`PaaReader::new`].
+	pub fn read_from_path(path: impl AsRef<Path>) -> PaaResult<Self> {
+		Self::read_from(&mut BufReader::new(File::open(path)?))
+	}
+
+
+	/// Summarize a PAA file read from `input`, reading its header and only the
+	/// smallest mipmap.
+	///
+	/// # Errors
+	/// - [`UnexpectedIoError`][`crate::PaaError::UnexpectedIoError`]: Seeking
+	///   to the end of `input` failed.
+	/// - Any error of [`PaaReader::new`].
+	///
+	/// # Panics
+	/// - As [`PaaReader::new`].
+	pub fn read_from<R: Read + Seek>(input: &mut R) -> PaaResult<Self> {
+		let mut reader = PaaReader::new(&mut *input)?;
+		let count = reader.mipmap_count();
+
+		let (width, height) = reader.mipmap_dimensions(0).unwrap_or((0, 0));
+		let compressions = (0..count).filter_map(|i| reader.mipmap_compression(i).ok()).collect();
+		let smallest = (0..count).rev().find_map(|i| reader.read_mipmap(i).ok());
+		let info = Self::from_header(reader.paatype(), reader.taggs());
+
+		let file_size = input.seek(SeekFrom::End(0))?;
+
+		Ok(Self {
+			width,
+			height,
+			mipmap_count: count,
+			has_alpha_content: has_alpha_content(info.paatype, smallest.as_ref()),
+			file_size: Some(file_size),
+			compressions,
+			..info
+		})
+	}
+
+
+	/// Summary of the header alone, without mipmaps.  Of repeated taggs, the
+	/// last one is used.
+	fn from_header(paatype: PaaType, taggs: &[Tagg]) -> Self {
+		Self {
+			paatype,
+			width: 0,
+			height: 0,
+			mipmap_count: 0,
+			has_alpha_content: false,
+			transparency: taggs.iter().rev().find_map(|t| if let Tagg::Flag { transparency } = t { Some(*transparency) } else { None }),
+			avgc: taggs.iter().rev().find_map(|t| if let Tagg::Avgc { rgba } = t { Some(*rgba) } else { None }),
+			maxc: taggs.iter().rev().find_map(|t| if let Tagg::Maxc { rgba } = t { Some(*rgba) } else { None }),
+			swizzle: taggs.iter().rev().find_map(|t| if let Tagg::Swiz { swizzle } = t { Some(*swizzle) } else { None }),
+			file_size: None,
+			compressions: vec![],
+		}
+	}
+}
+
+
+/// Probe the alpha of `smallest`, the cheapest mipmap to decode.
+/// [`PaaType::IndexPalette`] mipmaps cannot be decoded yet.
+#[allow(unused_variables)]
+fn has_alpha_content(paatype: PaaType, smallest: Option<&PaaMipmap>) -> bool {
+	#[cfg(feature = "image")]
+	{
+		if let Some(Ok(image)) = smallest.filter(|_| paatype != PaaType::IndexPalette).map(PaaMipmap::decode) {
+			return image.pixels().any(|p| p.0[3] < 0xFF);
+		};
+	};
+
+	paatype.has_alpha()
+}
+
+
+impl std::fmt::Display for PaaInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{} {}x{}, {} mipmaps", self.paatype, self.width, self.height, self.mipmap_count)?;
+
+		let mut compressions = self.compressions.clone();
+		compressions.dedup();
+
+		if !compressions.is_empty() {
+			let names: Vec<&str> = compressions.iter().map(PaaMipmapCompression::as_str).collect();
+			write!(f, " ({})", names.join("/"))?;
+		};
+
+		write!(f, ", {}", if self.has_alpha_content { "alpha" } else { "opaque" })?;
+
+		if let Some(transparency) = self.transparency {
+			write!(f, ", flag={}", transparency.as_str())?;
+		};
+
+		if let Some(swizzle) = self.swizzle.filter(|s| !s.is_noop()) {
+			write!(f, ", swizzle={}", swizzle.to_argb_string())?;
+		};
+
+		if let Some(file_size) = self.file_size {
+			write!(f, ", {file_size} bytes")?;
+		};
+
+		Ok(())
+	}
+}
+
+
+#[test]
+fn info_matches_full_parse() {
+	use std::io::Cursor;
+
+	let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/fixtures");
+	let mut images = vec![];
+
+	for name in ["checker_dxt1", "gradient_argb8888", "ramp_ai88"] {
+		let path = fixtures.join(name).with_extension("paa");
+		let data = std::fs::read(&path).unwrap();
+		let image = PaaImage::from_bytes(&data).unwrap();
+
+		let info = PaaInfo::read_from_path(&path).unwrap();
+		assert_eq!(info, PaaInfo { file_size: Some(data.len() as u64), ..PaaInfo::from_image(&image) }, "{name}");
+		images.push(image);
+	};
+
+	let checker = PaaInfo::read_from_path(fixtures.join("checker_dxt1.paa")).unwrap();
+	assert_eq!(checker.to_string(), "DXT1 4x4, 1 mipmaps (uncompressed), opaque, 25 bytes");
+
+	// Every other type, with taggs
+	#[cfg(feature = "image")]
+	for paatype in PaaType::ALL.iter().filter(|t| !matches!(t, PaaType::IndexPalette | PaaType::Dxt1 | PaaType::Argb8888 | PaaType::Ai88)) {
+		let mut image = images[1].transcode(*paatype, None).unwrap();
+		image.recompute_taggs().unwrap();
+		image.taggs.push(Tagg::Swiz { swizzle: ArgbSwizzle::parse_argb("1-A", "R", "G", "B").unwrap() });
+		let data = image.to_bytes().unwrap();
+
+		let info = PaaInfo::read_from(&mut Cursor::new(&data)).unwrap();
+		let reread = PaaImage::from_bytes(&data).unwrap();
+		assert_eq!(info, PaaInfo { file_size: Some(data.len() as u64), ..PaaInfo::from_image(&reread) }, "{paatype:?}");
+		assert_eq!(info.mipmap_count, 1);
+		assert!(info.avgc.is_some() && info.maxc.is_some() && info.transparency.is_some());
+		assert!(info.to_string().contains(", swizzle="), "{info}");
+	};
+
+	// Palette mipmaps are not decoded, and the last of repeated taggs is used
+	let palette = PaaImage {
+		paatype: PaaType::IndexPalette,
+		taggs: vec![Tagg::Flag { transparency: Transparency::AlphaInterpolated }, Tagg::Flag { transparency: Transparency::None }],
+		palette: Some(PaaPalette::with_pixels(&[Bgr888Pixel::default(); 2]).unwrap()),
+		mipmaps: vec![Ok(PaaMipmap { width: 4, height: 4, paatype: PaaType::IndexPalette, compression: PaaMipmapCompression::RleBlocks, data: vec![1; 16], ..Default::default() })],
+		..Default::default()
+	};
+	let data = palette.to_bytes().unwrap();
+
+	let info = PaaInfo::read_from(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(info, PaaInfo { file_size: Some(data.len() as u64), ..PaaInfo::from_image(&palette) });
+	assert!(!info.has_alpha_content);
+	assert_eq!(info.transparency, Some(Transparency::None));
+}
@@ -12,20 +12,44 @@
 mod macros;
 mod mipmap;
 mod pixel;
-mod imageops;
-mod cfgfile;
-mod decode;
-mod encode;
+#[cfg(feature = "image")] pub mod imageops;
+#[cfg(feature = "image")] mod cfgfile;
+#[cfg(feature = "image")] mod decode;
+#[cfg(feature = "image")] mod encode;
+#[cfg(feature = "image")] mod codec;
+mod reader;
+mod info;
+pub mod patch;
+mod blocks;
+mod progress;
+mod sniff;
+mod sansio;
+#[cfg(feature = "tokio")] mod async_reader;
+#[cfg(any(test, not(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32"))))))] mod lzo;
+#[cfg(feature = "dds")] mod dds;
+#[cfg(feature = "image-format")] mod image_format;
 
 pub use mipmap::*;
-pub use decode::*;
-pub use encode::*;
+#[cfg(feature = "image")] pub use decode::*;
+pub use reader::*;
+#[cfg(feature = "tokio")] pub use async_reader::{AsyncPaaReader, CompressedMipmap};
+pub use info::PaaInfo;
+pub use progress::{OffsetsRepair, ProgressEvent};
+pub use sniff::{sniff, PaaSniff};
+#[cfg(feature = "image")] pub use encode::*;
+#[cfg(feature = "image")] pub use codec::{DxtCodec, DxtQuality, TexpressoCodec};
+#[cfg(feature = "intel-tex")] pub use codec::IntelTexCodec;
+pub use pixel::rescale_channel;
+#[cfg(feature = "image")] pub use cfgfile::ParseDiagnostic;
+#[cfg(feature = "dds")] pub use dds::dds_layer_count;
+#[cfg(feature = "image-format")] pub use image_format::{PaaImageDecoder, PaaImageEncoder, load_paa};
 
 
 use std::fmt::Debug;
 use std::io::{Read, Seek, SeekFrom, Cursor};
 use std::iter::Extend;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::default::Default;
 
 #[cfg(feature = "arbitrary")] use arbitrary::{Arbitrary, Unstructured, Result as ArbitraryResult};
@@ -34,11 +58,11 @@ use byteorder::{LittleEndian, ByteOrder, ReadBytesExt};
 #[cfg(test)] use byteorder::BigEndian;
 use deku::prelude::*;
 use derive_more::{Display, Error};
-use image::{RgbaImage, Pixel};
+#[cfg(feature = "hash")] use sha2::{Digest, Sha256};
+#[cfg(feature = "image")] use image::{RgbaImage, Pixel};
 use static_assertions::const_assert;
 #[cfg(test)] use static_assertions::assert_impl_all;
 use surety::Ensure;
-use tap::prelude::*;
 use bohemia_compression::*;
 
 use PaaError::*;
@@ -47,6 +71,11 @@ use PaaError::*;
 pub type PaaResult<T> = Result<T, PaaError>;
 
 
+/// Largest width or height of a texture that the engine loads in practice,
+/// also without the `image` feature of `PaaEncoder::MAX_DIMENSION`
+const MAX_DIMENSION: u32 = 16384;
+
+
 /// `a3_paa`'s [`std::error::Error`]
 #[derive(Debug, Display, Error, Clone)]
 #[non_exhaustive]
@@ -57,7 +86,7 @@ pub enum PaaError {
 
 	/// Unexpected I/O error that is not UnexpectedEof.
 	#[display(fmt = "Unexpected I/O error: {}", _0)]
-	UnexpectedIoError(#[error(ignore)] std::io::ErrorKind),
+	UnexpectedIoError(std::io::ErrorKind, #[error(source)] ErrorSource),
 
 	/// Unexpected integer conversion error.
 	#[display(fmt = "Unexpected integer conversion error: {}", _0)]
@@ -79,6 +108,11 @@ pub enum PaaError {
 	#[display(fmt = "Attempted to read a TAGG with unexpected indicated payload size")]
 	UnexpectedTaggDataSize,
 
+	/// Attempted to read a [`Tagg::Proc`] with a payload (first member)
+	/// larger than [`PaaLimits::max_proc_len`] (second member).
+	#[display(fmt = "CORP payload of {} bytes exceeds the limit of {} bytes", _0, _1)]
+	ProcTaggTooLarge(usize, usize),
+
 	/// Attempted to read a [`Tagg::Flag`] with unexpected transparency value.
 	#[display(fmt = "Attempted to read a FLAGTAGG with unknown transparency value: {:02x?}", _0)]
 	UnknownTransparencyValue(#[error(ignore)] u8),
@@ -100,6 +134,11 @@ pub enum PaaError {
 	#[display(fmt = "Attempted to construct or index a palette with number of colors overflowing a u16")]
 	PaletteTooLarge,
 
+	/// Read a [`PaaPalette`] in a PAA of a type other than
+	/// [`PaaType::IndexPalette`].
+	#[display(fmt = "Read a palette in a PAA of type {}, which cannot have one", _0)]
+	UnexpectedPalette(#[error(ignore)] PaaType),
+
 	/// Mipmap returned by [`PaaMipmap::read_from`] or
 	/// [`PaaMipmap::read_from_until_eof`] had zero width or zero height.
 	#[display(fmt = "Read an empty mipmap")]
@@ -109,6 +148,17 @@ pub enum PaaError {
 	#[display(fmt = "DXTn mipmap dimensions not multiple of 4: {}x{}", _0, _1)]
 	DxtMipmapDimensionsNotMultipleOf4(u16, u16),
 
+	/// Attempted to encode an image larger than 16384 pixels, the most that the
+	/// engine loads, in either dimension; the nearest valid dimensions are
+	/// given.
+	#[display(fmt = "{}x{} is larger than {}x{}; nearest valid size is {}x{}", _0, _1, "MAX_DIMENSION", "MAX_DIMENSION", "_2.0", "_2.1")]
+	DimensionsTooLarge(u32, u32, (u32, u32)),
+
+	/// Attempted to encode a DXTn image of dimensions that are not powers of
+	/// two; the nearest valid dimensions, larger and smaller, are given.
+	#[display(fmt = "{}x{} is not a power of two; nearest valid sizes are {}x{} or {}x{}", _0, _1, "_2.0", "_2.1", "_3.0", "_3.1")]
+	DimensionsNotPowerOfTwo(u32, u32, (u32, u32), (u32, u32)),
+
 	/// Mipmap start offset (as indicated in the file) is beyond EOF.
 	#[display(fmt = "Mipmap start offset as indicated in metadata is beyond EOF")]
 	MipmapOffsetBeyondEof,
@@ -131,7 +181,7 @@ pub enum PaaError {
 
 	/// The [`PaaImage`] passed to [`PaaImage::to_bytes`] contained mipmap errors.
 	#[display(fmt = "The PaaImage passed to PaaImage::to_bytes contained mipmap errors")]
-	InputMipmapErrorWhileEncoding(usize, Box<PaaError>),
+	InputMipmapErrorWhileEncoding(usize, #[error(source)] Box<PaaError>),
 
 	/// [`PaaMipmap::to_bytes`] failed.
 	#[display(fmt = "PaaMipmap::to_bytes failed")]
@@ -146,9 +196,10 @@ pub enum PaaError {
 	#[display(fmt = "An error occurred while uncompressing RLE data (compressed data likely truncated)")]
 	RleError(BcError),
 
-	/// DXT-LZO de/compression failed.
+	/// DXT-LZO de/compression failed, with the error of the LZO
+	/// implementation.
 	#[display(fmt = "DXT-LZO decompression failed: {}", _0)]
-	LzoError(/*MinilzoError*/ #[error(ignore)] String),
+	LzoError(ErrorSource),
 
 	/// LZSS decompression failed, uncompressed data is not of expected length.
 	#[display(fmt = "LZSS decompression failed, uncompressed data is not of expected length")]
@@ -164,9 +215,77 @@ pub enum PaaError {
 	#[display(fmt = "Mipmap index out of range")]
 	MipmapIndexOutOfRange,
 
-	/// Generic parse error in TexConvert.cfg.
-	#[display(fmt = "TexConvert parse error: {}", _0)]
-	TexconvertParseError(nom::Err<String>),
+	/// [`PaaImage::validate`] found no mipmaps.
+	#[display(fmt = "PAA has no mipmaps")]
+	NoMipmaps,
+
+	/// [`PaaImage::validate`] found more than [`PaaImage::MAX_MIPMAPS`]
+	/// mipmaps.
+	#[display(fmt = "PAA has {} mipmaps, more than OFFSTAGG allows", _0)]
+	TooManyMipmaps(#[error(ignore)] usize),
+
+	/// [`PaaImage::read_from_with_limits`] did not read a mipmap (at the
+	/// 0-based index) whose decoded data would take the mipmaps over
+	/// [`PaaLimits::max_total_decoded_bytes`] (second member).
+	#[error(ignore)]
+	#[display(fmt = "Mipmap at index {} would exceed the budget of {} decoded bytes", _0, _1)]
+	ReadBudgetExceeded(usize, u64),
+
+	/// [`PaaImage::validate`] found a mipmap (at the 0-based index) which could
+	/// not be read.
+	#[display(fmt = "Mipmap at index {} could not be read: {}", _0, _1)]
+	InvalidMipmap(usize, #[error(source)] Box<PaaError>),
+
+	/// [`PaaImage::validate`] found a mipmap (at the 0-based index) of a
+	/// different [`PaaType`] than the image.
+	#[error(ignore)]
+	#[display(fmt = "Mipmap at index {} is of type {:?}, unlike the PAA", _0, _1)]
+	MipmapTypeMismatch(usize, PaaType),
+
+	/// [`PaaImage::validate`] found a mipmap (at the 0-based index, with the
+	/// given width and height) which is not half the size of the previous one.
+	#[error(ignore)]
+	#[display(fmt = "Mipmap at index {} ({}x{}) does not follow the previous mipmap", _0, _1, _2)]
+	MipmapNotInChain(usize, u16, u16),
+
+	/// Attempted to access the 4x4 blocks of a mipmap whose [`PaaType`] is not
+	/// DXTn.
+	#[display(fmt = "{:?} mipmaps are not block-compressed", _0)]
+	NotBlockCompressed(#[error(ignore)] PaaType),
+
+	/// [`PaaMipmap::block_at`] received block coordinates (first and second
+	/// member) outside of the mipmap.
+	#[error(ignore)]
+	#[display(fmt = "Block ({}, {}) is outside of the mipmap", _0, _1)]
+	BlockIndexOutOfRange(usize, usize),
+
+	/// Mipmaps of this [`PaaType`] cannot be decoded or encoded yet.
+	#[display(fmt = "Decoding and encoding {:?} mipmaps is not supported", _0)]
+	UnsupportedPaaType(#[error(ignore)] PaaType),
+
+	/// [`PaaImage::transcode`] cannot encode mipmaps to this [`PaaType`].
+	#[display(fmt = "Transcoding to {:?} is not supported", _0)]
+	UnsupportedTranscodeTarget(#[error(ignore)] PaaType),
+
+	/// The progress callback of [`PaaEncoder::with_progress_control`] asked
+	/// to stop encoding.
+	#[display(fmt = "Encoding cancelled")]
+	Cancelled,
+
+	/// [`patch::replace_tagg`] found no tagg of this name in the file.
+	#[display(fmt = "No {} tagg to replace in place", _0)]
+	TaggNotFound(#[error(ignore)] &'static str),
+
+	/// [`patch::replace_tagg`] cannot replace the payload of a tagg (first
+	/// member) of one size in the file (second member) with one of another
+	/// size (third member); the whole file must be rewritten instead.
+	#[display(fmt = "Cannot replace {} tagg in place: payload is {} bytes in file, {} new", _0, _1, _2)]
+	TaggSizeMismatch(&'static str, u64, usize),
+
+	/// Syntax error in TexConvert.cfg.
+	#[display(fmt = "TexConvert.cfg {}", _0)]
+	#[cfg(feature = "image")]
+	TexconvertParseError(#[error(ignore)] ParseDiagnostic),
 
 	/// Attempted to parse a `TextureHints` class in TexConvert.cfg without a `name` field.
 	#[display(fmt = "No name field in a TexConvert hint")]
@@ -176,6 +295,33 @@ pub enum PaaError {
 	#[display(fmt = "TexConvert hint attemps to inherit a non-existing parent: {}", _0)]
 	TexconvertInvalidInherit(#[error(ignore)] String),
 
+	/// Classes in TexConvert.cfg inherit from each other in a cycle.
+	#[display(fmt = "TexConvert hints inherit from each other in a cycle: {}", _0)]
+	TexconvertInheritCycle(#[error(ignore)] String),
+
+	/// A TexConvert.cfg file (or a file it `#include`s) could not be read.
+	#[display(fmt = "Could not read TexConvert file {}: {:?}", _0, _1)]
+	TexconvertIoError(String, std::io::ErrorKind, #[error(source)] ErrorSource),
+
+	/// TexConvert.cfg `#include` directives form a cycle.
+	#[display(fmt = "TexConvert #include cycle: {}", _0)]
+	TexconvertIncludeCycle(#[error(ignore)] String),
+
+	/// TexConvert.cfg `#include` directives are nested too deeply.
+	#[display(fmt = "TexConvert #include nesting too deep at {}", _0)]
+	TexconvertIncludeTooDeep(#[error(ignore)] String),
+
+	/// DDS pixel format or layout (as described) cannot be converted to or
+	/// from PAA.
+	#[display(fmt = "Unsupported DDS format: {}", _0)]
+	DdsFormatUnsupported(#[error(ignore)] String),
+
+	/// [`PaaImage::from_dds`] received a 0-based layer index (first member)
+	/// not below the number of DDS layers (second member).
+	#[error(ignore)]
+	#[display(fmt = "DDS layer index {} out of range ({} layers)", _0, _1)]
+	DdsLayerOutOfRange(u32, u32),
+
 	/// Attempted to read an [`ArgbPixel`] from invalid data.
 	#[doc(hidden)]
 	#[display(fmt = "Attempted to read an ArgbPixel from invalid data")]
@@ -183,11 +329,146 @@ pub enum PaaError {
 }
 
 
+impl PaaError {
+	/// Broad category of the error, to handle errors without matching every
+	/// variant.
+	pub fn kind(&self) -> PaaErrorKind {
+		use PaaErrorKind::*;
+
+		match self {
+			UnexpectedEof
+				| UnexpectedIoError(..)
+				| TexconvertIoError(..) => Io,
+
+			UnknownPaaType(_)
+				| UnexpectedPalette(_)
+				| UnexpectedTaggSignature
+				| UnknownTaggType(_)
+				| UnexpectedTaggDataSize
+				| UnknownTransparencyValue(_)
+				| UnknownSwizzleValues(_)
+				| EmptyMipmap
+				| MipmapOffsetBeyondEof
+				| MipmapDataBeyondEof
+				| UnexpectedMipmapDataSize(..)
+				| NoMipmaps
+				| InvalidMipmap(..)
+				| MipmapTypeMismatch(..)
+				| MipmapNotInChain(..)
+				| NotBlockCompressed(_)
+				| UnsupportedPaaType(_)
+				| DdsFormatUnsupported(_)
+				| PixelReadError => Format,
+
+			RleError(_)
+				| LzoError(_)
+				| LzssDecompressError
+				| LzssWrongChecksum => Compression,
+
+			UnexpectedTryFromIntError(_)
+				| PaletteTooLarge
+				| MipmapTooLarge
+				| ArithmeticOverflow
+				| MipmapIndexOutOfRange
+				| BlockIndexOutOfRange(..)
+				| TooManyMipmaps(_)
+				| ReadBudgetExceeded(..)
+				| ProcTaggTooLarge(..)
+				| DdsLayerOutOfRange(..)
+				| DimensionsTooLarge(..) => Limits,
+
+			DxtMipmapDimensionsNotMultipleOf4(..)
+				| DimensionsNotPowerOfTwo(..)
+				| InputMipmapErrorWhileEncoding(..)
+				| MipmapErrorWhileSerializing(_)
+				| UnsupportedTranscodeTarget(_)
+				| Cancelled
+				| TaggNotFound(_)
+				| TaggSizeMismatch(..) => Encode,
+
+			InvalidSwizzleString(_)
+				| InvalidChannelSwizzleIdString(_)
+				| TexconvertNoName
+				| TexconvertInvalidInherit(_)
+				| TexconvertInheritCycle(_)
+				| TexconvertIncludeCycle(_)
+				| TexconvertIncludeTooDeep(_) => Config,
+
+			#[cfg(feature = "image")]
+			TexconvertParseError(_) => Config,
+		}
+	}
+}
+
+
+/// Category of a [`PaaError`], as returned by [`PaaError::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PaaErrorKind {
+	/// Reading the input or a file failed.
+	Io,
+	/// The input is not a valid PAA (or DDS), or a [`PaaImage`] is
+	/// inconsistent.
+	Format,
+	/// Compressed mipmap data could not be compressed or decompressed.
+	Compression,
+	/// A size, count or index is out of the range PAA allows.
+	Limits,
+	/// An image could not be encoded to the requested format.
+	Encode,
+	/// A swizzle or TexConvert.cfg could not be parsed.
+	Config,
+}
+
+
+/// Error wrapped by a [`PaaError`], such as the [`std::io::Error`] of an
+/// [`UnexpectedIoError`], shared between clones of the `PaaError`
+///
+/// Displays as the wrapped error, and is the
+/// [`source`][`std::error::Error::source`] of the `PaaError`.
+#[derive(Debug, Clone)]
+pub struct ErrorSource(Arc<dyn std::error::Error + Send + Sync>);
+
+
+impl ErrorSource {
+	/// Wrap `error`.
+	pub fn new(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+		Self(Arc::new(error))
+	}
+
+
+	/// The wrapped error.
+	pub fn get_ref(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+		self.0.as_ref()
+	}
+}
+
+
+impl std::fmt::Display for ErrorSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		std::fmt::Display::fmt(&self.0, f)
+	}
+}
+
+
+impl std::error::Error for ErrorSource {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		self.0.source()
+	}
+}
+
+
+// The wrapped error is never mutated, so it cannot be left inconsistent by a
+// panic
+impl std::panic::UnwindSafe for ErrorSource {}
+impl std::panic::RefUnwindSafe for ErrorSource {}
+
+
 impl From<std::io::Error> for PaaError {
 	fn from(error: std::io::Error) -> Self {
 		match error.kind() {
 			std::io::ErrorKind::UnexpectedEof => UnexpectedEof,
-			kind => UnexpectedIoError(kind),
+			kind => UnexpectedIoError(kind, ErrorSource::new(error)),
 		}
 	}
 }
@@ -200,6 +481,78 @@ impl From<std::num::TryFromIntError> for PaaError {
 }
 
 
+/// Limits on what [`PaaImage::read_from_with_limits`] allocates for a file
+///
+/// Each mipmap decodes to no more than its dimensions call for, but a file
+/// may declare many mipmaps that together expand to far more than the file
+/// holds.  Readers of untrusted files cap the total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaaLimits {
+	/// Most bytes of decoded data of all mipmaps together.  The mipmap that
+	/// would exceed it is not decoded, but read as a [`ReadBudgetExceeded`]
+	/// error, and no mipmaps are read after it.
+	pub max_total_decoded_bytes: u64,
+	/// Largest [`Tagg::Proc`] payload read, in bytes.  Reading a larger one
+	/// fails with [`ProcTaggTooLarge`], before its payload is read.
+	pub max_proc_len: usize,
+}
+
+
+impl PaaLimits {
+	/// Default [`max_total_decoded_bytes`][`Self::max_total_decoded_bytes`]:
+	/// 2 GiB, more than the full mipmap chain of the largest texture the
+	/// engine loads (16384x16384 [`PaaType::Argb8888`]).
+	pub const DEFAULT_MAX_TOTAL_DECODED_BYTES: u64 = 2 << 30;
+
+	/// Default [`max_proc_len`][`Self::max_proc_len`].  Procedural texture
+	/// code in game data is a few hundred bytes at most.
+	pub const DEFAULT_MAX_PROC_LEN: usize = 4 << 20;
+}
+
+
+impl Default for PaaLimits {
+	fn default() -> Self {
+		Self {
+			max_total_decoded_bytes: Self::DEFAULT_MAX_TOTAL_DECODED_BYTES,
+			max_proc_len: Self::DEFAULT_MAX_PROC_LEN,
+		}
+	}
+}
+
+
+/// What [`PaaImage::read_from_with_limits`] spent reading a file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseReport {
+	/// Bytes allocated for decoded mipmap data, counted against
+	/// [`PaaLimits::max_total_decoded_bytes`].
+	pub allocated_bytes: u64,
+}
+
+
+/// How [`PaaImage::to_bytes_with_options`] lays out the file
+///
+/// The header of a PAA with a single tiny mipmap dwarfs its data: a 1x1
+/// [`PaaType::Argb8888`] mask without taggs is written in 97 bytes, 76 of
+/// them the OFFSTAGG, and in 21 with [`minimal_header`][`Self::minimal_header`].
+/// Without OFFSTAGG, readers find mipmaps one after the other up to the
+/// [`PaaImage::TERMINATOR`], as [`PaaImage::read_from`] does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SerializeOptions {
+	/// Write taggs other than OFFS as they are in [`PaaImage::taggs`],
+	/// duplicates included, rather than in the order of
+	/// [`Tagg::canonicalize`].
+	pub preserve_tagg_order: bool,
+	/// Write no [`Tagg::Offs`] if there is a single mipmap.
+	pub omit_offs_when_single_mipmap: bool,
+	/// Write no taggs at all, not even [`Tagg::Offs`], for the smallest valid
+	/// file: the magic, the palette, the mipmaps and the terminator.
+	pub minimal_header: bool,
+	/// Write LZSS [`PaaType::IndexPalette`] mipmaps without the length of
+	/// their data, as tools of Operation Flashpoint expect.
+	pub legacy_lzss_layout: bool,
+}
+
+
 /// A single PAA texture file represented as a struct
 #[derive(Default, Debug, Clone)]
 pub struct PaaImage {
@@ -207,10 +560,16 @@ pub struct PaaImage {
 	pub paatype: PaaType,
 	/// PAA header metadata.
 	pub taggs: Vec<Tagg>,
-	/// RGB888 LUT for [`PaaType::IndexPalette`] mipmaps.
+	/// RGB888 LUT for [`PaaType::IndexPalette`] mipmaps.  An empty palette is
+	/// read as `None`; `None` and `Some` of an empty palette are written
+	/// identically.
 	pub palette: Option<PaaPalette>,
 	/// PAA mipmaps.
 	pub mipmaps: Vec<PaaResult<PaaMipmap>>,
+	/// Bytes after the last mipmap read, up to [`PaaImage::MAX_TRAILER_LEN`]:
+	/// usually the [`PaaImage::TERMINATOR`], but some files are padded or
+	/// truncated.  Only written back by [`PaaImage::to_bytes_exact`].
+	pub trailer: Vec<u8>,
 }
 
 
@@ -220,52 +579,254 @@ impl PaaImage {
 	pub const MAX_MIPMAPS: u8 = 15;
 
 
+	/// End of the mipmaps, as written by [`PaaImage::to_bytes`].
+	///
+	/// The engine stops reading mipmaps at one with a width or height of 0,
+	/// so 4 zero bytes would do, but files written by TexView and ImageToPAA
+	/// end with 6 and so do ours.
+	pub const TERMINATOR: [u8; 6] = [0; 6];
+
+
+	/// Maximum number of bytes read into [`PaaImage::trailer`].
+	pub const MAX_TRAILER_LEN: usize = 4096;
+
+
 	/// Read a [`PaaImage`][Self] from an [`std::io::Read`].
 	///
+	/// Mipmaps are read at the offsets of the last [`Tagg::Offs`], or one after
+	/// the other up to the terminator if there is none.  Should an offset be
+	/// zero while later ones are not, the mipmaps from there on are also read
+	/// one after the other.
+	///
+	/// If no mipmap can be read at the offsets, they are tried as relative to
+	/// the end of the header, then ignored in favor of reading mipmaps one
+	/// after the other, and the attempt reading the most mipmaps is kept (see
+	/// [`OffsetsRepair`]).
+	///
+	/// Mipmaps are decoded within the default [`PaaLimits`]; see
+	/// [`read_from_with_limits`][`Self::read_from_with_limits`].
+	///
 	/// # Errors
 	/// - [`UnexpectedEof`]: Unexpected end of file.
 	/// - [`UnexpectedIoError`]: Unexpected read error.
 	/// - [`UnknownPaaType`]: If the input PAA does not have a correct magic sequence.
+	/// - [`UnexpectedPalette`]: If the input PAA has a palette but is not of
+	///   [`PaaType::IndexPalette`].
 	/// - [`ArithmeticOverflow`]: If mipmap offsets overflow a [`u32`].
 	/// - [`MipmapOffsetBeyondEof`]: PAA is truncated; EOF is in the middle of a mipmap.
+	/// - [`ProcTaggTooLarge`]: A [`Tagg::Proc`] is over
+	///   [`PaaLimits::max_proc_len`].
 	///
 	/// # Panics
-	/// - If backtracking [`std::io::Seek::seek()`] fails while parsing [`Tagg`]s.
 	/// - If [`deku::DekuContainerWrite::to_bytes()`] fails.
 	pub fn read_from<R: Read + Seek>(input: &mut R) -> PaaResult<Self> {
-		// [TODO] Index palette support
-		let paatype_bytes: [u8; 2] = input.read_exact_buffered(2)?
-			.try_into()
-			.expect("Could not convert paatype_bytes (this is a bug)");
-		let (_, paatype) = PaaType::from_bytes((&paatype_bytes, 0))
-			.map_err(|_| UnknownPaaType(paatype_bytes))?;
+		Self::read_from_reporting(input, CompressionPolicy::default(), PaaLimits::default(), None).map(|(image, _)| image)
+	}
 
-		let mut offsets = vec![0u32; 0];
 
-		let (taggs, _) = Tagg::read_taggs_from(input)?;
+	/// [`read_from`][`Self::read_from`], reading taggs and decoding mipmaps
+	/// within `limits`, and also returning what was allocated for them.
+	///
+	/// # Errors
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Panics
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{PaaImage, PaaLimits, PaaError};
+	/// # let bytes = std::fs::read("tests/reference/fixtures/gradient_argb8888.paa").unwrap();
+	/// let (_, report) = PaaImage::read_from_with_limits(&mut std::io::Cursor::new(&bytes), PaaLimits::default()).unwrap();
+	/// assert!(report.allocated_bytes > 0);
+	///
+	/// let limits = PaaLimits { max_total_decoded_bytes: report.allocated_bytes - 1, ..Default::default() };
+	/// let (image, _) = PaaImage::read_from_with_limits(&mut std::io::Cursor::new(&bytes), limits).unwrap();
+	/// assert!(matches!(image.mipmaps.last(), Some(Err(PaaError::ReadBudgetExceeded(..)))));
+	/// ```
+	pub fn read_from_with_limits<R: Read + Seek>(input: &mut R, limits: PaaLimits) -> PaaResult<(Self, ParseReport)> {
+		Self::read_from_reporting(input, CompressionPolicy::default(), limits, None)
+	}
+
 
-		for t in taggs.iter() {
-			if let Tagg::Offs { offsets: offs } = t {
-				offsets = offs.clone();
+	/// [`read_from`][`Self::read_from`], calling `progress` with a
+	/// [`ProgressEvent::TaggRead`] for every tagg and a
+	/// [`ProgressEvent::MipmapRead`] for every mipmap read.
+	///
+	/// # Errors
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Panics
+	/// - As [`read_from`][`Self::read_from`].
+	pub fn read_from_with_progress<R: Read + Seek>(input: &mut R, mut progress: impl FnMut(ProgressEvent)) -> PaaResult<Self> {
+		Self::read_from_reporting(input, CompressionPolicy::default(), PaaLimits::default(), Some(&mut progress)).map(|(image, _)| image)
+	}
+
+
+	/// [`read_from_with_progress`][`Self::read_from_with_progress`], telling
+	/// the compression of mipmaps apart as set by `policy`.
+	///
+	/// # Errors
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Panics
+	/// - As [`read_from`][`Self::read_from`].
+	pub fn read_from_with_policy<R: Read + Seek>(input: &mut R, policy: CompressionPolicy, mut progress: impl FnMut(ProgressEvent)) -> PaaResult<Self> {
+		Self::read_from_reporting(input, policy, PaaLimits::default(), Some(&mut progress)).map(|(image, _)| image)
+	}
+
+
+	fn read_from_reporting<R: Read + Seek>(input: &mut R, policy: CompressionPolicy, limits: PaaLimits, mut progress: Option<&mut (dyn FnMut(ProgressEvent) + '_)>) -> PaaResult<(Self, ParseReport)> {
+		let (paatype, taggs, palette) = Self::read_header_from(input, limits, progress.as_deref_mut())?;
+		let header_end = input.stream_position()?;
+		let offsets = Self::offsets_in(&taggs);
+		let mut budget = sansio::ReadBudget::new(limits);
+		let (mut mipmaps, mut end) = Self::read_mipmaps_at(input, paatype, policy, &mut budget, &offsets, header_end, progress.as_deref_mut())?;
+
+		if !offsets.is_empty() && mipmaps.iter().all(Result::is_err) {
+			if let Some((repair, repaired)) = Self::repair_offsets(input, paatype, policy, limits, &offsets, header_end)? {
+				macros::log!(warn, "No mipmap could be read at the offsets of OFFSTAGG; read them with {:?} offsets instead", repair);
+
+				if let Some(progress) = progress.as_deref_mut() {
+					progress(ProgressEvent::MipmapOffsetsRepaired { repair });
+				};
+
+				budget = sansio::ReadBudget::new(limits);
+				(mipmaps, end) = Self::read_mipmaps_at(input, paatype, policy, &mut budget, &repaired, header_end, progress)?;
+			};
+		};
+
+		let _ = input.seek(SeekFrom::Start(end))?;
+		let mut trailer = Vec::with_capacity(Self::TERMINATOR.len());
+		let _ = input.by_ref().take(Self::MAX_TRAILER_LEN as u64).read_to_end(&mut trailer)?;
+
+		let image = PaaImage { paatype, taggs, palette, mipmaps, trailer };
+
+		Ok((image, budget.report()))
+	}
+
+
+	/// Read the mipmaps at `offsets`, or one after the other from
+	/// `header_end` if there are none, as described in
+	/// [`read_from`][`Self::read_from`], up to the one exceeding `budget`.
+	/// Returns the mipmaps and the end of the last one read.
+	#[allow(clippy::type_complexity)]
+	fn read_mipmaps_at<R: Read + Seek>(input: &mut R, paatype: PaaType, policy: CompressionPolicy, budget: &mut sansio::ReadBudget, offsets: &[u32], header_end: u64, mut progress: Option<&mut (dyn FnMut(ProgressEvent) + '_)>) -> PaaResult<(Vec<PaaResult<PaaMipmap>>, u64)> {
+		let mut walk = sansio::MipmapWalk::new(offsets, header_end);
+		let mut mipmaps = Vec::with_capacity(offsets.len().max(8));
+		let mut position = None;
+
+		while let Some(offset) = walk.next_offset() {
+			// Sequential mipmaps follow each other without seeking
+			let seeked = match position {
+				Some(p) if p == offset => Ok(offset),
+				_ => input.seek(SeekFrom::Start(offset)).map_err(PaaError::from),
+			};
+
+			let mipmap = seeked.and_then(|_| Self::read_mipmap_reporting(input, paatype, policy, budget, mipmaps.len(), progress.as_deref_mut()));
+			let after = input.stream_position()?;
+			let exceeded = matches!(mipmap, Err(ReadBudgetExceeded(..)));
+
+			walk.record(&mipmap, after);
+			position = Some(after);
+			mipmaps.push(mipmap);
+
+			if exceeded {
+				macros::log!(warn, "Mipmap #{} exceeds the budget of decoded bytes; not reading further mipmaps", mipmaps.len() - 1);
+				break;
 			};
 		};
 
-		let palette = PaaPalette::read_from(input)?;
+		Ok((mipmaps, walk.end()))
+	}
+
+
+	/// Find how to read more than zero mipmaps despite `offsets`, trying each
+	/// of [`sansio::repair_candidates`].  Returns the repair reading the most
+	/// mipmaps, and the offsets to read them at.
+	fn repair_offsets<R: Read + Seek>(input: &mut R, paatype: PaaType, policy: CompressionPolicy, limits: PaaLimits, offsets: &[u32], header_end: u64) -> PaaResult<Option<(OffsetsRepair, Vec<u32>)>> {
+		let mut search = sansio::RepairSearch::default();
 
-		if palette.is_some() {
-			return Err(UnknownPaaType(PaaType::IndexPalette.to_bytes().unwrap().try_into().unwrap()));
+		for candidate in sansio::repair_candidates(offsets, header_end)? {
+			let mut budget = sansio::ReadBudget::new(limits);
+			let (mipmaps, _) = Self::read_mipmaps_at(input, paatype, policy, &mut budget, &candidate.1, header_end, None)?;
+			search.record(candidate, mipmaps.iter().filter(|m| m.is_ok()).count());
 		};
 
-		let mipmaps = if offsets.is_empty() {
-			PaaMipmap::read_from_until_eof(input, paatype)
-		}
-		else {
-			PaaMipmap::read_from_with_offsets(input, &offsets, paatype)
+		Ok(search.best())
+	}
+
+
+	/// [`PaaMipmap::read_from_with_policy`], charging its decoded data to
+	/// `budget` before decoding it, and logging and reporting the mipmap as
+	/// mipmap #`index` if it could be read.
+	fn read_mipmap_reporting<R: Read + Seek>(input: &mut R, paatype: PaaType, policy: CompressionPolicy, budget: &mut sansio::ReadBudget, index: usize, progress: Option<&mut (dyn FnMut(ProgressEvent) + '_)>) -> PaaResult<PaaMipmap> {
+		let start = input.stream_position()?;
+		let timer = progress::start_timer(progress.is_some());
+		let (head, payload, size) = PaaMipmap::fetch_from(input, paatype, policy)?;
+		let size = size as u64;
+
+		// Telling a legacy LZSS mipmap apart may have read past it
+		if input.stream_position()? != start + size {
+			let _ = input.seek(SeekFrom::Start(start + size))?;
+		};
+
+		budget.charge(index, &head)?;
+		let mipmap = PaaMipmap::from_head(head, payload)?;
+		let elapsed = progress::elapsed(timer);
+
+		macros::log!(debug, "Mipmap #{} at offset {}: {}x{}, {:?}, {} bytes for {} ({:.2}x), read in {:?}",
+			index, start, mipmap.width, mipmap.height, mipmap.compression, size, mipmap.data.len(), mipmap.data.len() as f64 / size as f64, elapsed);
+
+		if let Some(progress) = progress {
+			let (width, height, compression) = (mipmap.width, mipmap.height, mipmap.compression);
+			progress(ProgressEvent::MipmapRead { index, width, height, compression, size, elapsed });
 		};
 
-		let image = PaaImage { paatype, taggs, palette, mipmaps };
+		Ok(mipmap)
+	}
+
+
+	/// Read the [`PaaType`], [`Tagg`]s and [`PaaPalette`] from `input` within
+	/// `limits`, leaving it at the first mipmap, and report every tagg to
+	/// `progress`.  See [`read_from`][`Self::read_from`] for errors and panics.
+	pub(crate) fn read_header_from<R: Read + Seek>(input: &mut R, limits: PaaLimits, mut progress: Option<&mut (dyn FnMut(ProgressEvent) + '_)>) -> PaaResult<(PaaType, Vec<Tagg>, Option<PaaPalette>)> {
+		let (paatype, _) = sansio::drive(input, &mut vec![], |buf, _| sansio::parse_magic(buf))?;
+		let start = input.stream_position()?;
+
+		let mut parser = sansio::TaggsParser::new(limits);
+		let (taggs, consumed) = sansio::drive(input, &mut vec![], |buf, eof| parser.parse(buf, eof))?;
+
+		// Looking for one more tagg read past the last one
+		let _ = input.seek(SeekFrom::Start(start + consumed as u64))?;
+
+		let taggs = taggs.into_iter().map(|(tagg, offset)| {
+			macros::log!(debug, "Tagg {} at offset {}", tagg.as_taggname(), start + offset);
+
+			if let Some(progress) = progress.as_deref_mut() {
+				progress(ProgressEvent::TaggRead { name: tagg.as_taggname(), offset: start + offset });
+			};
+
+			tagg
+		}).collect();
+
+		let (palette, _) = sansio::drive(input, &mut vec![], |buf, _| sansio::parse_palette(buf, paatype))?;
 
-		Ok(image)
+		Ok((paatype, taggs, palette))
+	}
+
+
+	/// Mipmap offsets of the last [`Tagg::Offs`] in `taggs`, or none.
+	pub(crate) fn offsets_in(taggs: &[Tagg]) -> Vec<u32> {
+		let mut offsets = vec![0u32; 0];
+
+		for t in taggs.iter() {
+			if let Tagg::Offs { offsets: offs } = t {
+				offsets = offs.clone();
+			};
+		};
+
+		offsets
 	}
 
 
@@ -276,11 +837,12 @@ impl PaaImage {
 	/// - [`UnexpectedEof`]: Unexpected end of file.
 	/// - [`UnexpectedIoError`]: Unexpected read error.
 	/// - [`UnknownPaaType`]: If the input PAA does not have a correct magic sequence.
+	/// - [`UnexpectedPalette`]: If the input PAA has a palette but is not of
+	///   [`PaaType::IndexPalette`].
 	/// - [`ArithmeticOverflow`]: If mipmap offsets overflow a [`u32`].
 	/// - [`MipmapOffsetBeyondEof`]: PAA is truncated; EOF is in the middle of a mipmap.
 	///
 	/// # Panics
-	/// - If backtracking [`std::io::Seek::seek()`] fails while parsing [`Tagg`]s.
 	/// - If [`deku::DekuContainerWrite::to_bytes()`] fails.
 	pub fn from_bytes(input: &[u8]) -> PaaResult<Self> {
 		let mut cursor = Cursor::new(input);
@@ -288,10 +850,14 @@ impl PaaImage {
 	}
 
 
-	/// Convert self to PAA data as `Vec<u8>`.
+	/// Convert self to PAA data as `Vec<u8>`, ending with the
+	/// [`PaaImage::TERMINATOR`].
 	///
 	/// Ignores input `Tagg::Offs` and regenerates offsets based on actual mipmap
-	/// data.
+	/// data.  Other taggs are written in the order of
+	/// [`Tagg::canonicalize`]: AVGC, MAXC, FLAG, SWIZ and PROC, keeping the last
+	/// of each; OFFS always comes last.  A trailing [`EmptyMipmap`] error (the
+	/// terminator of a PAA without [`Tagg::Offs`]) is not written.
 	///
 	/// # Errors
 	/// - [`ArithmeticOverflow`]: [`Tagg`]s and [`PaaPalette`] overflow a [`u32`].
@@ -304,28 +870,70 @@ impl PaaImage {
 	///   [`Tagg`]s and large mipmaps.
 	/// - If [`deku::DekuContainerWrite::to_bytes()`] fails.
 	pub fn to_bytes(&self) -> PaaResult<Vec<u8>> {
+		self.to_bytes_with(&Self::TERMINATOR, SerializeOptions::default())
+	}
+
+
+	/// [`to_bytes`][`Self::to_bytes`], laying out the header as set by
+	/// `options`.
+	///
+	/// # Errors
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	///
+	/// # Panics
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	pub fn to_bytes_with_options(&self, options: SerializeOptions) -> PaaResult<Vec<u8>> {
+		self.to_bytes_with(&Self::TERMINATOR, options)
+	}
+
+
+	/// [`to_bytes`][`Self::to_bytes`], but end with [`PaaImage::trailer`] as
+	/// read instead of the [`PaaImage::TERMINATOR`].  Images not read from a
+	/// file have an empty trailer, and are written without a terminator.
+	///
+	/// If `preserve_tagg_order`, taggs other than OFFS are written as they are
+	/// in [`PaaImage::taggs`], duplicates included.
+	///
+	/// # Errors
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	///
+	/// # Panics
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	pub fn to_bytes_exact(&self, preserve_tagg_order: bool) -> PaaResult<Vec<u8>> {
+		self.to_bytes_with(&self.trailer, SerializeOptions { preserve_tagg_order, ..Default::default() })
+	}
+
+
+	fn to_bytes_with(&self, trailer: &[u8], options: SerializeOptions) -> PaaResult<Vec<u8>> {
 		let mut buf: Vec<u8> = Vec::with_capacity(10_000_000);
 
 		buf.extend(self.paatype.to_bytes().unwrap());
 
-		for t in &self.taggs {
+		let taggs = match options {
+			SerializeOptions { minimal_header: true, .. } => vec![],
+			SerializeOptions { preserve_tagg_order: true, .. } => self.taggs.clone(),
+			_ => Tagg::canonicalize(&self.taggs),
+		};
+
+		let mipmaps = match self.mipmaps.as_slice() {
+			[rest @ .., Err(EmptyMipmap)] => rest,
+			all => all,
+		};
+
+		let write_offs = !options.minimal_header && !(options.omit_offs_when_single_mipmap && mipmaps.len() == 1);
+
+		for t in &taggs {
 			if let Tagg::Offs { .. } = t {
 				continue;
 			};
 
-			buf.extend(t.to_bytes());
+			buf.extend(t.to_bytes()?);
 		};
 
 		#[allow(clippy::cast_possible_truncation)]
-		let offs_length = Tagg::Offs { offsets: vec![] }.to_bytes().len() as u32;
+		let offs_length = if write_offs { Tagg::Offs { offsets: vec![] }.to_bytes()?.len() as u32 } else { 0 };
 
-		let palette_data =
-			if let Some(p) = &self.palette {
-				p.to_bytes()?
-			}
-			else {
-				vec![0u8, 0]
-			};
+		let palette_data = self.palette.as_ref().unwrap_or(&PaaPalette::default()).to_bytes()?;
 
 		let mipmaps_offset = {
 			let buf_len = buf.len().checked();
@@ -335,12 +943,12 @@ impl PaaImage {
 			buf_len + (offs_length as usize) + palette_len
 		};
 
-		let mipmap_blocks = self.mipmaps
+		let mipmap_blocks = mipmaps
 			.iter()
 			.enumerate()
 			.map(|(i, m)| {
 				let m = m.clone().map_err(|e| InputMipmapErrorWhileEncoding(i, Box::new(e)))?;
-				m.to_bytes().map_err(|e| MipmapErrorWhileSerializing(Box::new(e)))
+				m.to_bytes_with_options(options).map_err(|e| MipmapErrorWhileSerializing(Box::new(e)))
 			})
 			.collect::<PaaResult<Vec<Vec<u8>>>>()?;
 
@@ -358,8 +966,10 @@ impl PaaImage {
 			.map(|c| <usize as TryInto<u32>>::try_into(*c).map_err(|_| ArithmeticOverflow))
 			.collect::<PaaResult<Vec<u32>>>()?;
 
-		let new_offs = Tagg::Offs { offsets: mipmap_block_offsets };
-		buf.extend(new_offs.to_bytes());
+		if write_offs {
+			let new_offs = Tagg::Offs { offsets: mipmap_block_offsets };
+			buf.extend(new_offs.to_bytes()?);
+		};
 
 		buf.extend(palette_data);
 
@@ -367,68 +977,1203 @@ impl PaaImage {
 			buf.extend(m);
 		};
 
-		buf.extend([0u8; 6]);
+		buf.extend(trailer);
 
 		Ok(buf)
 	}
-}
 
 
-/// Bitmap encoding used by all [mipmaps][`PaaImage::mipmaps`] of a given PAA
-#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
-#[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
-#[deku(type = "u16", endian = "little")]
-pub enum PaaType {
-	// See `int __stdcall sub_4276E0(void *Block, int)` (ImageToPAA v1.0.0.3).
+	/// Check that the image is well-formed: it has between 1 and
+	/// [`MAX_MIPMAPS`][`Self::MAX_MIPMAPS`] mipmaps, which were all read
+	/// successfully, are of the image's [`PaaType`], hold as much data as their
+	/// dimensions call for, and halve in size from one to the next.  A trailing
+	/// [`EmptyMipmap`] error (the terminator of a PAA without
+	/// [`Tagg::Offs`]) is ignored.
+	///
+	/// # Errors
+	/// - [`NoMipmaps`]: The image has no mipmaps.
+	/// - [`TooManyMipmaps`]: The image has more than
+	///   [`MAX_MIPMAPS`][`Self::MAX_MIPMAPS`] mipmaps.
+	/// - [`InvalidMipmap`]: A mipmap contains an error.
+	/// - [`MipmapTypeMismatch`]: A mipmap is not of [`PaaImage::paatype`].
+	/// - [`UnexpectedMipmapDataSize`]: Mipmap data is not of
+	///   [`PaaType::predict_size`].
+	/// - [`MipmapNotInChain`]: A mipmap is not half the size of the previous
+	///   one (each dimension is halved down to 1, or down to 4 if larger).
+	pub fn validate(&self) -> PaaResult<()> {
+		let mipmaps = match self.mipmaps.as_slice() {
+			[rest @ .., Err(EmptyMipmap)] => rest,
+			all => all,
+		};
 
-	/// 1 byte (offset into the index palette, which contains BGR 8:8:8).
-	#[deprecated = "[TODO] Index palette format is not implemented"]
-	#[deku(id = "0x47_47")]
-	IndexPalette,
+		if mipmaps.is_empty() {
+			return Err(NoMipmaps);
+		};
 
-	/// 8 bits alpha, 8 bits grayscale.
-	#[deku(id = "0x80_80")]
-	Ai88,
+		if mipmaps.len() > Self::MAX_MIPMAPS.into() {
+			return Err(TooManyMipmaps(mipmaps.len()));
+		};
 
-	/// RGBA 5:5:5:1 in a little-endian 2-byte integer.
-	#[deku(id = "0x15_55")]
-	Argb1555,
+		let follows = |d: u16, previous: u16| d == (previous / 2).max(1) || d == (previous / 2).max(previous.min(4));
+		let mut previous: Option<(u16, u16)> = None;
 
-	/// ARGB 4:4:4:4 in a little-endian 2-byte integer.
-	#[deku(id = "0x44_44")]
-	Argb4444,
+		for (index, mipmap) in mipmaps.iter().enumerate() {
+			let mipmap = mipmap.as_ref().map_err(|e| InvalidMipmap(index, Box::new(e.clone())))?;
+			let (width, height) = (mipmap.width, mipmap.height);
 
-	/// RGBA 8:8:8:8.
-	#[deku(id = "0x88_88")]
-	Argb8888,
+			if mipmap.paatype != self.paatype {
+				return Err(MipmapTypeMismatch(index, mipmap.paatype));
+			};
 
-	/// `[TODO]`
-	#[deku(id = "0xFF_01")]
-	Dxt1,
+			if mipmap.data.len() != self.paatype.predict_size(width, height) {
+				return Err(UnexpectedMipmapDataSize(width, height, mipmap.data.len()));
+			};
 
-	/// `[TODO]`
-	#[deprecated]
-	#[deku(id = "0xFF_02")]
-	Dxt2,
+			if let Some((w, h)) = previous {
+				if !follows(width, w) || !follows(height, h) {
+					return Err(MipmapNotInChain(index, width, height));
+				};
+			};
 
-	/// `[TODO]`
-	#[deprecated]
-	#[deku(id = "0xFF_03")]
-	Dxt3,
+			previous = Some((width, height));
+		};
 
-	/// `[TODO]`
-	#[deprecated]
-	#[deku(id = "0xFF_04")]
-	Dxt4,
+		Ok(())
+	}
 
-	/// DXT5 (BC3) texture.
-	#[deku(id = "0xFF_05")]
-	Dxt5,
-}
 
+	/// Compute the AVGC and MAXC colors of the first mipmap, weighted by alpha
+	/// as [`PaaEncoder`] does by default.
+	///
+	/// # Errors
+	/// - As [`computed_avgc_maxc_with`][`Self::computed_avgc_maxc_with`].
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn computed_avgc_maxc(&self) -> PaaResult<(Bgra8888Pixel, Bgra8888Pixel)> {
+		self.computed_avgc_maxc_with(AvgcWeighting::default())
+	}
 
-impl Default for PaaType {
-	/// Returns [`Dxt5`][`PaaType::Dxt5`].
+
+	/// Compute the AVGC and MAXC colors of the first mipmap with `weighting`,
+	/// to compare with the stored [`Tagg::Avgc`] and [`Tagg::Maxc`].
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: The image has no mipmaps.
+	/// - Any error contained in, or returned while decoding, the first mipmap.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn computed_avgc_maxc_with(&self, weighting: AvgcWeighting) -> PaaResult<(Bgra8888Pixel, Bgra8888Pixel)> {
+		let mipmap = self.mipmaps.first().ok_or(MipmapIndexOutOfRange)?;
+		let image = mipmap.as_ref().map_err(Clone::clone)?.decode()?;
+
+		Ok(imageops::get_avgc_maxc(&image, weighting))
+	}
+
+
+	/// Return the largest difference, over all channels, between the stored
+	/// [`Tagg::Avgc`] and the one [computed][`Self::computed_avgc_maxc`] from
+	/// the first mipmap, or [`None`] if there is no AVGC or the first mipmap
+	/// cannot be decoded.  A large drift usually means the channels were
+	/// swapped after the AVGC was computed.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn tagg_color_drift(&self) -> Option<u8> {
+		let stored = self.taggs.iter().rev().find_map(|t| if let Tagg::Avgc { rgba } = t { Some(*rgba) } else { None })?;
+		let (computed, _) = self.computed_avgc_maxc().ok()?;
+
+		[(stored.r, computed.r), (stored.g, computed.g), (stored.b, computed.b), (stored.a, computed.a)]
+			.iter()
+			.map(|(x, y)| x.abs_diff(*y))
+			.max()
+	}
+
+
+	/// SHA-256 of the dimensions and decoded RGBA pixels of every mipmap, in
+	/// order.  Taggs and how mipmaps are compressed do not count, so that two
+	/// files decoding to the same pixels hash the same, e.g. before and after
+	/// recompression.  See [`header_hash`][`Self::header_hash`] for the rest.
+	///
+	/// # Errors
+	/// - Any error contained in, or returned while decoding, a mipmap.
+	#[cfg(all(feature = "image", feature = "hash"))]
+	#[cfg_attr(doc, doc(cfg(all(feature = "image", feature = "hash"))))]
+	pub fn content_hash(&self) -> PaaResult<[u8; 32]> {
+		let mut hasher = Sha256::new();
+
+		for mipmap in &self.mipmaps {
+			let mipmap = mipmap.as_ref().map_err(Clone::clone)?;
+			let image = mipmap.decode()?;
+
+			hasher.update(mipmap.width.to_le_bytes());
+			hasher.update(mipmap.height.to_le_bytes());
+			hasher.update(image.as_raw());
+		};
+
+		Ok(hasher.finalize().into())
+	}
+
+
+	/// SHA-256 of the metadata alone: the [`PaaType`], the
+	/// [canonical][`Tagg::canonicalize`] taggs, without [`Tagg::Offs`] or
+	/// duplicates, and the palette.
+	///
+	/// # Errors
+	/// - Any error of [`Tagg::to_bytes`] or [`PaaPalette::to_bytes`].
+	#[cfg(feature = "hash")]
+	#[cfg_attr(doc, doc(cfg(feature = "hash")))]
+	pub fn header_hash(&self) -> PaaResult<[u8; 32]> {
+		let mut hasher = Sha256::new();
+		hasher.update(self.paatype.magic());
+
+		for tagg in Tagg::canonicalize(&self.taggs) {
+			hasher.update(tagg.to_bytes()?);
+		};
+
+		hasher.update(self.palette.as_ref().unwrap_or(&PaaPalette::default()).to_bytes()?);
+
+		Ok(hasher.finalize().into())
+	}
+
+
+	/// Replace the [`Tagg::Avgc`], [`Tagg::Maxc`] and [`Tagg::Flag`] taggs with
+	/// ones computed from the first mipmap.  FLAG is
+	/// [`Transparency::AlphaInterpolated`] if any pixel is not fully opaque,
+	/// and [`Transparency::None`] otherwise.
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: The image has no mipmaps.
+	/// - Any error contained in, or returned while decoding, the first mipmap.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn recompute_taggs(&mut self) -> PaaResult<()> {
+		let mipmap = self.mipmaps.first().ok_or(MipmapIndexOutOfRange)?;
+		let image = mipmap.as_ref().map_err(Clone::clone)?.decode()?;
+
+		let (avgc, maxc) = imageops::get_avgc_maxc(&image, AvgcWeighting::default());
+		let transparency = if image.pixels().any(|p| p.0[3] < 0xFF) { Transparency::AlphaInterpolated } else { Transparency::None };
+
+		self.taggs.retain(|t| !matches!(t, Tagg::Avgc { .. } | Tagg::Maxc { .. } | Tagg::Flag { .. }));
+		self.taggs.splice(0..0, [Tagg::Avgc { rgba: avgc }, Tagg::Maxc { rgba: maxc }, Tagg::Flag { transparency }]);
+
+		Ok(())
+	}
+
+
+	/// Generate the mipmaps following the last one, down to 4x4 for DXTn
+	/// types and 1x1 otherwise, and up to [`MAX_MIPMAPS`][`Self::MAX_MIPMAPS`]
+	/// in total.  Returns the number of mipmaps added.
+	///
+	/// # Errors
+	/// - [`NoMipmaps`]: The image has no mipmaps.
+	/// - [`InvalidMipmap`]: The last mipmap contains an error, or could not be
+	///   decoded.
+	/// - Any error of [`PaaEncoder::encode`].
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn generate_missing_mipmaps(&mut self) -> PaaResult<usize> {
+		let index = self.mipmaps.len().checked_sub(1).ok_or(NoMipmaps)?;
+		let smallest = self.mipmaps[index].as_ref().map_err(|e| InvalidMipmap(index, Box::new(e.clone())))?.clone();
+
+		let min_dimension = if self.paatype.is_dxtn() { 4 } else { 1 };
+		let missing = imageops::mipmap_dimensions((smallest.width.into(), smallest.height.into()), min_dimension).count() - 1;
+
+		if missing == 0 {
+			return Ok(0);
+		};
+
+		let single = PaaImage { paatype: self.paatype, taggs: vec![], palette: None, mipmaps: vec![Ok(smallest)], trailer: vec![] };
+		let image = PaaDecoder::with_paa(single).decode_first().map_err(|e| InvalidMipmap(index, Box::new(e)))?;
+		let settings = TextureEncodingSettings::builder().format(self.paatype).build();
+		let generated = PaaEncoder::with_image_and_settings(image, settings).encode()?;
+
+		let count = self.mipmaps.len();
+		self.mipmaps.extend(generated.mipmaps.into_iter().skip(1).take_while(Result::is_ok));
+		self.mipmaps.truncate(Self::MAX_MIPMAPS.into());
+
+		Ok(self.mipmaps.len() - count)
+	}
+
+
+	/// Convert every mipmap to `paatype`, keeping all taggs but
+	/// [`Tagg::Offs`].  AVGC, MAXC and FLAG are
+	/// [recomputed][`Self::recompute_taggs`] unless the conversion is lossless
+	/// (to the same type, or to [`PaaType::Argb8888`]).  DXTn targets drop
+	/// mipmaps smaller than 4x4.
+	///
+	/// With `alpha_threshold` and a [`PaaType::Dxt1`] target, alpha is made
+	/// binary before encoding: pixels with alpha below the threshold become
+	/// fully transparent, others fully opaque.
+	///
+	/// # Errors
+	/// - [`UnsupportedTranscodeTarget`]: `paatype` is [`PaaType::IndexPalette`].
+	/// - [`InvalidMipmap`]: A mipmap contains an error, or could not be
+	///   decoded.
+	/// - [`DxtMipmapDimensionsNotMultipleOf4`]: `paatype` is DXTn and a mipmap
+	///   of at least 4x4 has dimensions that are not multiples of 4.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn transcode(&self, paatype: PaaType, alpha_threshold: Option<u8>) -> PaaResult<Self> {
+		if paatype == PaaType::IndexPalette {
+			return Err(UnsupportedTranscodeTarget(paatype));
+		};
+
+		let mut mipmaps = Vec::with_capacity(self.mipmaps.len());
+
+		for (index, mipmap) in self.mipmaps.iter().enumerate() {
+			let mipmap = match mipmap {
+				Err(EmptyMipmap) if index + 1 == self.mipmaps.len() => break,
+				m => m.as_ref().map_err(|e| InvalidMipmap(index, Box::new(e.clone())))?,
+			};
+
+			if paatype == self.paatype {
+				mipmaps.push(Ok(mipmap.clone()));
+				continue;
+			};
+
+			if paatype.is_dxtn() && (mipmap.width < 4 || mipmap.height < 4) {
+				break;
+			};
+
+			let mut image = mipmap.decode().map_err(|e| InvalidMipmap(index, Box::new(e)))?;
+
+			if let (PaaType::Dxt1, Some(threshold)) = (paatype, alpha_threshold) {
+				for pixel in image.pixels_mut() {
+					pixel.0[3] = if pixel.0[3] >= threshold { 0xFF } else { 0 };
+				};
+			};
+
+			mipmaps.push(Ok(PaaMipmap::encode(paatype, &image)?));
+		};
+
+		let taggs = self.taggs.iter().filter(|t| !matches!(t, Tagg::Offs { .. })).cloned().collect();
+		let mut transcoded = PaaImage { paatype, taggs, palette: None, mipmaps, trailer: vec![] };
+
+		if paatype != self.paatype && paatype != PaaType::Argb8888 {
+			transcoded.recompute_taggs()?;
+		};
+
+		Ok(transcoded)
+	}
+
+
+	/// Rebuild a valid image from the mipmaps that were read successfully.
+	/// Each level that contains an error, or does not fit in the chain, is
+	/// regenerated by downscaling the nearest larger valid level; levels
+	/// above the first valid one are upscaled from it instead, losing detail
+	/// (logged as a warning).  AVGC, MAXC and FLAG are then
+	/// [recomputed][`Self::recompute_taggs`], and [`Tagg::Offs`] dropped.
+	///
+	/// # Errors
+	/// - [`UnsupportedTranscodeTarget`]: The image is a
+	///   [`PaaType::IndexPalette`], which cannot be encoded.
+	/// - [`NoMipmaps`]: No mipmap was read successfully.
+	/// - [`DimensionsTooLarge`]: The top level, doubled back up from the first
+	///   valid mipmap, is larger than [`PaaEncoder::MAX_DIMENSION`].
+	/// - [`InvalidMipmap`]: A valid mipmap could not be decoded.
+	/// - Any error of [`PaaMipmap::encode`] or [`validate`][`Self::validate`]
+	///   on the salvaged image.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn salvage(&self) -> PaaResult<PaaImage> {
+		if self.paatype == PaaType::IndexPalette {
+			return Err(UnsupportedTranscodeTarget(self.paatype));
+		};
+
+		let mipmaps = match self.mipmaps.as_slice() {
+			[rest @ .., Err(EmptyMipmap)] => rest,
+			all => all,
+		};
+		let mipmaps = &mipmaps[..mipmaps.len().min(Self::MAX_MIPMAPS.into())];
+		let min_dimension = if self.paatype.is_dxtn() { 4 } else { 1 };
+
+		let usable = |m: &&PaaMipmap| m.paatype == self.paatype && m.data.len() == self.paatype.predict_size(m.width, m.height);
+
+		// The chain is anchored on the first usable mipmap, doubled back up
+		// to the top level
+		let (anchor, first) = mipmaps.iter()
+			.enumerate()
+			.find_map(|(index, m)| m.as_ref().ok().filter(usable).map(|m| (index, m)))
+			.ok_or(NoMipmaps)?;
+		let top = (u32::from(first.width) << anchor, u32::from(first.height) << anchor);
+
+		if top.0 > PaaEncoder::MAX_DIMENSION || top.1 > PaaEncoder::MAX_DIMENSION {
+			let nearest = (encode::nearest_dimensions(self.paatype, top.0).0, encode::nearest_dimensions(self.paatype, top.1).0);
+			return Err(DimensionsTooLarge(top.0, top.1, nearest));
+		};
+
+		let chain: Vec<(u32, u32)> = imageops::mipmap_dimensions(top, min_dimension).take(mipmaps.len()).collect();
+
+		let valid = |index: usize| mipmaps[index]
+			.as_ref()
+			.ok()
+			.filter(usable)
+			.filter(|m| (u32::from(m.width), u32::from(m.height)) == chain[index]);
+
+		let mut salvaged = Vec::with_capacity(chain.len());
+
+		for (index, &(width, height)) in chain.iter().enumerate() {
+			if let Some(mipmap) = valid(index) {
+				salvaged.push(Ok(mipmap.clone()));
+				continue;
+			};
+
+			// Nearest larger valid level, or the nearest smaller one
+			let (source, mipmap) = (0..index)
+				.rev()
+				.chain(index + 1..chain.len())
+				.find_map(|i| valid(i).map(|m| (i, m)))
+				.ok_or(NoMipmaps)?;
+			let image = mipmap.decode().map_err(|e| InvalidMipmap(source, Box::new(e)))?;
+
+			let image = if source < index {
+				let options = imageops::MipmapChainOptions { min_dimension, max_levels: Some(index - source + 1), ..Default::default() };
+				imageops::mipmap_chain(image, options).pop().unwrap_or_default()
+			}
+			else {
+				macros::log!(warn, "PaaImage::salvage: mipmap #{} upscaled from #{} ({}x{} to {}x{}), losing detail", index, source, mipmap.width, mipmap.height, width, height);
+				image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle)
+			};
+
+			macros::log!(debug, "PaaImage::salvage: regenerated mipmap #{} ({}x{}) from #{}", index, width, height, source);
+			salvaged.push(Ok(PaaMipmap::encode(self.paatype, &image)?));
+		};
+
+		let taggs = self.taggs.iter().filter(|t| !matches!(t, Tagg::Offs { .. })).cloned().collect();
+		let mut paa = PaaImage { paatype: self.paatype, taggs, palette: self.palette.clone(), mipmaps: salvaged, trailer: vec![] };
+
+		paa.recompute_taggs()?;
+		paa.validate()?;
+
+		Ok(paa)
+	}
+}
+
+
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for PaaImage {
+	/// Generate an image that [`PaaImage::validate`] accepts: a few taggs, and
+	/// up to [`PaaImage::MAX_MIPMAPS`] mipmaps halving down to 4x4 (DXTn) or
+	/// 1x1.  Index palettes cannot be read yet, so there is neither an
+	/// [`PaaType::IndexPalette`] nor a palette.
+	fn arbitrary(input: &mut Unstructured) -> ArbitraryResult<Self> {
+		use PaaType::*;
+
+		let paatype = *input.choose(&[Ai88, Argb1555, Argb4444, Argb8888, Dxt1, Dxt2, Dxt3, Dxt4, Dxt5])?;
+
+		let tagg_count: usize = input.int_in_range(0..=8)?;
+		let taggs = (0..tagg_count).map(|_| input.arbitrary()).collect::<ArbitraryResult<Vec<Tagg>>>()?;
+
+		let min_dimension: u16 = if paatype.is_dxtn() { 4 } else { 1 };
+		let (mut width, mut height): (u16, u16) = if paatype.is_dxtn() {
+			(4 << input.int_in_range(0..=6u16)?, 4 << input.int_in_range(0..=6u16)?)
+		}
+		else {
+			(input.int_in_range(1..=256)?, input.int_in_range(1..=256)?)
+		};
+
+		let mipmap_count: u8 = input.int_in_range(1..=Self::MAX_MIPMAPS)?;
+		let mut mipmaps = Vec::with_capacity(mipmap_count.into());
+
+		for _ in 0..mipmap_count {
+			let compression = PaaMipmap::suggest_compression(paatype, width, height);
+			let mut data = vec![0u8; paatype.predict_size(width, height)];
+			input.fill_buffer(&mut data)?;
+			mipmaps.push(Ok(PaaMipmap { width, height, paatype, compression, data, payload: None }));
+
+			if width == min_dimension && height == min_dimension {
+				break;
+			};
+
+			width = (width / 2).max(min_dimension);
+			height = (height / 2).max(min_dimension);
+		};
+
+		Ok(Self { paatype, taggs, palette: None, mipmaps, trailer: vec![] })
+	}
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn transcode() {
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, y as u8 * 16, 0x80, if x < 8 { 0x40 } else { 0xFF }]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb4444).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	assert_eq!(paa.mipmaps.len(), 5);
+
+	let dxt5 = paa.transcode(PaaType::Dxt5, None).unwrap();
+	assert_eq!(dxt5.paatype, PaaType::Dxt5);
+	assert_eq!(dxt5.mipmaps.iter().map(|m| m.as_ref().unwrap().width).collect::<Vec<_>>(), [16, 8, 4]);
+	assert!(dxt5.validate().is_ok());
+	assert!(dxt5.taggs.contains(&Tagg::Flag { transparency: Transparency::AlphaInterpolated }));
+
+	let dxt1 = paa.transcode(PaaType::Dxt1, Some(0x80)).unwrap();
+	let decoded = PaaDecoder::with_paa(dxt1).decode_first().unwrap();
+	assert!(decoded.pixels().all(|p| p.0[3] == 0 || p.0[3] == 0xFF));
+	assert_eq!(decoded.get_pixel(0, 0).0[3], 0);
+
+	let ai88 = paa.transcode(PaaType::Ai88, None).unwrap();
+	let decoded = PaaDecoder::with_paa(ai88).decode_first().unwrap();
+	assert!(decoded.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]));
+	assert_eq!(decoded.get_pixel(8, 0).0[3], 0xFF);
+
+	let same = paa.transcode(PaaType::Argb4444, None).unwrap();
+	assert!(same.mipmaps.iter().zip(&paa.mipmaps).all(|(a, b)| a.as_ref().ok() == b.as_ref().ok()));
+	assert!(matches!(paa.transcode(PaaType::IndexPalette, None), Err(UnsupportedTranscodeTarget(_))));
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn validate() {
+	let image = RgbaImage::from_fn(16, 8, |x, y| image::Rgba([x as u8 * 16, y as u8 * 32, 0, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	assert!(paa.validate().is_ok());
+
+	let mut terminated = paa.clone();
+	terminated.mipmaps.push(Err(EmptyMipmap));
+	assert!(terminated.validate().is_ok());
+
+	let mut skipped = paa.clone();
+	skipped.mipmaps.remove(1);
+	assert!(matches!(skipped.validate(), Err(MipmapNotInChain(1, 4, 4))));
+
+	let mut truncated = paa.clone();
+	truncated.mipmaps[0].as_mut().unwrap().data.pop();
+	assert!(matches!(truncated.validate(), Err(UnexpectedMipmapDataSize(16, 8, 63))));
+
+	let mut corrupted = paa;
+	corrupted.mipmaps[1] = Err(lzo::Lzo1xError::InputOverrun.into());
+	assert!(matches!(corrupted.validate(), Err(InvalidMipmap(1, _))));
+	assert!(matches!(PaaImage::default().validate(), Err(NoMipmaps)));
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn salvage() {
+	let image = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	assert_eq!(paa.mipmaps.len(), 5);
+
+	let mut corrupted = paa.clone();
+	corrupted.mipmaps[2] = Err(lzo::Lzo1xError::InputOverrun.into());
+	corrupted.mipmaps.push(Err(EmptyMipmap));
+	let salvaged = corrupted.salvage().unwrap();
+	assert!(salvaged.validate().is_ok());
+	assert_eq!(salvaged.mipmaps.len(), 5);
+	assert!(salvaged.mipmaps.iter().all(|m| m.as_ref().unwrap().decode().is_ok()));
+	assert_eq!(salvaged.mipmaps[3].as_ref().ok(), paa.mipmaps[3].as_ref().ok());
+	assert_eq!(salvaged.taggs.iter().filter(|t| matches!(t, Tagg::Avgc { .. })).count(), 1);
+
+	// A corrupt first mipmap is upscaled from the next one
+	let mut corrupted = paa.clone();
+	corrupted.mipmaps[0] = Err(lzo::Lzo1xError::InputOverrun.into());
+	let salvaged = corrupted.salvage().unwrap();
+	assert!(salvaged.validate().is_ok());
+	assert_eq!(salvaged.mipmaps[0].as_ref().map(|m| (m.width, m.height)).unwrap(), (64, 64));
+
+	// A small mipmap after many unreadable ones would be upscaled too far
+	let mut deep = paa.clone();
+	deep.mipmaps = (0..14).map(|_| Err(EmptyMipmap)).chain([paa.mipmaps[4].clone()]).collect();
+	assert!(matches!(deep.salvage(), Err(DimensionsTooLarge(65536, 65536, (16384, 16384)))));
+
+	let mut unreadable = paa;
+	unreadable.mipmaps.iter_mut().for_each(|m| *m = Err(EmptyMipmap));
+	assert!(matches!(unreadable.salvage(), Err(NoMipmaps)));
+	assert!(matches!(PaaImage { paatype: PaaType::IndexPalette, ..Default::default() }.salvage(), Err(UnsupportedTranscodeTarget(_))));
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn recompute_taggs() {
+	let image = RgbaImage::from_fn(8, 8, |x, _| image::Rgba([0x40, 0x80, 0xC0, if x == 0 { 0x00 } else { 0xFF }]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb4444).build();
+	let mut paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	paa.taggs = vec![Tagg::Flag { transparency: Transparency::None }];
+
+	paa.recompute_taggs().unwrap();
+	assert_eq!(paa.taggs.len(), 3);
+	assert!(paa.taggs.contains(&Tagg::Flag { transparency: Transparency::AlphaInterpolated }));
+	assert!(paa.taggs.iter().any(|t| matches!(t, Tagg::Avgc { rgba } if rgba.a == 0xDF)));
+
+	paa.mipmaps.clear();
+	assert!(paa.recompute_taggs().is_err());
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn tagg_color_drift() {
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([0xC0 + x as u8, 0x80, 0x20 + y as u8, if x < 8 { 0xFF } else { 0x10 }]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb8888).build();
+	let mut paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	let (avgc, maxc) = paa.computed_avgc_maxc().unwrap();
+	assert!(paa.taggs.contains(&Tagg::Avgc { rgba: avgc }) && paa.taggs.contains(&Tagg::Maxc { rgba: maxc }));
+	assert!(paa.tagg_color_drift().unwrap() <= 1);
+	assert_ne!(paa.computed_avgc_maxc_with(AvgcWeighting::Uniform).unwrap().0, avgc);
+
+	// As if re-exported with red and blue swapped
+	let swapped = Bgra8888Pixel { r: avgc.b, b: avgc.r, ..avgc };
+	paa.taggs.retain(|t| !matches!(t, Tagg::Avgc { .. }));
+	paa.taggs.push(Tagg::Avgc { rgba: swapped });
+	assert!(paa.tagg_color_drift().unwrap() >= 0x80);
+
+	paa.taggs.clear();
+	assert_eq!(paa.tagg_color_drift(), None);
+	paa.taggs.push(Tagg::Avgc { rgba: avgc });
+	paa.mipmaps.clear();
+	assert_eq!(paa.tagg_color_drift(), None);
+	assert!(matches!(paa.computed_avgc_maxc(), Err(MipmapIndexOutOfRange)));
+}
+
+
+#[cfg(all(feature = "image", feature = "hash"))]
+#[test]
+fn content_hash() {
+	let image = RgbaImage::from_fn(256, 256, |x, y| image::Rgba([x as u8, y as u8, 0x80, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	assert!(paa.mipmaps.iter().flatten().any(|m| m.compression == PaaMipmapCompression::Lzo));
+
+	let mut uncompressed = paa.clone();
+	uncompressed.taggs.reverse();
+
+	for mipmap in uncompressed.mipmaps.iter_mut().flatten() {
+		mipmap.compression = PaaMipmapCompression::Uncompressed;
+	};
+
+	// Neither compression nor tagg order count
+	let (data, uncompressed_data) = (paa.to_bytes().unwrap(), uncompressed.to_bytes().unwrap());
+	assert_ne!(data, uncompressed_data);
+	let (reread, uncompressed) = (PaaImage::from_bytes(&data).unwrap(), PaaImage::from_bytes(&uncompressed_data).unwrap());
+	assert_eq!(reread.content_hash().unwrap(), uncompressed.content_hash().unwrap());
+	assert_eq!(reread.header_hash().unwrap(), uncompressed.header_hash().unwrap());
+
+	// Taggs only count in the header hash, and pixels only in the content hash
+	let mut flagged = reread.clone();
+	flagged.taggs.push(Tagg::Flag { transparency: Transparency::AlphaNotInterpolated });
+	assert_eq!(flagged.content_hash().unwrap(), reread.content_hash().unwrap());
+	assert_ne!(flagged.header_hash().unwrap(), reread.header_hash().unwrap());
+
+	let mut edited = reread.clone();
+	edited.mipmaps.pop();
+	assert_ne!(edited.content_hash().unwrap(), reread.content_hash().unwrap());
+	assert_eq!(edited.header_hash().unwrap(), reread.header_hash().unwrap());
+
+	edited.mipmaps.push(Err(EmptyMipmap));
+	assert!(matches!(edited.content_hash(), Err(EmptyMipmap)));
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn read_progress() {
+	let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, y as u8 * 16, 0x80, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt5).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	let bytes = paa.to_bytes().unwrap();
+
+	let mut events = vec![];
+	let read = PaaImage::read_from_with_progress(&mut Cursor::new(&bytes), |e| events.push(e)).unwrap();
+	assert_eq!(read.mipmaps.len(), 3);
+
+	let (taggs, mipmaps) = events.split_at(read.taggs.len());
+	let mut previous_offset = 0;
+
+	for (event, tagg) in taggs.iter().zip(&read.taggs) {
+		match event {
+			ProgressEvent::TaggRead { name, offset } if *name == tagg.as_taggname() && *offset > previous_offset => previous_offset = *offset,
+			e => panic!("Unexpected event {e:?} for {tagg:?}"),
+		};
+	};
+
+	assert_eq!(mipmaps.len(), 3);
+
+	for (index, (event, mipmap)) in mipmaps.iter().zip(&read.mipmaps).enumerate() {
+		let mipmap = mipmap.as_ref().unwrap();
+		assert!(matches!(event, ProgressEvent::MipmapRead { index: i, width, height, size, .. }
+			if *i == index && *width == mipmap.width && *height == mipmap.height && *size > 0));
+	};
+
+	let plain = PaaImage::read_from(&mut Cursor::new(&bytes)).unwrap();
+	assert_eq!(plain.taggs, read.taggs);
+	assert!(plain.mipmaps.iter().zip(&read.mipmaps).all(|(a, b)| a.as_ref().ok() == b.as_ref().ok()));
+}
+
+
+#[test]
+fn index_palette_payloads_preserved() {
+	// Compressed by hand, not as LzssWriter and RleWriter would: LZSS with
+	// literals only, and RLE with a literal run of repeated bytes
+	let lzss_data = [0u8, 1].repeat(8);
+	let mut lzss = vec![0xFF];
+	lzss.extend(&lzss_data[..8]);
+	lzss.push(0xFF);
+	lzss.extend(&lzss_data[8..]);
+	lzss.extend(get_additive_i32_cksum(&lzss_data).to_le_bytes());
+	let rle = [0x03, 1, 1, 1, 1];
+
+	let mut lzss_block = vec![0xD2, 0x04, 0x3D, 0x22, 4, 0, 4, 0];
+	lzss_block.extend(&(lzss.len() as u32).to_le_bytes()[..3]);
+	lzss_block.extend(&lzss);
+	let mut rle_block = vec![2, 0, 2, 0];
+	rle_block.extend(&(rle.len() as u32).to_le_bytes()[..3]);
+	rle_block.extend(rle);
+
+	let mut pac = vec![0x47, 0x47, 2, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF];
+	pac.extend(&lzss_block);
+	pac.extend(&rle_block);
+	pac.extend([0; 6]);
+
+	let mut image = PaaImage::from_bytes(&pac).unwrap();
+	assert_eq!(image.paatype, PaaType::IndexPalette);
+	assert_eq!(image.palette.as_ref().map(PaaPalette::len), Some(2));
+	assert!(matches!(image.mipmaps.pop(), Some(Err(EmptyMipmap))));
+
+	let mipmaps = image.mipmaps.iter().map(|m| m.as_ref().unwrap()).collect::<Vec<_>>();
+	assert_eq!((mipmaps[0].compression, &mipmaps[0].data), (PaaMipmapCompression::Lzss, &lzss_data));
+	assert_eq!((mipmaps[1].compression, &mipmaps[1].data), (PaaMipmapCompression::RleBlocks, &vec![1; 4]));
+	assert_eq!(mipmaps[0].to_bytes().unwrap(), lzss_block);
+	assert_eq!(mipmaps[1].to_bytes().unwrap(), rle_block);
+
+	// Palette mipmaps are read, but cannot be decoded yet
+	#[cfg(feature = "image")]
+	{
+		assert!(matches!(PaaDecoder::with_paa(image.clone()).decode_first(), Err(UnsupportedPaaType(PaaType::IndexPalette))));
+		assert!(matches!(mipmaps[1].decode_rgba16(), Err(UnsupportedPaaType(PaaType::IndexPalette))));
+		assert!(matches!(image.transcode(PaaType::Argb8888, None), Err(InvalidMipmap(0, _))));
+	};
+
+	// Mipmaps are written back as read, after the palette
+	let written = image.to_bytes().unwrap();
+	let reread = PaaImage::from_bytes(&written).unwrap();
+	assert_eq!(reread.palette.map(|p| p.to_bytes().unwrap()), Some(pac[2..10].to_vec()));
+	assert!(written.ends_with(&[&lzss_block[..], &rle_block, &[0; 6]].concat()));
+
+	// Changed data is compressed again
+	let mut changed = image.mipmaps[1].clone().unwrap();
+	changed.data = vec![0; 4];
+	assert_ne!(changed.to_bytes().unwrap(), rle_block);
+	assert_eq!(PaaMipmap::from_bytes(&changed.to_bytes().unwrap(), PaaType::IndexPalette).unwrap().data, changed.data);
+}
+
+
+#[test]
+fn read_budget() {
+	let mipmaps = (1..=6u16).rev()
+		.map(|level| {
+			let size = 1 << level;
+			let data = vec![0x80; PaaType::Argb8888.predict_size(size, size)];
+			Ok(PaaMipmap { width: size, height: size, paatype: PaaType::Argb8888, compression: PaaMipmapCompression::Uncompressed, data, payload: None })
+		})
+		.collect::<Vec<_>>();
+	let image = PaaImage { paatype: PaaType::Argb8888, mipmaps, ..Default::default() };
+
+	// Levels 0 to 4 fit, level 5 does not
+	let budget = (64 * 64 + 32 * 32 + 16 * 16 + 8 * 8 + 4 * 4) * 4;
+	let limits = PaaLimits { max_total_decoded_bytes: budget, ..Default::default() };
+
+	for options in [SerializeOptions::default(), SerializeOptions { minimal_header: true, ..Default::default() }] {
+		let bytes = image.to_bytes_with_options(options).unwrap();
+		let (read, report) = PaaImage::read_from_with_limits(&mut Cursor::new(&bytes), limits).unwrap();
+
+		assert_eq!(read.mipmaps.len(), 6);
+		assert!(read.mipmaps[..5].iter().zip(&image.mipmaps).all(|(r, m)| r.as_ref().ok() == m.as_ref().ok()));
+		assert!(matches!(read.mipmaps[5], Err(ReadBudgetExceeded(5, b)) if b == budget));
+		assert_eq!(report.allocated_bytes, budget);
+
+		let (read, report) = PaaImage::read_from_with_limits(&mut Cursor::new(&bytes), PaaLimits::default()).unwrap();
+		assert_eq!(read.mipmaps.iter().filter(|m| m.is_ok()).count(), 6);
+		assert_eq!(report.allocated_bytes, budget + 2 * 2 * 4);
+	};
+}
+
+
+#[test]
+fn legacy_lzss_layout() {
+	let lzss_data = [0u8, 1].repeat(8);
+	let mut lzss = vec![0xFF];
+	lzss.extend(&lzss_data[..8]);
+	lzss.push(0xFF);
+	lzss.extend(&lzss_data[8..]);
+	lzss.extend(get_additive_i32_cksum(&lzss_data).to_le_bytes());
+
+	// No length: its place is taken by the flags and first two literals, which
+	// read as a length beyond the end of file
+	let mut legacy_block = vec![0xD2, 0x04, 0x3D, 0x22, 4, 0, 4, 0];
+	legacy_block.extend(&lzss);
+	let mut rle_block = vec![2, 0, 2, 0, 5, 0, 0];
+	rle_block.extend([0x03, 1, 1, 1, 1]);
+
+	let mut pac = vec![0x47, 0x47, 2, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF];
+	pac.extend(&legacy_block);
+	pac.extend(&rle_block);
+	pac.extend([0; 6]);
+
+	let mipmap = PaaMipmap::from_bytes(&legacy_block, PaaType::IndexPalette).unwrap();
+	assert_eq!((mipmap.compression, &mipmap.data), (PaaMipmapCompression::Lzss, &lzss_data));
+
+	let legacy = SerializeOptions { legacy_lzss_layout: true, ..Default::default() };
+	assert_eq!(mipmap.to_bytes_with_options(legacy).unwrap(), legacy_block);
+	assert_eq!(mipmap.to_bytes().unwrap().len(), legacy_block.len() + 3);
+	assert_eq!(PaaMipmap::from_bytes(&mipmap.to_bytes().unwrap(), PaaType::IndexPalette).unwrap(), mipmap);
+
+	// The mipmap following a legacy one is found both sequentially and lazily
+	let image = PaaImage::from_bytes(&pac).unwrap();
+	assert!(matches!(image.mipmaps[..], [Ok(_), Ok(_), Err(EmptyMipmap)]));
+	assert_eq!(image.mipmaps[0].as_ref().ok(), Some(&mipmap));
+	assert_eq!(image.mipmaps[1].as_ref().map(|m| m.data.clone()).ok(), Some(vec![1; 4]));
+
+	let mut reader = PaaReader::new(Cursor::new(&pac)).unwrap();
+	assert_eq!(reader.mipmap_count(), 2);
+	assert_eq!(reader.read_mipmap(1).unwrap().data, vec![1; 4]);
+
+	// Other types are written as usual
+	let written = image.to_bytes_with_options(SerializeOptions { minimal_header: true, ..legacy }).unwrap();
+	assert_eq!(written, pac);
+	let argb = PaaMipmap { width: 1, height: 1, paatype: PaaType::Argb8888, compression: PaaMipmapCompression::Uncompressed, data: vec![0; 4], payload: None };
+	assert_eq!(argb.to_bytes_with_options(legacy).unwrap(), argb.to_bytes().unwrap());
+}
+
+
+#[test]
+fn unexpected_palette() {
+	let mut paa = vec![0x47, 0x47, 1, 0, 0x10, 0x20, 0x30, 0, 0, 0, 0, 0, 0];
+	let image = PaaImage::from_bytes(&paa).unwrap();
+	assert_eq!(image.palette.map(|p| p.to_bytes().unwrap()), Some(paa[2..7].to_vec()));
+	assert!(matches!(image.mipmaps[..], [Err(EmptyMipmap)]));
+
+	paa[0..2].copy_from_slice(&PaaType::Argb8888.magic());
+	assert!(matches!(PaaImage::from_bytes(&paa), Err(UnexpectedPalette(PaaType::Argb8888))));
+
+	// An empty palette is no palette
+	let paa = [&paa[..2], &[0; 8]].concat();
+	assert!(PaaImage::from_bytes(&paa).unwrap().palette.is_none());
+}
+
+
+#[test]
+fn palette_roundtrip() {
+	for len in [0usize, 1, 255, 256, 65535] {
+		#[allow(clippy::cast_possible_truncation)]
+		let pixels: Vec<Bgr888Pixel> = (0..len).map(|i| [i as u8, (i >> 8) as u8, 0x7F].into()).collect();
+		let palette = PaaPalette::with_pixels(&pixels).unwrap();
+
+		let bytes = palette.to_bytes().unwrap();
+		assert_eq!(bytes.len(), 2 + 3 * len);
+		assert_eq!(usize::from(LittleEndian::read_u16(&bytes)), len);
+
+		let reread = PaaPalette::read_from(&mut &bytes[..]).unwrap();
+		assert_eq!(reread.as_ref().map(PaaPalette::len), (len > 0).then_some(len));
+		assert!(reread.iter().flat_map(PaaPalette::iter).eq(pixels.iter()));
+	};
+
+	// Full palettes cannot grow
+	let mut full = PaaPalette::with_pixels(&vec![Bgr888Pixel::default(); 65535]).unwrap();
+	assert!(matches!(full.push(Bgr888Pixel::default()), Err(PaletteTooLarge)));
+	assert!(matches!(PaaPalette::with_pixels(&vec![Bgr888Pixel::default(); 65536]), Err(PaletteTooLarge)));
+
+	// Palettes of IndexPalette textures stop at 256 colors
+	let mut indexed = PaaPalette::with_pixels(&vec![Bgr888Pixel::default(); 255]).unwrap();
+	assert_eq!(indexed.push_indexed(Bgr888Pixel::default()).unwrap(), 255);
+	assert!(matches!(indexed.push_indexed(Bgr888Pixel::default()), Err(PaletteTooLarge)));
+	assert_eq!(indexed.len(), 256);
+	assert_eq!(indexed.push(Bgr888Pixel::default()).unwrap(), 256);
+
+	// No palette and an empty palette are written identically, and read as no
+	// palette
+	let none = PaaImage { paatype: PaaType::IndexPalette, ..Default::default() };
+	let empty = PaaImage { palette: Some(PaaPalette::default()), ..none.clone() };
+	assert_eq!(none.to_bytes().unwrap(), empty.to_bytes().unwrap());
+	#[cfg(feature = "hash")]
+	assert_eq!(none.header_hash().unwrap(), empty.header_hash().unwrap());
+	assert!(PaaImage::from_bytes(&empty.to_bytes().unwrap()).unwrap().palette.is_none());
+}
+
+
+#[test]
+fn offs_zeros() {
+	let payload = |offsets: &[u32]| offsets.iter().flat_map(|o| o.to_le_bytes()).collect::<Vec<u8>>();
+
+	let mut slots = [0u32; 16];
+	slots[..2].copy_from_slice(&[0x80, 0x100]);
+	let padded = Tagg::from_name_and_payload("SFFO", &payload(&slots)).unwrap();
+	assert!(matches!(&padded, Tagg::Offs { offsets } if *offsets == [0x80, 0x100]));
+	assert_eq!(padded.offset_slots(), Some(slots));
+
+	slots[..3].copy_from_slice(&[0x80, 0, 0x100]);
+	let interior = Tagg::from_name_and_payload("SFFO", &payload(&slots)).unwrap();
+	assert!(matches!(&interior, Tagg::Offs { offsets } if *offsets == [0x80, 0, 0x100]));
+	assert_eq!(interior.offset_slots(), Some(slots));
+
+	let zeros = Tagg::from_name_and_payload("SFFO", &payload(&[0; 16])).unwrap();
+	assert!(matches!(&zeros, Tagg::Offs { offsets } if offsets.is_empty()));
+	assert_eq!(zeros.offset_slots(), Some([0; 16]));
+	assert_eq!(Tagg::Flag { transparency: Transparency::None }.offset_slots(), None);
+
+	// Mipmaps after a zero offset are read one after the other; a zero last
+	// offset is padding, as above
+	let mipmap = |size: u16| PaaMipmap {
+		width: size,
+		height: size,
+		paatype: PaaType::Argb8888,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0x40; usize::from(size).pow(2) * 4],
+		payload: None,
+	};
+	let image = PaaImage { paatype: PaaType::Argb8888, mipmaps: vec![Ok(mipmap(4)), Ok(mipmap(2)), Ok(mipmap(1))], ..Default::default() };
+	let mut bytes = image.to_bytes().unwrap();
+
+	for zeroed in 0..2 {
+		let slot = 2 + 12 + zeroed * 4;
+		let mut bytes = bytes.clone();
+		bytes[slot..slot + 4].fill(0);
+
+		let reread = PaaImage::from_bytes(&bytes).unwrap();
+		assert_eq!(reread.mipmaps.len(), 4);
+		assert!(reread.mipmaps.iter().zip(&image.mipmaps).all(|(a, b)| a.as_ref().ok() == b.as_ref().ok()));
+		assert_eq!(reread.trailer, PaaImage::TERMINATOR);
+
+		let mut reader = PaaReader::new(Cursor::new(&bytes)).unwrap();
+		assert_eq!(reader.mipmap_count(), 3);
+		assert_eq!(reader.read_mipmap(2).ok().as_ref(), image.mipmaps[2].as_ref().ok());
+	};
+
+	// All-zero offsets are no offsets
+	bytes[2 + 12..2 + 12 + 64].fill(0);
+	assert_eq!(PaaImage::from_bytes(&bytes).unwrap().mipmaps.len(), 4);
+}
+
+
+#[test]
+fn shifted_offsets_repaired() {
+	let mipmap = |size: u16| PaaMipmap {
+		width: size,
+		height: size,
+		paatype: PaaType::Argb8888,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0x40; usize::from(size).pow(2) * 4],
+		payload: None,
+	};
+	let image = PaaImage { paatype: PaaType::Argb8888, mipmaps: vec![Ok(mipmap(4)), Ok(mipmap(2)), Ok(mipmap(1))], ..Default::default() };
+	let bytes = image.to_bytes().unwrap();
+	let offsets = PaaImage::offsets_in(&PaaImage::from_bytes(&bytes).unwrap().taggs);
+	assert_eq!(offsets[0], 2 + 12 + 64 + 2);
+
+	let with_offsets = |offsets: &[u32]| {
+		let mut bytes = bytes.clone();
+		LittleEndian::write_u32_into(offsets, &mut bytes[2 + 12..2 + 12 + offsets.len() * 4]);
+
+		let mut events = vec![];
+		let reread = PaaImage::read_from_with_progress(&mut Cursor::new(&bytes), |e| events.push(e)).unwrap();
+		assert!(reread.mipmaps.iter().zip(&image.mipmaps).all(|(a, b)| a.as_ref().ok() == b.as_ref().ok()));
+		assert_eq!(events.iter().filter(|e| matches!(e, ProgressEvent::MipmapRead { .. })).count(), 3);
+
+		events.into_iter().find_map(|e| if let ProgressEvent::MipmapOffsetsRepaired { repair } = e { Some(repair) } else { None })
+	};
+
+	// Offsets relative to the end of the header lead into the OFFS payload
+	let shifted: Vec<u32> = offsets.iter().map(|o| o - 60).collect();
+	assert_eq!(with_offsets(&shifted), Some(OffsetsRepair::HeaderRelative));
+
+	let garbage = [20, 24, 28];
+	assert_eq!(with_offsets(&garbage), Some(OffsetsRepair::Sequential));
+
+	assert_eq!(with_offsets(&offsets), None);
+}
+
+
+#[test]
+fn backtracking_seek_failure() {
+	/// Fails every seek but those that only query the position
+	struct NoRewind<'a>(Cursor<&'a [u8]>);
+
+	impl Read for NoRewind<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> { self.0.read(buf) }
+	}
+
+	impl Seek for NoRewind<'_> {
+		fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+			match pos {
+				SeekFrom::Current(0) => self.0.seek(pos),
+				_ => Err(std::io::Error::new(std::io::ErrorKind::Other, "no rewind")),
+			}
+		}
+	}
+
+	// A tagg, then neither a tagg nor a palette
+	let mut bytes = PaaType::Dxt1.magic().to_vec();
+	bytes.extend(Tagg::Avgc { rgba: Bgra8888Pixel::default() }.to_bytes().unwrap());
+	bytes.extend(b"GGAT");
+	assert!(matches!(PaaImage::read_from(&mut NoRewind(Cursor::new(&bytes))), Err(UnexpectedIoError(..))));
+	assert!(matches!(Tagg::read_taggs_from(&mut NoRewind(Cursor::new(&bytes[2..]))), Err(UnexpectedIoError(..))));
+	assert!(matches!(Tagg::read_tagg_from(&mut NoRewind(Cursor::new(b"GGATCGVA"))), Err(UnexpectedIoError(..))));
+}
+
+
+#[test]
+fn proc_tagg_bytes() {
+	// Not UTF-8, and with NULs: kept byte for byte, and displayed unambiguously
+	let text: &[u8] = b"fresnel(1.3,\x007)\xFF\xC3\\";
+	let code = TextureMacro { text: text.into() };
+	assert_eq!(code.to_string(), "fresnel(1.3,\\07)\\xFF\\xC3\\\\");
+
+	let tagg = Tagg::Proc { code };
+	let bytes = tagg.to_bytes().unwrap();
+	assert_eq!(&bytes[8..12], &u32::try_from(text.len()).unwrap().to_le_bytes());
+	assert_eq!(&bytes[12..], text);
+	assert_eq!(Tagg::read_tagg_from(&mut Cursor::new(&bytes)).unwrap(), tagg);
+
+	// An oversized payload is refused before it is read
+	let mut oversized = bytes[..8].to_vec();
+	oversized.extend(u32::MAX.to_le_bytes());
+	oversized.extend(text);
+	assert!(matches!(Tagg::read_tagg_from(&mut Cursor::new(&oversized)), Err(ProcTaggTooLarge(_, PaaLimits::DEFAULT_MAX_PROC_LEN))));
+
+	let mut paa = PaaType::Dxt1.magic().to_vec();
+	paa.extend(&oversized);
+	assert!(matches!(PaaImage::from_bytes(&paa), Err(ProcTaggTooLarge(..))));
+
+	let payload = vec![b' '; PaaLimits::DEFAULT_MAX_PROC_LEN + 1];
+	assert!(matches!(Tagg::from_name_and_payload("CORP", &payload), Err(ProcTaggTooLarge(..))));
+
+	// The limit is raised for a single read
+	let large = Tagg::Proc { code: TextureMacro { text: payload.into() } };
+	let mipmap = PaaMipmap { width: 4, height: 4, paatype: PaaType::Argb8888, compression: PaaMipmapCompression::Uncompressed, data: vec![0x40; 64], payload: None };
+	let image = PaaImage { paatype: PaaType::Argb8888, taggs: vec![large.clone()], mipmaps: vec![Ok(mipmap)], ..Default::default() };
+	let bytes = image.to_bytes().unwrap();
+	assert!(matches!(PaaImage::from_bytes(&bytes), Err(ProcTaggTooLarge(..))));
+
+	let limits = PaaLimits { max_proc_len: 2 * PaaLimits::DEFAULT_MAX_PROC_LEN, ..Default::default() };
+	let (read, _) = PaaImage::read_from_with_limits(&mut Cursor::new(&bytes), limits).unwrap();
+	assert!(read.taggs.contains(&large));
+}
+
+
+#[test]
+fn tagg_order_on_write() {
+	let avgc = |r| Tagg::Avgc { rgba: Bgra8888Pixel { r, ..Default::default() } };
+	let maxc = Tagg::Maxc { rgba: Bgra8888Pixel { b: 0xFF, g: 0xFF, r: 0xFF, a: 0xFF } };
+	let flag = Tagg::Flag { transparency: Transparency::AlphaInterpolated };
+	let swiz = Tagg::Swiz { swizzle: ArgbSwizzle::default() };
+	let corp = Tagg::from_name_and_payload("CORP", b"color(1,0,0,1)").unwrap();
+	let offs = Tagg::Offs { offsets: vec![0x1234] };
+
+	let taggs = vec![corp.clone(), avgc(1), offs, swiz.clone(), flag.clone(), maxc.clone(), avgc(2), swiz.clone()];
+	let image = PaaImage { paatype: PaaType::Argb8888, taggs, ..Default::default() };
+	let layout = |taggs: &[&Tagg]| taggs.iter().flat_map(|t| t.to_bytes().unwrap()).collect::<Vec<u8>>();
+
+	// Canonical order, the last of each kind, then a regenerated OFFS
+	let canonical = image.to_bytes().unwrap();
+	let expected = layout(&[&avgc(2), &maxc, &flag, &swiz, &corp]);
+	assert_eq!(&canonical[2..2 + expected.len()], expected);
+	assert!(canonical[2 + expected.len()..].starts_with(b"GGATSFFO"));
+
+	let shuffled = vec![swiz.clone(), avgc(2), maxc.clone(), corp.clone(), flag.clone()];
+	assert_eq!(canonical, PaaImage { taggs: shuffled, ..image.clone() }.to_bytes().unwrap());
+
+	let preserved = image.to_bytes_exact(true).unwrap();
+	let expected = layout(&[&corp, &avgc(1), &swiz, &flag, &maxc, &avgc(2), &swiz]);
+	assert_eq!(&preserved[2..2 + expected.len()], expected);
+	assert_eq!(image.to_bytes_exact(false).unwrap(), &canonical[..canonical.len() - 6]);
+}
+
+
+#[test]
+fn minimal_header() {
+	let mask = PaaMipmap {
+		width: 1,
+		height: 1,
+		paatype: PaaType::Argb8888,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0x80, 0x40, 0x20, 0xFF],
+		payload: None,
+	};
+	let mut image = PaaImage { paatype: PaaType::Argb8888, mipmaps: vec![Ok(mask.clone())], ..Default::default() };
+
+	let full = image.to_bytes().unwrap();
+	let minimal = image.to_bytes_with_options(SerializeOptions { minimal_header: true, ..Default::default() }).unwrap();
+	let single = image.to_bytes_with_options(SerializeOptions { omit_offs_when_single_mipmap: true, ..Default::default() }).unwrap();
+	assert_eq!((full.len(), minimal.len()), (97, 21));
+	assert_eq!(single, minimal);
+
+	// Without OFFSTAGG, the mipmap is read sequentially up to the terminator
+	for data in [&full, &minimal] {
+		let reread = PaaImage::from_bytes(data).unwrap();
+		assert_eq!(reread.mipmaps[0].as_ref().ok(), Some(&mask));
+		assert_eq!(reread.trailer, PaaImage::TERMINATOR);
+		assert_eq!(PaaReader::new(Cursor::new(data)).unwrap().mipmap_count(), 1);
+	};
+
+	assert!(PaaImage::from_bytes(&minimal).unwrap().taggs.is_empty());
+	assert!(matches!(PaaImage::from_bytes(&minimal).unwrap().mipmaps[1], Err(EmptyMipmap)));
+
+	// The terminator read back is not written as a mipmap
+	let reread = PaaImage::from_bytes(&minimal).unwrap();
+	assert_eq!(reread.to_bytes_with_options(SerializeOptions { minimal_header: true, ..Default::default() }).unwrap(), minimal);
+	assert_eq!(PaaImage::from_bytes(&reread.to_bytes().unwrap()).unwrap().mipmaps[0].as_ref().ok(), Some(&mask));
+
+	// Taggs other than OFFS are kept unless the header is minimal
+	image.taggs.push(Tagg::Flag { transparency: Transparency::None });
+	let single = image.to_bytes_with_options(SerializeOptions { omit_offs_when_single_mipmap: true, ..Default::default() }).unwrap();
+	assert_eq!(PaaImage::from_bytes(&single).unwrap().taggs, image.taggs);
+	assert_eq!(image.to_bytes_with_options(SerializeOptions { minimal_header: true, ..Default::default() }).unwrap(), minimal);
+
+	// OFFS is only omitted for a single mipmap
+	image.mipmaps.push(Ok(mask));
+	let double = image.to_bytes_with_options(SerializeOptions { omit_offs_when_single_mipmap: true, ..Default::default() }).unwrap();
+	assert_eq!(double, image.to_bytes().unwrap());
+}
+
+
+#[test]
+fn trailer_roundtrip() {
+	let fixture = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference/fixtures/checker_dxt1.paa")).unwrap();
+	let image = PaaImage::from_bytes(&fixture).unwrap();
+	assert_eq!(image.trailer, PaaImage::TERMINATOR);
+	assert_eq!(image.to_bytes_exact(false).unwrap(), image.to_bytes().unwrap());
+	assert!(image.to_bytes().unwrap().ends_with(&fixture[fixture.len() - 7..]));
+
+	// Padding is kept by to_bytes_exact only
+	let mut padded = fixture.clone();
+	padded.extend(b"\xAA\xBB");
+	let image = PaaImage::from_bytes(&padded).unwrap();
+	assert_eq!(image.trailer, b"\0\0\0\0\0\0\xAA\xBB");
+	assert!(image.to_bytes_exact(false).unwrap().ends_with(&padded[padded.len() - 8..]));
+	assert!(image.to_bytes().unwrap().ends_with(&[0xE1, 0, 0, 0, 0, 0, 0]));
+
+	// With offsets, the trailer follows the mipmap furthest into the file
+	let reread = PaaImage::from_bytes(&image.to_bytes_exact(false).unwrap()).unwrap();
+	assert_eq!(reread.trailer, image.trailer);
+
+	let truncated = PaaImage::from_bytes(&fixture[..fixture.len() - 6]).unwrap();
+	assert!(truncated.trailer.is_empty());
+
+	padded.resize(fixture.len() + PaaImage::MAX_TRAILER_LEN, 0xAA);
+	assert_eq!(PaaImage::from_bytes(&padded).unwrap().trailer.len(), PaaImage::MAX_TRAILER_LEN);
+}
+
+
+#[test]
+fn lzss_as_long_as_uncompressed() {
+	// LZSS data (with its checksum) exactly as long as the uncompressed data
+	// reads back as uncompressed; such mipmaps must be written uncompressed
+	let mut seed = 0x1234_5678u32;
+	let mut random = || {
+		seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+		(seed >> 16) as u8
+	};
+	let mut ambiguous = 0;
+
+	for width in 8..16 {
+		let data_len = PaaType::Argb8888.predict_size(width, 8);
+
+		for noise in 0..=data_len {
+			let mut data = vec![0u8; data_len];
+			data[..noise].iter_mut().for_each(|b| *b = random());
+
+			if PaaMipmapCompression::Lzss.compress_slice(&data).unwrap().len() + 4 == data_len {
+				ambiguous += 1;
+			};
+
+			let mipmap = PaaMipmap { width, height: 8, paatype: PaaType::Argb8888, compression: PaaMipmapCompression::Lzss, data, payload: None };
+			let read = PaaMipmap::from_bytes(&mipmap.to_bytes().unwrap(), PaaType::Argb8888).unwrap();
+			assert_eq!(read.data, mipmap.data, "{width}x8, {noise} random bytes");
+		};
+	};
+
+	assert!(ambiguous > 0);
+}
+
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn paa_image_arbitrary_roundtrip() {
+	let seed = (0..65_536u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect::<Vec<u8>>();
+
+	for start in (0..1024).step_by(64) {
+		let image = PaaImage::arbitrary(&mut Unstructured::new(&seed[start..])).unwrap();
+		assert!(image.validate().is_ok());
+		assert!(image.palette.is_none() && image.paatype != PaaType::IndexPalette);
+
+		let read = PaaImage::from_bytes(&image.to_bytes().unwrap()).unwrap();
+		let without_offs = |taggs: &[Tagg]| taggs.iter().filter(|t| !matches!(t, Tagg::Offs { .. })).cloned().collect::<Vec<_>>();
+		assert_eq!(read.paatype, image.paatype);
+		assert_eq!(without_offs(&read.taggs), Tagg::canonicalize(&image.taggs));
+
+		let exact = PaaImage::from_bytes(&image.to_bytes_exact(true).unwrap()).unwrap();
+		assert_eq!(without_offs(&exact.taggs), without_offs(&image.taggs));
+		assert_eq!(read.mipmaps.len(), image.mipmaps.len());
+
+		for (a, b) in read.mipmaps.iter().zip(&image.mipmaps) {
+			let (a, b) = (a.as_ref().unwrap(), b.as_ref().unwrap());
+			assert_eq!((a.width, a.height, &a.data), (b.width, b.height, &b.data));
+		};
+	};
+}
+
+
+/// Bitmap encoding used by all [mipmaps][`PaaImage::mipmaps`] of a given PAA
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(type = "u16", endian = "little")]
+pub enum PaaType {
+	// See `int __stdcall sub_4276E0(void *Block, int)` (ImageToPAA v1.0.0.3).
+
+	/// 1 byte (offset into the index palette, which contains BGR 8:8:8).
+	#[deprecated = "[TODO] Index palette format is not implemented"]
+	#[deku(id = "0x47_47")]
+	IndexPalette,
+
+	/// 8 bits alpha, 8 bits grayscale.
+	#[deku(id = "0x80_80")]
+	Ai88,
+
+	/// RGBA 5:5:5:1 in a little-endian 2-byte integer.
+	#[deku(id = "0x15_55")]
+	Argb1555,
+
+	/// ARGB 4:4:4:4 in a little-endian 2-byte integer.
+	#[deku(id = "0x44_44")]
+	Argb4444,
+
+	/// RGBA 8:8:8:8.
+	#[deku(id = "0x88_88")]
+	Argb8888,
+
+	/// `[TODO]`
+	#[deku(id = "0xFF_01")]
+	Dxt1,
+
+	/// `[TODO]`
+	#[deprecated]
+	#[deku(id = "0xFF_02")]
+	Dxt2,
+
+	/// `[TODO]`
+	#[deprecated]
+	#[deku(id = "0xFF_03")]
+	Dxt3,
+
+	/// `[TODO]`
+	#[deprecated]
+	#[deku(id = "0xFF_04")]
+	Dxt4,
+
+	/// DXT5 (BC3) texture.
+	#[deku(id = "0xFF_05")]
+	Dxt5,
+}
+
+
+impl Default for PaaType {
+	/// Returns [`Dxt5`][`PaaType::Dxt5`].
 	fn default() -> Self {
 		PaaType::Dxt5
 	}
@@ -460,7 +2205,115 @@ impl FromStr for PaaType {
 }
 
 
-impl PaaType {
+impl std::fmt::Display for PaaType {
+	/// Writes [`as_str`][`PaaType::as_str`].
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+
+impl PaaType {
+	/// Every [`PaaType`], in declaration order.
+	pub const ALL: &'static [PaaType] = {
+		use PaaType::*;
+		&[IndexPalette, Ai88, Argb1555, Argb4444, Argb8888, Dxt1, Dxt2, Dxt3, Dxt4, Dxt5]
+	};
+
+
+	/// Return the name of the type as written in TexConvert.cfg, which
+	/// [`FromStr`] parses back.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::PaaType;
+	/// assert_eq!(PaaType::Argb8888.as_str(), "ARGB8888");
+	/// assert_eq!("DXT5".parse::<PaaType>(), Ok(PaaType::Dxt5));
+	/// ```
+	pub const fn as_str(&self) -> &'static str {
+		use PaaType::*;
+
+		match self {
+			IndexPalette => "INDEXPALETTE",
+			Ai88 => "AI88",
+			Argb1555 => "ARGB1555",
+			Argb4444 => "ARGB4444",
+			Argb8888 => "ARGB8888",
+			Dxt1 => "DXT1",
+			Dxt2 => "DXT2",
+			Dxt3 => "DXT3",
+			Dxt4 => "DXT4",
+			Dxt5 => "DXT5",
+		}
+	}
+
+
+	/// Return the two bytes starting a PAA file of this type.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::PaaType;
+	/// assert_eq!(PaaType::Dxt5.magic(), [0x05, 0xFF]);
+	/// ```
+	pub const fn magic(&self) -> [u8; 2] {
+		use PaaType::*;
+
+		let id: u16 = match self {
+			IndexPalette => 0x47_47,
+			Ai88 => 0x80_80,
+			Argb1555 => 0x15_55,
+			Argb4444 => 0x44_44,
+			Argb8888 => 0x88_88,
+			Dxt1 => 0xFF_01,
+			Dxt2 => 0xFF_02,
+			Dxt3 => 0xFF_03,
+			Dxt4 => 0xFF_04,
+			Dxt5 => 0xFF_05,
+		};
+
+		id.to_le_bytes()
+	}
+
+
+	/// Return the type of a PAA file starting with `magic`, or [`None`] if it
+	/// is unknown.
+	pub fn from_magic(magic: [u8; 2]) -> Option<Self> {
+		Self::ALL.iter().copied().find(|t| t.magic() == magic)
+	}
+
+
+	/// Return the usual format of textures with the (case-insensitive) type
+	/// `suffix`, as in `"wall_co.paa"`, for use when no TexConvert.cfg says
+	/// otherwise.
+	///
+	/// Color, normal and most other maps are DXT5; specular (SMDI) and ambient
+	/// shadow (AS) maps, which have no alpha, are DXT1; masks (MASK) and light
+	/// color maps (LCO) are ARGB8888.
+	///
+	/// This is also the format of each entry of `TextureHints::builtin()`.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::PaaType;
+	/// assert_eq!(PaaType::default_for_suffix("nohq"), Some(PaaType::Dxt5));
+	/// assert_eq!(PaaType::default_for_suffix("SMDI"), Some(PaaType::Dxt1));
+	/// assert_eq!(PaaType::default_for_suffix("unknown"), None);
+	/// ```
+	pub fn default_for_suffix(suffix: &str) -> Option<Self> {
+		use PaaType::*;
+
+		let format = match suffix.to_uppercase().as_str() {
+			"CO" | "CA" | "MC" | "MCO" | "TI" | "SKY" => Dxt5,
+			"NO" | "NOHQ" | "NOVHQ" | "NOPX" | "DT" | "DTSMDI" => Dxt5,
+			"SMDI" | "AS" => Dxt1,
+			"MASK" | "LCO" => Argb8888,
+			_ => return None,
+		};
+
+		Some(format)
+	}
+
+
 	/// Calculate the size in bytes of uncompressed mipmap data from its width
 	/// and height in pixels.
 	pub const fn predict_size(&self, width: u16, height: u16) -> usize {
@@ -536,6 +2389,37 @@ impl PaaType {
 }
 
 
+#[test]
+fn paatype_magic_and_names() {
+	assert_eq!(PaaType::ALL.len(), 10);
+
+	for paatype in PaaType::ALL {
+		assert_eq!(paatype.to_bytes().unwrap(), paatype.magic(), "{paatype:?}");
+		assert_eq!(PaaType::from_bytes((&paatype.magic(), 0)).unwrap().1, *paatype);
+		assert_eq!(PaaType::from_magic(paatype.magic()), Some(*paatype));
+		assert_eq!(paatype.to_string().parse::<PaaType>(), Ok(*paatype));
+		assert_eq!(paatype.as_str().to_lowercase().parse::<PaaType>(), Ok(*paatype));
+	};
+
+	assert_eq!(PaaType::from_magic([0x00, 0x00]), None);
+	assert_eq!(PaaType::from_magic([0xFF, 0x05]), None);
+}
+
+
+#[test]
+fn paatype_default_for_suffix() {
+	assert_eq!(PaaType::default_for_suffix("CO"), Some(PaaType::Dxt5));
+	assert_eq!(PaaType::default_for_suffix("ca"), Some(PaaType::Dxt5));
+	assert_eq!(PaaType::default_for_suffix("NoHQ"), Some(PaaType::Dxt5));
+	assert_eq!(PaaType::default_for_suffix("SMDI"), Some(PaaType::Dxt1));
+	assert_eq!(PaaType::default_for_suffix("as"), Some(PaaType::Dxt1));
+	assert_eq!(PaaType::default_for_suffix("LCO"), Some(PaaType::Argb8888));
+	assert_eq!(PaaType::default_for_suffix("mask"), Some(PaaType::Argb8888));
+	assert_eq!(PaaType::default_for_suffix(""), None);
+	assert_eq!(PaaType::default_for_suffix("co_dirty"), None);
+}
+
+
 /// Metadata frame present in PAA headers
 #[derive(Debug, Display, Clone, PartialEq, Eq)]
 pub enum Tagg {
@@ -585,11 +2469,26 @@ pub enum Tagg {
 
 
 impl Tagg {
+	/// Fail with [`ProcTaggTooLarge`] if a [`Tagg::Proc`] payload of `len`
+	/// bytes is over `max_len`.
+	pub(crate) fn check_proc_len(len: usize, max_len: usize) -> PaaResult<()> {
+		if len > max_len {
+			return Err(ProcTaggTooLarge(len, max_len));
+		};
+
+		Ok(())
+	}
+
+
 	/// Serialize a Tagg into PAA-ready data.
 	///
+	/// # Errors
+	/// - [`UnexpectedTryFromIntError`]: A [`Tagg::Proc`] payload does not fit
+	///   the 32-bit length of a tagg.
+	///
 	/// # Panics
 	/// - If [`deku::DekuContainerWrite::to_bytes()`] fails.
-	pub fn to_bytes(&self) -> Vec<u8> {
+	pub fn to_bytes(&self) -> PaaResult<Vec<u8>> {
 		#[allow(clippy::cast_possible_truncation)]
 		const U32_SIZE: u32 = std::mem::size_of::<u32>() as u32;
 
@@ -620,9 +2519,7 @@ impl Tagg {
 			},
 
 			Self::Proc { code } => {
-				// Tagg data length is guaranteed to fit in a u32
-				#[allow(clippy::cast_possible_truncation)]
-				let len = (code.text[..]).len() as u32;
+				let len = u32::try_from(code.text.len())?;
 				bytes.extend_with_uint::<LittleEndian, _, 4>(len);
 				bytes.extend(&code.text[..]);
 			},
@@ -643,7 +2540,7 @@ impl Tagg {
 			},
 		};
 
-		bytes
+		Ok(bytes)
 	}
 
 
@@ -662,7 +2559,7 @@ impl Tagg {
 	/// ```
 	/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 	/// # use a3_paa::Tagg;
-	/// let offsdata = Tagg::Offs { offsets: vec![] }.to_bytes();
+	/// let offsdata = Tagg::Offs { offsets: vec![] }.to_bytes()?;
 	/// let headdata = (&offsdata[..12]).try_into()?;
 	/// let (taggname, payload_size) = Tagg::try_head_from(headdata)?;
 	/// assert_eq!(taggname, "SFFO");
@@ -697,11 +2594,20 @@ impl Tagg {
 	/// # Errors
 	/// - [`UnexpectedTaggSignature`]: Encountered an unknown type of [`Tagg`].
 	/// - [`UnexpectedTaggDataSize`]: Payload was of an unexpected length.
+	/// - [`ProcTaggTooLarge`]: A [`Tagg::Proc`] payload is over the default
+	///   [`PaaLimits::max_proc_len`].
 	///
 	/// # Panics
 	/// - If [`deku::DekuContainerRead::from_bytes`] fails (should never happen).
 	/// - If &[u8] of length 4 fails to convert to [u8; 4] (should never happen).
 	pub fn from_name_and_payload(taggname: &str, data: &[u8]) -> PaaResult<Self> {
+		Self::from_name_and_payload_within(taggname, data, PaaLimits::default())
+	}
+
+
+	/// [`from_name_and_payload`][`Self::from_name_and_payload`], with a
+	/// [`Tagg::Proc`] payload of up to [`PaaLimits::max_proc_len`] of `limits`.
+	pub(crate) fn from_name_and_payload_within(taggname: &str, data: &[u8], limits: PaaLimits) -> PaaResult<Self> {
 		if taggname.len() != 4 {
 			return Err(UnexpectedTaggSignature);
 		};
@@ -742,6 +2648,7 @@ impl Tagg {
 			},
 
 			"CORP" => {
+				Self::check_proc_len(data.len(), limits.max_proc_len)?;
 				let text = BString::from(data);
 				Ok(Self::Proc { code: TextureMacro { text } })
 			},
@@ -758,9 +2665,10 @@ impl Tagg {
 
 				LittleEndian::read_u32_into(data, &mut offsets[..]);
 
-				if let Some(idx) = offsets.iter().position(|x| *x == 0) {
-					offsets.truncate(idx);
-				};
+				// Trailing zeros are padding, but zeros followed by offsets
+				// are kept for readers to notice
+				let len = offsets.iter().rposition(|x| *x != 0).map_or(0, |idx| idx + 1);
+				offsets.truncate(len);
 
 				Ok(Self::Offs { offsets })
 			},
@@ -771,33 +2679,32 @@ impl Tagg {
 
 
 	/// Try to read a [`Tagg`] from [`Read`][std::io::Read].  If the read fails,
-	/// this function attempts to seek back to the starting point.
+	/// this function seeks back to the starting point.
 	///
 	/// # Errors
 	/// - [`UnexpectedEof`]:
-	/// - [`UnexpectedIoError`]:
+	/// - [`UnexpectedIoError`]: Also if seeking back fails, instead of the
+	///   error that interrupted reading.
 	/// - [`UnexpectedTryFromIntError`]:
 	/// - [`UnknownTaggType`]: Encountered an unknown type of [`Tagg`].
 	/// - [`UnexpectedTaggSignature`]: No "TAGG" signature at the beginning.
 	/// - [`UnexpectedTaggDataSize`]: Payload was of an unexpected length.
-	///
-	/// # Panics
-	/// - If the backtracking seek fails after an error occurs.
+	/// - [`ProcTaggTooLarge`]: A [`Tagg::Proc`] is over the default
+	///   [`PaaLimits::max_proc_len`]; its payload is not read.
 	pub fn read_tagg_from<R: Read + Seek>(input: &mut R) -> PaaResult<Self> {
 		let start_position = input.stream_position()?;
 
-		let get_tagg = |input: &mut R| -> PaaResult<Self> {
-			let mut tagghead_data = [0u8; 12];
-			input.read_exact(&mut tagghead_data)?;
-			let (taggname, payload_length) = Tagg::try_head_from(&tagghead_data)?;
-			let payload = input.read_exact_buffered(payload_length.try_into()?)?;
-			let tagg = Tagg::from_name_and_payload(&taggname, &payload)?;
-			Ok(tagg)
-		};
+		Self::read_tagg_in_place(input).or_else(|e| {
+			let _ = input.seek(SeekFrom::Start(start_position))?;
+			Err(e)
+		})
+	}
 
-		let tagg = get_tagg(input)
-			.tap_err(|_| { let _ = input.seek(SeekFrom::Start(start_position)).expect("Backtracking seek failed"); })?;
 
+	/// [`read_tagg_from`][`Self::read_tagg_from`], but leave `input` wherever
+	/// reading stopped.
+	fn read_tagg_in_place<R: Read>(input: &mut R) -> PaaResult<Self> {
+		let ((tagg, _), _) = sansio::drive(input, &mut vec![], |buf, _| sansio::parse_tagg(buf, PaaLimits::default()))?;
 		Ok(tagg)
 	}
 
@@ -809,20 +2716,39 @@ impl Tagg {
 	/// [`UnexpectedTaggSignature`].
 	///
 	/// # Errors
-	/// - [`UnexpectedIoError`]: If [`Seek::stream_position()`] fails.
-	///
-	/// # Panics
-	/// - If the backtracking seek fails after an error occurs.
+	/// - [`UnexpectedIoError`]: If [`Seek::stream_position()`] fails, or
+	///   seeking back to the end of the last tagg read does.
 	pub fn read_taggs_from<R: Read + Seek>(input: &mut R) -> PaaResult<(Vec<Self>, PaaError)> {
+		Self::read_taggs_reporting(input, None)
+	}
+
+
+	/// [`read_taggs_from`][`Self::read_taggs_from`], logging every tagg and
+	/// reporting it to `progress`.
+	pub(crate) fn read_taggs_reporting<R: Read + Seek>(input: &mut R, mut progress: Option<&mut (dyn FnMut(ProgressEvent) + '_)>) -> PaaResult<(Vec<Self>, PaaError)> {
 		let mut result: Vec<Self> = Vec::with_capacity(10);
 		let error: PaaError;
 
 		loop {
-			let tagg = Tagg::read_tagg_from(input);
+			let offset = input.stream_position()?;
+			let tagg = Tagg::read_tagg_in_place(input);
 
 			match tagg {
-				Ok(t) => result.push(t),
-				Err(e) => { error = e; break; },
+				Ok(t) => {
+					macros::log!(debug, "Tagg {} at offset {}", t.as_taggname(), offset);
+
+					if let Some(progress) = progress.as_deref_mut() {
+						progress(ProgressEvent::TaggRead { name: t.as_taggname(), offset });
+					};
+
+					result.push(t);
+				},
+
+				Err(e) => {
+					let _ = input.seek(SeekFrom::Start(offset))?;
+					error = e;
+					break;
+				},
 			};
 		};
 
@@ -844,6 +2770,53 @@ impl Tagg {
 	}
 
 
+	/// Return the 16 offsets of a [`Tagg::Offs`] as stored in the file, zeros
+	/// included, or [`None`] for other taggs.  Only trailing zeros are dropped
+	/// when reading, so these are the offsets of the original file as long as
+	/// it had 16 of them.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::Tagg;
+	/// let offs = Tagg::Offs { offsets: vec![0x80, 0, 0x100] };
+	/// assert_eq!(offs.offset_slots().unwrap()[..4], [0x80, 0, 0x100, 0]);
+	/// ```
+	pub fn offset_slots(&self) -> Option<[u32; 16]> {
+		match self {
+			Self::Offs { offsets } => {
+				let mut slots = [0u32; 16];
+				let len = offsets.len().min(slots.len());
+				slots[..len].copy_from_slice(&offsets[..len]);
+				Some(slots)
+			},
+
+			_ => None,
+		}
+	}
+
+
+	/// Return `taggs` in the order [`PaaImage::to_bytes`] writes them: AVGC,
+	/// MAXC, FLAG, SWIZ, then PROC, keeping only the last tagg of each kind.
+	/// [`Tagg::Offs`] is left out, as it is regenerated on write.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{Tagg, Transparency};
+	/// let flag = Tagg::Flag { transparency: Transparency::None };
+	/// let avgc = |r| Tagg::Avgc { rgba: a3_paa::Bgra8888Pixel { r, ..Default::default() } };
+	/// let canonical = Tagg::canonicalize(&[flag.clone(), avgc(1), avgc(2)]);
+	/// assert_eq!(canonical, [avgc(2), flag]);
+	/// ```
+	pub fn canonicalize(taggs: &[Tagg]) -> Vec<Tagg> {
+		const ORDER: [&str; 5] = ["CGVA", "CXAM", "GALF", "ZIWS", "CORP"];
+
+		ORDER.iter()
+			.filter_map(|name| taggs.iter().rev().find(|t| t.as_taggname() == *name))
+			.cloned()
+			.collect()
+	}
+
+
 	/// Check if `name` is a valid 4-character Tagg name as represented in the
 	/// file (e.g. "SFFO").
 	///
@@ -938,6 +2911,94 @@ impl PaaPalette {
 	}
 
 
+	/// Append `pixel` to the palette, returning its index.
+	///
+	/// NOTE: [`PaaType::IndexPalette`] can only index up to [`u8::MAX`]; build
+	/// palettes of such textures with [`push_indexed`][`Self::push_indexed`].
+	///
+	/// # Errors
+	/// - [`PaletteTooLarge`]: The palette already contains [`u16::MAX`] colors.
+	pub fn push(&mut self, pixel: Bgr888Pixel) -> PaaResult<u16> {
+		let index: u16 = self.pixels.len().try_into().map_err(|_| PaletteTooLarge)?;
+
+		if index == u16::MAX {
+			return Err(PaletteTooLarge);
+		};
+
+		self.pixels.push(pixel);
+		Ok(index)
+	}
+
+
+	/// Append `pixel` to the palette of a [`PaaType::IndexPalette`] texture,
+	/// returning its index.
+	///
+	/// # Errors
+	/// - [`PaletteTooLarge`]: The palette already contains 256 colors.
+	pub fn push_indexed(&mut self, pixel: Bgr888Pixel) -> PaaResult<u8> {
+		let index: u8 = self.pixels.len().try_into().map_err(|_| PaletteTooLarge)?;
+
+		self.pixels.push(pixel);
+		Ok(index)
+	}
+
+
+	/// Return the number of colors in the palette.
+	pub fn len(&self) -> usize {
+		self.pixels.len()
+	}
+
+
+	/// Return `true` if the palette contains no colors.
+	pub fn is_empty(&self) -> bool {
+		self.pixels.is_empty()
+	}
+
+
+	/// Iterate over the colors of the palette in index order.
+	pub fn iter(&self) -> impl Iterator<Item=&Bgr888Pixel> {
+		self.pixels.iter()
+	}
+
+
+	/// Remove repeated colors, keeping the first occurrence of each.  Note that
+	/// this changes the indices of the colors following a removed duplicate.
+	pub fn dedup(&mut self) {
+		let mut seen: std::collections::HashSet<Bgr888Pixel> = std::collections::HashSet::new();
+		self.pixels.retain(|p| seen.insert(*p));
+	}
+
+
+	/// Return the index and value of the palette color closest to `rgb`
+	/// (ordered R, G, B) by Euclidean distance; ties are resolved in favor of
+	/// the lowest index.  Returns `None` if the palette is empty.
+	///
+	/// # Example
+	/// ```
+	/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+	/// # use a3_paa::{PaaPalette, Bgr888Pixel};
+	/// let palette = PaaPalette::with_pixels(&[[0, 0, 0].into(), [0xFF, 0, 0].into()])?;
+	/// let (index, _) = palette.nearest([0xC0, 0x10, 0x10]).unwrap();
+	/// assert_eq!(index, 1);
+	/// # Ok(()) }
+	/// ```
+	pub fn nearest(&self, rgb: [u8; 3]) -> Option<(u16, &Bgr888Pixel)> {
+		let distance = |p: &Bgr888Pixel| -> u32 {
+			let [r, g, b]: [u8; 3] = (*p).into();
+			[(r, rgb[0]), (g, rgb[1]), (b, rgb[2])]
+				.iter()
+				.map(|(x, y)| u32::from(x.abs_diff(*y)).pow(2))
+				.sum()
+		};
+
+		self.pixels
+			.iter()
+			.enumerate()
+			.min_by_key(|(i, p)| (distance(*p), *i))
+			.and_then(|(i, p)| Some((u16::try_from(i).ok()?, p)))
+	}
+
+
 	/// Convert self to PAA data.
 	///
 	/// # Errors
@@ -972,7 +3033,9 @@ impl PaaPalette {
 	}
 
 
-	/// Return `Ok(None)` if palette is empty, `Ok(palette)` otherwise.
+	/// Return `Ok(None)` if palette is empty, `Ok(palette)` otherwise.  This
+	/// is the inverse of [`PaaPalette::to_bytes`], which writes an empty
+	/// palette as a zero count.
 	///
 	/// # Errors
 	/// - [`UnexpectedEof`]: Encountered EOF before reading the entire palette.
@@ -1006,7 +3069,10 @@ impl PaaPalette {
 
 
 /// BGR888 pixel used in [`PaaPalette`]
-#[derive(Default, Debug, Clone, Copy, DekuRead, DekuWrite)]
+///
+/// The in-file byte layout is B:G:R; conversions to and from `[u8; 3]` use
+/// R, G, B order instead, to match [`image::Rgb`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, DekuRead, DekuWrite)]
 pub struct Bgr888Pixel {
 	#[allow(missing_docs)]
 	pub b: u8,
@@ -1017,11 +3083,60 @@ pub struct Bgr888Pixel {
 }
 
 
+impl From<[u8; 3]> for Bgr888Pixel {
+	/// Construct a pixel from `[r, g, b]`.
+	fn from(rgb: [u8; 3]) -> Self {
+		let [r, g, b] = rgb;
+		Self { b, g, r }
+	}
+}
+
+
+impl From<Bgr888Pixel> for [u8; 3] {
+	/// Return the channels of `pixel` as `[r, g, b]`.
+	fn from(pixel: Bgr888Pixel) -> Self {
+		[pixel.r, pixel.g, pixel.b]
+	}
+}
+
+
+#[test]
+fn palette_nearest() {
+	let colors: [[u8; 3]; 4] = [[0, 0, 0], [0xFF, 0, 0], [0, 0xFF, 0], [0xFF, 0, 0]];
+	let pixels: Vec<Bgr888Pixel> = colors.iter().map(|c| Bgr888Pixel::from(*c)).collect();
+	let mut palette = PaaPalette::with_pixels(&pixels).unwrap();
+
+	assert_eq!(<[u8; 3]>::from(pixels[1]), [0xFF, 0, 0]);
+	assert_eq!(palette.nearest([0x10, 0x10, 0x10]).map(|(i, _)| i), Some(0));
+	assert_eq!(palette.nearest([0xF0, 0x20, 0x00]).map(|(i, _)| i), Some(1));
+	assert_eq!(palette.nearest([0x80, 0x80, 0x00]).map(|(i, _)| i), Some(1));
+	assert_eq!(palette.nearest([0x00, 0xA0, 0x00]).map(|(i, _)| i), Some(2));
+
+	palette.dedup();
+	assert_eq!(palette.len(), 3);
+	assert!(PaaPalette::default().nearest([0, 0, 0]).is_none());
+}
+
+
+#[test]
+fn palette_too_large() {
+	let pixels = vec![Bgr888Pixel::default(); 65535];
+	let mut palette = PaaPalette::with_pixels(&pixels).unwrap();
+	assert!(matches!(palette.push(Bgr888Pixel::default()), Err(PaletteTooLarge)));
+	assert!(matches!(PaaPalette::with_pixels(&vec![Bgr888Pixel::default(); 65536]), Err(PaletteTooLarge)));
+
+	let mut palette = PaaPalette::default();
+	assert_eq!(palette.push(Bgr888Pixel::default()).unwrap(), 0);
+	assert_eq!(palette.push(Bgr888Pixel::default()).unwrap(), 1);
+}
+
+
 
 
 /// The color data used in AVGCTAGG and MAXCTAGG; its byte layout is B:G:R:A
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bgra8888Pixel {
 	#[allow(missing_docs)]
 	pub b: u8,
@@ -1043,6 +3158,8 @@ impl std::fmt::Display for Bgra8888Pixel {
 }
 
 
+#[cfg(feature = "image")]
+#[cfg_attr(doc, doc(cfg(feature = "image")))]
 impl From<image::Rgba<u8>> for Bgra8888Pixel {
 	fn from(rgba: image::Rgba<u8>) -> Self {
 		let b = rgba.0[2];
@@ -1054,9 +3171,93 @@ impl From<image::Rgba<u8>> for Bgra8888Pixel {
 }
 
 
+#[cfg(feature = "image")]
+#[cfg_attr(doc, doc(cfg(feature = "image")))]
+impl From<Bgra8888Pixel> for image::Rgba<u8> {
+	fn from(pixel: Bgra8888Pixel) -> Self {
+		image::Rgba::<u8>([pixel.r, pixel.g, pixel.b, pixel.a])
+	}
+}
+
+
+impl Bgra8888Pixel {
+	/// Return the channels as `[r, g, b, a]`, each normalized to `0.0..=1.0`.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::Bgra8888Pixel;
+	/// let pixel = Bgra8888Pixel { b: 0, g: 0, r: 0xFF, a: 0xFF };
+	/// assert_eq!(pixel.to_rgba_f32(), [1.0, 0.0, 0.0, 1.0]);
+	/// ```
+	pub fn to_rgba_f32(&self) -> [f32; 4] {
+		[self.r, self.g, self.b, self.a].map(|c| f32::from(c) / 255.0)
+	}
+
+
+	/// Relative luminance of the color channels in `0.0..=1.0` (Rec. 601
+	/// weights; alpha is ignored).
+	pub fn luminance(&self) -> f32 {
+		let [r, g, b, _] = self.to_rgba_f32();
+		0.299 * r + 0.587 * g + 0.114 * b
+	}
+
+
+	/// Return the channel-wise maximum of `self` and `other`, as used when
+	/// computing [`Tagg::Maxc`].
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::Bgra8888Pixel;
+	/// let x = Bgra8888Pixel { b: 0x10, g: 0x80, r: 0x00, a: 0xFF };
+	/// let y = Bgra8888Pixel { b: 0x20, g: 0x40, r: 0x01, a: 0x00 };
+	/// assert_eq!(x.max_channels(y), Bgra8888Pixel { b: 0x20, g: 0x80, r: 0x01, a: 0xFF });
+	/// ```
+	pub fn max_channels(self, other: Self) -> Self {
+		Self {
+			b: std::cmp::max(self.b, other.b),
+			g: std::cmp::max(self.g, other.g),
+			r: std::cmp::max(self.r, other.r),
+			a: std::cmp::max(self.a, other.a),
+		}
+	}
+
+
+	/// Return the channel-wise minimum of `self` and `other`.
+	pub fn min_channels(self, other: Self) -> Self {
+		Self {
+			b: std::cmp::min(self.b, other.b),
+			g: std::cmp::min(self.g, other.g),
+			r: std::cmp::min(self.r, other.r),
+			a: std::cmp::min(self.a, other.a),
+		}
+	}
+
+
+	/// Returns `true` if every channel of `self` is greater than or equal to
+	/// the respective channel of `other` (e.g. a valid MAXC relative to any
+	/// pixel of the texture).
+	pub fn dominates(&self, other: &Self) -> bool {
+		self.b >= other.b && self.g >= other.g && self.r >= other.r && self.a >= other.a
+	}
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn bgra8888pixel_rgba_roundtrip() {
+	let rgba = image::Rgba::<u8>([0x11, 0x22, 0x33, 0x44]);
+	let pixel = Bgra8888Pixel::from(rgba);
+	assert_eq!(pixel, Bgra8888Pixel { b: 0x33, g: 0x22, r: 0x11, a: 0x44 });
+	assert_eq!(image::Rgba::<u8>::from(pixel), rgba);
+	assert!(pixel.max_channels(Default::default()).dominates(&pixel));
+	assert!((Bgra8888Pixel { b: 0xFF, g: 0xFF, r: 0xFF, a: 0 }.luminance() - 1.0).abs() < 1e-6);
+}
+
+
 /// Alpha interpolation algorithm used when the texture is rendered
 #[derive(Debug, Display, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(type = "u8")]
 pub enum Transparency {
 	/// Transparency disabled
@@ -1083,6 +3284,74 @@ impl Default for Transparency {
 }
 
 
+impl Transparency {
+	/// Every [`Transparency`], in declaration order.
+	pub const ALL: &'static [Transparency] = {
+		use Transparency::*;
+		&[None, AlphaInterpolated, AlphaNotInterpolated]
+	};
+
+
+	/// Return the short name of the transparency, which [`FromStr`] parses
+	/// back, as does its `Display` form.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::Transparency;
+	/// assert_eq!(Transparency::AlphaNotInterpolated.as_str(), "noninterpolated");
+	/// assert_eq!("Interpolated".parse(), Ok(Transparency::AlphaInterpolated));
+	/// ```
+	pub const fn as_str(&self) -> &'static str {
+		use Transparency::*;
+
+		match self {
+			None => "none",
+			AlphaInterpolated => "interpolated",
+			AlphaNotInterpolated => "noninterpolated",
+		}
+	}
+}
+
+
+impl FromStr for Transparency {
+	type Err = ();
+
+	/// Parse [`as_str`][`Transparency::as_str`], the variant name or the
+	/// `Display` form, ignoring case, hyphens and underscores.
+	fn from_str(input: &str) -> Result<Self, <Self as FromStr>::Err> {
+		use Transparency::*;
+
+		if let Some(transparency) = Self::ALL.iter().find(|t| t.to_string().eq_ignore_ascii_case(input)) {
+			return Ok(*transparency);
+		};
+
+		let normalized: String = input.chars().filter(|c| !matches!(c, '-' | '_')).collect::<String>().to_lowercase();
+
+		match normalized.as_str() {
+			"none" => Ok(None),
+			"interpolated" | "alphainterpolated" => Ok(AlphaInterpolated),
+			"noninterpolated" | "notinterpolated" | "alphanoninterpolated" | "alphanotinterpolated" => Ok(AlphaNotInterpolated),
+			_ => Err(()),
+		}
+	}
+}
+
+
+#[test]
+fn transparency_names() {
+	assert_eq!(Transparency::ALL.len(), 3);
+
+	for transparency in Transparency::ALL {
+		assert_eq!(transparency.to_string().parse::<Transparency>(), Ok(*transparency));
+		assert_eq!(transparency.as_str().to_uppercase().parse::<Transparency>(), Ok(*transparency));
+		assert_eq!(format!("{transparency:?}").parse::<Transparency>(), Ok(*transparency));
+	};
+
+	assert_eq!("non-interpolated".parse::<Transparency>(), Ok(Transparency::AlphaNotInterpolated));
+	assert_eq!("opaque".parse::<Transparency>(), Err(()));
+}
+
+
 /// PAA texture ARGB swizzle data (see [`ChannelSwizzle`])
 #[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
 pub struct ArgbSwizzle {
@@ -1118,6 +3387,47 @@ impl std::fmt::Display for ArgbSwizzle {
 }
 
 
+/// Parses the comma-separated A, R, G and B swizzle values, in the same format
+/// as [`parse_argb`][`ArgbSwizzle::parse_argb`]; e.g. `"1-R, 1-A, G, B"`.
+///
+/// # Example
+/// ```
+/// # use a3_paa::ArgbSwizzle;
+/// let swiz = "1-G, R, 1-A, B".parse::<ArgbSwizzle>().unwrap();
+/// assert_eq!(swiz, ArgbSwizzle::parse_argb("1-G", "R", "1-A", "B").unwrap());
+/// assert_eq!(swiz.to_argb_string(), "1-g, r, 1-a, b");
+/// ```
+impl FromStr for ArgbSwizzle {
+	type Err = PaaError;
+
+	fn from_str(input: &str) -> PaaResult<Self> {
+		let channels = input.split(',').collect::<Vec<&str>>();
+
+		match channels[..] {
+			[a, r, g, b] => ArgbSwizzle::parse_argb(a, r, g, b),
+			_ => Err(InvalidSwizzleString(String::from(input))),
+		}
+	}
+}
+
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ArgbSwizzle {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.to_argb_string())
+	}
+}
+
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArgbSwizzle {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let input = <String as serde::Deserialize>::deserialize(deserializer)?;
+		input.parse::<ArgbSwizzle>().map_err(serde::de::Error::custom)
+	}
+}
+
+
 #[cfg(feature = "arbitrary")]
 impl<'a> Arbitrary<'a> for ArgbSwizzle {
 	fn arbitrary(input: &mut Unstructured) -> ArbitraryResult<Self> {
@@ -1175,9 +3485,16 @@ impl ArgbSwizzle {
 	}
 
 
+	/// Return the A, R, G and B swizzle values separated by commas, as parsed
+	/// by the [`FromStr`] implementation.
+	pub fn to_argb_string(&self) -> String {
+		format!("{}, {}, {}, {}", self.a.data, self.r.data, self.g.data, self.b.data)
+	}
+
+
 	/// Return an [`FnMut`] that acts on an RGBA8888 pixel, processing it according
 	/// to the value of `self`.  See also [`ChannelSwizzle::to_subpixel_map()`].
-	pub fn to_rgba8_map(&self) -> Box<dyn FnMut(&[u8; 4]) -> [u8; 4]> {
+	pub fn to_rgba8_map(&self) -> Box<dyn FnMut(&[u8; 4]) -> [u8; 4] + Send> {
 		let mut a_flt = self.a.to_subpixel_map();
 		let mut r_flt = self.r.to_subpixel_map();
 		let mut g_flt = self.g.to_subpixel_map();
@@ -1211,6 +3528,8 @@ impl ArgbSwizzle {
 	/// swiz.apply_to_image(&mut image);
 	/// # Ok(()) }
 	/// ```
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
 	pub fn apply_to_image(&self, image: &mut RgbaImage) {
 		let mut map = self.to_rgba8_map();
 
@@ -1227,6 +3546,60 @@ impl ArgbSwizzle {
 	pub fn is_noop(&self) -> bool {
 		self.a.is_noop() && self.r.is_noop() && self.g.is_noop() && self.b.is_noop()
 	}
+
+
+	/// Return the swizzle that restores the channels of an image swizzled by
+	/// `self`, as far as possible.  Channels of the original image that no
+	/// channel is sourced from cannot be restored, and are filled with ones
+	/// (see [`lost_channels`][`Self::lost_channels`]).
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::ArgbSwizzle;
+	/// let nohq = ArgbSwizzle::parse_argb("1-R", "1-A", "G", "B").unwrap();
+	/// let swizzled = nohq.to_rgba8_map()(&[0x10, 0x20, 0x30, 0x40]);
+	/// assert_eq!(nohq.inverse().to_rgba8_map()(&swizzled), [0x10, 0x20, 0x30, 0x40]);
+	/// ```
+	pub fn inverse(&self) -> Self {
+		use ChannelSwizzleData::*;
+		use ChannelSwizzleId::*;
+
+		let channels = [self.a, self.r, self.g, self.b];
+
+		// Prefer plain copies over negated ones, if a channel is sourced twice
+		let restore = |original: ChannelSwizzleId| {
+			let data = channels.iter()
+				.filter_map(|c| match c.data {
+					Source { neg_flag, source } if source == original => Some(Source { neg_flag, source: c.target }),
+					_ => None,
+				})
+				.min_by_key(|d| matches!(d, Source { neg_flag: true, .. }))
+				.unwrap_or(Fill { value: ChannelSwizzleFill::FillFF });
+
+			ChannelSwizzle { target: original, data }
+		};
+
+		ArgbSwizzle { a: restore(Alpha), r: restore(Red), g: restore(Green), b: restore(Blue) }
+	}
+
+
+	/// Return the channels of the original image that no channel is sourced
+	/// from, and that [`inverse`][`Self::inverse`] cannot restore.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{ArgbSwizzle, ChannelSwizzleId::*};
+	/// let novhq = ArgbSwizzle::parse_argb("1-R", "1", "G", "1").unwrap();
+	/// assert_eq!(novhq.lost_channels(), [Alpha, Blue]);
+	/// ```
+	pub fn lost_channels(&self) -> Vec<ChannelSwizzleId> {
+		let inverse = self.inverse();
+
+		[inverse.a, inverse.r, inverse.g, inverse.b].into_iter()
+			.filter(|c| matches!(c.data, ChannelSwizzleData::Fill { .. }))
+			.map(|c| c.target)
+			.collect()
+	}
 }
 
 
@@ -1314,7 +3687,7 @@ impl ChannelSwizzle {
 	///     .to_subpixel_map()(&pixel_in, &mut pixel_out);
 	/// assert_eq!(pixel_out[ChannelSwizzleId::Green as usize], 0xFF);
 	/// ```
-	pub fn to_subpixel_map(&self) -> Box<dyn FnMut(&[u8; 4], &mut [u8; 4])> {
+	pub fn to_subpixel_map(&self) -> Box<dyn FnMut(&[u8; 4], &mut [u8; 4]) + Send> {
 		use ChannelSwizzleData::*;
 
 		let target_idx = self.target as usize;
@@ -1538,14 +3911,57 @@ fn parse_swizzle() {
 
 
 /// `[TODO]`
+///
+/// The text is kept as the raw bytes of the [`Tagg::Proc`] payload, which
+/// need not be UTF-8 and may contain NULs, so that it is written back as is.
 #[allow(rustdoc::broken_intra_doc_links)]
-#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TextureMacro {
 	/// `[TODO]`
 	pub text: BString,
 }
 
 
+/// The text, with backslashes, NULs and bytes that are not UTF-8 escaped as
+/// `\\`, `\0` and `\xNN`, so that no byte is lost.
+impl std::fmt::Display for TextureMacro {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let write_str = |f: &mut std::fmt::Formatter<'_>, s: &str| -> std::fmt::Result {
+			for c in s.chars() {
+				match c {
+					'\\' => f.write_str("\\\\")?,
+					'\0' => f.write_str("\\0")?,
+					c => write!(f, "{c}")?,
+				};
+			};
+
+			Ok(())
+		};
+
+		let mut rest: &[u8] = &self.text;
+
+		loop {
+			match std::str::from_utf8(rest) {
+				Ok(s) => return write_str(f, s),
+
+				Err(e) => {
+					let (valid, invalid) = rest.split_at(e.valid_up_to());
+					write_str(f, std::str::from_utf8(valid).unwrap_or_default())?;
+
+					let invalid_len = e.error_len().unwrap_or(invalid.len());
+
+					for b in &invalid[..invalid_len] {
+						write!(f, "\\x{b:02X}")?;
+					};
+
+					rest = &invalid[invalid_len..];
+				},
+			};
+		};
+	}
+}
+
+
 #[cfg(feature = "arbitrary")]
 impl<'a> Arbitrary<'a> for TextureMacro {
 	fn arbitrary(input: &mut Unstructured) -> ArbitraryResult<Self> {
@@ -1619,8 +4035,66 @@ fn test_read_exact_buffered() {
 }
 
 
-fn get_additive_i32_cksum(_: &[u8]) -> i32 {
-	0
+/// Sum of all bytes of `data`, as stored after LZSS-compressed mipmap data.
+fn get_additive_i32_cksum(data: &[u8]) -> i32 {
+	data.iter().fold(0i32, |sum, b| sum.wrapping_add(i32::from(*b)))
+}
+
+
+#[test]
+fn error_source_and_kind() {
+	use std::error::Error;
+
+	let io = PaaError::from(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "locked"));
+	assert_eq!(io.to_string(), "Unexpected I/O error: permission denied");
+	assert_eq!(io.source().unwrap().to_string(), "locked");
+	assert_eq!(io.kind(), PaaErrorKind::Io);
+
+	let eof = PaaError::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+	assert!(matches!(eof, UnexpectedEof));
+	assert!(eof.source().is_none());
+
+	let lzo = PaaError::from(lzo::Lzo1xError::LookbehindOverrun);
+	assert_eq!(lzo.to_string(), "DXT-LZO decompression failed: lookbehind overrun");
+	assert_eq!(lzo.source().unwrap().to_string(), "lookbehind overrun");
+	assert_eq!(lzo.kind(), PaaErrorKind::Compression);
+
+	// Boxed errors chain down to the innermost cause
+	let invalid = InvalidMipmap(2, Box::new(lzo.clone()));
+	let inner = invalid.source().unwrap();
+	assert_eq!(inner.to_string(), lzo.to_string());
+	assert_eq!(inner.source().unwrap().to_string(), "lookbehind overrun");
+	assert_eq!(invalid.kind(), PaaErrorKind::Format);
+
+	let serializing = InputMipmapErrorWhileEncoding(0, Box::new(MipmapErrorWhileSerializing(Box::new(MipmapTooLarge))));
+	assert_eq!(serializing.source().unwrap().source().unwrap().to_string(), MipmapTooLarge.to_string());
+	assert_eq!(serializing.kind(), PaaErrorKind::Encode);
+
+	assert!(UnexpectedTryFromIntError(u8::try_from(256u16).unwrap_err()).source().is_some());
+	assert!(MipmapTooLarge.source().is_none());
+
+	for (error, kind) in [
+		(UnknownPaaType([0, 0]), PaaErrorKind::Format),
+		(UnexpectedPalette(PaaType::Dxt1), PaaErrorKind::Format),
+		(MipmapDataBeyondEof, PaaErrorKind::Format),
+		(LzssWrongChecksum, PaaErrorKind::Compression),
+		(TooManyMipmaps(16), PaaErrorKind::Limits),
+		(ReadBudgetExceeded(5, 1 << 20), PaaErrorKind::Limits),
+		(ProcTaggTooLarge(1 << 30, PaaLimits::DEFAULT_MAX_PROC_LEN), PaaErrorKind::Limits),
+		(DimensionsTooLarge(20000, 100, (16384, 100)), PaaErrorKind::Limits),
+		(DimensionsNotPowerOfTwo(1000, 1000, (1024, 1024), (512, 512)), PaaErrorKind::Encode),
+		(MipmapIndexOutOfRange, PaaErrorKind::Limits),
+		(UnsupportedTranscodeTarget(PaaType::IndexPalette), PaaErrorKind::Encode),
+		(UnsupportedPaaType(PaaType::IndexPalette), PaaErrorKind::Format),
+		(Cancelled, PaaErrorKind::Encode),
+		(TaggSizeMismatch("CORP", 4, 8), PaaErrorKind::Encode),
+		(NotBlockCompressed(PaaType::Argb8888), PaaErrorKind::Format),
+		(BlockIndexOutOfRange(4, 0), PaaErrorKind::Limits),
+		(InvalidSwizzleString("R=G".into()), PaaErrorKind::Config),
+		(TexconvertInheritCycle("a -> a".into()), PaaErrorKind::Config),
+	] {
+		assert_eq!(error.kind(), kind, "{error}");
+	};
 }
 
 
@@ -1631,4 +4105,10 @@ fn assert_traits() {
 	use std::panic::{UnwindSafe, RefUnwindSafe};
 
 	assert_impl_all!(PaaError: Debug, Display, Error, Send, Sync, UnwindSafe, RefUnwindSafe);
+	assert_impl_all!(PaaImage: Send, Sync);
+	assert_impl_all!(PaaMipmap: Send, Sync);
+	assert_impl_all!(PaaReader<std::fs::File>: Send, Sync);
+	#[cfg(feature = "image")] assert_impl_all!(PaaDecoder: Send, Sync);
+	#[cfg(feature = "image")] assert_impl_all!(PaaEncoder: Send, Sync);
+	#[cfg(feature = "image")] assert_impl_all!(TextureHints: Send, Sync);
 }
@@ -0,0 +1,245 @@
+//! Pure-Rust LZO1X, used instead of `minilzo-rs` on targets where C code
+//! cannot be built (see the `minilzo` and `wasm` features).
+
+use derive_more::{Display, Error};
+
+use crate::{ErrorSource, PaaError, PaaResult};
+
+
+/// Error of the LZO1X decompressor, the source of a [`PaaError::LzoError`]
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lzo1xError {
+	#[display(fmt = "input overrun")]
+	InputOverrun,
+	#[display(fmt = "output overrun")]
+	OutputOverrun,
+	#[display(fmt = "lookbehind overrun")]
+	LookbehindOverrun,
+	#[display(fmt = "input not consumed")]
+	InputNotConsumed,
+}
+
+
+impl From<Lzo1xError> for PaaError {
+	fn from(error: Lzo1xError) -> Self {
+		PaaError::LzoError(ErrorSource::new(error))
+	}
+}
+
+
+/// Decompressor state over LZO1X `input`, writing at most `dst_len` bytes
+struct Lzo1xReader<'a> {
+	input: &'a [u8],
+	ip: usize,
+	out: Vec<u8>,
+	dst_len: usize,
+}
+
+
+impl Lzo1xReader<'_> {
+	fn byte(&mut self) -> PaaResult<usize> {
+		let byte = *self.input.get(self.ip).ok_or(Lzo1xError::InputOverrun)?;
+		self.ip += 1;
+		Ok(byte.into())
+	}
+
+
+	fn le16(&mut self) -> PaaResult<usize> {
+		Ok(self.byte()? | self.byte()? << 8)
+	}
+
+
+	/// Length of a run or match encoded as a zero count field: `base` plus
+	/// 255 for every zero byte, plus the first non-zero byte.
+	fn extended(&mut self, base: usize) -> PaaResult<usize> {
+		let mut length = base;
+
+		loop {
+			match self.byte()? {
+				0 => length += 255,
+				b => return Ok(length + b),
+			};
+		};
+	}
+
+
+	fn literals(&mut self, count: usize) -> PaaResult<()> {
+		let literals = self.input.get(self.ip..self.ip + count).ok_or(Lzo1xError::InputOverrun)?;
+
+		if self.out.len() + count > self.dst_len {
+			return Err(Lzo1xError::OutputOverrun.into());
+		};
+
+		self.out.extend_from_slice(literals);
+		self.ip += count;
+		Ok(())
+	}
+
+
+	fn copy_match(&mut self, distance: usize, length: usize) -> PaaResult<()> {
+		if distance > self.out.len() {
+			return Err(Lzo1xError::LookbehindOverrun.into());
+		};
+
+		if self.out.len() + length > self.dst_len {
+			return Err(Lzo1xError::OutputOverrun.into());
+		};
+
+		// Matches may overlap the bytes they produce
+		for _ in 0..length {
+			self.out.push(self.out[self.out.len() - distance]);
+		};
+
+		Ok(())
+	}
+}
+
+
+/// Decompress LZO1X `input` to at most `dst_len` bytes, failing as
+/// `lzo1x_decompress_safe` does.
+pub(crate) fn decompress(input: &[u8], dst_len: usize) -> PaaResult<Vec<u8>> {
+	let mut reader = Lzo1xReader { input, ip: 0, out: Vec::with_capacity(dst_len), dst_len };
+
+	// Literals copied by the previous instruction: none, 1 to 3 trailing a
+	// match, or 4 for a literal run; this gives meaning to instructions below 16
+	let mut state;
+	let mut t = reader.byte()?;
+
+	if t > 17 {
+		reader.literals(t - 17)?;
+		state = (t - 17).min(4);
+		t = reader.byte()?;
+	} else {
+		state = 0;
+	};
+
+	loop {
+		match t {
+			0..=15 if state == 0 => {
+				let length = if t == 0 { reader.extended(15)? } else { t };
+				reader.literals(length + 3)?;
+				state = 4;
+				t = reader.byte()?;
+				continue;
+			},
+
+			0..=15 if state == 4 => {
+				let distance = 1 + 0x0800 + (t >> 2) + (reader.byte()? << 2);
+				reader.copy_match(distance, 3)?;
+			},
+
+			0..=15 => {
+				let distance = 1 + (t >> 2) + (reader.byte()? << 2);
+				reader.copy_match(distance, 2)?;
+			},
+
+			32..=63 => {
+				let length = match t & 31 { 0 => reader.extended(31)?, l => l };
+				let distance = 1 + (reader.le16()? >> 2);
+				reader.copy_match(distance, length + 2)?;
+			},
+
+			16..=31 => {
+				let length = match t & 7 { 0 => reader.extended(7)?, l => l };
+				let distance = ((t & 8) << 11) + (reader.le16()? >> 2);
+
+				if distance == 0 {
+					break;
+				};
+
+				reader.copy_match(distance + 0x4000, length + 2)?;
+			},
+
+			// 64 and above
+			_ => {
+				let distance = 1 + ((t >> 2) & 7) + (reader.byte()? << 3);
+				reader.copy_match(distance, (t >> 5) + 1)?;
+			},
+		};
+
+		// The low bits of the next-to-last byte read count the literals trailing
+		// the match
+		state = usize::from(input[reader.ip - 2] & 3);
+
+		if state > 0 {
+			reader.literals(state)?;
+		};
+
+		t = reader.byte()?;
+	};
+
+	if reader.ip != input.len() {
+		return Err(Lzo1xError::InputNotConsumed.into());
+	};
+
+	Ok(reader.out)
+}
+
+
+/// Compress `input` to LZO1X as a single literal run.  The output is slightly
+/// larger than `input`, but any LZO1X decompressor reads it back.
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+	let mut output = Vec::with_capacity(input.len() + 8);
+
+	match input.len() {
+		0 => (),
+		n @ 1..=238 => output.push((n + 17) as u8),
+
+		// Instruction 0 is a run of 18 literals, plus 255 for every zero byte
+		// that follows, plus the first non-zero byte
+		n => {
+			let remainder = n - 18;
+			let zeros = (remainder - 1) / 255;
+			output.push(0);
+			output.resize(output.len() + zeros, 0);
+			#[allow(clippy::cast_possible_truncation)]
+			output.push((remainder - zeros * 255) as u8);
+		},
+	};
+
+	output.extend_from_slice(input);
+
+	// End of stream: an M4 match at distance 0
+	output.extend_from_slice(&[0x11, 0x00, 0x00]);
+	output
+}
+
+
+#[test]
+fn lzo1x_roundtrip() {
+	for length in [0, 1, 3, 4, 238, 239, 255, 273, 274, 528, 529, 70_000] {
+		let input = (0..length).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+		let compressed = compress(&input);
+		assert_eq!(decompress(&compressed, length).unwrap(), input, "length {length}");
+	};
+
+	assert!(decompress(&compress(&[1, 2, 3, 4, 5]), 4).is_err());
+	assert!(decompress(&compress(&[1, 2, 3, 4, 5])[..7], 5).is_err());
+	assert!(decompress(&[0x11, 0x00, 0x00, 0x00], 0).is_err());
+}
+
+
+#[test]
+fn lzo1x_decompress_matches() {
+	// 4 literals, an M2 match of 3 bytes at distance 4 followed by 2
+	// literals, an M1 match of 2 bytes at distance 1, then the end
+	let compressed = [21, b'a', b'b', b'c', b'd', (2 << 5) | (3 << 2) | 2, 0, b'e', b'f', 0, 0, 0x11, 0x00, 0x00];
+	assert_eq!(decompress(&compressed, 64).unwrap(), b"abcdabcefff");
+
+	// A match reaching before the start of the output
+	assert!(decompress(&[21, b'a', b'b', b'c', b'd', (2 << 5) | (7 << 2), 0, 0x11, 0x00, 0x00], 64).is_err());
+}
+
+
+#[cfg(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32"))))]
+#[test]
+fn lzo1x_minilzo_interop() {
+	let input = (0..100_000u32).map(|i| (i / 64 % 7 + i % 3) as u8).collect::<Vec<_>>();
+
+	let mut lzo = minilzo_rs::LZO::init().unwrap();
+	let compressed = lzo.compress(&input).unwrap();
+	assert!(compressed.len() < input.len() / 4);
+	assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+
+	assert_eq!(lzo.decompress_safe(&compress(&input), input.len()).unwrap(), input);
+}
@@ -1,8 +1,9 @@
-macro_rules! log {
+macro_rules! log_if_enabled {
 	($fn:ident, $($arg:tt)*) => {
 		#[cfg(feature = "log")]
-		log::$fn!($($arg)*);
+		::log::$fn!($($arg)*);
 	}
 }
 
-pub(crate) use log;
+// Renamed on import, so as not to be ambiguous with the `log` crate
+pub(crate) use log_if_enabled as log;
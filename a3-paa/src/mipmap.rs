@@ -2,24 +2,25 @@ use std::fmt::Debug;
 use std::io::{Read, Seek, SeekFrom, Cursor};
 use std::iter::Extend;
 use std::default::Default;
+use std::str::FromStr;
 
 #[cfg(feature = "arbitrary")] use arbitrary::{Arbitrary, Unstructured, Result as ArbitraryResult};
-use byteorder::{LittleEndian, ByteOrder, ReadBytesExt};
-use image::RgbaImage;
-use texpresso::Format as TextureFormat;
+use byteorder::{LittleEndian, ByteOrder};
+#[cfg(feature = "image")] use image::{ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+#[cfg(feature = "image")] use crate::codec::{DxtCodec, DxtQuality, TexpressoCodec};
 use static_assertions::const_assert;
 use surety::Ensure;
 use bohemia_compression::*;
 
 
-use crate::PaaResult;
+use crate::{PaaResult, SerializeOptions};
 use crate::PaaError::*;
 use crate::PaaType;
 use crate::get_additive_i32_cksum;
-use crate::ReadExt;
 use crate::ExtendExt;
 use crate::pixel::*;
 use crate::macros;
+use crate::sansio::{self, MipmapHead};
 #[cfg(doc)] use crate::PaaImage;
 
 
@@ -36,12 +37,22 @@ pub struct PaaMipmap {
 	pub compression: PaaMipmapCompression,
 	/// Uncompressed [`paatype`][`Self::paatype`]-encoded image data.
 	pub data: Vec<u8>,
+	/// Compressed data of a [`PaaType::IndexPalette`] mipmap as read, which
+	/// [`to_bytes`][`Self::to_bytes`] writes back instead of recompressing
+	/// [`data`][`Self::data`], for as long as it still decompresses to it
+	/// with [`compression`][`Self::compression`].  Always [`None`] for other
+	/// types.
+	pub payload: Option<Vec<u8>>,
 }
 
 
 impl PaaMipmap {
 	/// Attempt to read the mipmap from a [`Read`].
 	///
+	/// LZSS [`PaaType::IndexPalette`] mipmaps without the length of their
+	/// data, as in Operation Flashpoint-era files, are read too.  Telling
+	/// them apart may read past the end of the mipmap.
+	///
 	/// # Errors
 	/// - [`EmptyMipmap`]: Width or height of the mipmap is 0.
 	/// - [`UnexpectedEof`]: [`std::io::Read::read_exact()`] or
@@ -61,54 +72,65 @@ impl PaaMipmap {
 	///
 	/// [`Read`]: std::io::Read
 	pub fn read_from<R: Read>(input: &mut R, paatype: PaaType) -> PaaResult<Self> {
-		use PaaType::*;
-		use PaaMipmapCompression::*;
+		Self::read_from_with_policy(input, paatype, CompressionPolicy::default())
+	}
 
-		let mut paatype = paatype;
-		let mut compression = Uncompressed;
 
-		let mut width = input.read_u16::<LittleEndian>()?;
-		let mut height = input.read_u16::<LittleEndian>()?;
+	/// [`read_from`][`Self::read_from`], telling the compression of the
+	/// mipmap apart as set by `policy`.
+	///
+	/// # Errors
+	/// - As [`read_from`][`Self::read_from`].
+	///
+	/// # Panics
+	/// - As [`read_from`][`Self::read_from`].
+	pub fn read_from_with_policy<R: Read>(input: &mut R, paatype: PaaType, policy: CompressionPolicy) -> PaaResult<Self> {
+		let (head, payload, _) = Self::fetch_from(input, paatype, policy)?;
+		Self::from_head(head, payload)
+	}
 
-		if width == 0 || height == 0 {
-			return Err(EmptyMipmap);
-		};
 
-		if width == 1234 && height == 8765 {
-			paatype = IndexPalette;
-			compression = Lzss;
+	/// Read the header and payload of the mipmap at the position of `input`,
+	/// without decompressing it, along with the bytes it takes in `input`.
+	pub(crate) fn fetch_from<R: Read>(input: &mut R, paatype: PaaType, policy: CompressionPolicy) -> PaaResult<(MipmapHead, Vec<u8>, usize)> {
+		let mut buf = vec![];
+		let ((head, start), end) = sansio::drive(input, &mut buf, |buf, eof| sansio::parse_mipmap(buf, eof, paatype, policy))?;
+		buf.truncate(end);
+		let payload = buf.split_off(start);
 
-			width = input.read_u16::<LittleEndian>()?;
-			height = input.read_u16::<LittleEndian>()?;
-		};
+		Ok((head, payload, end))
+	}
 
-		if width & 0x8000 != 0 && paatype.is_dxtn() {
-			compression = Lzo;
-			width ^= 0x8000;
-		};
 
-		const_assert!(std::mem::size_of::<usize>() >= 3);
-		let data_len = paatype.predict_size(width, height);
-		#[allow(clippy::cast_possible_truncation)]
-		let data_compressed_len = input.read_uint::<LittleEndian>(3)? as usize;
+	/// Decompress `payload`, the data following the mipmap header `head`.
+	pub(crate) fn from_head(head: MipmapHead, payload: Vec<u8>) -> PaaResult<Self> {
+		use PaaMipmapCompression::*;
 
-		if matches!(paatype, IndexPalette) && !matches!(compression, Lzss) {
-			compression = RleBlocks;
-		}
-		else if matches!(compression, Uncompressed) && data_len != data_compressed_len && !paatype.is_dxtn() {
-			compression = Lzss;
+		let MipmapHead { width, height, paatype, compression, data_len, .. } = head;
+
+		let (data, payload) = match compression {
+			Uncompressed => (payload, None),
+			_ if paatype == PaaType::IndexPalette => (Self::decompress_payload(compression, &payload, data_len)?, Some(payload)),
+			_ => (Self::decompress_payload(compression, &payload, data_len)?, None),
 		};
 
-		let compressed_data_buf: Vec<u8> = input.read_exact_buffered(data_compressed_len)?;
+		Ok(PaaMipmap { width, height, paatype, compression, data, payload })
+	}
+
 
-		let data: Vec<u8> = match compression {
-			Uncompressed => compressed_data_buf,
+	/// Decompress `payload`, the compressed data of a mipmap as stored in a
+	/// file (with the checksum of LZSS data), to `data_len` bytes.
+	fn decompress_payload(compression: PaaMipmapCompression, payload: &[u8], data_len: usize) -> PaaResult<Vec<u8>> {
+		use PaaMipmapCompression::*;
+
+		let data = match compression {
+			Uncompressed => payload.to_vec(),
 
-			Lzo => Lzo.decompress_slice(&compressed_data_buf[..], data_len)?,
+			Lzo => Lzo.decompress_slice(payload, data_len)?,
 
 			Lzss => {
-				let split_pos = compressed_data_buf.len().checked_sub(4).ok_or(ArithmeticOverflow)?;
-				let (lzss_slice, checksum_slice) = compressed_data_buf.split_at(split_pos);
+				let split_pos = payload.len().checked_sub(4).ok_or(ArithmeticOverflow)?;
+				let (lzss_slice, checksum_slice) = payload.split_at(split_pos);
 				let checksum = LittleEndian::read_i32(checksum_slice);
 				let uncompressed_data = LzssReader::new().filter_slice_to_vec(lzss_slice).unwrap();
 
@@ -126,10 +148,10 @@ impl PaaMipmap {
 				uncompressed_data
 			},
 
-			RleBlocks => RleReader::new().filter_slice_to_vec(&compressed_data_buf[..]).map_err(RleError)?,
+			RleBlocks => RleReader::new().filter_slice_to_vec(payload).map_err(RleError)?,
 		};
 
-		Ok(PaaMipmap { width, height, paatype, compression, data })
+		Ok(data)
 	}
 
 
@@ -197,6 +219,21 @@ impl PaaMipmap {
 	/// - If [`bohemia_compression::RleWriter::filter_slice_to_vec()`] fails
 	///   (should never happen).
 	pub fn to_bytes(&self) -> PaaResult<Vec<u8>> {
+		self.to_bytes_with_options(SerializeOptions::default())
+	}
+
+
+	/// [`to_bytes`][`Self::to_bytes`], without the length of the data of an
+	/// LZSS [`PaaType::IndexPalette`] mipmap if
+	/// [`options.legacy_lzss_layout`][`SerializeOptions::legacy_lzss_layout`].
+	/// Other options concern the header of a [`PaaImage`], and are ignored.
+	///
+	/// # Errors
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	///
+	/// # Panics
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	pub fn to_bytes_with_options(&self, options: SerializeOptions) -> PaaResult<Vec<u8>> {
 		use PaaType::*;
 		use PaaMipmapCompression::*;
 
@@ -236,24 +273,41 @@ impl PaaMipmap {
 		if let (Lzss { .. }, IndexPalette) = (&self.compression, &self.paatype) {
 			bytes.extend_with_uint::<LittleEndian, _, 2>(self.width);
 			bytes.extend_with_uint::<LittleEndian, _, 2>(self.height);
-
-			// [TODO] Does the mipmap code on Biki mean that index palette lzss
-			// data does not have `byte size[3]`?  I'm thinking probably not but
-			// this needs to be tested on old PACs
 		};
 
 		let mut compressed_data: Vec<u8> = Vec::with_capacity(std::cmp::min(self.data.len() * 2, 128));
 
-		let data = self.compression.compress_slice(&self.data[..])?;
-		compressed_data.extend(data);
+		// The payload read from the file, unless `data` or `compression` changed
+		let payload = self.payload.as_deref().filter(|p| {
+			Self::decompress_payload(self.compression, p, self.data.len()).map_or(false, |d| d == self.data)
+		});
+
+		if let Some(payload) = payload {
+			compressed_data.extend(payload);
+		}
+		else {
+			let data = self.compression.compress_slice(&self.data[..])?;
+			compressed_data.extend(data);
+
+			if self.compression == PaaMipmapCompression::Lzss {
+				let cksum = get_additive_i32_cksum(&self.data[..]);
+				let mut buf = [0u8; 4];
+				LittleEndian::write_i32(&mut buf, cksum);
+				compressed_data.extend(buf);
+			};
+		};
 
-		if self.compression == PaaMipmapCompression::Lzss {
-			let cksum = get_additive_i32_cksum(&self.data[..]);
-			let mut buf = [0u8; 4];
-			LittleEndian::write_i32(&mut buf, cksum);
-			compressed_data.extend(buf);
+		// Readers take non-DXTn data as long as the uncompressed data for
+		// uncompressed, which it might as well be
+		if self.compression == Lzss && self.paatype != IndexPalette && compressed_data.len() == self.data.len() {
+			compressed_data.clear();
+			compressed_data.extend(&self.data);
 		};
 
+		macros::log!(trace, "PaaMipmap::to_bytes: {}x{} {:?}, {} bytes compressed to {} ({:.2}x)",
+			self.width, self.height, self.compression, self.data.len(), compressed_data.len(),
+			self.data.len() as f64 / compressed_data.len().max(1) as f64);
+
 		const_assert!(std::mem::size_of::<usize>() >= 4);
 
 		#[allow(clippy::cast_possible_truncation)]
@@ -261,8 +315,14 @@ impl PaaMipmap {
 			return Err(MipmapTooLarge);
 		};
 
+		// Operation Flashpoint-era LZSS data runs up to its expanded length
+		let legacy = options.legacy_lzss_layout && (self.compression, self.paatype) == (Lzss, IndexPalette);
+
 		#[allow(clippy::cast_possible_truncation)]
-		bytes.extend_with_uint::<LittleEndian, u32, 3>(compressed_data.len() as u32);
+		if !legacy {
+			bytes.extend_with_uint::<LittleEndian, u32, 3>(compressed_data.len() as u32);
+		};
+
 		bytes.extend(&compressed_data[..]);
 
 		Ok(bytes.into_iter().collect::<Vec<u8>>())
@@ -293,8 +353,46 @@ impl PaaMipmap {
 	}
 
 
+	/// Compression this crate would choose for `self` if it re-encoded it
+	/// (see [`suggest_compression`][`Self::suggest_compression`]).
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::{PaaMipmap, PaaMipmapCompression, PaaType};
+	/// let mipmap = PaaMipmap { width: 512, height: 512, paatype: PaaType::Dxt5, ..Default::default() };
+	/// assert_eq!(mipmap.expected_compression(), PaaMipmapCompression::Lzo);
+	/// ```
+	pub fn expected_compression(&self) -> PaaMipmapCompression {
+		Self::suggest_compression(self.paatype, self.width, self.height)
+	}
+
+
+	/// Return true if [`compression`][`Self::compression`] is the
+	/// [`expected_compression`][`Self::expected_compression`], or data that
+	/// LZSS did not shrink, which [`to_bytes`][`Self::to_bytes`] stores as
+	/// is, and which reads back as uncompressed.
+	pub fn has_expected_compression(&self) -> bool {
+		use PaaMipmapCompression::*;
+
+		match (self.expected_compression(), self.compression) {
+			(expected, compression) if expected == compression => true,
+			(Lzss, Uncompressed) => self.paatype != PaaType::IndexPalette,
+			_ => false,
+		}
+	}
+
+
 	/// Attempt to decode `self` into an [`image::RgbaImage`].
+	#[cfg(feature = "image")]
 	pub(crate) fn decode(&self) -> PaaResult<RgbaImage> {
+		self.decode_with(&TexpressoCodec)
+	}
+
+
+	/// Attempt to decode `self` into an [`image::RgbaImage`], decompressing
+	/// DXTn data with `codec`.
+	#[cfg(feature = "image")]
+	pub(crate) fn decode_with(&self, codec: &dyn DxtCodec) -> PaaResult<RgbaImage> {
 		use PaaType::*;
 
 		if self.is_empty() {
@@ -303,21 +401,20 @@ impl PaaMipmap {
 
 		match self.paatype {
 			paatype if paatype.is_dxtn() => {
-				#[allow(clippy::match_same_arms)]
-				let (comp_ratio, format) = match &paatype {
-					Dxt1 => (8, TextureFormat::Bc1),
-					Dxt2 => (4, TextureFormat::Bc2),
-					Dxt3 => (4, TextureFormat::Bc2),
-					Dxt4 => (4, TextureFormat::Bc3),
-					Dxt5 => (4, TextureFormat::Bc3),
-					_ => unreachable!(),
-				};
-
+				let comp_ratio = if paatype == Dxt1 { 8 } else { 4 };
 				let buf_len = self.data.len()
 					.checked_mul(comp_ratio)
 					.ok_or(MipmapTooLarge)?;
 				let mut buffer = vec![0u8; buf_len];
-				format.decompress(&self.data, self.width.into(), self.height.into(), &mut buffer);
+				let (width, height) = (usize::from(self.width), usize::from(self.height));
+
+				match paatype {
+					Dxt1 => codec.decompress_bc1(&self.data, width, height, width * 4, &mut buffer),
+					Dxt2 | Dxt3 => codec.decompress_bc2(&self.data, width, height, width * 4, &mut buffer),
+					Dxt4 | Dxt5 => codec.decompress_bc3(&self.data, width, height, width * 4, &mut buffer),
+					_ => unreachable!(),
+				};
+
 				let image = RgbaImage::from_vec(self.width.into(), self.height.into(), buffer).unwrap();
 				Ok(image)
 			},
@@ -334,14 +431,154 @@ impl PaaMipmap {
 				Ok(image)
 			},
 
-			f => todo!("Pixel format not yet implemented: {:?}", f),
+			Argb8888 => {
+				if self.data.len() != Argb8888.predict_size(self.width, self.height) {
+					return Err(UnexpectedMipmapDataSize(self.width, self.height, self.data.len()));
+				};
+
+				let data = self.data.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+				let image = RgbaImage::from_vec(self.width.into(), self.height.into(), data).unwrap();
+				Ok(image)
+			},
+
+			Ai88 => {
+				if self.data.len() != Ai88.predict_size(self.width, self.height) {
+					return Err(UnexpectedMipmapDataSize(self.width, self.height, self.data.len()));
+				};
+
+				let data = self.data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect();
+				let image = RgbaImage::from_vec(self.width.into(), self.height.into(), data).unwrap();
+				Ok(image)
+			},
+
+			f => Err(UnsupportedPaaType(f)),
 		}
 	}
 
 
-	pub(crate) fn encode(paatype: PaaType, image: &image::RgbaImage) -> PaaResult<Self> {
+	/// Attempt to decode `self` at 16 bits per channel.  ARGB4444 and
+	/// ARGB1555 channels are rescaled to the full 16-bit range, and DXTn
+	/// colors are interpolated from their endpoints at 16 bits, so no
+	/// precision is lost to an intermediate 8-bit image.  Other types are
+	/// decoded at 8 bits and widened.
+	///
+	/// # Errors
+	/// - [`EmptyMipmap`]: Either dimension is 0.
+	/// - [`UnexpectedMipmapDataSize`]: Mipmap data is not of
+	///   [`PaaType::predict_size`].
+	/// - [`UnsupportedPaaType`]: `self` is [`PaaType::IndexPalette`], which
+	///   cannot be decoded yet.
+	///
+	/// # Panics
+	/// - If the decoded data does not fill the dimensions of `self` (should
+	///   never happen).
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn decode_rgba16(&self) -> PaaResult<ImageBuffer<Rgba<u16>, Vec<u16>>> {
 		use PaaType::*;
 
+		if self.is_empty() {
+			return Err(EmptyMipmap);
+		};
+
+		if self.data.len() != self.paatype.predict_size(self.width, self.height) {
+			return Err(UnexpectedMipmapDataSize(self.width, self.height, self.data.len()));
+		};
+
+		let (width, height) = (usize::from(self.width), usize::from(self.height));
+
+		let data = match self.paatype {
+			paatype if paatype.is_dxtn() => {
+				let (columns, _) = self.block_dimensions();
+				let mut data = vec![0u16; width * height * 4];
+
+				for (i, block) in self.blocks()?.enumerate() {
+					let (bx, by) = (i % columns * 4, i / columns * 4);
+
+					for (t, texel) in crate::blocks::decode_block_rgba16(paatype, block).iter().enumerate() {
+						let (x, y) = (bx + t % 4, by + t / 4);
+
+						if x < width && y < height {
+							let start = (y * width + x) * 4;
+							data[start..start + 4].copy_from_slice(texel);
+						};
+					};
+				};
+
+				data
+			},
+
+			Argb4444 => Argb4444Pixel::convert_to_rgba16_slice(&self.data)?,
+			Argb1555 => Argb1555Pixel::convert_to_rgba16_slice(&self.data)?,
+			_ => self.decode()?.into_raw().into_iter().map(|c| u16::from(c) * 257).collect(),
+		};
+
+		Ok(ImageBuffer::from_vec(self.width.into(), self.height.into(), data).unwrap())
+	}
+
+
+	/// Attempt to decode `self` with channels in `0.0..=1.0`, as
+	/// [`decode_rgba16`][`Self::decode_rgba16`].
+	///
+	/// # Errors
+	/// - As [`decode_rgba16`][`Self::decode_rgba16`].
+	///
+	/// # Panics
+	/// - As [`decode_rgba16`][`Self::decode_rgba16`].
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn decode_rgba32f(&self) -> PaaResult<Rgba32FImage> {
+		Ok(image::DynamicImage::ImageRgba16(self.decode_rgba16()?).into_rgba32f())
+	}
+
+
+	/// Encode `image` into a mipmap of type `paatype`, without resizing it.
+	///
+	/// # Errors
+	/// - [`MipmapTooLarge`]: Either dimension of `image` overflows a [`u16`].
+	/// - [`DxtMipmapDimensionsNotMultipleOf4`]: `paatype` is DXTn and either
+	///   dimension of `image` is not a multiple of 4.
+	/// - [`UnsupportedPaaType`]: `paatype` is [`PaaType::IndexPalette`], which
+	///   cannot be encoded yet.
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn encode(paatype: PaaType, image: &image::RgbaImage) -> PaaResult<Self> {
+		Self::encode_with(paatype, image, &TexpressoCodec, DxtQuality::default())
+	}
+
+
+	/// Encode `image` into a mipmap of type `paatype`, without resizing it,
+	/// compressing DXTn data with `codec` at `quality`.
+	///
+	/// # Errors
+	/// - As [`encode`][`Self::encode`].
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn encode_with(paatype: PaaType, image: &image::RgbaImage, codec: &dyn DxtCodec, quality: DxtQuality) -> PaaResult<Self> {
+		Self::encode_with_alpha1(paatype, image, codec, quality, Alpha1Mode::default())
+	}
+
+
+	/// As [`encode_with`][`Self::encode_with`], reducing alpha to the single
+	/// bit of [`PaaType::Argb1555`] and punch-through [`PaaType::Dxt1`] as
+	/// set by `alpha1`.  Other types ignore it.
+	///
+	/// # Errors
+	/// - As [`encode`][`Self::encode`].
+	#[cfg(feature = "image")]
+	#[cfg_attr(doc, doc(cfg(feature = "image")))]
+	pub fn encode_with_alpha1(paatype: PaaType, image: &image::RgbaImage, codec: &dyn DxtCodec, quality: DxtQuality, alpha1: Alpha1Mode) -> PaaResult<Self> {
+		use PaaType::*;
+
+		let binary;
+		let image = if matches!(paatype, Dxt1 | Argb1555) {
+			binary = alpha1.apply(image);
+			&binary
+		}
+		else {
+			image
+		};
+
 		let (w, h) = image.dimensions();
 		let width: u16 = w.try_into().map_err(|_| MipmapTooLarge)?;
 		let height: u16 = h.try_into().map_err(|_| MipmapTooLarge)?;
@@ -349,37 +586,53 @@ impl PaaMipmap {
 
 		match paatype {
 			t if t.is_dxtn() => {
-				let textureformat = match t {
-					Dxt1 => TextureFormat::Bc1,
-					Dxt2 | Dxt3 => TextureFormat::Bc2,
-					Dxt4 | Dxt5 => TextureFormat::Bc3,
-					_ => unreachable!(),
-				};
-
 				if width % 4 != 0 || height % 4 != 0 {
 					return Err(DxtMipmapDimensionsNotMultipleOf4(width, height));
 				};
 
-				let mut data: Vec<u8> = vec![0; textureformat.compressed_size(width.into(), height.into())];
-				let params = texpresso::Params { algorithm: texpresso::Algorithm::IterativeClusterFit, ..Default::default() };
-				textureformat.compress(image.as_raw(), width.into(), height.into(), params, &mut data);
-				let mipmap = PaaMipmap { width, height, paatype, compression, data };
+				let (w, h) = (usize::from(width), usize::from(height));
+				let block_size = if t == Dxt1 { 8 } else { 16 };
+				let mut data: Vec<u8> = vec![0; (w / 4) * (h / 4) * block_size];
+
+				match t {
+					Dxt1 => codec.compress_bc1(image.as_raw(), w, h, w * 4, quality, &mut data),
+					Dxt2 | Dxt3 => codec.compress_bc2(image.as_raw(), w, h, w * 4, quality, &mut data),
+					Dxt4 | Dxt5 => codec.compress_bc3(image.as_raw(), w, h, w * 4, quality, &mut data),
+					_ => unreachable!(),
+				};
+
+				let mipmap = PaaMipmap { width, height, paatype, compression, data, payload: None };
 				Ok(mipmap)
 			},
 
 			Argb1555 => {
 				let data = Argb1555Pixel::convert_from_rgba8_slice(image.as_raw())?;
-				let mipmap = PaaMipmap { width, height, paatype, compression, data };
+				let mipmap = PaaMipmap { width, height, paatype, compression, data, payload: None };
 				Ok(mipmap)
 			},
 
 			Argb4444 => {
 				let data = Argb4444Pixel::convert_from_rgba8_slice(image.as_raw())?;
-				let mipmap = PaaMipmap { width, height, paatype, compression, data };
+				let mipmap = PaaMipmap { width, height, paatype, compression, data, payload: None };
+				Ok(mipmap)
+			},
+
+			Argb8888 => {
+				let data = image.pixels().flat_map(|p| [p.0[2], p.0[1], p.0[0], p.0[3]]).collect();
+				let mipmap = PaaMipmap { width, height, paatype, compression, data, payload: None };
 				Ok(mipmap)
 			},
 
-			t => todo!("PaaMipmap::encode: PaaType not yet implemented: {:?}", t),
+			Ai88 => {
+				// Rec. 601 luma
+				#[allow(clippy::cast_possible_truncation)]
+				let intensity = |[r, g, b, _]: [u8; 4]| ((299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b) + 500) / 1000) as u8;
+				let data = image.pixels().flat_map(|p| [intensity(p.0), p.0[3]]).collect();
+				let mipmap = PaaMipmap { width, height, paatype, compression, data, payload: None };
+				Ok(mipmap)
+			},
+
+			t => Err(UnsupportedPaaType(t)),
 		}
 	}
 
@@ -399,7 +652,7 @@ impl Default for PaaMipmap {
 		let paatype = PaaType::Dxt5;
 		let compression = PaaMipmap::suggest_compression(paatype, width, height);
 		let data = vec![];
-		PaaMipmap { width, height, paatype, compression, data }
+		PaaMipmap { width, height, paatype, compression, data, payload: None }
 	}
 }
 
@@ -453,7 +706,7 @@ impl<'a> Arbitrary<'a> for PaaMipmap {
 		let mut data = vec![0u8; data_len];
 		input.fill_buffer(&mut data)?;
 
-		Ok(Self { width, height, paatype, compression, data })
+		Ok(Self { width, height, paatype, compression, data, payload: None })
 	}
 }
 
@@ -461,6 +714,7 @@ impl<'a> Arbitrary<'a> for PaaMipmap {
 /// The algorithm compressing the data of a given mipmap
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PaaMipmapCompression {
 	/// Data is stored as-is.
 	Uncompressed,
@@ -475,6 +729,34 @@ pub enum PaaMipmapCompression {
 
 
 impl PaaMipmapCompression {
+	/// Every [`PaaMipmapCompression`], in declaration order.
+	pub const ALL: &'static [PaaMipmapCompression] = {
+		use PaaMipmapCompression::*;
+		&[Uncompressed, Lzo, Lzss, RleBlocks]
+	};
+
+
+	/// Return the short name of the compression, which [`FromStr`] parses
+	/// back.
+	///
+	/// # Example
+	/// ```
+	/// # use a3_paa::PaaMipmapCompression;
+	/// assert_eq!(PaaMipmapCompression::RleBlocks.as_str(), "rle");
+	/// assert_eq!("RLE".parse(), Ok(PaaMipmapCompression::RleBlocks));
+	/// ```
+	pub const fn as_str(&self) -> &'static str {
+		use PaaMipmapCompression::*;
+
+		match self {
+			Uncompressed => "uncompressed",
+			Lzo => "lzo",
+			Lzss => "lzss",
+			RleBlocks => "rle",
+		}
+	}
+
+
 	/// # Errors
 	/// - [`LzoError`]: failed to compress input as LZO.
 	/// - [`RleError`]: `RleReader` failed to compress `input` as RLE.
@@ -486,10 +768,7 @@ impl PaaMipmapCompression {
 		use PaaMipmapCompression::*;
 		match self {
 			Uncompressed => Ok(input.to_vec()),
-			Lzo => {
-				let mut lzo = minilzo_rs::LZO::init().unwrap();
-				lzo.compress(input).map_err(|e| LzoError(format!("{:?}", e)))
-			},
+			Lzo => lzo_compress(input),
 			Lzss => {
 				macros::log!(trace, "LZSS compression");
 				let data = LzssWriter::new().filter_slice_to_vec(input).unwrap();
@@ -509,12 +788,218 @@ impl PaaMipmapCompression {
 		use PaaMipmapCompression::*;
 		match self {
 			Uncompressed => Ok(input.to_vec()),
-			Lzo => {
-				let lzo = minilzo_rs::LZO::init().unwrap();
-				lzo.decompress_safe(input, dst_len).map_err(|e| LzoError(format!("{:?}", e)))
-			},
+			Lzo => lzo_decompress(input, dst_len),
 			Lzss => LzssReader::new().filter_slice_to_vec(input).map_err(|_| LzssDecompressError),
 			RleBlocks => RleReader::new().filter_slice_to_vec(input).map_err(RleError),
 		}
 	}
 }
+
+
+impl FromStr for PaaMipmapCompression {
+	type Err = ();
+
+	/// Parse [`as_str`][`PaaMipmapCompression::as_str`] or the variant name,
+	/// ignoring case.
+	fn from_str(input: &str) -> Result<Self, <Self as FromStr>::Err> {
+		use PaaMipmapCompression::*;
+
+		let normalized = input.to_lowercase();
+
+		match normalized.as_str() {
+			"uncompressed" => Ok(Uncompressed),
+			"lzo" => Ok(Lzo),
+			"lzss" => Ok(Lzss),
+			"rle" | "rleblocks" => Ok(RleBlocks),
+			_ => Err(()),
+		}
+	}
+}
+
+
+impl std::fmt::Display for PaaMipmapCompression {
+	/// Writes [`as_str`][`PaaMipmapCompression::as_str`].
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+
+/// How [`PaaMipmap::read_from_with_policy`] tells the compression of a
+/// mipmap apart, where its header does not say
+///
+/// Headers only flag LZO (on DXTn) and LZSS (on
+/// [`PaaType::IndexPalette`]); other mipmaps are taken as LZSS if their
+/// stored length differs from [`PaaType::predict_size`].  The default policy
+/// accepts the combinations the engine writes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+	/// Read DXTn mipmaps without the LZO flag whose stored length differs
+	/// from [`PaaType::predict_size`] as LZSS, as some third-party packers
+	/// write them, rather than as uncompressed data of the wrong length.
+	pub dxtn_lzss: bool,
+}
+
+
+impl CompressionPolicy {
+	/// Policy accepting every combination of [`PaaType`] and
+	/// [`PaaMipmapCompression`] that decodes.
+	pub const LENIENT: Self = Self { dxtn_lzss: true };
+}
+
+
+/// How 8-bit alpha is reduced to the single bit of [`PaaType::Argb1555`] and
+/// punch-through [`PaaType::Dxt1`] (see [`PaaMipmap::encode_with_alpha1`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alpha1Mode {
+	/// Opaque at or above the threshold, transparent below it.  The default
+	/// threshold, 128, is the midpoint that rescaling alpha rounds at.
+	Threshold(u8),
+	/// Opaque at or above a threshold picked for each mipmap, so that the
+	/// fraction of opaque pixels matches its mean alpha.  Keeps the coverage
+	/// of alpha-tested textures such as foliage, which a fixed threshold
+	/// thins out or thickens as mipmaps average soft edges.
+	PreserveCoverage,
+}
+
+
+impl Default for Alpha1Mode {
+	fn default() -> Self {
+		Self::Threshold(128)
+	}
+}
+
+
+impl Alpha1Mode {
+	/// Return `image` with alpha made 0 or 0xFF.
+	#[cfg(feature = "image")]
+	fn apply(self, image: &RgbaImage) -> RgbaImage {
+		let threshold = match self {
+			Self::Threshold(t) => u16::from(t),
+			Self::PreserveCoverage => coverage_threshold(image),
+		};
+
+		let mut binary = image.clone();
+
+		for pixel in binary.pixels_mut() {
+			pixel.0[3] = if u16::from(pixel.0[3]) >= threshold { 0xFF } else { 0 };
+		};
+
+		binary
+	}
+}
+
+
+/// Alpha threshold (256 for none opaque) that makes the number of opaque
+/// pixels of `image` nearest to its total alpha, breaking ties towards 128.
+#[cfg(feature = "image")]
+fn coverage_threshold(image: &RgbaImage) -> u16 {
+	let mut histogram = [0u64; 256];
+
+	for pixel in image.pixels() {
+		histogram[usize::from(pixel.0[3])] += 1;
+	};
+
+	let total_alpha: u64 = (0..=255).zip(histogram).map(|(a, n)| a * n).sum();
+
+	// Pixels at or above the threshold, from none (256) to all (0)
+	let mut opaque = 0u64;
+	let mut best = (u64::MAX, 256u16);
+
+	for threshold in (0..=256u16).rev() {
+		if let Some(n) = histogram.get(usize::from(threshold)) {
+			opaque += n;
+		};
+
+		let error = (opaque * 255).abs_diff(total_alpha);
+
+		if error < best.0 || (error == best.0 && threshold.abs_diff(128) < best.1.abs_diff(128)) {
+			best = (error, threshold);
+		};
+	};
+
+	best.1
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn alpha1_coverage() {
+	let coverage = |image: &RgbaImage| image.pixels().filter(|p| p.0[3] == 0xFF).count() as f64 / f64::from(image.width() * image.height());
+
+	// Soft edges: a quarter of the pixels at each of 0x00, 0x40, 0xC0 and 0xFF
+	let image = RgbaImage::from_fn(4, 4, |x, _| Rgba([0x80, 0x80, 0x80, [0x00, 0x40, 0xC0, 0xFF][x as usize]]));
+	assert_eq!(coverage(&Alpha1Mode::default().apply(&image)), 0.5);
+	assert_eq!(coverage(&Alpha1Mode::Threshold(0x41).apply(&image)), 0.5);
+	assert_eq!(coverage(&Alpha1Mode::Threshold(0x40).apply(&image)), 0.75);
+	assert_eq!(coverage(&Alpha1Mode::Threshold(0).apply(&image)), 1.0);
+	assert_eq!(coverage(&Alpha1Mode::PreserveCoverage.apply(&image)), 0.5);
+
+	// Mean alpha of a quarter at 0xFF is 25%
+	let sparse = RgbaImage::from_fn(4, 4, |x, _| Rgba([0, 0, 0, if x == 0 { 0xFF } else { 0x20 }]));
+	assert_eq!(coverage(&Alpha1Mode::default().apply(&sparse)), 0.25);
+	let faint = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0x60]));
+	assert_eq!(coverage(&Alpha1Mode::default().apply(&faint)), 0.0);
+	assert_eq!(coverage(&Alpha1Mode::PreserveCoverage.apply(&faint)), 0.0);
+	assert_eq!(coverage(&Alpha1Mode::PreserveCoverage.apply(&RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0xFF])))), 1.0);
+
+	// Argb1555 and DXT1 store the bit as set
+	for paatype in [PaaType::Argb1555, PaaType::Dxt1] {
+		let mipmap = PaaMipmap::encode_with_alpha1(paatype, &image, &TexpressoCodec, DxtQuality::Fast, Alpha1Mode::Threshold(0x30)).unwrap();
+		assert_eq!(coverage(&mipmap.decode().unwrap()), 0.75, "{paatype:?}");
+	};
+
+	// The default threshold is the one rescaling alpha to a single bit rounds at
+	let rescaled = Argb1555Pixel::convert_from_rgba8_slice(image.as_raw()).unwrap();
+	assert_eq!(PaaMipmap::encode(PaaType::Argb1555, &image).unwrap().data, rescaled);
+}
+
+
+#[test]
+fn compression_names() {
+	assert_eq!(PaaMipmapCompression::ALL.len(), 4);
+
+	for compression in PaaMipmapCompression::ALL {
+		assert_eq!(compression.to_string().parse::<PaaMipmapCompression>(), Ok(*compression));
+		assert_eq!(compression.as_str().to_uppercase().parse::<PaaMipmapCompression>(), Ok(*compression));
+		assert_eq!(format!("{compression:?}").parse::<PaaMipmapCompression>(), Ok(*compression));
+	};
+
+	assert_eq!("lz".parse::<PaaMipmapCompression>(), Err(()));
+}
+
+
+/// Error of minilzo, the source of a [`LzoError`]
+#[cfg(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32"))))]
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+#[display(fmt = "{:?}", _0)]
+struct MinilzoError(#[error(ignore)] minilzo_rs::Error);
+
+
+#[cfg(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32"))))]
+fn lzo_compress(input: &[u8]) -> PaaResult<Vec<u8>> {
+	let mut lzo = minilzo_rs::LZO::init().unwrap();
+	lzo.compress(input).map_err(|e| LzoError(crate::ErrorSource::new(MinilzoError(e))))
+}
+
+
+#[cfg(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32"))))]
+fn lzo_decompress(input: &[u8], dst_len: usize) -> PaaResult<Vec<u8>> {
+	let lzo = minilzo_rs::LZO::init().unwrap();
+	lzo.decompress_safe(input, dst_len).map_err(|e| LzoError(crate::ErrorSource::new(MinilzoError(e))))
+}
+
+
+/// Without minilzo, LZO data is written as literal runs rather than compressed
+#[cfg(not(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32")))))]
+#[allow(clippy::unnecessary_wraps)]
+fn lzo_compress(input: &[u8]) -> PaaResult<Vec<u8>> {
+	Ok(crate::lzo::compress(input))
+}
+
+
+#[cfg(not(all(feature = "minilzo", not(any(feature = "wasm", target_arch = "wasm32")))))]
+fn lzo_decompress(input: &[u8], dst_len: usize) -> PaaResult<Vec<u8>> {
+	crate::lzo::decompress(input, dst_len)
+}
@@ -0,0 +1,123 @@
+//! Changes to PAA files on disk that leave mipmaps untouched, to fix the
+//! metadata of large texture sets without rewriting them
+//!
+//! # Example
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use a3_paa::{PaaError, PaaImage, Tagg, Transparency};
+//!
+//! let tagg = Tagg::Flag { transparency: Transparency::AlphaNotInterpolated };
+//!
+//! match a3_paa::patch::replace_tagg("texture_ca.paa", &tagg) {
+//! 	Err(PaaError::TaggNotFound(_) | PaaError::TaggSizeMismatch(..)) => {
+//! 		let mut paa = PaaImage::from_bytes(&std::fs::read("texture_ca.paa")?)?;
+//! 		paa.taggs.retain(|t| t.as_taggname() != tagg.as_taggname());
+//! 		paa.taggs.push(tagg);
+//! 		std::fs::write("texture_ca.paa", paa.to_bytes_exact(false)?)?;
+//! 	},
+//!
+//! 	result => result?,
+//! };
+//! # Ok(()) }
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{PaaReader, PaaResult, Tagg};
+use crate::PaaError::*;
+
+
+/// Length of the signature, name and payload length preceding a tagg payload
+const TAGG_HEAD_LEN: usize = 12;
+
+
+/// Overwrite, in the PAA file at `path`, the payload of the tagg named as
+/// `tagg` with that of `tagg`.  The rest of the file is left as is.  If the
+/// name occurs more than once, the last tagg, which readers use, is replaced.
+///
+/// Only a payload of the same size can be replaced, which AVGC, MAXC, FLAG,
+/// SWIZ and OFFS payloads always are.  Otherwise, nothing is written, and the
+/// whole file must be rewritten instead.
+///
+/// # Errors
+/// - [`TaggNotFound`]: The file has no tagg of this name.
+/// - [`TaggSizeMismatch`]: The payload in the file is of another size.
+/// - [`UnexpectedTaggSignature`]: The tagg header is not found again at the
+///   offset recorded while reading the file, e.g. if it changed meanwhile.
+/// - [`UnexpectedIoError`]: The file could not be opened, read or written.
+/// - Any error of [`PaaReader::new`].
+pub fn replace_tagg(path: impl AsRef<Path>, tagg: &Tagg) -> PaaResult<()> {
+	let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+	replace_tagg_in(&mut file, tagg)?;
+	file.sync_data()?;
+	Ok(())
+}
+
+
+/// [`replace_tagg`] in `file`, which is left positioned after the replaced
+/// payload.
+///
+/// # Errors
+/// - As [`replace_tagg`].
+pub fn replace_tagg_in<F: Read + Write + Seek>(file: &mut F, tagg: &Tagg) -> PaaResult<()> {
+	let name = tagg.as_taggname();
+	let (offset, length) = PaaReader::new(&mut *file)?.layout().tagg_span(name).ok_or(TaggNotFound(name))?;
+
+	let bytes = tagg.to_bytes()?;
+	let (head, payload) = bytes.split_at(TAGG_HEAD_LEN);
+
+	if payload.len() as u64 != length {
+		return Err(TaggSizeMismatch(name, length, payload.len()));
+	};
+
+	// Signature, name and length must still be where they were read
+	let mut found = [0u8; TAGG_HEAD_LEN];
+	let _ = file.seek(SeekFrom::Start(offset - TAGG_HEAD_LEN as u64))?;
+	file.read_exact(&mut found)?;
+
+	if found != head {
+		return Err(UnexpectedTaggSignature);
+	};
+
+	file.write_all(payload)?;
+	file.flush()?;
+
+	Ok(())
+}
+
+
+#[test]
+fn replace_tagg_galf() {
+	use crate::{PaaError, PaaImage, Transparency};
+
+	let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/fixtures/checker_dxt1.paa");
+	let mut paa = PaaImage::from_bytes(&std::fs::read(fixture).unwrap()).unwrap();
+	paa.taggs.push(Tagg::Flag { transparency: Transparency::None });
+	paa.taggs.push(Tagg::Proc { code: crate::TextureMacro { text: "fresnel(1.3,7)".into() } });
+	let original = paa.to_bytes().unwrap();
+
+	let dir = std::env::temp_dir().join(format!("a3-paa-patch-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let path = dir.join("flag.paa");
+	std::fs::write(&path, &original).unwrap();
+
+	let flag = Tagg::Flag { transparency: Transparency::AlphaNotInterpolated };
+	replace_tagg(&path, &flag).unwrap();
+	let patched = std::fs::read(&path).unwrap();
+
+	// Only the transparency byte of the payload differs
+	let offset = original.windows(8).position(|w| w == b"GGATGALF").unwrap() + TAGG_HEAD_LEN;
+	let differences: Vec<usize> = original.iter().zip(&patched).enumerate().filter(|(_, (a, b))| a != b).map(|(i, _)| i).collect();
+	assert_eq!(patched.len(), original.len());
+	assert_eq!(differences, [offset]);
+	assert!(PaaImage::from_bytes(&patched).unwrap().taggs.contains(&flag));
+
+	// Nothing is written unless the payload fits
+	let longer = Tagg::Proc { code: crate::TextureMacro { text: "fresnel(1.3,7.5)".into() } };
+	assert!(matches!(replace_tagg(&path, &longer), Err(PaaError::TaggSizeMismatch("CORP", 14, 16))));
+	assert!(matches!(replace_tagg(&path, &Tagg::Swiz { swizzle: Default::default() }), Err(PaaError::TaggNotFound("ZIWS"))));
+	assert_eq!(std::fs::read(&path).unwrap(), patched);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -7,6 +7,82 @@ use surety::Ensure;
 use tap::prelude::*;
 
 
+/// Rescale an unsigned channel value from `from_bits` to `to_bits` bits of
+/// depth, rounding to the nearest representable value.
+///
+/// Endpoints are preserved (`0` maps to `0`, and the largest `from_bits` value
+/// maps to the largest `to_bits` value), and the mapping is monotonic.  Bits
+/// of `value` above `from_bits` are ignored.
+///
+/// # Panics
+/// - If `from_bits` or `to_bits` is not in `1..=8`.
+///
+/// # Example
+/// ```
+/// # use a3_paa::rescale_channel;
+/// assert_eq!(rescale_channel(0x1F, 5, 8), 0xFF);
+/// assert_eq!(rescale_channel(0x80, 8, 1), 1);
+/// assert_eq!(rescale_channel(0x6B, 8, 5), 0x0D);
+/// ```
+pub fn rescale_channel(value: u8, from_bits: u8, to_bits: u8) -> u8 {
+	assert!((1..=8).contains(&from_bits), "from_bits out of range: {}", from_bits);
+	assert!((1..=8).contains(&to_bits), "to_bits out of range: {}", to_bits);
+
+	let range_from = u32::from(channel_max(from_bits));
+	let range_into = u32::from(channel_max(to_bits));
+	let value = u32::from(value) & range_from;
+	let bias = range_from / 2; // needed for symmetry
+
+	u8::try_from((value * range_into + bias) / range_from).unwrap_or(u8::MAX)
+}
+
+
+/// Largest value of a `bits`-wide unsigned channel (`bits` must be in `1..=8`).
+#[allow(clippy::cast_possible_truncation)]
+const fn channel_max(bits: u8) -> u8 {
+	(0xFFu16 >> (8 - bits)) as u8
+}
+
+
+/// Rescale an unsigned `bits`-wide channel value (`bits` in `1..=8`) to 16
+/// bits of depth, rounding to the nearest representable value.
+pub(crate) fn widen_channel(value: u8, bits: u8) -> u16 {
+	let range_from = u32::from(channel_max(bits));
+	let value = u32::from(value) & range_from;
+
+	u16::try_from((value * 0xFFFF + range_from / 2) / range_from).unwrap_or(u16::MAX)
+}
+
+
+#[test]
+fn rescale_channel_all_depths() {
+	const DEPTHS: [u8; 4] = [1, 4, 5, 8];
+
+	for from in DEPTHS {
+		for into in DEPTHS {
+			let max_from = channel_max(from);
+			let max_into = channel_max(into);
+
+			assert_eq!(rescale_channel(0, from, into), 0);
+			assert_eq!(rescale_channel(max_from, from, into), max_into);
+
+			let mut previous = 0u8;
+
+			for value in 0..=max_from {
+				let scaled = rescale_channel(value, from, into);
+				assert!(scaled >= previous, "not monotonic: {}->{} bits at {}", from, into, value);
+				assert!(scaled <= max_into);
+				previous = scaled;
+
+				if from <= into {
+					assert_eq!(rescale_channel(scaled, into, from), value, "lossy roundtrip: {}->{} bits at {}", from, into, value);
+				};
+			};
+		};
+	};
+}
+
+
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::integer_arithmetic)]
 pub(crate) trait ArgbPixel: for<'a> DekuContainerRead<'a> + DekuContainerWrite + Sized {
@@ -25,9 +101,8 @@ pub(crate) trait ArgbPixel: for<'a> DekuContainerRead<'a> + DekuContainerWrite +
 	const PIXEL_WIDTH_BYTES: usize = (Self::PIXEL_WIDTH + 7) / 8;
 
 
-	fn uint_range(width: u8) -> u8 { (2u16.pow(width.into()) - 1) as u8 }
-	fn alpha_range() -> u8 { Self::uint_range(Self::ALPHA_WIDTH) }
-	fn color_range() -> u8 { Self::uint_range(Self::COLOR_WIDTH) }
+	fn alpha_range() -> u8 { channel_max(Self::ALPHA_WIDTH) }
+	fn color_range() -> u8 { channel_max(Self::COLOR_WIDTH) }
 
 
 	fn from_data(data: &[u8]) -> PaaResult<Self> {
@@ -57,36 +132,36 @@ pub(crate) trait ArgbPixel: for<'a> DekuContainerRead<'a> + DekuContainerWrite +
 	}
 
 
-	fn convert_u8(value: u8, from_width: u8, into_width: u8) -> u8 {
-		let range_from = Self::uint_range(from_width) as u16;
-		let range_into = Self::uint_range(into_width) as u16;
-		let bias = range_from / 2; // needed for symmetry
-		(((value as u16) * range_into + bias) / range_from) as u8
+	fn into_rgba8(self) -> [u8; 4] {
+		let r = rescale_channel(self.r(), Self::COLOR_WIDTH, 8);
+		let g = rescale_channel(self.g(), Self::COLOR_WIDTH, 8);
+		let b = rescale_channel(self.b(), Self::COLOR_WIDTH, 8);
+		let a = rescale_channel(self.a(), Self::ALPHA_WIDTH, 8);
+		[r, g, b, a]
 	}
 
 
-	fn into_rgba8(self) -> image::Rgba<u8> {
-		let r = Self::convert_u8(self.r(), Self::COLOR_WIDTH, 8);
-		let g = Self::convert_u8(self.g(), Self::COLOR_WIDTH, 8);
-		let b = Self::convert_u8(self.b(), Self::COLOR_WIDTH, 8);
-		let a = Self::convert_u8(self.a(), Self::ALPHA_WIDTH, 8);
-		image::Rgba::<u8>([r, g, b, a])
+	fn into_rgba16(self) -> [u16; 4] {
+		let r = widen_channel(self.r(), Self::COLOR_WIDTH);
+		let g = widen_channel(self.g(), Self::COLOR_WIDTH);
+		let b = widen_channel(self.b(), Self::COLOR_WIDTH);
+		let a = widen_channel(self.a(), Self::ALPHA_WIDTH);
+		[r, g, b, a]
 	}
 
 
 	#[inline]
 	fn convert_data_into_rgba8_data(data: &[u8]) -> [u8; 4] {
 		let pix = Self::from_data(data).unwrap();
-		let rgba = pix.into_rgba8();
-		rgba.0
+		pix.into_rgba8()
 	}
 
 
-	fn from_rgba8(rgba8: &image::Rgba<u8>) -> Self {
-		let r = Self::convert_u8(rgba8.0[0], 8, Self::COLOR_WIDTH);
-		let g = Self::convert_u8(rgba8.0[1], 8, Self::COLOR_WIDTH);
-		let b = Self::convert_u8(rgba8.0[2], 8, Self::COLOR_WIDTH);
-		let a = Self::convert_u8(rgba8.0[3], 8, Self::ALPHA_WIDTH);
+	fn from_rgba8(rgba8: &[u8; 4]) -> Self {
+		let r = rescale_channel(rgba8[0], 8, Self::COLOR_WIDTH);
+		let g = rescale_channel(rgba8[1], 8, Self::COLOR_WIDTH);
+		let b = rescale_channel(rgba8[2], 8, Self::COLOR_WIDTH);
+		let a = rescale_channel(rgba8[3], 8, Self::ALPHA_WIDTH);
 		Self::from_rgba([r, g, b, a])
 	}
 
@@ -110,8 +185,7 @@ pub(crate) trait ArgbPixel: for<'a> DekuContainerRead<'a> + DekuContainerWrite +
 		let mut result = Vec::with_capacity(result_len);
 
 		for pixdata in data.chunks(4).map(|s| s.try_into().unwrap()) {
-			let rgba = image::Rgba::<u8>(pixdata);
-			let pix = Self::from_rgba8(&rgba);
+			let pix = Self::from_rgba8(&pixdata);
 			let bytes = pix.to_data().unwrap();
 			result.extend(&bytes);
 		};
@@ -135,6 +209,23 @@ pub(crate) trait ArgbPixel: for<'a> DekuContainerRead<'a> + DekuContainerWrite +
 
 		Ok(result)
 	}
+
+
+	fn convert_to_rgba16_slice(data: &[u8]) -> PaaResult<Vec<u16>> {
+		if data.len() % Self::PIXEL_WIDTH_BYTES != 0 {
+			return Err(PixelReadError);
+		};
+
+		let result_len: usize = (data.len().checked() / Self::PIXEL_WIDTH_BYTES * 4)
+			.ok_or(ArithmeticOverflow)?;
+		let mut result = Vec::with_capacity(result_len);
+
+		for pixdata in data.chunks(Self::PIXEL_WIDTH_BYTES) {
+			result.extend(Self::from_data(pixdata)?.into_rgba16());
+		};
+
+		Ok(result)
+	}
 }
 
 
@@ -180,10 +271,10 @@ impl ArgbPixel for Argb1555Pixel {
 		let g: u8 = (pixel[0] << 3 | pixel[1] >> 5) & 0x1F;
 		let b: u8 = pixel[1] & 0x1F;
 
-		let r: u8 = ((u16::from(r) * 0xFF + 0xF) / 0x1F) as u8;
-		let g: u8 = ((u16::from(g) * 0xFF + 0xF) / 0x1F) as u8;
-		let b: u8 = ((u16::from(b) * 0xFF + 0xF) / 0x1F) as u8;
-		let a: u8 = a * 0xFF;
+		let r: u8 = rescale_channel(r, Self::COLOR_WIDTH, 8);
+		let g: u8 = rescale_channel(g, Self::COLOR_WIDTH, 8);
+		let b: u8 = rescale_channel(b, Self::COLOR_WIDTH, 8);
+		let a: u8 = rescale_channel(a, Self::ALPHA_WIDTH, 8);
 
 		[r, g, b, a]
 	}
@@ -253,10 +344,10 @@ impl ArgbPixel for Argb4444Pixel {
 		let g: u8 = pixel[1] >> 4;
 		let b: u8 = pixel[1] & 0x0F;
 
-		let r: u8 = ((u16::from(r) * 0xFF + 0x07) / 0x0F) as u8;
-		let g: u8 = ((u16::from(g) * 0xFF + 0x07) / 0x0F) as u8;
-		let b: u8 = ((u16::from(b) * 0xFF + 0x07) / 0x0F) as u8;
-		let a: u8 = ((u16::from(a) * 0xFF + 0x07) / 0x0F) as u8;
+		let r: u8 = rescale_channel(r, Self::COLOR_WIDTH, 8);
+		let g: u8 = rescale_channel(g, Self::COLOR_WIDTH, 8);
+		let b: u8 = rescale_channel(b, Self::COLOR_WIDTH, 8);
+		let a: u8 = rescale_channel(a, Self::ALPHA_WIDTH, 8);
 
 		[r, g, b, a]
 	}
@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use crate::PaaMipmapCompression;
+#[cfg(doc)] use crate::PaaImage;
+
+
+/// Step of a long operation, reported to the callback of
+/// [`PaaImage::read_from_with_progress`] or
+/// [`PaaEncoder::with_progress`][`crate::PaaEncoder::with_progress`]
+///
+/// Elapsed times are measured with [`std::time::Instant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+	/// A tagg was read from the header.
+	TaggRead {
+		/// Tagg name as stored in the file, e.g. `"SFFO"`.
+		name: &'static str,
+		/// Position of the tagg in the input.
+		offset: u64,
+	},
+
+	/// A mipmap was read and decompressed.  Mipmaps that could not be read
+	/// are not reported.
+	MipmapRead {
+		/// 0-based index of the mipmap.
+		index: usize,
+		/// Width in pixels.
+		width: u16,
+		/// Height in pixels.
+		height: u16,
+		/// Compression of the mipmap data in the input.
+		compression: PaaMipmapCompression,
+		/// Bytes taken by the mipmap in the input, including its header.
+		size: u64,
+		/// Time taken to read and decompress the mipmap.
+		elapsed: Duration,
+	},
+
+	/// No mipmap could be read at the offsets of
+	/// [`Tagg::Offs`][`crate::Tagg::Offs`], but some could once they were
+	/// repaired.  Mipmaps read with the repaired offsets are reported next.
+	MipmapOffsetsRepaired {
+		/// How the offsets were repaired.
+		repair: OffsetsRepair,
+	},
+
+	/// Encoding of a mipmap started.
+	MipmapEncodeStarted {
+		/// 0-based index of the mipmap.
+		index: usize,
+		/// Number of mipmaps being encoded.
+		count: usize,
+		/// Width in pixels.
+		width: u32,
+		/// Height in pixels.
+		height: u32,
+	},
+
+	/// A mipmap was encoded, successfully or not.
+	MipmapEncoded {
+		/// 0-based index of the mipmap.
+		index: usize,
+		/// Number of mipmaps being encoded.
+		count: usize,
+		/// Time taken to encode the mipmap.
+		elapsed: Duration,
+	},
+}
+
+
+/// How [`PaaImage::read_from`] locates mipmaps when none can be read at the
+/// offsets of [`Tagg::Offs`][`crate::Tagg::Offs`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetsRepair {
+	/// The offsets are relative to another position than the start of the
+	/// file, such as the end of the header as some third-party packers write
+	/// them.  They are shifted for the first mipmap to start at the end of
+	/// the header, after the palette.
+	HeaderRelative,
+	/// The offsets are ignored, and mipmaps are read one after the other from
+	/// the end of the header.
+	Sequential,
+}
+
+
+/// Start timing a step if it is reported, or logged at debug level.
+pub(crate) fn start_timer(reported: bool) -> Option<Instant> {
+	#[cfg(feature = "log")]
+	let reported = reported || log::log_enabled!(log::Level::Debug);
+
+	reported.then(Instant::now)
+}
+
+
+/// Time since [`start_timer`], or zero if the step was not timed.
+pub(crate) fn elapsed(timer: Option<Instant>) -> Duration {
+	timer.map_or(Duration::ZERO, |t| t.elapsed())
+}
@@ -0,0 +1,439 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{CompressionPolicy, PaaImage, PaaLimits, PaaMipmap, PaaMipmapCompression, PaaPalette, PaaResult, PaaType, ProgressEvent, Tagg};
+use crate::PaaError::*;
+use crate::sansio::{self, MipmapHead, WalkFrom};
+
+
+/// Absolute offset and length in bytes of a part of a PAA file
+pub type PaaSpan = (u64, u64);
+
+
+/// Where the tagg and mipmap payloads of a PAA file lie, for patching them in
+/// place; see [`PaaReader::layout`] and [`PaaImage::to_bytes_with_layout`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PaaLayout {
+	taggs: Vec<(&'static str, PaaSpan)>,
+	mipmaps: Vec<Option<PaaSpan>>,
+}
+
+
+impl PaaLayout {
+	/// Span of the payload of the last tagg named `name` (e.g. `"CGVA"`, see
+	/// [`Tagg::as_taggname`]), after its signature and length.
+	pub fn tagg_span(&self, name: &str) -> Option<PaaSpan> {
+		self.taggs.iter().rev().find(|(n, _)| *n == name).map(|(_, span)| *span)
+	}
+
+
+	/// Span of the (possibly compressed) data of mipmap #`index` (0-based),
+	/// after its dimensions and length, or [`None`] if its header could not
+	/// be read.
+	pub fn mipmap_span(&self, index: usize) -> Option<PaaSpan> {
+		self.mipmaps.get(index).copied().flatten()
+	}
+
+
+	/// Names and payload spans of all taggs, in file order.
+	pub fn tagg_spans(&self) -> &[(&'static str, PaaSpan)] {
+		&self.taggs
+	}
+
+
+	/// Data spans of all mipmaps, as [`mipmap_span`][`Self::mipmap_span`].
+	pub fn mipmap_spans(&self) -> &[Option<PaaSpan>] {
+		&self.mipmaps
+	}
+}
+
+
+/// Lazy reader of PAA files: the header is parsed up front, but mipmaps are
+/// only read and decompressed on demand
+#[derive(Debug)]
+pub struct PaaReader<R> {
+	input: R,
+	paatype: PaaType,
+	taggs: Vec<Tagg>,
+	palette: Option<PaaPalette>,
+	offsets: Vec<u32>,
+	layout: PaaLayout,
+	policy: CompressionPolicy,
+}
+
+
+impl<R: Read + Seek> PaaReader<R> {
+	/// Read the header of a PAA file from `input`, and locate its mipmaps from
+	/// [`Tagg::Offs`], or by skipping from one mipmap header to the next if
+	/// there is none.  Mipmaps after a zero offset are also located by
+	/// skipping.
+	///
+	/// # Errors
+	/// - [`UnexpectedEof`]: Unexpected end of file within the header.
+	/// - [`UnexpectedIoError`]: Unexpected read or seek error.
+	/// - [`UnknownPaaType`]: If the input PAA does not have a correct magic
+	///   sequence.
+	/// - [`UnexpectedPalette`]: If the input PAA has a palette but is not of
+	///   [`PaaType::IndexPalette`].
+	///
+	/// # Panics
+	/// - If [`deku::DekuContainerWrite::to_bytes()`] fails.
+	pub fn new(mut input: R) -> PaaResult<Self> {
+		let mut tagg_offsets = vec![];
+		let mut record = |event: ProgressEvent| if let ProgressEvent::TaggRead { name, offset } = event {
+			tagg_offsets.push((name, offset));
+		};
+
+		let (paatype, taggs, palette) = PaaImage::read_header_from(&mut input, PaaLimits::default(), Some(&mut record))?;
+		let start = input.stream_position()?;
+
+		// Payloads follow the 4-byte signature, 4-byte name and 4-byte length
+		let tagg_spans = tagg_offsets
+			.into_iter()
+			.map(|(name, offset)| {
+				let _ = input.seek(SeekFrom::Start(offset + 8))?;
+				let length = input.read_u32::<LittleEndian>()?;
+				Ok((name, (offset + 12, u64::from(length))))
+			})
+			.collect::<PaaResult<Vec<_>>>()?;
+
+		let (mut offsets, walk) = sansio::lazy_offsets(&taggs);
+
+		match walk {
+			WalkFrom::Nowhere => (),
+			WalkFrom::HeaderEnd => offsets.extend(Self::walk_mipmaps(&mut input, paatype, start)?),
+			WalkFrom::Offset(offset) => offsets.extend(Self::walk_mipmaps(&mut input, paatype, offset.into())?),
+		};
+
+		let mipmap_spans = offsets.iter().map(|o| Self::read_mipmap_span(&mut input, paatype, *o)).collect();
+		let layout = PaaLayout { taggs: tagg_spans, mipmaps: mipmap_spans };
+
+		Ok(PaaReader { input, paatype, taggs, palette, offsets, layout, policy: CompressionPolicy::default() })
+	}
+
+
+	/// Read the header of the mipmap at `offset`, and return the span of its
+	/// data.
+	fn read_mipmap_span(input: &mut R, paatype: PaaType, offset: u32) -> Option<PaaSpan> {
+		let _ = input.seek(SeekFrom::Start(offset.into())).ok()?;
+		let (head, consumed) = read_mipmap_head(input, paatype).ok()?;
+
+		Some((u64::from(offset) + consumed as u64, head.payload_len as u64))
+	}
+
+
+	/// Collect the offsets of sequential mipmaps from `offset` on, up to the
+	/// empty mipmap or the end of file.
+	fn walk_mipmaps(input: &mut R, paatype: PaaType, offset: u64) -> PaaResult<Vec<u32>> {
+		let mut offsets = vec![];
+		let mut offset = offset;
+		let _ = input.seek(SeekFrom::Start(offset))?;
+
+		while let Ok((head, consumed)) = read_mipmap_head(input, paatype) {
+			offsets.push(offset.try_into().map_err(|_| ArithmeticOverflow)?);
+			offset += (consumed + head.payload_len) as u64;
+			let _ = input.seek(SeekFrom::Start(offset))?;
+		};
+
+		Ok(offsets)
+	}
+
+
+	/// Format of all mipmaps.
+	pub fn paatype(&self) -> PaaType {
+		self.paatype
+	}
+
+
+	/// Taggs of the PAA header.
+	pub fn taggs(&self) -> &[Tagg] {
+		&self.taggs
+	}
+
+
+	/// Palette of [`PaaType::IndexPalette`] mipmaps, if any.
+	pub fn palette(&self) -> Option<&PaaPalette> {
+		self.palette.as_ref()
+	}
+
+
+	/// Spans of the tagg and mipmap payloads in the input.
+	pub fn layout(&self) -> &PaaLayout {
+		&self.layout
+	}
+
+
+	/// Number of mipmaps.
+	pub fn mipmap_count(&self) -> usize {
+		self.offsets.len()
+	}
+
+
+	/// Read the width and height of mipmap #`index` (0-based), without
+	/// reading its data.
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: `index` is not below
+	///   [`mipmap_count`][`Self::mipmap_count`].
+	/// - [`UnexpectedEof`]: The mipmap is beyond the end of file.
+	/// - [`UnexpectedIoError`]: Unexpected read or seek error.
+	pub fn mipmap_dimensions(&mut self, index: usize) -> PaaResult<(u16, u16)> {
+		self.seek_to(index)?;
+		let mut width = self.input.read_u16::<LittleEndian>()?;
+		let mut height = self.input.read_u16::<LittleEndian>()?;
+
+		if (width, height) == (1234, 8765) {
+			width = self.input.read_u16::<LittleEndian>()?;
+			height = self.input.read_u16::<LittleEndian>()?;
+		};
+
+		// The highest bit of the width flags LZO-compressed DXTn mipmaps
+		let width = if self.paatype.is_dxtn() { width & 0x7FFF } else { width };
+
+		Ok((width, height))
+	}
+
+
+	/// Read how mipmap #`index` (0-based) is compressed, as
+	/// [`read_mipmap`][`Self::read_mipmap`] would, without reading its data.
+	///
+	/// # Errors
+	/// - As [`mipmap_dimensions`][`Self::mipmap_dimensions`].
+	pub fn mipmap_compression(&mut self, index: usize) -> PaaResult<PaaMipmapCompression> {
+		use PaaMipmapCompression::*;
+
+		self.seek_to(index)?;
+		let width = self.input.read_u16::<LittleEndian>()?;
+		let height = self.input.read_u16::<LittleEndian>()?;
+
+		if (width, height) == (1234, 8765) {
+			return Ok(Lzss);
+		};
+
+		if self.paatype == PaaType::IndexPalette {
+			return Ok(RleBlocks);
+		};
+
+		if self.paatype.is_dxtn() && width & 0x8000 != 0 {
+			return Ok(Lzo);
+		};
+
+		if self.paatype.is_dxtn() && !self.policy.dxtn_lzss {
+			return Ok(Uncompressed);
+		};
+
+		#[allow(clippy::cast_possible_truncation)]
+		let length = self.input.read_uint::<LittleEndian>(3)? as usize;
+
+		Ok(if length == self.paatype.predict_size(width, height) { Uncompressed } else { Lzss })
+	}
+
+
+	/// Read and decompress mipmap #`index` (0-based), as
+	/// [`PaaMipmap::read_from_with_policy`] with the policy set by
+	/// [`with_policy`][`Self::with_policy`].
+	///
+	/// # Errors
+	/// - [`MipmapIndexOutOfRange`]: `index` is not below
+	///   [`mipmap_count`][`Self::mipmap_count`].
+	/// - Any error of [`PaaMipmap::read_from`].
+	///
+	/// # Panics
+	/// - As [`PaaMipmap::read_from`].
+	pub fn read_mipmap(&mut self, index: usize) -> PaaResult<PaaMipmap> {
+		self.seek_to(index)?;
+		PaaMipmap::read_from_with_policy(&mut self.input, self.paatype, self.policy)
+	}
+
+
+	/// Tell the compression of mipmaps apart as set by `policy`, rather than
+	/// [`CompressionPolicy::default`].
+	pub fn with_policy(self, policy: CompressionPolicy) -> Self {
+		Self { policy, ..self }
+	}
+
+
+	/// Return the underlying reader.
+	pub fn into_inner(self) -> R {
+		self.input
+	}
+
+
+	fn seek_to(&mut self, index: usize) -> PaaResult<()> {
+		let offset = self.offsets.get(index).ok_or(MipmapIndexOutOfRange)?;
+		let _ = self.input.seek(SeekFrom::Start((*offset).into()))?;
+		Ok(())
+	}
+}
+
+
+/// Read the header of the `paatype` mipmap at the position of `input`, and
+/// return it with the bytes it takes.
+fn read_mipmap_head<R: Read + Seek>(input: &mut R, paatype: PaaType) -> PaaResult<(MipmapHead, usize)> {
+	let start = input.stream_position()?;
+	let policy = CompressionPolicy::default();
+	let (head, consumed) = sansio::drive(input, &mut vec![], |buf, _| sansio::parse_mipmap_head(buf, paatype, policy))?;
+
+	if (head.paatype, head.compression) != (PaaType::IndexPalette, PaaMipmapCompression::Lzss) {
+		return Ok((head, consumed));
+	};
+
+	// The layout of LZSS index palette mipmaps is told apart by their data
+	let _ = input.seek(SeekFrom::Start(start))?;
+	let ((head, consumed), _) = sansio::drive(input, &mut vec![], |buf, eof| sansio::parse_mipmap(buf, eof, paatype, policy))?;
+
+	Ok((head, consumed))
+}
+
+
+impl PaaImage {
+	/// [`to_bytes`][`Self::to_bytes`], also returning where the tagg and
+	/// mipmap payloads were written.
+	///
+	/// # Errors
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	///
+	/// # Panics
+	/// - As [`to_bytes`][`Self::to_bytes`].
+	pub fn to_bytes_with_layout(&self) -> PaaResult<(Vec<u8>, PaaLayout)> {
+		let data = self.to_bytes()?;
+		let layout = PaaReader::new(Cursor::new(&data))?.layout;
+
+		Ok((data, layout))
+	}
+}
+
+
+#[cfg(feature = "image")]
+#[test]
+fn paa_reader() {
+	use crate::{PaaEncoder, TextureEncodingSettings};
+
+	let image = image::RgbaImage::from_fn(64, 32, |x, y| image::Rgba([x as u8 * 4, y as u8 * 8, 0, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	let mut reader = PaaReader::new(Cursor::new(paa.to_bytes().unwrap())).unwrap();
+	assert_eq!(reader.paatype(), PaaType::Dxt1);
+	assert_eq!(reader.mipmap_count(), 5);
+	assert_eq!(reader.mipmap_dimensions(1).unwrap(), (32, 16));
+	assert_eq!(reader.read_mipmap(2).ok(), paa.mipmaps[2].as_ref().ok().cloned());
+	assert!(matches!(reader.read_mipmap(5), Err(MipmapIndexOutOfRange)));
+
+	for (index, mipmap) in paa.mipmaps.iter().flatten().enumerate() {
+		assert_eq!(reader.mipmap_compression(index).unwrap(), mipmap.compression);
+	};
+
+	// Without OFFSTAGG, mipmaps are found by skipping over their data
+	let mut sequential = vec![0x01, 0xFF, 0, 0];
+
+	for mipmap in paa.mipmaps.iter().flatten() {
+		sequential.extend(mipmap.to_bytes().unwrap());
+	};
+
+	sequential.extend([0u8; 6]);
+
+	let mut reader = PaaReader::new(Cursor::new(sequential)).unwrap();
+	assert!(reader.taggs().is_empty());
+	assert_eq!(reader.mipmap_count(), 5);
+	assert_eq!(reader.mipmap_dimensions(4).unwrap(), (4, 4));
+	assert_eq!(reader.read_mipmap(0).ok(), paa.mipmaps[0].as_ref().ok().cloned());
+}
+
+
+#[test]
+fn paa_reader_palette() {
+	// A 2x2 LZSS mipmap of four literals, with a zero checksum
+	let mut pac = vec![0x47, 0x47, 1, 0, 0x10, 0x20, 0x30];
+	pac.extend([0xD2, 0x04, 0x3D, 0x22, 2, 0, 2, 0, 9, 0, 0, 0x0F, 0, 0, 0, 0, 0, 0, 0, 0]);
+	pac.extend([0; 6]);
+
+	let mut reader = PaaReader::new(Cursor::new(&pac)).unwrap();
+	assert_eq!(reader.paatype(), PaaType::IndexPalette);
+	assert_eq!(reader.palette().map(PaaPalette::len), Some(1));
+	assert_eq!(reader.mipmap_count(), 1);
+	assert_eq!(reader.mipmap_dimensions(0).unwrap(), (2, 2));
+	assert_eq!(reader.mipmap_compression(0).unwrap(), PaaMipmapCompression::Lzss);
+	assert_eq!(reader.read_mipmap(0).unwrap().data, [0; 4]);
+
+	pac[0..2].copy_from_slice(&PaaType::Dxt1.magic());
+	assert!(matches!(PaaReader::new(Cursor::new(&pac)), Err(UnexpectedPalette(PaaType::Dxt1))));
+}
+
+
+#[test]
+fn layout() {
+	use crate::Bgra8888Pixel;
+
+	let mipmap = |size: u16| Ok(PaaMipmap {
+		width: size,
+		height: size,
+		paatype: PaaType::Dxt1,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0; usize::from(size) * usize::from(size) / 2],
+		payload: None,
+	});
+	let taggs = vec![Tagg::Avgc { rgba: Bgra8888Pixel::default() }];
+	let paa = PaaImage { paatype: PaaType::Dxt1, taggs, palette: None, mipmaps: vec![mipmap(8), mipmap(4)], trailer: vec![] };
+
+	// Magic (2), AVGC (12 + 4), OFFS (12 + 64), palette (2), then the
+	// mipmaps with 7-byte headers
+	let (data, layout) = paa.to_bytes_with_layout().unwrap();
+	assert_eq!(layout.tagg_span("CGVA"), Some((14, 4)));
+	assert_eq!(layout.tagg_span("SFFO"), Some((30, 64)));
+	assert_eq!(layout.tagg_span("CXAM"), None);
+	assert_eq!(layout.mipmap_span(0), Some((103, 32)));
+	assert_eq!(layout.mipmap_span(1), Some((142, 8)));
+	assert_eq!(layout.mipmap_span(2), None);
+	assert_eq!(data.len(), 150 + PaaImage::TERMINATOR.len());
+	assert_eq!(PaaReader::new(Cursor::new(&data)).unwrap().layout(), &layout);
+
+	// Without OFFSTAGG, mipmaps follow the empty palette
+	let mut sequential = vec![0x01, 0xFF, 0, 0];
+	sequential.extend(&data[96..]);
+
+	let layout = PaaReader::new(Cursor::new(sequential)).unwrap().layout().clone();
+	assert!(layout.tagg_spans().is_empty());
+	assert_eq!(layout.mipmap_spans(), [Some((11, 32)), Some((50, 8))]);
+}
+
+
+#[test]
+fn lzss_dxtn_policy() {
+	use PaaMipmapCompression::*;
+
+	// An 8x8 DXT5 mipmap of four solid blocks, compressed with LZSS (as
+	// literals only) but not flagged as such, like some third-party packers
+	let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference/fixtures/unpaired/lzss_dxt5.paa")).unwrap();
+
+	// Taken as is, the LZSS stream is uncompressed data of the wrong length
+	let strict = PaaImage::from_bytes(&data).unwrap();
+	let mipmap = strict.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.compression, mipmap.data.len()), (Uncompressed, 76));
+	assert!(mipmap.has_expected_compression());
+
+	let lenient = PaaImage::read_from_with_policy(&mut Cursor::new(&data), CompressionPolicy::LENIENT, |_| ()).unwrap();
+	let mipmap = lenient.mipmaps[0].as_ref().unwrap();
+	assert_eq!((mipmap.compression, mipmap.data.len()), (Lzss, PaaType::Dxt5.predict_size(8, 8)));
+	assert_eq!(mipmap.expected_compression(), Uncompressed);
+	assert!(!mipmap.has_expected_compression());
+
+	#[cfg(feature = "image")]
+	{
+		let decoded = mipmap.decode().unwrap();
+		assert_eq!([0, 4, 8, 12].map(|i| decoded.get_pixel(i % 8, i / 8 * 4).0), [[0xFF, 0, 0, 0xFF], [0, 0xFF, 0, 0xFF], [0, 0, 0xFF, 0xFF], [0xFF; 4]]);
+	};
+
+	let mut reader = PaaReader::new(Cursor::new(&data)).unwrap();
+	assert_eq!(reader.mipmap_compression(0).unwrap(), Uncompressed);
+	let mut reader = reader.with_policy(CompressionPolicy::LENIENT);
+	assert_eq!(reader.mipmap_compression(0).unwrap(), Lzss);
+	assert_eq!(&reader.read_mipmap(0).unwrap(), mipmap);
+
+	// Rewritten as the crate would, the file reads the same under any policy
+	let mut rewritten = lenient.clone();
+	rewritten.mipmaps = vec![Ok(PaaMipmap { compression: mipmap.expected_compression(), ..mipmap.clone() })];
+	let rewritten = rewritten.to_bytes().unwrap();
+	assert_eq!(PaaImage::from_bytes(&rewritten).unwrap().mipmaps[0].as_ref().unwrap().data, mipmap.data);
+}
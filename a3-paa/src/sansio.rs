@@ -0,0 +1,625 @@
+//! Parsers of the PAA header, taggs and mipmap headers that do no I/O
+//!
+//! A parser is handed the bytes read so far, from where it starts, and either
+//! parses them or tells how many it needs.  Drivers read just as many, so that
+//! blocking and async readers share the parsing, and both leave their input
+//! where parsing ended.
+
+use std::io::{ErrorKind, Read};
+
+use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "tokio")] use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{CompressionPolicy, OffsetsRepair, PaaLimits, PaaMipmapCompression, PaaPalette, PaaResult, PaaType, ParseReport, Tagg};
+use crate::PaaError::*;
+use crate::macros;
+
+
+/// Most bytes a driver reads at once, so that a bogus length does not allocate
+/// more than the input holds
+const MAX_READ_SIZE: usize = 1 << 16;
+
+
+/// Outcome of a parser over the bytes read so far
+#[derive(Debug)]
+pub(crate) enum Parse<T> {
+	/// At least this many bytes are needed, counted from the start.
+	Incomplete(usize),
+	/// Parsed from this many bytes.
+	Complete(T, usize),
+}
+
+
+impl<T> Parse<T> {
+	/// Map the parsed value with `f`.
+	pub(crate) fn map<U>(self, f: impl FnOnce(T) -> U) -> Parse<U> {
+		match self {
+			Parse::Incomplete(needed) => Parse::Incomplete(needed),
+			Parse::Complete(value, consumed) => Parse::Complete(f(value), consumed),
+		}
+	}
+}
+
+
+/// Read from `input` into `buf` until `parse` (given the bytes read so far,
+/// and whether `input` has no more) completes, and return what it parsed and
+/// the bytes it consumed.  Fail with [`UnexpectedEof`] if it needs more bytes
+/// than `input` holds.
+pub(crate) fn drive<R: Read, T>(input: &mut R, buf: &mut Vec<u8>, mut parse: impl FnMut(&[u8], bool) -> PaaResult<Parse<T>>) -> PaaResult<(T, usize)> {
+	let mut eof = false;
+
+	loop {
+		let needed = match parse(buf, eof)? {
+			Parse::Complete(value, consumed) => return Ok((value, consumed)),
+			Parse::Incomplete(_) if eof => return Err(UnexpectedEof),
+			Parse::Incomplete(needed) => needed,
+		};
+
+		let start = buf.len();
+		buf.resize(needed.min(start + MAX_READ_SIZE), 0);
+		let mut filled = start;
+
+		while filled < buf.len() {
+			match input.read(&mut buf[filled..]) {
+				Ok(0) => break,
+				Ok(n) => filled += n,
+				Err(e) if e.kind() == ErrorKind::Interrupted => (),
+				Err(e) => return Err(e.into()),
+			};
+		};
+
+		eof = filled < buf.len();
+		buf.truncate(filled);
+	};
+}
+
+
+/// [`drive`] over an async `input`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn drive_async<R: AsyncRead + Unpin, T>(input: &mut R, buf: &mut Vec<u8>, mut parse: impl FnMut(&[u8], bool) -> PaaResult<Parse<T>>) -> PaaResult<(T, usize)> {
+	let mut eof = false;
+
+	loop {
+		let needed = match parse(buf, eof)? {
+			Parse::Complete(value, consumed) => return Ok((value, consumed)),
+			Parse::Incomplete(_) if eof => return Err(UnexpectedEof),
+			Parse::Incomplete(needed) => needed,
+		};
+
+		let start = buf.len();
+		buf.resize(needed.min(start + MAX_READ_SIZE), 0);
+		let mut filled = start;
+
+		while filled < buf.len() {
+			match input.read(&mut buf[filled..]).await {
+				Ok(0) => break,
+				Ok(n) => filled += n,
+				Err(e) if e.kind() == ErrorKind::Interrupted => (),
+				Err(e) => return Err(e.into()),
+			};
+		};
+
+		eof = filled < buf.len();
+		buf.truncate(filled);
+	};
+}
+
+
+/// Parse a tagg within `limits`, as [`Tagg::read_tagg_from`], along with the
+/// length of its payload.
+pub(crate) fn parse_tagg(buf: &[u8], limits: PaaLimits) -> PaaResult<Parse<(Tagg, u32)>> {
+	let head: &[u8; 12] = match buf.get(..12) {
+		Some(head) => head.try_into().expect("Could not convert tagg head (this is a bug)"),
+		None => return Ok(Parse::Incomplete(12)),
+	};
+
+	let (taggname, payload_length) = Tagg::try_head_from(head)?;
+	let length: usize = payload_length.try_into()?;
+
+	if taggname == "CORP" {
+		Tagg::check_proc_len(length, limits.max_proc_len)?;
+	};
+
+	let end = length.checked_add(12).ok_or(ArithmeticOverflow)?;
+
+	match buf.get(12..end) {
+		Some(payload) => Ok(Parse::Complete((Tagg::from_name_and_payload_within(&taggname, payload, limits)?, payload_length), end)),
+		None => Ok(Parse::Incomplete(end)),
+	}
+}
+
+
+/// Parser of `len` bytes, taken as they are.
+pub(crate) fn take(buf: &[u8], len: usize) -> Parse<()> {
+	if buf.len() < len { Parse::Incomplete(len) } else { Parse::Complete((), len) }
+}
+
+
+/// Parse the magic sequence starting a PAA file.
+pub(crate) fn parse_magic(buf: &[u8]) -> PaaResult<Parse<PaaType>> {
+	match buf.get(..2) {
+		Some(&[a, b]) => PaaType::from_magic([a, b]).map(|paatype| Parse::Complete(paatype, 2)).ok_or(UnknownPaaType([a, b])),
+		_ => Ok(Parse::Incomplete(2)),
+	}
+}
+
+
+/// Parser of the taggs of a PAA header, as
+/// [`PaaImage::read_from`][`crate::PaaImage::read_from`]: up to the first one
+/// that cannot be read, where the palette starts.  It resumes where it left
+/// off when handed more bytes.
+#[derive(Debug)]
+pub(crate) struct TaggsParser {
+	/// Taggs, with their offsets from the first one.
+	taggs: Vec<(Tagg, u64)>,
+	/// Where the next tagg starts.
+	position: usize,
+	/// Limits of every tagg.
+	limits: PaaLimits,
+}
+
+
+impl TaggsParser {
+	/// Parse taggs within `limits`.
+	pub(crate) fn new(limits: PaaLimits) -> Self {
+		Self { taggs: vec![], position: 0, limits }
+	}
+
+
+
+	/// Parse the taggs from `buf`, all the rest of the file if `eof`.  Once
+	/// complete, the parser is left empty.
+	pub(crate) fn parse(&mut self, buf: &[u8], eof: bool) -> PaaResult<Parse<Vec<(Tagg, u64)>>> {
+		loop {
+			match parse_tagg(&buf[self.position..], self.limits) {
+				Ok(Parse::Complete((tagg, _), consumed)) => {
+					self.taggs.push((tagg, self.position as u64));
+					self.position += consumed;
+				},
+
+				Ok(Parse::Incomplete(needed)) if !eof => return Ok(Parse::Incomplete(self.position + needed)),
+
+				// Not the end of the taggs, but one too large to read
+				Err(e @ ProcTaggTooLarge(..)) => return Err(e),
+
+				_ => {
+					let end = std::mem::take(&mut self.position);
+					return Ok(Parse::Complete(std::mem::take(&mut self.taggs), end));
+				},
+			};
+		};
+	}
+}
+
+
+/// Parse the palette following the taggs of a `paatype` file.
+pub(crate) fn parse_palette(buf: &[u8], paatype: PaaType) -> PaaResult<Parse<Option<PaaPalette>>> {
+	if buf.len() < 2 {
+		return Ok(Parse::Incomplete(2));
+	};
+
+	let end = 2 + 3 * usize::from(LittleEndian::read_u16(buf));
+
+	if buf.len() < end {
+		return Ok(Parse::Incomplete(end));
+	};
+
+	let palette = PaaPalette::read_from(&mut &buf[..end])?;
+
+	if palette.is_some() && paatype != PaaType::IndexPalette {
+		return Err(UnexpectedPalette(paatype));
+	};
+
+	Ok(Parse::Complete(palette, end))
+}
+
+
+/// Header of a mipmap, up to its (possibly compressed) data
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MipmapHead {
+	pub(crate) width: u16,
+	pub(crate) height: u16,
+	pub(crate) paatype: PaaType,
+	pub(crate) compression: PaaMipmapCompression,
+	/// Length of the data once decompressed.
+	pub(crate) data_len: usize,
+	/// Length of the data as stored, following the header.
+	pub(crate) payload_len: usize,
+}
+
+
+/// Parse the header of a mipmap of a `paatype` file, as
+/// [`PaaMipmap::read_from_with_policy`][`crate::PaaMipmap::read_from_with_policy`].
+pub(crate) fn parse_mipmap_head(buf: &[u8], paatype: PaaType, policy: CompressionPolicy) -> PaaResult<Parse<MipmapHead>> {
+	use PaaType::*;
+	use PaaMipmapCompression::*;
+
+	if buf.len() < 4 {
+		return Ok(Parse::Incomplete(4));
+	};
+
+	let mut paatype = paatype;
+	let mut compression = Uncompressed;
+	let mut width = LittleEndian::read_u16(&buf[0..]);
+	let mut height = LittleEndian::read_u16(&buf[2..]);
+	let mut position = 4;
+
+	if width == 0 || height == 0 {
+		return Err(EmptyMipmap);
+	};
+
+	if width == 1234 && height == 8765 {
+		if buf.len() < 8 {
+			return Ok(Parse::Incomplete(8));
+		};
+
+		paatype = IndexPalette;
+		compression = Lzss;
+
+		width = LittleEndian::read_u16(&buf[4..]);
+		height = LittleEndian::read_u16(&buf[6..]);
+		position = 8;
+	};
+
+	if width & 0x8000 != 0 && paatype.is_dxtn() {
+		compression = Lzo;
+		width ^= 0x8000;
+	};
+
+	if buf.len() < position + 3 {
+		return Ok(Parse::Incomplete(position + 3));
+	};
+
+	let data_len = paatype.predict_size(width, height);
+	#[allow(clippy::cast_possible_truncation)]
+	let payload_len = LittleEndian::read_uint(&buf[position..], 3) as usize;
+
+	if matches!(paatype, IndexPalette) && !matches!(compression, Lzss) {
+		compression = RleBlocks;
+	}
+	else if matches!(compression, Uncompressed) && data_len != payload_len && (!paatype.is_dxtn() || policy.dxtn_lzss) {
+		compression = Lzss;
+	};
+
+	Ok(Parse::Complete(MipmapHead { width, height, paatype, compression, data_len, payload_len }, position + 3))
+}
+
+
+/// Parse a mipmap: its header, as [`parse_mipmap_head`], and its payload,
+/// whose offset is returned with the header.
+///
+/// LZSS [`PaaType::IndexPalette`] mipmaps of Operation Flashpoint-era files
+/// have no length before their data.  Such a mipmap is told apart by its
+/// length running past the end of the file, or its data not expanding to
+/// [`PaaType::predict_size`] exactly at the checksum ending that length; the
+/// length is then taken as
+/// the start of the data, which runs up to the predicted size, followed by the
+/// checksum.  Telling it apart may take reading past the mipmap.
+pub(crate) fn parse_mipmap(buf: &[u8], eof: bool, paatype: PaaType, policy: CompressionPolicy) -> PaaResult<Parse<(MipmapHead, usize)>> {
+	let (mut head, start) = match parse_mipmap_head(buf, paatype, policy)? {
+		Parse::Complete(head, consumed) => (head, consumed),
+		Parse::Incomplete(needed) => return Ok(Parse::Incomplete(needed)),
+	};
+
+	let end = start + head.payload_len;
+
+	if (head.paatype, head.compression) != (PaaType::IndexPalette, PaaMipmapCompression::Lzss) {
+		return Ok(take(buf, end).map(|_| (head, start)));
+	};
+
+	if buf.len() < end && !eof {
+		return Ok(Parse::Incomplete(end));
+	};
+
+	// Misaligned data mostly reads as back-references, expanding up to 18
+	// bytes each, so it often reaches the predicted size early
+	let plausible = buf.len() >= end
+		&& head.payload_len >= 4
+		&& matches!(lzss_stream_len(&buf[start..end - 4], head.data_len), Parse::Complete((), len) if len == head.payload_len - 4);
+
+	if plausible {
+		return Ok(Parse::Complete((head, start), end));
+	};
+
+	let start = start - 3;
+
+	match lzss_stream_len(&buf[start..], head.data_len) {
+		Parse::Complete(_, len) => {
+			macros::log!(debug, "parse_mipmap: LZSS data without length ({} bytes)", len);
+			head.payload_len = len + 4;
+			Ok(take(buf, start + head.payload_len).map(|_| (head, start)))
+		},
+		Parse::Incomplete(needed) => Ok(Parse::Incomplete(start + needed)),
+	}
+}
+
+
+/// Parser of LZSS data up to where it expands to `data_len` bytes.
+fn lzss_stream_len(buf: &[u8], data_len: usize) -> Parse<()> {
+	let mut position = 0;
+	let mut expanded = 0;
+
+	// Each flag bit, from the lowest, is set for a literal byte and clear for
+	// a 2-byte back-reference of 3 to 18 bytes
+	while expanded < data_len {
+		let flags = match buf.get(position) {
+			Some(flags) => *flags,
+			None => return Parse::Incomplete(position + 1),
+		};
+		position += 1;
+
+		for bit in 0..8 {
+			if expanded >= data_len {
+				break;
+			};
+
+			if flags & (1 << bit) != 0 {
+				if buf.len() < position + 1 {
+					return Parse::Incomplete(position + 1);
+				};
+
+				position += 1;
+				expanded += 1;
+			}
+			else {
+				if buf.len() < position + 2 {
+					return Parse::Incomplete(position + 2);
+				};
+
+				expanded += usize::from(buf[position + 1] & 0x0F) + 3;
+				position += 2;
+			};
+		};
+	};
+
+	Parse::Complete((), position)
+}
+
+
+/// Where a lazy reader finds the mipmaps that [`Tagg::Offs`] does not locate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WalkFrom {
+	/// Nowhere; the offsets locate all mipmaps.
+	Nowhere,
+	/// From the end of the header, the first mipmap on.
+	HeaderEnd,
+	/// From this offset, which the offsets no longer hold.
+	Offset(u32),
+}
+
+
+/// The offsets of `taggs` a lazy reader trusts, and where it walks from one
+/// mipmap header to the next to locate the rest: offsets after a zero one are
+/// not trusted, and the walk goes on from the previous mipmap instead.
+pub(crate) fn lazy_offsets(taggs: &[Tagg]) -> (Vec<u32>, WalkFrom) {
+	let mut offsets = crate::PaaImage::offsets_in(taggs);
+
+	let walk = match offsets.iter().position(|o| *o == 0) {
+		Some(idx) => {
+			offsets.truncate(idx);
+			offsets.pop().map_or(WalkFrom::HeaderEnd, WalkFrom::Offset)
+		},
+		None if offsets.is_empty() => WalkFrom::HeaderEnd,
+		None => WalkFrom::Nowhere,
+	};
+
+	(offsets, walk)
+}
+
+
+/// Where [`PaaImage::read_from`][`crate::PaaImage::read_from`] reads mipmaps:
+/// at `offsets`, then one after the other if there are none or one is zero
+#[derive(Debug)]
+pub(crate) struct MipmapWalk<'a> {
+	offsets: &'a [u32],
+	sequential: bool,
+	/// Mipmaps read at `offsets` so far.
+	read: usize,
+	/// Where the next sequential mipmap starts.
+	position: u64,
+	/// End of the last mipmap read, where the trailer starts.
+	end: u64,
+	done: bool,
+}
+
+
+impl<'a> MipmapWalk<'a> {
+	/// Walk the mipmaps at `offsets` of a header ending at `header_end`.
+	pub(crate) fn new(offsets: &'a [u32], header_end: u64) -> Self {
+		// Offsets after a zero one are not trusted; mipmaps from there on are
+		// read sequentially instead
+		let (offsets, sequential) = match offsets.iter().position(|o| *o == 0) {
+			Some(idx) => {
+				macros::log!(warn, "OFFSTAGG has no offset for mipmap #{}, reading on from the previous mipmap", idx);
+				(&offsets[..idx], true)
+			},
+			None => (offsets, offsets.is_empty()),
+		};
+
+		Self { offsets, sequential, read: 0, position: header_end, end: header_end, done: false }
+	}
+
+
+	/// Offset of the next mipmap to read, or [`None`] once all are read.
+	pub(crate) fn next_offset(&self) -> Option<u64> {
+		match self.offsets.get(self.read) {
+			Some(offset) => Some((*offset).into()),
+			None if self.sequential && !self.done => Some(self.position),
+			None => None,
+		}
+	}
+
+
+	/// Record reading the mipmap at [`next_offset`][`Self::next_offset`],
+	/// which left the input at `position`.
+	pub(crate) fn record<T>(&mut self, mipmap: &PaaResult<T>, position: u64) {
+		if self.read < self.offsets.len() {
+			self.read += 1;
+
+			if mipmap.is_ok() {
+				self.end = self.end.max(position);
+			};
+
+			// Sequential mipmaps start after the furthest one read
+			self.position = self.end;
+		}
+		else {
+			if mipmap.is_ok() {
+				self.end = position;
+			};
+
+			self.position = position;
+			self.done = matches!(mipmap, Err(MipmapDataBeyondEof | EmptyMipmap | UnexpectedEof));
+		};
+	}
+
+
+	/// End of the last mipmap read, where the trailer starts.
+	pub(crate) fn end(&self) -> u64 {
+		self.end
+	}
+}
+
+
+/// Decoded mipmap data allocated while reading a file, up to
+/// [`PaaLimits::max_total_decoded_bytes`]
+#[derive(Debug)]
+pub(crate) struct ReadBudget {
+	limit: u64,
+	used: u64,
+}
+
+
+impl ReadBudget {
+	pub(crate) fn new(limits: PaaLimits) -> Self {
+		Self { limit: limits.max_total_decoded_bytes, used: 0 }
+	}
+
+
+	/// Charge the decoded data of mipmap #`index`, before it is decoded.
+	pub(crate) fn charge(&mut self, index: usize, head: &MipmapHead) -> PaaResult<()> {
+		let used = self.used.saturating_add(head.data_len as u64);
+
+		if used > self.limit {
+			return Err(ReadBudgetExceeded(index, self.limit));
+		};
+
+		self.used = used;
+		Ok(())
+	}
+
+
+	pub(crate) fn report(&self) -> ParseReport {
+		ParseReport { allocated_bytes: self.used }
+	}
+}
+
+
+/// Offsets to try when no mipmap can be read at `offsets`, as described in
+/// [`PaaImage::read_from`][`crate::PaaImage::read_from`]: shifted so that the
+/// first one is `header_end`, which repairs files of some third-party
+/// packers, or none at all.
+pub(crate) fn repair_candidates(offsets: &[u32], header_end: u64) -> PaaResult<Vec<(OffsetsRepair, Vec<u32>)>> {
+	let shift = i64::try_from(header_end)? - i64::from(offsets[0]);
+	let relative = offsets.iter()
+		.map(|o| u32::try_from(i64::from(*o) + shift).ok().filter(|o| *o != 0))
+		.collect::<Option<Vec<u32>>>();
+
+	Ok(relative
+		.map(|r| (OffsetsRepair::HeaderRelative, r))
+		.into_iter()
+		.chain([(OffsetsRepair::Sequential, vec![])])
+		.collect())
+}
+
+
+/// Best of the [`repair_candidates`] tried: the one reading the most mipmaps
+#[derive(Debug, Default)]
+pub(crate) struct RepairSearch {
+	best: Option<(OffsetsRepair, Vec<u32>)>,
+	best_count: usize,
+}
+
+
+impl RepairSearch {
+	/// Record that `candidate` read `count` mipmaps.
+	pub(crate) fn record(&mut self, candidate: (OffsetsRepair, Vec<u32>), count: usize) {
+		// Header-relative offsets win ties, since they repair the file exactly
+		if count > self.best_count {
+			self.best = Some(candidate);
+			self.best_count = count;
+		};
+	}
+
+
+	/// The repair reading the most mipmaps, and the offsets to read them at,
+	/// unless none reads any.
+	pub(crate) fn best(self) -> Option<(OffsetsRepair, Vec<u32>)> {
+		self.best
+	}
+}
+
+
+#[test]
+fn taggs_parser_resumes() {
+	use crate::{Bgra8888Pixel, PaaImage, PaaMipmap, Transparency};
+
+	let mipmap = PaaMipmap {
+		width: 4,
+		height: 4,
+		paatype: PaaType::Argb8888,
+		compression: PaaMipmapCompression::Uncompressed,
+		data: vec![0x40; 64],
+		payload: None,
+	};
+	let taggs = vec![Tagg::Avgc { rgba: Bgra8888Pixel::default() }, Tagg::Flag { transparency: Transparency::None }];
+	let image = PaaImage { paatype: PaaType::Argb8888, taggs, mipmaps: vec![Ok(mipmap)], ..Default::default() };
+	let bytes = image.to_bytes().unwrap();
+	let taggs = &bytes[2..];
+
+	let parse_all = |step: usize| {
+		let mut parser = TaggsParser::new(PaaLimits::default());
+		let mut len = 0;
+
+		loop {
+			match parser.parse(&taggs[..len], len == taggs.len()).unwrap() {
+				Parse::Complete(parsed, consumed) => return (parsed, consumed),
+				Parse::Incomplete(needed) => {
+					assert!(needed > len);
+					len = needed.min(len + step).min(taggs.len());
+				},
+			};
+		};
+	};
+
+	let (whole, consumed) = parse_all(usize::MAX);
+	assert!(whole.len() >= 2);
+
+	for step in [1, 3, 12] {
+		assert_eq!(parse_all(step), (whole.clone(), consumed));
+	};
+
+	// Taggs end at a short read at the end of file, as with the blocking reader
+	let (parsed, _) = drive(&mut &taggs[..consumed + 4], &mut vec![], |buf, eof| TaggsParser::new(PaaLimits::default()).parse(buf, eof)).unwrap();
+	assert_eq!(parsed, whole);
+}
+
+
+#[test]
+fn legacy_lzss_length_within_file() {
+	let lzss_data = [0u8, 0].into_iter().chain([7; 14]).collect::<Vec<u8>>();
+	let checksum = lzss_data.iter().fold(0i32, |sum, b| sum.wrapping_add(i32::from(*b)));
+
+	// All literals; the flags and first two read as a length of 255
+	let mut block = vec![0xD2, 0x04, 0x3D, 0x22, 4, 0, 4, 0, 0xFF];
+	block.extend(&lzss_data[..8]);
+	block.push(0xFF);
+	block.extend(&lzss_data[8..]);
+	block.extend(checksum.to_le_bytes());
+
+	let mut buf = block.clone();
+	buf.extend([0; 300]);
+
+	let parsed = parse_mipmap(&buf, true, PaaType::IndexPalette, CompressionPolicy::default()).unwrap();
+	assert!(matches!(parsed, Parse::Complete((head, 8), consumed) if head.payload_len == block.len() - 8 && consumed == block.len()));
+}
@@ -0,0 +1,145 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::{PaaImage, PaaResult, PaaType};
+
+
+/// Signature starting every serialized [`Tagg`][`crate::Tagg`]
+const TAGG_SIGNATURE: &[u8; 4] = b"GGAT";
+
+
+/// What the first bytes of a file say about it, as returned by [`sniff`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaaSniff {
+	/// Type given by the magic bytes, or guessed for legacy files.
+	pub paatype: PaaType,
+	/// A tagg immediately follows the magic bytes.  Few other files start with
+	/// a valid magic and a tagg signature.
+	pub has_tagg: bool,
+	/// Width and height of the first mipmap, if an empty palette and a mipmap
+	/// with power-of-two dimensions immediately follow the magic bytes, as in
+	/// PAAs without taggs.
+	pub first_mipmap: Option<(u16, u16)>,
+	/// The file has no magic bytes and starts with a tagg, as legacy PACs of
+	/// [`PaaType::IndexPalette`] do; this is a best-effort guess.
+	pub legacy: bool,
+}
+
+
+impl PaaSniff {
+	/// Return true if more than the magic bytes look like a PAA: a tagg or a
+	/// plausible first mipmap follows them, or the file is a legacy PAC.
+	pub fn is_probably_paa(&self) -> bool {
+		self.has_tagg || self.first_mipmap.is_some() || self.legacy
+	}
+}
+
+
+/// Identify a PAA from `prefix`, the first bytes of a file, or return [`None`]
+/// if it does not start with the magic bytes of a [`PaaType`] (or a tagg).
+///
+/// Two bytes are enough to read the magic; 8 bytes also tell whether a tagg
+/// or a mipmap follows.  Nothing is allocated.
+///
+/// # Example
+/// ```
+/// # use a3_paa::{sniff, PaaType};
+/// let sniffed = sniff(b"\x05\xFFGGATCGVA").unwrap();
+/// assert_eq!(sniffed.paatype, PaaType::Dxt5);
+/// assert!(sniffed.has_tagg);
+/// assert_eq!(sniff(b"\x89PNG\r\n\x1A\n"), None);
+/// ```
+pub fn sniff(prefix: &[u8]) -> Option<PaaSniff> {
+	if prefix.starts_with(TAGG_SIGNATURE) {
+		return Some(PaaSniff { paatype: PaaType::IndexPalette, has_tagg: true, first_mipmap: None, legacy: true });
+	};
+
+	let paatype = match prefix {
+		[a, b, ..] => PaaType::from_magic([*a, *b])?,
+		_ => return None,
+	};
+
+	let has_tagg = prefix[2..].starts_with(TAGG_SIGNATURE);
+
+	let first_mipmap = match prefix[2..] {
+		[0, 0, w0, w1, h0, h1, ..] => {
+			// The highest bit of the width flags LZO-compressed DXTn mipmaps
+			let width = u16::from_le_bytes([w0, w1]) & if paatype.is_dxtn() { 0x7FFF } else { 0xFFFF };
+			let height = u16::from_le_bytes([h0, h1]);
+			Some((width, height)).filter(|_| width.is_power_of_two() && height.is_power_of_two())
+		},
+		_ => None,
+	};
+
+	Some(PaaSniff { paatype, has_tagg, first_mipmap, legacy: false })
+}
+
+
+impl PaaImage {
+	/// Read the first 16 bytes of the file at `path`, and return true if
+	/// [`sniff`] finds them [probably a PAA][`PaaSniff::is_probably_paa`].
+	///
+	/// # Errors
+	/// - [`UnexpectedIoError`][`crate::PaaError::UnexpectedIoError`]: The file
+	///   could not be opened or read.
+	pub fn is_probably_paa<P: AsRef<Path>>(path: P) -> PaaResult<bool> {
+		let mut prefix = [0u8; 16];
+		let mut len = 0;
+		let mut file = std::fs::File::open(path)?;
+
+		while len < prefix.len() {
+			match file.read(&mut prefix[len..]) {
+				Ok(0) => break,
+				Ok(n) => len += n,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e.into()),
+			};
+		};
+
+		Ok(sniff(&prefix[..len]).map_or(false, |s| s.is_probably_paa()))
+	}
+}
+
+
+#[test]
+fn sniff_fixtures() {
+	let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/fixtures");
+
+	for (name, paatype) in [("checker_dxt1", PaaType::Dxt1), ("gradient_argb8888", PaaType::Argb8888), ("ramp_ai88", PaaType::Ai88)] {
+		let path = fixtures.join(name).with_extension("paa");
+		let sniffed = sniff(&std::fs::read(&path).unwrap()).unwrap();
+		assert_eq!(sniffed, PaaSniff { paatype, has_tagg: false, first_mipmap: Some((4, 4)), legacy: false });
+		assert!(PaaImage::is_probably_paa(&path).unwrap());
+		assert!(!PaaImage::is_probably_paa(fixtures.join(name).with_extension("png")).unwrap());
+	};
+}
+
+
+#[test]
+fn sniff_prefixes() {
+	let mut paa = vec![0x05, 0xFF];
+	paa.extend(crate::Tagg::Offs { offsets: vec![0; 16] }.to_bytes().unwrap());
+	assert_eq!(sniff(&paa), Some(PaaSniff { paatype: PaaType::Dxt5, has_tagg: true, first_mipmap: None, legacy: false }));
+	assert!(sniff(&paa[..16]).unwrap().is_probably_paa());
+
+	// The magic alone is a weak signal
+	let magic_only = sniff(&paa[..2]).unwrap();
+	assert_eq!(magic_only.paatype, PaaType::Dxt5);
+	assert!(!magic_only.is_probably_paa());
+	assert!(!sniff(&[0x05, 0xFF, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC]).unwrap().is_probably_paa());
+
+	// LZO-compressed DXTn mipmaps flag their width
+	assert_eq!(sniff(&[0x01, 0xFF, 0, 0, 0x00, 0x82, 0x00, 0x02]).unwrap().first_mipmap, Some((512, 512)));
+
+	assert!(sniff(&paa[2..]).unwrap().legacy);
+	assert_eq!(sniff(&[0x05]), None);
+	assert_eq!(sniff(&[]), None);
+	assert_eq!(sniff(b"\x89PNG\r\n\x1A\n\0\0\0\x0DIHDR"), None);
+
+	let dir = std::env::temp_dir().join(format!("a3-paa-sniff-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join("truncated.paa"), [0x05]).unwrap();
+	assert!(!PaaImage::is_probably_paa(dir.join("truncated.paa")).unwrap());
+	assert!(PaaImage::is_probably_paa(dir.join("missing.paa")).is_err());
+	std::fs::remove_dir_all(&dir).unwrap();
+}
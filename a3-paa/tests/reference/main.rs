@@ -0,0 +1,168 @@
+//! Differential test of the decoder against reference PNGs.
+//!
+//! Every `<name>.paa` in a directory is paired with `<name>.png`, as exported
+//! by TexView 2.  The first mipmap is decoded and compared to the PNG: pixel
+//! for pixel for lossless types, and with a PSNR above [`DXT_MIN_PSNR`] for
+//! DXTn, whose decoders round interpolated colors differently.  Every PAA must
+//! also end with exactly [`PaaImage::TERMINATOR`].
+//!
+//! The tiny hand-made pairs in `fixtures/` always run; `fixtures/unpaired/`
+//! holds PAA files for other tests, without a reference.  Set
+//! `A3_PAA_REFERENCE_DIR` to a directory of TexView pairs to check those too;
+//! they are too large to keep in the repository.  Run with `--nocapture` for
+//! the per-file report.
+
+#![cfg(feature = "image")]
+
+use std::path::{Path, PathBuf};
+
+use a3_paa::{PaaDecoder, PaaImage, PaaType};
+use a3_paa::imageops::{image_difference, ImageDifference};
+
+
+/// Lowest PSNR, in decibels, of a DXTn mipmap against its reference
+const DXT_MIN_PSNR: f64 = 40.0;
+
+
+/// Result of comparing a decoded PAA to its reference PNG
+#[derive(Debug, PartialEq)]
+enum Outcome {
+	Identical,
+	WithinTolerance(f64),
+	Mismatch(String),
+}
+
+
+/// Judge the difference between a decoded mipmap of `paatype` and its
+/// reference.
+fn judge(paatype: PaaType, difference: &ImageDifference) -> Outcome {
+	if difference.max_error == [0; 4] {
+		return Outcome::Identical;
+	};
+
+	if !paatype.is_dxtn() {
+		return Outcome::Mismatch(format!("Lossless {paatype:?} differs by up to {:?} (RGBA)", difference.max_error));
+	};
+
+	match difference.total_psnr() {
+		psnr if psnr > DXT_MIN_PSNR => Outcome::WithinTolerance(psnr),
+		psnr => Outcome::Mismatch(format!("PSNR {psnr:.2} dB, expected above {DXT_MIN_PSNR} dB")),
+	}
+}
+
+
+fn compare(paa: &Path, png: &Path) -> Outcome {
+	let image = match std::fs::read(paa).map_err(|e| e.to_string()).and_then(|b| PaaImage::from_bytes(&b).map_err(|e| e.to_string())) {
+		Ok(i) => i,
+		Err(e) => return Outcome::Mismatch(format!("Could not read PAA: {e}")),
+	};
+
+	if image.trailer != PaaImage::TERMINATOR {
+		return Outcome::Mismatch(format!("Ends with {:02X?} instead of the terminator", image.trailer));
+	};
+
+	let paatype = image.paatype;
+
+	let decoded = match PaaDecoder::with_paa(image).decode_first() {
+		Ok(d) => d,
+		Err(e) => return Outcome::Mismatch(format!("Could not decode first mipmap: {e}")),
+	};
+
+	let expected = match image::open(png) {
+		Ok(i) => i.into_rgba8(),
+		Err(e) => return Outcome::Mismatch(format!("Could not open {}: {e}", png.display())),
+	};
+
+	match image_difference(&decoded, &expected) {
+		Some(difference) => judge(paatype, &difference),
+		None => Outcome::Mismatch(format!("Decoded {:?}, reference is {:?}", decoded.dimensions(), expected.dimensions())),
+	}
+}
+
+
+/// `(paa, png)` pairs in `dir`, sorted by name.
+fn pairs_in(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+	let mut pairs = std::fs::read_dir(dir)
+		.unwrap_or_else(|e| panic!("Could not read {}: {e}", dir.display()))
+		.map(|e| e.unwrap().path())
+		.filter(|p| p.extension().map_or(false, |e| e.eq_ignore_ascii_case("paa")))
+		.map(|paa| (paa.clone(), paa.with_extension("png")))
+		.collect::<Vec<_>>();
+
+	pairs.sort();
+	pairs
+}
+
+
+/// Compare every pair in `dir`, printing one report line per pair, and
+/// return the number of mismatches.
+fn check_dir(dir: &Path) -> usize {
+	let pairs = pairs_in(dir);
+	assert!(!pairs.is_empty(), "No PAA files in {}", dir.display());
+
+	let mut mismatches = 0;
+
+	for (paa, png) in &pairs {
+		let name = paa.file_name().unwrap().to_string_lossy();
+
+		match compare(paa, png) {
+			Outcome::Identical => println!("ok   {name}: identical"),
+			Outcome::WithinTolerance(psnr) => println!("ok   {name}: PSNR {psnr:.2} dB"),
+			Outcome::Mismatch(reason) => {
+				println!("FAIL {name}: {reason}");
+				mismatches += 1;
+			},
+		};
+	};
+
+	println!("{}: {} of {} pairs match", dir.display(), pairs.len() - mismatches, pairs.len());
+
+	mismatches
+}
+
+
+#[test]
+fn committed_fixtures() {
+	let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/fixtures");
+	assert_eq!(check_dir(&dir), 0);
+}
+
+
+#[test]
+fn reference_dir() {
+	match std::env::var_os("A3_PAA_REFERENCE_DIR") {
+		Some(dir) => assert_eq!(check_dir(Path::new(&dir)), 0),
+		None => eprintln!("A3_PAA_REFERENCE_DIR is not set; skipping TexView reference pairs"),
+	};
+}
+
+
+#[test]
+fn tolerance() {
+	let difference = |max_error, mean_squared_error| ImageDifference { max_error, mean_error: [0.0; 4], mean_squared_error };
+
+	let identical = difference([0; 4], [0.0; 4]);
+	assert_eq!(judge(PaaType::Argb8888, &identical), Outcome::Identical);
+	assert_eq!(judge(PaaType::Dxt5, &identical), Outcome::Identical);
+
+	// Off by one everywhere: PSNR 48 dB
+	let rounding = difference([1; 4], [1.0; 4]);
+	assert!(matches!(judge(PaaType::Argb4444, &rounding), Outcome::Mismatch(_)));
+	assert!(matches!(judge(PaaType::Dxt1, &rounding), Outcome::WithinTolerance(p) if p > 48.0));
+
+	// Off by 16 everywhere: PSNR 24 dB
+	let blocky = difference([16; 4], [256.0; 4]);
+	assert!(matches!(judge(PaaType::Dxt1, &blocky), Outcome::Mismatch(_)));
+
+	let dir = std::env::temp_dir().join(format!("a3-paa-reference-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/fixtures");
+
+	// A missing reference, or one of other dimensions, is a mismatch
+	std::fs::copy(fixtures.join("checker_dxt1.paa"), dir.join("missing.paa")).unwrap();
+	std::fs::copy(fixtures.join("checker_dxt1.paa"), dir.join("resized.paa")).unwrap();
+	image::RgbaImage::new(8, 4).save(dir.join("resized.png")).unwrap();
+	assert_eq!(check_dir(&dir), 2);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
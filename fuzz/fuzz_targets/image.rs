@@ -0,0 +1,23 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use a3_paa::{PaaImage, Tagg};
+
+
+fuzz_target!(|image: PaaImage| {
+	let bytes = image.to_bytes().unwrap();
+	let imagep = PaaImage::from_bytes(&bytes).unwrap();
+
+	// OFFSTAGG is regenerated by to_bytes
+	let without_offs = |taggs: &[Tagg]| taggs.iter().filter(|t| !matches!(t, Tagg::Offs { .. })).cloned().collect::<Vec<_>>();
+
+	assert_eq!(image.paatype, imagep.paatype);
+	assert_eq!(Tagg::canonicalize(&image.taggs), without_offs(&imagep.taggs));
+	assert_eq!(image.mipmaps.len(), imagep.mipmaps.len());
+
+	for (mip, mipp) in image.mipmaps.iter().zip(&imagep.mipmaps) {
+		let (mip, mipp) = (mip.as_ref().unwrap(), mipp.as_ref().unwrap());
+		assert_eq!(mip.width, mipp.width);
+		assert_eq!(mip.height, mipp.height);
+		assert_eq!(mip.data, mipp.data);
+	};
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use a3_paa::TextureHints;
+
+fuzz_target!(|data: &str| {
+	if let Ok(hints) = TextureHints::try_parse_from_str(data) {
+		let _ = hints.settings_for_filename("fuzz_co.paa");
+	};
+});
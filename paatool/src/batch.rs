@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::report::BatchError;
+use crate::stdio;
+
+
+/// Input and output path of a single conversion
+pub type Job = (PathBuf, PathBuf);
+
+
+/// Collect conversion jobs from the `paths` argument and the `--out-dir`
+/// option.
+///
+/// Without `--out-dir`, `paths` must be exactly one input and one output path.
+/// With it, every path is an input file, or a directory whose files accepted
+/// by `is_input` are converted; outputs are named after their inputs, with
+/// the extension swapped for `out_extension`.  Inputs that would be written
+/// to the same output are rejected, and so is "-" (standard input), which
+/// can only be used without `--out-dir`.
+pub fn collect_jobs(matches: &clap::ArgMatches, is_input: fn(&Path) -> bool, out_extension: &str) -> AnyhowResult<Vec<Job>> {
+	let paths: Vec<&str> = matches.values_of("paths").expect("PATHS required").collect();
+
+	let out_dir = if let Some(out_dir) = matches.value_of("out_dir") {
+		PathBuf::from(out_dir)
+	}
+	else {
+		return match paths[..] {
+			[input, output] => Ok(vec![(input.into(), output.into())]),
+			_ => Err(anyhow::anyhow!("Expected an input and an output path; use --out-dir to convert multiple files")),
+		};
+	};
+
+	if paths.iter().any(|p| stdio::is_stdio(Path::new(p))) {
+		return Err(anyhow::anyhow!("\"-\" (standard input) cannot be used with --out-dir"));
+	};
+
+	let mut jobs = vec![];
+	let mut outputs: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+	for path in paths.iter().map(PathBuf::from) {
+		let inputs = if path.is_dir() {
+			let mut entries = std::fs::read_dir(&path)
+				.with_context(|| format!("{path:?}: Could not list directory"))?
+				.map(|e| e.map(|e| e.path()))
+				.collect::<Result<Vec<PathBuf>, _>>()
+				.with_context(|| format!("{path:?}: Could not list directory"))?;
+			entries.retain(|p| p.is_file() && is_input(p));
+			entries.sort();
+			entries
+		}
+		else {
+			vec![path]
+		};
+
+		for input in inputs {
+			let output = output_path(&out_dir, &input, out_extension)?;
+
+			if let Some(other) = outputs.insert(output.clone(), input.clone()) {
+				return Err(anyhow::anyhow!("{other:?} and {input:?} would both be written to {output:?}"));
+			};
+
+			jobs.push((input, output));
+		};
+	};
+
+	Ok(jobs)
+}
+
+
+/// Output path of `input` in `out_dir`: its file name, with the extension
+/// swapped for `out_extension`.
+pub fn output_path(out_dir: &Path, input: &Path, out_extension: &str) -> AnyhowResult<PathBuf> {
+	let name = input.file_name().with_context(|| format!("{input:?}: Input path has no file name"))?;
+	Ok(out_dir.join(name).with_extension(out_extension))
+}
+
+
+/// Number of worker threads from the `--jobs` option, defaulting to the
+/// number of logical CPUs.
+pub fn thread_count(matches: &clap::ArgMatches) -> AnyhowResult<usize> {
+	if let Some(jobs) = matches.value_of("jobs") {
+		jobs.parse::<usize>()
+			.ok()
+			.filter(|n| *n > 0)
+			.with_context(|| format!("Could not parse job count from \"{jobs}\""))
+	}
+	else {
+		Ok(std::thread::available_parallelism().map_or(1, NonZeroUsize::get))
+	}
+}
+
+
+/// Run `convert` on a single job, creating a missing output directory.  Log
+/// lines are prefixed by the input path; a failure is returned, not logged.
+pub fn run_job<F>(input: &Path, output: &Path, convert: &F) -> AnyhowResult<()>
+where
+	F: Fn(&Path, &Path) -> AnyhowResult<()>,
+{
+	let _span = tracing::error_span!("file", path = ?input).entered();
+
+	if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+		std::fs::create_dir_all(parent).with_context(|| format!("{parent:?}: Could not create output directory"))?;
+	};
+
+	convert(input, output)
+}
+
+
+/// Log the failure of the job reading `input`, one line per cause.
+pub fn log_failure(input: &Path, error: &anyhow::Error) {
+	for (depth, cause) in error.chain().enumerate() {
+		let suffix = if depth == 0 { "" } else { "... " };
+		tracing::error!("{input:?}: {suffix}{cause}");
+	};
+}
+
+
+/// Run `convert` on every job on up to `threads` threads with [`run_job`].
+/// Failures are logged in job order after all jobs have run, and the result
+/// fails with a [`BatchError`] if any job did; a single job fails with a
+/// [`BatchError`] displaying as its own error, left for the caller to log.
+pub fn run_jobs<F>(jobs: &[Job], threads: usize, convert: F) -> AnyhowResult<()>
+where
+	F: Fn(&Path, &Path) -> AnyhowResult<()> + Sync,
+{
+	let run = |input: &Path, output: &Path| run_job(input, output, &convert);
+
+	if let [(input, output)] = jobs {
+		return run(input, output).or_else(|e| BatchError::check(vec![(input.clone(), e)], 1));
+	};
+
+	let (run, next) = (&run, &AtomicUsize::new(0));
+
+	let mut results: Vec<(usize, AnyhowResult<()>)> = std::thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads.clamp(1, jobs.len().max(1)))
+			.map(|_| scope.spawn(move || {
+				let mut done = vec![];
+
+				loop {
+					let index = next.fetch_add(1, Ordering::Relaxed);
+					let (input, output) = if let Some(job) = jobs.get(index) { job } else { break; };
+					done.push((index, run(input, output)));
+				};
+
+				done
+			}))
+			.collect();
+
+		workers.into_iter()
+			.flat_map(|w| w.join().expect("Worker thread panicked"))
+			.collect()
+	});
+
+	results.sort_by_key(|(index, _)| *index);
+
+	let mut failures = vec![];
+
+	for (index, result) in results {
+		if let Err(e) = result {
+			let input = &jobs[index].0;
+			log_failure(input, &e);
+			failures.push((input.clone(), e));
+		};
+	};
+
+	tracing::info!("{} ok, {} failed", jobs.len() - failures.len(), failures.len());
+
+	BatchError::check(failures, jobs.len())
+}
@@ -0,0 +1,122 @@
+use a3_paa::*;
+use a3_paa::imageops::{image_difference, ImageDifference};
+use anyhow::{Context, Result as AnyhowResult};
+
+
+pub fn command_compare(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let a_path = matches.value_of("a").expect("A required");
+	let b_path = matches.value_of("b").expect("B required");
+
+	let mip_idx = matches.value_of("mipmap")
+		.map(|m| m.parse::<usize>()
+			.ok()
+			.filter(|i| *i > 0)
+			.with_context(|| format!("Could not parse mipmap index from \"{m}\"")))
+		.transpose()?;
+	let max_mae = parse_threshold(matches, "max_mae")?;
+	let min_psnr = parse_threshold(matches, "min_psnr")?;
+
+	let a = read_paa(a_path)?;
+	let b = read_paa(b_path)?;
+
+	diff_taggs(&a.taggs, &b.taggs);
+
+	let (a_count, b_count) = (mipmap_count(&a), mipmap_count(&b));
+	let common = a_count.min(b_count);
+
+	if a_count != b_count {
+		tracing::warn!("{a_path} has {a_count} mipmaps, {b_path} has {b_count}; comparing the first {common}");
+	};
+
+	let indices = match mip_idx {
+		Some(i) if i > common => anyhow::bail!("Mipmap index {i} out of range (should be in [1..{common}])"),
+		Some(i) => i - 1..i,
+		None => 0..common,
+	};
+
+	let (a, b) = (PaaDecoder::with_paa(a), PaaDecoder::with_paa(b));
+	let mut failed = false;
+
+	for index in indices {
+		let mip_idx = index + 1;
+		let a_image = a.decode_nth(index).with_context(|| format!("{a_path}: Could not decode mipmap #{mip_idx}"))?;
+		let b_image = b.decode_nth(index).with_context(|| format!("{b_path}: Could not decode mipmap #{mip_idx}"))?;
+
+		let (width, height) = a_image.dimensions();
+		let diff = image_difference(&a_image, &b_image)
+			.with_context(|| format!("Mipmap #{mip_idx} is {width}x{height} in {a_path}, but {}x{} in {b_path}", b_image.width(), b_image.height()))?;
+
+		println!("Mipmap #{mip_idx} ({width}x{height}): {}", format_difference(&diff));
+
+		let (mae, psnr) = (diff.total_mean_error(), diff.total_psnr());
+
+		if let Some(max_mae) = max_mae.filter(|m| mae > *m) {
+			tracing::error!("Mipmap #{mip_idx}: mean absolute error {mae:.3} exceeds {max_mae}");
+			failed = true;
+		};
+
+		if let Some(min_psnr) = min_psnr.filter(|m| psnr < *m) {
+			tracing::error!("Mipmap #{mip_idx}: PSNR {psnr:.2} dB is below {min_psnr} dB");
+			failed = true;
+		};
+	};
+
+	if failed {
+		return Err(anyhow::anyhow!("{a_path} and {b_path} differ beyond the given thresholds"));
+	};
+
+	Ok(())
+}
+
+
+fn parse_threshold(matches: &clap::ArgMatches, name: &str) -> AnyhowResult<Option<f64>> {
+	matches.value_of(name)
+		.map(|v| v.parse::<f64>().with_context(|| format!("Could not parse threshold from \"{v}\"")))
+		.transpose()
+}
+
+
+fn read_paa(path: &str) -> AnyhowResult<PaaImage> {
+	let mut file = std::fs::File::open(path).with_context(|| format!("Could not open file: {path}"))?;
+	PaaImage::read_from(&mut file).with_context(|| format!("Could not read PaaImage: {path}"))
+}
+
+
+/// Number of mipmaps, not counting the empty mipmap ending a sequentially
+/// read PAA.
+fn mipmap_count(image: &PaaImage) -> usize {
+	match image.mipmaps.last() {
+		Some(Err(PaaError::EmptyMipmap)) => image.mipmaps.len() - 1,
+		_ => image.mipmaps.len(),
+	}
+}
+
+
+/// Print taggs present in only one of the PAAs, or differing between them.
+/// Offsets are not compared, as they follow from mipmap compression.
+fn diff_taggs(a: &[Tagg], b: &[Tagg]) {
+	let find = |taggs: &[Tagg], name: &str| taggs.iter().find(|t| t.as_taggname() == name).cloned();
+	let mut names: Vec<&str> = a.iter().chain(b).map(Tagg::as_taggname).collect();
+	names.sort_unstable();
+	names.dedup();
+
+	for name in names.into_iter().filter(|n| *n != "SFFO") {
+		match (find(a, name), find(b, name)) {
+			(Some(a), Some(b)) if a == b => (),
+			(Some(a), Some(b)) => println!("Tagg differs: {a} -> {b}"),
+			(Some(a), None) => println!("Tagg only in A: {a}"),
+			(None, Some(b)) => println!("Tagg only in B: {b}"),
+			(None, None) => unreachable!(),
+		};
+	};
+}
+
+
+fn format_difference(diff: &ImageDifference) -> String {
+	let psnr = diff.psnr().map(|p| if p.is_finite() { format!("{p:.2}") } else { "inf".into() });
+
+	format!("max error {:?}, MAE [{:.3}, {:.3}, {:.3}, {:.3}], PSNR [{}] dB",
+		diff.max_error,
+		diff.mean_error[0], diff.mean_error[1], diff.mean_error[2], diff.mean_error[3],
+		psnr.join(", "))
+}
@@ -0,0 +1,15 @@
+use anyhow::Result as AnyhowResult;
+use clap_complete::Shell;
+
+
+pub fn command_completions(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let shell = matches.value_of("shell")
+		.unwrap()
+		.parse::<Shell>()
+		.map_err(anyhow::Error::msg)?;
+
+	let mut app = crate::construct_app();
+	clap_complete::generate(shell, &mut app, "paatool", &mut std::io::stdout());
+
+	Ok(())
+}
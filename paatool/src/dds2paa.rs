@@ -1,10 +1,26 @@
-use std::fs::File;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
 
-use a3_paa::{PaaType, PaaError, PaaResult, PaaMipmap, PaaImage};
-use anyhow::{Context, Error as AnyhowError, Result as AnyhowResult};
-use ddsfile::{Dds, D3DFormat, DxgiFormat};
+use a3_paa::{dds_layer_count, PaaType, PaaImage};
+use anyhow::{Context, Result as AnyhowResult};
+use ddsfile::Dds;
 use tap::prelude::*;
 
+use crate::stdio;
+
+
+/// Suffixes of the +X, -X, +Y, -Y, +Z and -Z cube map faces, in DDS order
+const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+
+/// How every layer of a DDS file is converted to PAA
+#[derive(Debug, Clone, Copy)]
+struct LayerConversion {
+	compress: Option<PaaType>,
+	gen_mips: bool,
+	taggs: bool,
+}
+
 
 pub fn command_dds2paa(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 	let dds_path = matches.value_of("dds").expect("DDS required");
@@ -13,64 +29,122 @@ pub fn command_dds2paa(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 		.value_of("layer")
 		.map_or(Ok(1), |l| l.parse::<u32>().context(format!("Could not parse layer index: {l}")))
 		.tap_ok(|i| tracing::trace!("Requested layer: {i}"))?;
+	let compress = matches
+		.value_of("compress")
+		.map(|c| c.parse::<PaaType>().ok().context(format!("Could not parse PAA type: {c}")))
+		.transpose()?;
 
-	let dds_file = File::open(dds_path)
+	let dds_data = stdio::read(Path::new(dds_path))
 		.context(format!("{dds_path}: Could not open DDS file"))?;
-	let dds = Dds::read(dds_file)
+	let dds = Dds::read(Cursor::new(dds_data))
 		.context(format!("{dds_path}: Could not parse DDS file"))?;
 
 	let d3dfmt = dds.get_d3d_format().map_or("None".into(), |f| format!("{f:?}"));
 	let dxgifmt = dds.get_dxgi_format().map_or("None".into(), |f| format!("{f:?}"));
 	let (w, h) = (dds.get_width(), dds.get_height());
-	let levels = dds.get_num_array_layers();
+	let (levels, cubemap) = dds_layer_count(&dds);
 	let mips = dds.get_num_mipmap_levels();
-	tracing::info!("{dds_path}: {d3dfmt}/{dxgifmt}, {w}x{h}, {levels} layers, {mips} mipmaps");
-
-	#[allow(deprecated)]
-	let paatype = match (dds.get_d3d_format(), dds.get_dxgi_format()) {
-		(Some(D3DFormat::DXT1), _) | (_, Some(DxgiFormat::BC1_UNorm_sRGB)) => PaaType::Dxt1,
-		(Some(D3DFormat::DXT2), _) => PaaType::Dxt2,
-		(Some(D3DFormat::DXT3), _) | (_, Some(DxgiFormat::BC2_UNorm_sRGB)) => PaaType::Dxt3,
-		(Some(D3DFormat::DXT4), _) => PaaType::Dxt4,
-		(Some(D3DFormat::DXT5), _) | (_, Some(DxgiFormat::BC3_UNorm_sRGB)) => PaaType::Dxt5,
-		f => anyhow::bail!("DDS to PAA conversion not implemented for this D3D format: {f:?}"),
+	let kind = if cubemap { "cube map faces" } else { "layers" };
+	tracing::info!("{dds_path}: {d3dfmt}/{dxgifmt}, {w}x{h}, {levels} {kind}, {mips} mipmaps");
+
+	let conversion = LayerConversion {
+		compress,
+		gen_mips: matches.is_present("gen_mips"),
+		taggs: !matches.is_present("no_taggs"),
 	};
 
-	let data = dds.get_data(layer-1)
-		.context(format!("Could not get data for layer {layer}"))?;
-	let mut width: u16 = w.try_into().context("Width overflows a u16")?;
-	let mut height: u16 = h.try_into().context("Height overflows a u16")?;
-	let mut mip_size = paatype.predict_size(width, height);
-	let mut cursor: usize = 0;
-	let mut mipmaps: Vec<PaaResult<PaaMipmap>> = vec![];
-
-	for i in 0..mips {
-		if width < 4 || height < 4 {
-			tracing::info!("One or both DXT dimensions less than 4, stopping at previous mipmap: {width}x{height}");
-			break;
+	if !matches.is_present("all_layers") {
+		if layer == 0 || layer > levels {
+			anyhow::bail!("Layer index {layer} out of range (should be in [1..{levels}])");
 		};
 
-		if width % 4 != 0 || height % 4 != 0 {
-			let err = PaaError::DxtMipmapDimensionsNotMultipleOf4(width, height);
-			return AnyhowResult::Err(AnyhowError::new(err));
-		};
+		let data = convert_layer(&dds, layer, &conversion)?;
+		stdio::write(Path::new(paa_path), &data).context(format!("{paa_path}: Could not write PAA data"))?;
+
+		return Ok(());
+	};
 
-		let compression = PaaMipmap::suggest_compression(paatype, width, height);
-		let left = cursor;
-		let right = cursor + mip_size;
-		let data = &data[left..right];
-		let mip = PaaMipmap { width, height, compression, paatype, data: data.to_owned() };
-		mipmaps.push(Ok(mip));
-
-		cursor += mip_size;
-		mip_size /= 4;
-		width /= 2;
-		height /= 2;
+	if stdio::is_stdio(Path::new(paa_path)) {
+		anyhow::bail!("--all-layers writes multiple files, and cannot write to standard output");
 	};
 
-	let paa = PaaImage { paatype, taggs: vec![], palette: None, mipmaps };
-	let data = paa.to_bytes().context("Could not serialize PAA")?;
-	std::fs::write(paa_path, &data).context("{paa_path}: Could not write PAA data")?;
+	let default_template = if cubemap && levels == 6 { "{stem}_{face}" } else { "{stem}_{layer}" };
+	let template = matches.value_of("name_template").unwrap_or(default_template);
+	let outputs = (1..=levels)
+		.map(|l| layer_path(Path::new(paa_path), template, l, cubemap))
+		.collect::<AnyhowResult<Vec<PathBuf>>>()?;
+
+	if (1..outputs.len()).any(|i| outputs[..i].contains(&outputs[i])) {
+		anyhow::bail!("Name template \"{template}\" gives several layers the same output path");
+	};
+
+	let mut failed = 0usize;
+
+	for (layer, output) in (1..=levels).zip(&outputs) {
+		let result = convert_layer(&dds, layer, &conversion)
+			.and_then(|data| stdio::write(output, &data).with_context(|| format!("{output:?}: Could not write PAA data")));
+
+		match result {
+			Ok(()) => tracing::info!("Layer {layer} -> {output:?}"),
+			Err(e) => {
+				failed += 1;
+
+				for (depth, cause) in e.chain().enumerate() {
+					let suffix = if depth == 0 { "" } else { "... " };
+					tracing::error!("Layer {layer}: {suffix}{cause}");
+				};
+			},
+		};
+	};
+
+	if failed > 0 {
+		return Err(anyhow::anyhow!("{failed} of {levels} layers failed to convert"));
+	};
 
 	Ok(())
 }
+
+
+/// Output path of `layer` (1-based): `template` with `{stem}` replaced by the
+/// file stem of `paa_path`, `{layer}` by the two-digit layer index, and
+/// `{face}` by the cube map face suffix, in the directory of `paa_path`.
+fn layer_path(paa_path: &Path, template: &str, layer: u32, cubemap: bool) -> AnyhowResult<PathBuf> {
+	let stem = paa_path.file_stem().with_context(|| format!("{paa_path:?}: Output path has no file name"))?;
+
+	if template.contains("{face}") && !cubemap {
+		anyhow::bail!("Name template \"{template}\" uses {{face}}, but the DDS file is not a cube map");
+	};
+
+	let face = CUBE_FACES[(layer as usize - 1) % CUBE_FACES.len()];
+	let name = template
+		.replace("{stem}", &stem.to_string_lossy())
+		.replace("{layer}", &format!("{layer:02}"))
+		.replace("{face}", face);
+
+	Ok(paa_path.with_file_name(name).with_extension("paa"))
+}
+
+
+/// Convert array layer or cube map face #`layer` (1-based) of `dds` to PAA
+/// data.
+fn convert_layer(dds: &Dds, layer: u32, conversion: &LayerConversion) -> AnyhowResult<Vec<u8>> {
+	let mut paa = PaaImage::from_dds(dds, layer - 1).context(format!("Could not convert layer {layer}"))?;
+
+	if conversion.gen_mips {
+		paa.mipmaps.truncate(1);
+		let generated = paa.generate_missing_mipmaps().context("Could not generate mipmaps")?;
+		tracing::info!("Generated {generated} mipmaps from the first one");
+	};
+
+	match conversion.compress {
+		Some(_) if paa.paatype.is_dxtn() => tracing::warn!("DDS is already DXTn compressed; ignoring --compress"),
+		Some(paatype) => paa = paa.transcode(paatype, None).context(format!("Could not encode layer {layer} to {paatype:?}"))?,
+		None => (),
+	};
+
+	if !conversion.taggs {
+		paa.taggs.clear();
+	};
+
+	paa.to_bytes().context("Could not serialize PAA")
+}
@@ -1,25 +1,201 @@
+use std::io::Cursor;
+use std::path::Path;
+
 use a3_paa::*;
+use a3_paa::imageops::color::{is_normal_map_suffix, srgb_to_linear_image};
 use anyhow::{Context, Result as AnyhowResult};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+
+use crate::{batch, stdio};
+use crate::report::{Stage, StageContext};
 
 
 pub fn command_decode(matches: &clap::ArgMatches) -> AnyhowResult<()> {
-	let paa_path = matches.value_of("paa").expect("PAA required");
-	let png_path = matches.value_of("png").expect("PNG required");
 	let mip_idx_str = matches.value_of("mipmap").unwrap_or("1");
 	let mip_idx = mip_idx_str.parse::<usize>()
 		.with_context(|| format!("Could not parse mipmap index from \"{mip_idx_str}\""))
 		.and_then(|i| if i > 0 { Ok(i) } else { Err(anyhow::anyhow!("Mipmap index cannot be 0")) })?;
+	let mipmaps = if matches.is_present("all") { None } else { Some(mip_idx) };
+
+	if matches.is_present("raw") || matches.is_present("dds") {
+		let paths: Vec<&str> = matches.values_of("paths").expect("PATHS required").collect();
+
+		let paa_path = match paths[..] {
+			[paa_path] => Path::new(paa_path),
+			_ => anyhow::bail!("--raw and --dds take the place of the output path; expected a single PAA input"),
+		};
+
+		return write_undecoded(mip_idx, paa_path, matches.value_of("raw").map(Path::new), matches.value_of("dds").map(Path::new));
+	};
+
+	let color = if matches.is_present("linear") { ColorOutput::Linear }
+		else if matches.is_present("srgb") { ColorOutput::Srgb }
+		else { ColorOutput::Unmanaged };
+
+	// Linear light needs a float format, EXR unless another is given
+	let format_str = if color == ColorOutput::Linear && matches.occurrences_of("format") == 0 { "exr" }
+		else { matches.value_of("format").expect("FORMAT has a default value") };
+	let format = ImageFormat::from_extension(format_str).expect("FORMAT values are image extensions");
+
+	if !format.can_write() {
+		return Err(anyhow::anyhow!("Writing {format:?} images is not supported"));
+	};
+
+	match (color, format) {
+		(ColorOutput::Srgb, ImageFormat::Png) | (ColorOutput::Linear, ImageFormat::OpenExr | ImageFormat::Tiff) | (ColorOutput::Unmanaged, _) => (),
+		(ColorOutput::Srgb, _) => anyhow::bail!("--srgb tags PNG output only, not {format:?}"),
+		(ColorOutput::Linear, _) => anyhow::bail!("--linear writes float EXR or TIFF output only, not {format:?}"),
+	};
+
+	let is_paa = |p: &Path| p.extension().map_or(false, |e| e.eq_ignore_ascii_case("paa"));
+	let jobs = batch::collect_jobs(matches, is_paa, format_str)?;
+
+	let options = DecodeOptions {
+		respect_flag: matches.is_present("respect_flag"),
+		unswizzle: matches.is_present("unswizzle"),
+		premultiply_alpha: matches.is_present("premultiply"),
+		opaque: matches.is_present("opaque"),
+	};
+
+	let threads = batch::thread_count(matches)?;
+
+	batch::run_jobs(&jobs, threads, |paa_path, out_path| decode_file(mipmaps, format, color, options, paa_path, out_path))
+}
+
+
+/// How decoded colors are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorOutput {
+	/// As stored, without color space information
+	Unmanaged,
+	/// As stored, in a PNG tagged as sRGB
+	Srgb,
+	/// Converted from sRGB to linear light, as 32-bit floats
+	Linear,
+}
+
+
+/// Decode mipmap #`mip_idx` (1-based) of `paa_path` to `out_path`, or every
+/// mipmap to `<out_path stem>_mipNN` if `mip_idx` is [`None`].
+fn decode_file(mip_idx: Option<usize>, format: ImageFormat, color: ColorOutput, options: DecodeOptions, paa_path: &Path, out_path: &Path) -> AnyhowResult<()> {
+	let paa_data = stdio::read(paa_path).stage(Stage::Parse)?;
+	let image = PaaImage::from_bytes(&paa_data).with_context(|| format!("Could not read PaaImage: {paa_path:?}")).stage(Stage::Parse)?;
+	let mip_count = image.mipmaps.len();
+
+	// Normal maps hold vectors, not colors
+	let is_normal_map = TextureHints::texture_filename_to_suffix(&paa_path).map_or(false, |s| is_normal_map_suffix(&s));
+
+	if is_normal_map && color != ColorOutput::Unmanaged {
+		tracing::debug!("{paa_path:?}: Normal map; writing its values without color conversion or tagging");
+	};
+
+	let decoder = PaaDecoder::with_paa(image).with_options(options);
 
-	let mut paa_file = std::fs::File::open(paa_path).with_context(|| format!("Could not open file: {paa_path}"))?;
-	let image = PaaImage::read_from(&mut paa_file).with_context(|| format!("Could not read PaaImage: {paa_path}"))?;
+	if options.unswizzle {
+		match decoder.swizzle() {
+			None => tracing::warn!("{paa_path:?}: --unswizzle requested, but the PAA has no SWIZTAGG; channels are left as is"),
+			Some(s) if s.lost_channels().is_empty() => (),
+			Some(s) => {
+				let lost: Vec<String> = s.lost_channels().iter().map(ToString::to_string).collect();
+				tracing::info!("{paa_path:?}: Swizzle <{s}> discards channels [{}]; filling them with ones", lost.join(", "));
+			},
+		};
+	};
+
+	if let Some(mip_idx) = mip_idx {
+		let decoded_image = decoder.decode_nth(mip_idx-1)
+			.with_context(|| format!("Failed to decode mipmap #{mip_idx} (should be in [1..{mip_count}])"))
+			.stage(Stage::Decode)?;
+		let data = encode_image(decoded_image, format, color, is_normal_map).stage(Stage::Encode)?;
+		return stdio::write(out_path, &data).stage(Stage::Write);
+	};
+
+	if stdio::is_stdio(out_path) {
+		return Err(anyhow::anyhow!("--all writes multiple files, and cannot write to standard output"));
+	};
+
+	let stem = out_path.file_stem().with_context(|| format!("{out_path:?}: Output path has no file name"))?;
+	let extension = format.extensions_str()[0];
+
+	for (index, decoded_image) in &decoder {
+		let mip_idx = index + 1;
+
+		let decoded_image = match decoded_image {
+			Ok(i) => i,
+			Err(e) => {
+				tracing::warn!("Skipping mipmap #{mip_idx}: {e}");
+				continue;
+			},
+		};
+
+		let mip_path = out_path.with_file_name(format!("{}_mip{mip_idx:02}.{extension}", stem.to_string_lossy()));
+		let data = encode_image(decoded_image, format, color, is_normal_map).stage(Stage::Encode)?;
+		stdio::write(&mip_path, &data).stage(Stage::Write)?;
+	};
+
+	Ok(())
+}
+
+
+/// Encode a decoded mipmap as `format`, converting or tagging its colors
+/// unless it is a normal map.
+fn encode_image(image: RgbaImage, format: ImageFormat, color: ColorOutput, is_normal_map: bool) -> AnyhowResult<Vec<u8>> {
+	let image = match color {
+		ColorOutput::Srgb if !is_normal_map => return encode_srgb_png(&image),
+		ColorOutput::Linear if !is_normal_map => DynamicImage::ImageRgba32F(srgb_to_linear_image(&image)),
+		// Float formats store the values as is
+		_ if color == ColorOutput::Linear || format == ImageFormat::OpenExr => DynamicImage::ImageRgba32F(DynamicImage::ImageRgba8(image).into_rgba32f()),
+		_ => DynamicImage::ImageRgba8(image),
+	};
+
+	let mut buffer = Cursor::new(vec![]);
+	image.write_to(&mut buffer, format).context("Could not encode decoded image")?;
+	Ok(buffer.into_inner())
+}
+
+
+/// Encode `image` as a PNG with an sRGB chunk, which image-rs cannot write.
+fn encode_srgb_png(image: &RgbaImage) -> AnyhowResult<Vec<u8>> {
+	let mut data = vec![];
+
+	let mut encoder = png::Encoder::new(&mut data, image.width(), image.height());
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+	let mut writer = encoder.write_header().context("Could not encode decoded image")?;
+	writer.write_image_data(image.as_raw()).context("Could not encode decoded image")?;
+	writer.finish().context("Could not encode decoded image")?;
+
+	Ok(data)
+}
+
+
+/// Write mipmap #`mip_idx` (1-based) of `paa_path` without decoding it: its
+/// data, as encoded for its [`PaaType`], to `raw_path`, and a DDS of that
+/// level alone to `dds_path`.
+fn write_undecoded(mip_idx: usize, paa_path: &Path, raw_path: Option<&Path>, dds_path: Option<&Path>) -> AnyhowResult<()> {
+	let paa_data = stdio::read(paa_path).stage(Stage::Parse)?;
+	let image = PaaImage::from_bytes(&paa_data).with_context(|| format!("Could not read PaaImage: {paa_path:?}")).stage(Stage::Parse)?;
 	let mip_count = image.mipmaps.len();
 
-	let decoder = PaaDecoder::with_paa(image);
+	let mipmap = image.mipmaps.get(mip_idx - 1)
+		.with_context(|| format!("No mipmap #{mip_idx} (should be in [1..{mip_count}])"))
+		.and_then(|m| m.clone().with_context(|| format!("Could not read mipmap #{mip_idx}")))
+		.stage(Stage::Parse)?;
+
+	if let Some(raw_path) = raw_path {
+		stdio::write(raw_path, &mipmap.data).stage(Stage::Write)?;
+	};
+
+	if let Some(dds_path) = dds_path {
+		let level = PaaImage { paatype: image.paatype, mipmaps: vec![Ok(mipmap)], ..Default::default() };
+		let dds = level.to_dds().with_context(|| format!("Could not convert {:?} mipmap to DDS", image.paatype)).stage(Stage::Encode)?;
 
-	let decoded_image = decoder.decode_nth(mip_idx-1)
-		.with_context(|| format!("Failed to decode mipmap #{mip_idx} (should be in [1..{mip_count}])"))?;
-	decoded_image.save_with_format(png_path, image::ImageFormat::Png)
-		.with_context(|| format!("save_with_format to path failed: {png_path}"))?;
+		let mut data = vec![];
+		dds.write(&mut data).context("Could not serialize DDS").stage(Stage::Encode)?;
+		stdio::write(dds_path, &data).stage(Stage::Write)?;
+	};
 
 	Ok(())
 }
@@ -1,9 +1,12 @@
 use std::io::{Seek as _, SeekFrom, prelude::*};
+use std::path::Path;
 
 use a3_paa::*;
 use anyhow::{Context, Result as AnyhowResult};
 use byteorder::{ReadBytesExt as _, LittleEndian};
 
+use crate::stdio;
+
 
 pub fn command_dump_mipmap(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 	let paa_path = matches.value_of("paa").expect("PAA required");
@@ -29,7 +32,7 @@ pub fn command_dump_mipmap(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 				.to_owned()
 				.context("Mipmap read error")?;
 
-			std::fs::write(bin_path, &mipmap.data)
+			stdio::write(Path::new(bin_path), &mipmap.data)
 				.context(format!("{bin_path}: Could not write mipmap data"))?;
 		},
 
@@ -49,6 +52,10 @@ pub fn command_dump_mipmap(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 			let offset = offsets.get(mip_idx-1)
 				.context("Mipmap index out of range of OFFSTAGG")?;
 
+			if *offset == 0 {
+				anyhow::bail!("OFFSTAGG has no offset for mipmap #{mip_idx}");
+			};
+
 			tracing::trace!("Mipmap offset is 0x{offset:02X}");
 
 			paa_file.seek(SeekFrom::Start((*offset).into()))
@@ -64,7 +71,7 @@ pub fn command_dump_mipmap(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 			let mut data: Vec<u8> = vec![0; l];
 			paa_file.read_exact(&mut data)
 				.context("Could not read mipmap data")?;
-			std::fs::write(bin_path, &data)
+			stdio::write(Path::new(bin_path), &data)
 				.context(format!("{bin_path}: Could not write mipmap data"))?;
 		},
 	};
@@ -1,53 +1,275 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use a3_paa::*;
-use anyhow::{Context, anyhow, Result as AnyhowResult};
+use anyhow::{Context, Result as AnyhowResult};
+use image::imageops::FilterType;
 use tap::prelude::*;
 
+use crate::{batch, manifest, stdio, watch};
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::report::{Stage, StageContext};
+
 
 const ARMA3_TOOLS_STEAM_APPID: u32 = 233880;
 
 
+/// Settings given on the command line, overriding those from texture hints
+#[derive(Debug, Default, Clone, Copy)]
+struct SettingsOverrides {
+	format: Option<PaaType>,
+	swizzle: Option<ArgbSwizzle>,
+	no_autoreduce: bool,
+	max_mipmaps: Option<usize>,
+	filter: Option<FilterType>,
+	alpha_threshold: Option<u8>,
+	quality: Option<DxtQuality>,
+	fit: Option<FitMode>,
+	transparency: Option<Transparency>,
+}
+
+
+impl SettingsOverrides {
+	fn from_matches(matches: &clap::ArgMatches) -> AnyhowResult<Self> {
+		let format = matches.value_of("format")
+			.map(|f| f.parse::<PaaType>().map_err(|_| anyhow::anyhow!("Unknown PAA type \"{f}\"")))
+			.transpose()?;
+
+		if let Some(format @ (PaaType::IndexPalette | PaaType::Dxt2 | PaaType::Dxt3 | PaaType::Dxt4)) = format {
+			return Err(anyhow::anyhow!("Encoding to {format:?} is not supported; use DXT1, DXT5, ARGB8888, ARGB4444, ARGB1555 or AI88"));
+		};
+
+		let swizzle = matches.value_of("swizzle")
+			.map(|s| s.parse::<ArgbSwizzle>().with_context(|| format!("Could not parse swizzle from \"{s}\"")))
+			.transpose()?;
+		let max_mipmaps = matches.value_of("max_mips")
+			.map(|m| m.parse::<usize>()
+				.ok()
+				.filter(|n| *n > 0)
+				.with_context(|| format!("Could not parse mipmap count from \"{m}\"")))
+			.transpose()?;
+		let filter = matches.value_of("filter").map(|f| match f {
+			"nearest" => FilterType::Nearest,
+			"lanczos3" => FilterType::Lanczos3,
+			_ => FilterType::Triangle,
+		});
+		let alpha_threshold = matches.value_of("alpha_threshold")
+			.map(|t| t.parse::<u8>().with_context(|| format!("Could not parse alpha threshold from \"{t}\"")))
+			.transpose()?;
+
+		if let (Some(format), Some(_)) = (format, alpha_threshold) {
+			if !format.has_alpha() {
+				return Err(anyhow::anyhow!("--alpha-threshold cannot be used with --format {format:?}, which has no alpha channel"));
+			};
+		};
+
+		let quality = matches.value_of("quality").map(|q| match q {
+			"fast" => DxtQuality::Fast,
+			"normal" => DxtQuality::Normal,
+			_ => DxtQuality::Best,
+		});
+
+		let fit = matches.value_of("fit").map(|f| match f {
+			"pad" => FitMode::PadTransparent,
+			"crop" => FitMode::Crop,
+			_ => FitMode::Stretch,
+		});
+
+		let transparency = matches.value_of("flag")
+			.map(|t| t.parse::<Transparency>().map_err(|_| anyhow::anyhow!("Unknown transparency \"{t}\" (expected none, interpolated or noninterpolated)")))
+			.transpose()?;
+
+		let no_autoreduce = matches.is_present("no_autoreduce");
+
+		Ok(Self { format, swizzle, no_autoreduce, max_mipmaps, filter, alpha_threshold, quality, fit, transparency })
+	}
+
+
+	fn apply(&self, settings: TextureEncodingSettings) -> TextureEncodingSettings {
+		TextureEncodingSettings {
+			format: self.format.unwrap_or(settings.format),
+			swizzle: self.swizzle.unwrap_or(settings.swizzle),
+			autoreduce: settings.autoreduce && !self.no_autoreduce,
+			mipmap_resize_filter: self.filter.or(settings.mipmap_resize_filter),
+			max_mipmaps: self.max_mipmaps.or(settings.max_mipmaps),
+			alpha_threshold: self.alpha_threshold.or(settings.alpha_threshold),
+			dxt_quality: self.quality.unwrap_or(settings.dxt_quality),
+			resize_to_fit: self.fit.or(settings.resize_to_fit),
+			transparency: self.transparency.or(settings.transparency),
+			..settings
+		}
+	}
+}
+
+
 pub fn command_encode(matches: &clap::ArgMatches) -> AnyhowResult<()> {
-	let img_path = matches.value_of("img").expect("IMG required");
-	let paa_path = matches.value_of("paa").expect("PAA required");
+	let overrides = SettingsOverrides::from_matches(matches)?;
+
+	if matches.is_present("watch") {
+		let paths: Vec<&str> = matches.values_of("paths").expect("PATHS required").collect();
+
+		let (src_dir, out_dir) = match paths[..] {
+			[src_dir, out_dir] => (Path::new(src_dir), Path::new(out_dir)),
+			_ => return Err(anyhow::anyhow!("--watch expects a source and an output directory")),
+		};
 
-	let hints_str: String = if let Some(path) = matches.value_of("hints") {
-		std::fs::read_to_string(&path)
-			.context(format!("{path:?}: Failed to read TexConvert.cfg"))?
+		let hints = load_hints(matches)?;
+		let suffix = matches.value_of("suffix");
+
+		return watch::watch(src_dir, out_dir, |img_path, paa_path| encode_file(&hints, suffix, &overrides, img_path, paa_path));
+	};
+
+	let jobs = batch::collect_jobs(matches, is_image, "paa")?;
+	let hints = load_hints(matches)?;
+	let suffix = matches.value_of("suffix");
+
+	let threads = batch::thread_count(matches)?;
+
+	let manifest_path = if let Some(path) = matches.value_of("manifest") {
+		Path::new(path)
+	}
+	else {
+		return batch::run_jobs(&jobs, threads, |img_path, paa_path| encode_file(&hints, suffix, &overrides, img_path, paa_path));
+	};
+
+	if jobs.iter().any(|(i, o)| stdio::is_stdio(i) || stdio::is_stdio(o)) {
+		return Err(anyhow::anyhow!("\"-\" (standard input/output) cannot be used with --manifest"));
+	};
+
+	let skip_unchanged = matches.is_present("skip_unchanged");
+	let manifest = Mutex::new(Manifest::load(manifest_path)?);
+	let (encoded, skipped) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+	let result = batch::run_jobs(&jobs, threads, |img_path, paa_path| {
+		let source_sha256 = manifest::file_sha256(img_path)?;
+		let settings = texture_settings(&hints, suffix, &overrides, paa_path)?;
+		let settings_sha256 = manifest::settings_sha256(&settings);
+
+		if skip_unchanged && manifest.lock().expect("Manifest lock poisoned").is_unchanged(paa_path, &source_sha256, &settings_sha256) {
+			tracing::info!("{img_path:?}: Source and settings unchanged since {paa_path:?} was encoded; skipping");
+			let _ = skipped.fetch_add(1, Ordering::Relaxed);
+			return Ok(());
+		};
+
+		encode_with_settings(settings, img_path, paa_path)?;
+		let _ = encoded.fetch_add(1, Ordering::Relaxed);
+
+		let entry = ManifestEntry::from_paa(paa_path, Some(source_sha256), Some(settings_sha256))?;
+		manifest.lock().expect("Manifest lock poisoned").insert(paa_path, entry);
+		Ok(())
+	});
+
+	// Record the files that were encoded, even if others failed
+	manifest.into_inner().expect("Manifest lock poisoned").save(manifest_path)?;
+	println!("{} encoded, {} skipped", encoded.into_inner(), skipped.into_inner());
+
+	result
+}
+
+
+/// Return true if `path` has an image file extension.
+pub fn is_image(path: &Path) -> bool {
+	image::ImageFormat::from_path(path).is_ok()
+}
+
+
+/// Load the texture hints from `--hints` files, TexConvert.cfg files found
+/// in usual locations, or built-in hints, with the `--default-suffix`.
+pub fn load_hints(matches: &clap::ArgMatches) -> AnyhowResult<TextureHints> {
+	// Later files override the hints of earlier ones
+	let hints_strs: Vec<(String, String)> = if let Some(paths) = matches.values_of("hints") {
+		paths
+			.map(|path| std::fs::read_to_string(&path)
+				.context(format!("{path:?}: Failed to read TexConvert.cfg"))
+				.map(|s| (path.to_owned(), s)))
+			.collect::<AnyhowResult<_>>()?
 	}
 	else {
 		suggest_hints_paths()
-			.find_map(|p| std::fs::read_to_string(&p).ok())
-			.tap_some(|p| tracing::trace!("Located TexConvert.cfg at path: {p:?}"))
-			.context("No TexConvert.cfg file provided, and could not locate any")?
+			.find_map(|p| std::fs::read_to_string(&p).ok().map(|s| (p.display().to_string(), s)))
+			.tap_some(|(p, _)| tracing::trace!("Located TexConvert.cfg at path: {p:?}"))
+			.into_iter()
+			.collect()
 	};
 
-	let hints = TextureHints
-		::try_parse_from_str(&hints_str)
-		.tap_ok(|h| tracing::trace!("Parsed TexConvert.cfg; got {} hints", h.len()))
-		.context("Failed to parse TexConvert.cfg")?;
+	let parsed = hints_strs.iter()
+		.map(|(path, hints_str)| TextureHints
+			::try_parse_from_str(hints_str)
+			.tap_ok(|h| tracing::trace!("{path:?}: Parsed TexConvert.cfg; got {} hints", h.len()))
+			.tap_err(|e| if let PaaError::TexconvertParseError(d) = e { tracing::error!("{:>5} | {}", d.line, d.snippet); })
+			.context(format!("{path:?}: Failed to parse TexConvert.cfg")))
+		.collect::<AnyhowResult<Vec<TextureHints>>>()?;
+
+	let default_suffix = matches.value_of("default_suffix").expect("DEFAULT_SUFFIX has a default value");
 
-	let paa_path_suffix = TextureHints
-		::texture_filename_to_suffix(&paa_path)
-		.context(format!("{paa_path:?}: No suffix in texture path"));
+	let hints = parsed.into_iter()
+		.reduce(TextureHints::merge)
+		.unwrap_or_else(|| {
+			tracing::info!("No TexConvert.cfg file provided, and could not locate any; using built-in texture hints");
+			TextureHints::builtin()
+		})
+		.with_default_suffix(default_suffix);
 
-	let suffix = matches.value_of("suffix")
-		.map(String::from)
-		.ok_or_else(|| anyhow!("SUFFIX not specified"))
-		.or(paa_path_suffix)
-		.context("Texture suffix was not specified and not found in texture path")?;
+	for (pattern, properties) in hints.unknown_properties() {
+		for (name, value) in properties {
+			tracing::debug!("TexConvert.cfg: {pattern:?}: Ignoring unknown or unsupported property `{name} = {value}`");
+		};
+	};
+
+	Ok(hints)
+}
 
-	let image = image::open(img_path)
-		.context(format!("{img_path:?}: Failed to open input IMG"))?
-		.into_rgba8();
 
-	let settings = hints
-		.get(&suffix)
-		.context(format!("{suffix:?}: Texture type not found in config"))?;
+fn encode_file(hints: &TextureHints, suffix: Option<&str>, overrides: &SettingsOverrides, img_path: &Path, paa_path: &Path) -> AnyhowResult<()> {
+	let settings = texture_settings(hints, suffix, overrides, paa_path)?;
+	encode_with_settings(settings, img_path, paa_path)
+}
+
+
+/// Settings `paa_path` is encoded with: those of its texture hints, or of
+/// its suffix, with the command line `overrides` applied.
+fn texture_settings(hints: &TextureHints, suffix: Option<&str>, overrides: &SettingsOverrides, paa_path: &Path) -> AnyhowResult<TextureEncodingSettings> {
+	// With --format, textures need no matching hints
+	let settings = match resolve_settings(hints, suffix, paa_path) {
+		Ok(settings) => *settings,
+		Err(e) if overrides.format.is_some() => {
+			tracing::debug!("{paa_path:?}: {e}; using default settings");
+			TextureEncodingSettings::default()
+		},
+		// Suffixes missing from the config still have a usual format
+		Err(e) => {
+			let suffix = suffix.map(str::to_owned).or_else(|| TextureHints::texture_filename_to_suffix(&paa_path));
+			let format = suffix.as_deref().and_then(PaaType::default_for_suffix).ok_or(e)?;
+			tracing::warn!("{paa_path:?}: Texture type not found in config; using the default format of its suffix, {format}");
+			TextureEncodingSettings::builder().format(format).build()
+		},
+	};
+
+	let settings = overrides.apply(settings);
 	tracing::info!("Texture settings for {paa_path:?}: {settings}");
 
-	let warn_unimplemented = |path, prop| tracing::error!("{path}: Texture has `{prop}` \
+	if settings.alpha_threshold.is_some() && !settings.format.has_alpha() {
+		return Err(anyhow::anyhow!("--alpha-threshold cannot be used with {:?} textures, which have no alpha channel", settings.format));
+	};
+
+	Ok(settings)
+}
+
+
+fn encode_with_settings(settings: TextureEncodingSettings, img_path: &Path, paa_path: &Path) -> AnyhowResult<()> {
+	let image = if stdio::is_stdio(img_path) {
+		image::load_from_memory(&stdio::read(img_path).stage(Stage::Parse)?)
+	}
+	else {
+		image::open(img_path)
+	};
+
+	let image = image
+		.context(format!("{img_path:?}: Failed to open input IMG"))
+		.stage(Stage::Parse)?;
+
+	let warn_unimplemented = |path, prop| tracing::error!("{path:?}: Texture has `{prop}` \
 		set, which is currently not implemented; ignoring it and continuing");
 
 	if settings.dynrange.is_some() {
@@ -62,20 +284,46 @@ pub fn command_encode(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 		warn_unimplemented(paa_path, "errorMetrics");
 	};
 
-	let encoder = PaaEncoder::with_image_and_settings(image, *settings);
+	let encoder = PaaEncoder::with_dynamic_image(image, settings);
 
 	let paa = encoder.encode()
-		.context("Failed to encode image")?;
+		.context("Failed to encode image")
+		.stage(Stage::Encode)?;
 	let data = paa.to_bytes()
-		.context("Failed to serialize PAA to bytes")?;
+		.context("Failed to serialize PAA to bytes")
+		.stage(Stage::Encode)?;
 
-	std::fs::write(paa_path, data)
-		.context(format!("Failed to write PAA data to {paa_path:?}"))?;
+	stdio::write(paa_path, &data)
+		.context(format!("Failed to write PAA data to {paa_path:?}"))
+		.stage(Stage::Write)?;
 
 	Ok(())
 }
 
 
+/// Settings for the texture `suffix` if given, otherwise for the `name`
+/// pattern or texture type suffix matching `paa_path`.
+pub fn resolve_settings<'h>(hints: &'h TextureHints, suffix: Option<&str>, paa_path: &Path) -> AnyhowResult<&'h TextureEncodingSettings> {
+	let settings = if let Some(suffix) = suffix {
+		hints
+			.get(&suffix.to_uppercase())
+			.context(format!("{suffix:?}: Texture type not found in config"))?
+	}
+	else if let Some(settings) = hints.settings_for_filename(&paa_path.to_string_lossy()) {
+		settings
+	}
+	else {
+		let suffix = hints
+			.suffix_for_path(paa_path)
+			.context(format!("{paa_path:?}: Texture suffix was not specified and texture path matches no texture type in config"))?;
+		tracing::info!("{paa_path:?}: Texture path matches no `name` pattern in config; using texture suffix {suffix}");
+		&hints[suffix]
+	};
+
+	Ok(settings)
+}
+
+
 fn suggest_hints_paths() -> impl Iterator<Item=PathBuf> {
 	fn append_file(p: PathBuf) -> impl Iterator<Item=PathBuf> {
 		let with_last = |f: &str| p.clone().tap_mut(|p| p.push(f));
@@ -105,3 +353,45 @@ fn suggest_hints_paths() -> impl Iterator<Item=PathBuf> {
 		.into_iter()
 		.flat_map(append_file)
 }
+
+
+#[test]
+fn encode_overrides() {
+	let dir = std::env::temp_dir().join(format!("paatool-encode-overrides-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let png = dir.join("texture_nohq.png");
+	let image = image::RgbaImage::from_fn(32, 32, |x, _| image::Rgba([0x80, 0x40, 0x20, x as u8 * 8]));
+	image.save(&png).unwrap();
+
+	let run = |args: &[&str], paa: &str| {
+		let paa = dir.join(paa);
+		let mut argv = vec!["paatool", "encode"];
+		argv.extend(args);
+		argv.extend([png.to_str().unwrap(), paa.to_str().unwrap()]);
+
+		let matches = crate::construct_app().try_get_matches_from(argv).unwrap();
+		let (_, matches) = matches.subcommand().unwrap();
+		command_encode(matches).map(|_| PaaImage::from_bytes(&std::fs::read(paa).unwrap()).unwrap())
+	};
+
+	// Overrides win over the NOHQ hints of the file name
+	let paa = run(&["--format", "dxt1", "--swizzle", "A, R, G, B", "--max-mips", "2", "--filter", "lanczos3", "--alpha-threshold", "128"], "dxt1.paa").unwrap();
+	assert_eq!(paa.paatype, PaaType::Dxt1);
+	assert!(!paa.taggs.iter().any(|t| matches!(t, Tagg::Swiz { .. })));
+	assert_eq!(paa.mipmaps.iter().filter(|m| m.is_ok()).count(), 2);
+
+	let paa = run(&["--format", "argb8888", "--alpha-threshold", "128", "--no-autoreduce"], "argb.paa").unwrap();
+	let decoded = PaaDecoder::with_paa(paa).decode_first().unwrap();
+	assert!(decoded.pixels().all(|p| p.0[3] == 0 || p.0[3] == 0xFF));
+
+	assert!(run(&["--format", "indexpalette"], "palette.paa").is_err());
+	assert!(run(&["--format", "dxt5", "--swizzle", "X, Y"], "swizzle.paa").is_err());
+	assert!(run(&["--max-mips", "0"], "mips.paa").is_err());
+
+	let paa = run(&["--format", "dxt5", "--flag", "NonInterpolated"], "flag.paa").unwrap();
+	assert!(paa.taggs.contains(&Tagg::Flag { transparency: Transparency::AlphaNotInterpolated }));
+	assert!(run(&["--flag", "opaque"], "opaque.paa").is_err());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::stdio;
+
+
+pub fn command_fix(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let in_path = matches.value_of("input").expect("IN required");
+	let out_path = matches.value_of("output").expect("OUT required");
+
+	let in_data = std::fs::read(in_path).with_context(|| format!("Could not read file: {in_path}"))?;
+	let paa = PaaImage::from_bytes(&in_data).with_context(|| format!("Could not read PaaImage: {in_path}"))?;
+
+	let mipmaps = match paa.mipmaps.as_slice() {
+		[rest @ .., Err(PaaError::EmptyMipmap)] => rest,
+		all => all,
+	};
+
+	let salvaged = paa.salvage().with_context(|| format!("{in_path}: Could not salvage PAA"))?;
+
+	// Levels that are not kept as read, whether unreadable or out of the chain
+	let regenerated = salvaged.mipmaps.iter()
+		.enumerate()
+		.filter(|(index, m)| mipmaps.get(*index).and_then(|o| o.as_ref().ok()) != m.as_ref().ok())
+		.map(|(index, _)| index)
+		.collect::<Vec<_>>();
+
+	for index in &regenerated {
+		tracing::info!("{in_path}: Regenerated mipmap #{}", index + 1);
+	};
+
+	if regenerated.first() == Some(&0) {
+		let first_kept = (0..salvaged.mipmaps.len()).find(|i| !regenerated.contains(i)).map_or(0, |i| i + 1);
+		tracing::warn!("{in_path}: Mipmap #1 was unusable and has been upscaled from mipmap #{first_kept}; it has lost detail");
+	};
+
+	let out_data = salvaged.to_bytes().context("Could not serialize PAA")?;
+	stdio::write(Path::new(out_path), &out_data).with_context(|| format!("{out_path}: Could not write PAA data"))?;
+
+	println!("{in_path}: {} of {} mipmaps regenerated", regenerated.len(), salvaged.mipmaps.len());
+
+	Ok(())
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use a3_paa::*;
+use a3_paa::imageops::noise::ValueNoise;
+use anyhow::{Context, Result as AnyhowResult};
+use image::{Rgba, RgbaImage};
+
+use crate::stdio;
+
+
+pub fn command_gen(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let out_path = Path::new(matches.value_of("paa").expect("PAA required"));
+	let pattern = matches.value_of("pattern").expect("PATTERN has a default value");
+
+	let size_str = matches.value_of("size").expect("SIZE has a default value");
+	let (width, height) = parse_size(size_str).with_context(|| format!("Could not parse size from \"{size_str}\" (expected WxH)"))?;
+
+	let format_str = matches.value_of("format").expect("FORMAT has a default value");
+	let paatype = format_str.parse::<PaaType>().ok().with_context(|| format!("Could not parse PAA type: {format_str}"))?;
+
+	let color_str = matches.value_of("color").expect("COLOR has a default value");
+	let color = parse_color(color_str).with_context(|| format!("Could not parse color from \"{color_str}\" (expected #RRGGBB or #RRGGBBAA)"))?;
+
+	let cell_str = matches.value_of("cell").expect("CELL has a default value");
+	let cell = cell_str.parse::<u32>().ok().filter(|c| *c > 0).with_context(|| format!("Could not parse cell size from \"{cell_str}\""))?;
+
+	let seed_str = matches.value_of("seed").expect("SEED has a default value");
+	let seed = seed_str.parse::<u32>().with_context(|| format!("Could not parse seed from \"{seed_str}\""))?;
+
+	let image = generate(pattern, (width, height), color, cell, seed);
+
+	if matches.is_present("png") {
+		let png_path = out_path.with_extension("png");
+		stdio::write_image(&png_path, &image, image::ImageFormat::Png).with_context(|| format!("{png_path:?}: Could not write source image"))?;
+	};
+
+	let settings = TextureEncodingSettings::builder().format(paatype).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode()
+		.with_context(|| format!("Could not encode {width}x{height} {pattern} texture to {paatype:?}"))?;
+	let data = paa.to_bytes().context("Could not serialize PAA")?;
+	stdio::write(out_path, &data).with_context(|| format!("{out_path:?}: Could not write PAA data"))?;
+
+	Ok(())
+}
+
+
+/// Build a `width`x`height` image of `pattern`:
+/// - `solid`: `color` everywhere.
+/// - `checker`: squares of `cell` pixels, alternating between `color` and
+///   black with the alpha of `color`, starting with `color` at the top left.
+/// - `gradient`: from black on the left to `color` on the right.
+/// - `noise`: `color` scaled by tileable value noise with a lattice of `cell`
+///   pixels, seeded by `seed`.
+fn generate(pattern: &str, (width, height): (u32, u32), color: Rgba<u8>, cell: u32, seed: u32) -> RgbaImage {
+	let [r, g, b, a] = color.0;
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	let scale = |f: f32| Rgba([(f32::from(r) * f).round() as u8, (f32::from(g) * f).round() as u8, (f32::from(b) * f).round() as u8, a]);
+
+	match pattern {
+		"checker" => RgbaImage::from_fn(width, height, |x, y| if (x / cell + y / cell) % 2 == 0 { color } else { Rgba([0, 0, 0, a]) }),
+
+		#[allow(clippy::cast_precision_loss)]
+		"gradient" => RgbaImage::from_fn(width, height, |x, _| scale(x as f32 / (width.max(2) - 1) as f32)),
+
+		"noise" => {
+			let noise = ValueNoise::new(seed, (width / cell, height / cell));
+			#[allow(clippy::cast_precision_loss)]
+			let at = |x: u32, y: u32| noise.sample(x as f32 / cell as f32, y as f32 / cell as f32);
+			RgbaImage::from_fn(width, height, |x, y| scale(at(x, y)))
+		},
+
+		_ => RgbaImage::from_pixel(width, height, color),
+	}
+}
+
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+	let (width, height) = size.split_once(['x', 'X'])?;
+	let (width, height) = (width.parse::<u32>().ok()?, height.parse::<u32>().ok()?);
+	(width > 0 && height > 0).then(|| (width, height))
+}
+
+
+pub fn parse_color(color: &str) -> Option<Rgba<u8>> {
+	let hex = color.strip_prefix('#').unwrap_or(color);
+
+	if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+		return None;
+	};
+
+	let channel = |i: usize| hex.get(i * 2..i * 2 + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+	let alpha = if hex.len() == 8 { channel(3)? } else { 0xFF };
+
+	Some(Rgba([channel(0)?, channel(1)?, channel(2)?, alpha]))
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::Result as AnyhowResult;
+
+use crate::encode;
+
+
+pub fn command_hints(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let hints = encode::load_hints(matches)?;
+	let json = matches.is_present("json");
+
+	let names: Vec<&str> = if let Some(names) = matches.values_of("names") {
+		names.collect()
+	}
+	else {
+		let all: BTreeMap<&str, &TextureEncodingSettings> = hints.iter().map(|(s, t)| (s.as_str(), t)).collect();
+
+		if json {
+			println!("{}", serde_json::to_string_pretty(&all)?);
+		}
+		else {
+			for (suffix, settings) in all {
+				println!("{suffix}: {}", describe(settings));
+			};
+		};
+
+		return Ok(());
+	};
+
+	let mut resolved = vec![];
+
+	for name in names {
+		// Names with an extension are texture file names; others are suffixes
+		let path = Path::new(name);
+		let suffix = if path.extension().is_some() { None } else { Some(name) };
+		let settings = encode::resolve_settings(&hints, suffix, path)?;
+
+		if json {
+			resolved.push(serde_json::json!({ "name": name, "settings": settings }));
+		}
+		else {
+			println!("{name}: {}", describe(settings));
+		};
+	};
+
+	if json {
+		println!("{}", serde_json::to_string_pretty(&resolved)?);
+	};
+
+	Ok(())
+}
+
+
+/// The settings TexConvert.cfg can set, in its own property names.
+fn describe(settings: &TextureEncodingSettings) -> String {
+	fn or_unset<T: std::fmt::Display>(value: Option<T>) -> String {
+		value.map_or_else(|| "unset".into(), |v| v.to_string())
+	}
+
+	format!("format={:?}, swizzle=<{}>, autoreduce={}, dynRange={}, mipmapFilter={}, errorMetrics={}",
+		settings.format,
+		settings.swizzle,
+		settings.autoreduce,
+		or_unset(settings.dynrange),
+		or_unset(settings.mipmap_filter),
+		or_unset(settings.error_metrics))
+}
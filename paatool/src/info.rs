@@ -1,26 +1,165 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
 use a3_paa::*;
 use anyhow::{Context, Result as AnyhowResult};
+use sha2::{Digest, Sha256};
+
+use crate::manifest;
+use crate::report::{BatchError, Stage, StageContext};
+use crate::stdio;
 
 
 pub fn command_info(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 	let brief = matches.is_present("brief");
 	let serialize = matches.is_present("serialize_back");
+	let deep = matches.is_present("deep");
+	let layout = matches.is_present("layout");
+	let quiet = matches.is_present("quiet");
+	let mut summary = matches.is_present("summary").then(InfoSummary::default);
+	let json = matches.value_of("format") == Some("json");
+
+	let paths: Vec<&str> = matches.values_of("input").expect("INPUT required").collect();
+	let mut failures = vec![];
 
-	let mut result = Ok(());
+	for path in &paths {
+		// Standard input can only be read once
+		let result = stdio::read(Path::new(path)).stage(Stage::Parse).and_then(|data| {
+			// The summary only reads headers, even after a full parse
+			let summarized = match summary.as_mut() {
+				Some(summary) => summary.add(path, &data),
+				None => Ok(()),
+			};
 
-	for path in matches.values_of("input").expect("INPUT required") {
-		let result_now = paa_path_info(path, brief, serialize);
+			if quiet { summarized } else { paa_path_info(path, &data, brief, serialize, deep, layout).and(summarized) }
+		});
 
-		if let Err(ref e) = result_now {
-			result = result_now;
+		if let Err(e) = result {
+			if paths.len() > 1 {
+				tracing::error!("{path}: {e:#}");
+			};
+
+			failures.push((path.into(), e));
+		};
+	};
+
+	if let Some(summary) = summary {
+		if json {
+			println!("{}", serde_json::to_string_pretty(&summary)?);
+		}
+		else {
+			summary.print();
 		};
 	};
 
-	result
+	BatchError::check(failures, paths.len())
 }
 
 
-fn paa_path_info(path: &str, brief: bool, serialize_back: bool) -> AnyhowResult<()> {
+/// Aggregate statistics of `info --summary`, from the headers of every file
+#[derive(Debug, Default, serde::Serialize)]
+struct InfoSummary {
+	files: usize,
+	parse_failures: usize,
+	paatypes: BTreeMap<String, usize>,
+	/// Number of mipmaps per compression.
+	compressions: BTreeMap<String, usize>,
+	/// Bytes of mipmap data as stored.
+	stored_bytes: u64,
+	/// Bytes of mipmap data once decompressed.
+	uncompressed_bytes: u64,
+	/// Number of files per dimensions of the first mipmap, as "WxH".
+	first_mipmap_dimensions: BTreeMap<String, usize>,
+	missing_flag: usize,
+	missing_avgc: usize,
+}
+
+
+impl InfoSummary {
+	/// Count the file at `path`, or its parse failure.
+	fn add(&mut self, path: &str, data: &[u8]) -> AnyhowResult<()> {
+		self.files += 1;
+
+		let result = self.add_headers(path, data);
+
+		if result.is_err() {
+			self.parse_failures += 1;
+		};
+
+		result
+	}
+
+
+	fn add_headers(&mut self, path: &str, data: &[u8]) -> AnyhowResult<()> {
+		let mut reader = PaaReader::new(std::io::Cursor::new(data))
+			.with_context(|| format!("Could not read PAA headers: {path}"))
+			.stage(Stage::Parse)?;
+
+		let paatype = reader.paatype();
+		let mut compressions = vec![];
+		let mut dimensions = vec![];
+
+		for index in 0..reader.mipmap_count() {
+			let context = || format!("Could not read header of mipmap #{}: {path}", index + 1);
+
+			compressions.push(reader.mipmap_compression(index).with_context(context).stage(Stage::Parse)?);
+			dimensions.push(reader.mipmap_dimensions(index).with_context(context).stage(Stage::Parse)?);
+		};
+
+		let stored_bytes: u64 = reader.layout().mipmap_spans().iter().flatten().map(|(_, length)| length).sum();
+		let uncompressed_bytes: u64 = dimensions.iter().map(|(w, h)| paatype.predict_size(*w, *h) as u64).sum();
+
+		*self.paatypes.entry(format!("{paatype:?}")).or_default() += 1;
+
+		for compression in compressions {
+			*self.compressions.entry(format!("{compression:?}")).or_default() += 1;
+		};
+
+		self.stored_bytes += stored_bytes;
+		self.uncompressed_bytes += uncompressed_bytes;
+
+		if let Some((width, height)) = dimensions.first() {
+			*self.first_mipmap_dimensions.entry(format!("{width}x{height}")).or_default() += 1;
+		};
+
+		if !reader.taggs().iter().any(|t| matches!(t, Tagg::Flag { .. })) {
+			self.missing_flag += 1;
+		};
+
+		if !reader.taggs().iter().any(|t| matches!(t, Tagg::Avgc { .. })) {
+			self.missing_avgc += 1;
+		};
+
+		Ok(())
+	}
+
+
+	fn print(&self) {
+		println!("Files: {} ({} failed to parse)", self.files, self.parse_failures);
+
+		for (paatype, count) in &self.paatypes {
+			println!("PaaType {paatype}: {count}");
+		};
+
+		for (compression, count) in &self.compressions {
+			println!("Compression {compression}: {count} mipmaps");
+		};
+
+		#[allow(clippy::cast_precision_loss)]
+		let ratio = if self.uncompressed_bytes == 0 { 0.0 } else { 100.0 * self.stored_bytes as f64 / self.uncompressed_bytes as f64 };
+		println!("Mipmap data: {} bytes stored, {} bytes uncompressed ({ratio:.1}%)", self.stored_bytes, self.uncompressed_bytes);
+
+		for (dimensions, count) in &self.first_mipmap_dimensions {
+			println!("First mipmap {dimensions}: {count}");
+		};
+
+		println!("Missing FLAG: {}", self.missing_flag);
+		println!("Missing AVGC: {}", self.missing_avgc);
+	}
+}
+
+
+fn paa_path_info(path: &str, data: &[u8], brief: bool, serialize_back: bool, deep: bool, layout: bool) -> AnyhowResult<()> {
 	let brief_prefix = if brief {
 		"".to_string()
 	}
@@ -28,38 +167,95 @@ fn paa_path_info(path: &str, brief: bool, serialize_back: bool) -> AnyhowResult<
 		format!("{}: ", path)
 	};
 
-	let mut file = std::fs::File::open(path).with_context(|| format!("Could not open file: {path}"))?;
-	let filesize = file.metadata().with_context(|| format!("Could not read metadata to determine size: {path}"))?.len();
-	let image = PaaImage::read_from(&mut file).with_context(|| format!("Could not read PaaImage: {path}"))?;
+	let filesize = data.len();
+	let mut repair = None;
+	// Accept any compression that decodes, and flag the unexpected ones
+	let image = PaaImage::read_from_with_policy(&mut std::io::Cursor::new(data), CompressionPolicy::LENIENT, |e| if let ProgressEvent::MipmapOffsetsRepaired { repair: r } = e {
+		repair = Some(r);
+	})
+		.with_context(|| format!("Could not read PaaImage: {path}"))
+		.stage(Stage::Parse)?;
 
 	println!("{brief_prefix}File size: {filesize} (0x{filesize:X})");
 	println!("{brief_prefix}PaaType: {:?}", image.paatype);
+	println!("{brief_prefix}Summary: {}", PaaInfo { file_size: Some(filesize as u64), ..PaaInfo::from_image(&image) });
 
 	for (pos, tagg) in image.taggs.iter().enumerate() {
 		println!("{brief_prefix}Tagg #{}: {tagg}", pos+1);
 	};
 
-	let mipmaps = image.mipmaps.clone();
+	if let Some(repair) = repair {
+		println!("{brief_prefix}OFFSTAGG: no mipmap at these offsets, read with {repair:?} offsets instead; rewrite the file to fix it");
+	};
+
+	let decoder = deep.then(|| PaaDecoder::with_paa(image.clone()));
 
-	for (pos, m) in mipmaps.iter().enumerate() {
-		let pos = pos + 1;
+	for (index, m) in image.mipmaps.iter().enumerate() {
+		let pos = index + 1;
+
+		// Hash decoded pixels, which do not depend on mipmap compression
+		let decoded = match decoder.as_ref().map(|d| d.decode_nth(index)) {
+			Some(Ok(decoded)) => format!(" decode=OK sha256={}", &format!("{:x}", Sha256::digest(decoded.as_raw()))[..8]),
+			Some(Err(e)) => format!(" decode=ERROR {e}"),
+			None => "".to_string(),
+		};
 
 		if let Ok(m) = m {
-			println!("{brief_prefix}Mipmap #{pos}, {}x{} [{:?}], size={}",
+			let unexpected = if m.has_expected_compression() { "".to_string() } else { format!(" (expected {:?})", m.expected_compression()) };
+
+			println!("{brief_prefix}Mipmap #{pos}, {}x{} [{:?}]{unexpected}, size={}{decoded}",
 				m.width,
 				m.height,
 				m.compression,
 				m.data.len());
 		}
 		else {
-			println!("{brief_prefix}Mipmap #{pos} ERROR {m:?}");
+			println!("{brief_prefix}Mipmap #{pos} ERROR {m:?}{decoded}");
+		};
+	};
+
+	if deep {
+		match image.content_hash() {
+			Ok(hash) => println!("{brief_prefix}Content hash: {}", manifest::hex(&hash)),
+			Err(e) => println!("{brief_prefix}Content hash: ERROR {e}"),
+		};
+
+		match image.header_hash() {
+			Ok(hash) => println!("{brief_prefix}Header hash: {}", manifest::hex(&hash)),
+			Err(e) => println!("{brief_prefix}Header hash: ERROR {e}"),
+		};
+	};
+
+	if layout {
+		let reader = PaaReader::new(std::io::Cursor::new(data)).with_context(|| format!("Could not read layout: {path}"))?;
+		let layout = reader.layout();
+
+		for (name, (offset, length)) in layout.tagg_spans() {
+			println!("{brief_prefix}Layout: tagg {name:<8} offset={offset} (0x{offset:X}) length={length}");
+		};
+
+		for (index, span) in layout.mipmap_spans().iter().enumerate() {
+			let pos = index + 1;
+
+			match span {
+				Some((offset, length)) => println!("{brief_prefix}Layout: mipmap #{pos:<2} offset={offset} (0x{offset:X}) length={length}"),
+				None => println!("{brief_prefix}Layout: mipmap #{pos:<2} unreadable header"),
+			};
 		};
 	};
 
 	if serialize_back {
 		tracing::trace!("Attempting to serialize PaaImage back");
 
-		let data = image.to_bytes().context("Could not serialize image to bytes")?;
+		let serialized = image.to_bytes().context("Could not serialize image to bytes")?;
+		let first_difference = data.iter().zip(&serialized).position(|(a, b)| a != b)
+			.or_else(|| (data.len() != serialized.len()).then(|| data.len().min(serialized.len())));
+
+		match first_difference {
+			None => println!("{brief_prefix}Serialized back: identical ({filesize} bytes)"),
+			Some(offset) => println!("{brief_prefix}Serialized back: differs at offset {offset} (0x{offset:X}); {filesize} bytes in file, {} serialized",
+				serialized.len()),
+		};
 	};
 
 	Ok(())
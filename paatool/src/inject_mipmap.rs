@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+use byteorder::{LittleEndian, ByteOrder};
+
+use crate::raw::{self, MIPMAP_HEADER_SIZE};
+use crate::stdio;
+
+
+pub fn command_inject_mipmap(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let paa_path = matches.value_of("paa").expect("PAA required");
+	let bin_path = matches.value_of("bin").expect("BIN required");
+	let out_path = matches.value_of("output").unwrap_or(paa_path);
+	let compressed = matches.is_present("compressed");
+	let mip_idx_str = matches.value_of("mipmap").expect("INDEX has a default value");
+	let mip_idx = mip_idx_str.parse::<usize>()
+		.context(format!("Could not parse mipmap index from \"{mip_idx_str}\""))
+		.and_then(|i| if i > 0 { Ok(i) } else { Err(anyhow::anyhow!("Mipmap index cannot be 0")) })?;
+
+	let paa_data = std::fs::read(paa_path).context(format!("{paa_path}: Could not read file"))?;
+	let bin_data = std::fs::read(bin_path).context(format!("{bin_path}: Could not read mipmap data"))?;
+
+	let data = if compressed {
+		splice_payload(&paa_data, mip_idx, &bin_data)?
+	}
+	else {
+		replace_data(&paa_data, mip_idx, bin_data)?
+	};
+
+	stdio::write(Path::new(out_path), &data).context(format!("{out_path}: Could not write PAA data"))?;
+
+	Ok(())
+}
+
+
+/// Replace the uncompressed data of mipmap #`mip_idx` and serialize the PAA
+/// again, compressing the mipmap as it was.
+fn replace_data(paa_data: &[u8], mip_idx: usize, data: Vec<u8>) -> AnyhowResult<Vec<u8>> {
+	let mut image = PaaImage::from_bytes(paa_data).context("Could not read PaaImage")?;
+
+	// The empty mipmap ending a sequentially read PAA is written back by to_bytes
+	if let Some(Err(PaaError::EmptyMipmap)) = image.mipmaps.last() {
+		image.mipmaps.pop();
+	};
+
+	let count = image.mipmaps.len();
+	let mipmap = image.mipmaps.get_mut(mip_idx - 1)
+		.with_context(|| format!("Mipmap index {mip_idx} out of range (should be in [1..{count}])"))?
+		.as_mut()
+		.map_err(|e| anyhow::anyhow!("{e}"))
+		.context(format!("Mipmap #{mip_idx} could not be read"))?;
+
+	let size = mipmap.paatype.predict_size(mipmap.width, mipmap.height);
+
+	if data.len() != size {
+		anyhow::bail!("Mipmap #{mip_idx} ({}x{}) needs {size} bytes of data, got {}", mipmap.width, mipmap.height, data.len());
+	};
+
+	mipmap.data = data;
+	image.to_bytes().context("Could not serialize PAA")
+}
+
+
+/// Replace the payload of mipmap #`mip_idx` as stored, without recompressing
+/// anything, and shift the OFFS entries of the following mipmaps.
+fn splice_payload(paa_data: &[u8], mip_idx: usize, payload: &[u8]) -> AnyhowResult<Vec<u8>> {
+	let blocks = raw::mipmap_blocks(paa_data).context("Could not locate mipmaps")?;
+	let count = blocks.len();
+	let block = blocks.get(mip_idx - 1)
+		.with_context(|| format!("Mipmap index {mip_idx} out of range (should be in [1..{count}])"))?;
+
+	if payload.len() >= 1 << 24 {
+		anyhow::bail!("Payload of {} bytes overflows the 3-byte mipmap length", payload.len());
+	};
+
+	let start = block.offset as usize;
+	let end = start + MIPMAP_HEADER_SIZE + block.payload.len();
+
+	let mut data = Vec::with_capacity(paa_data.len() + payload.len());
+	data.extend_from_slice(&paa_data[..start + 4]);
+	data.extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+	data.extend_from_slice(payload);
+	data.extend_from_slice(&paa_data[end..]);
+
+	// OFFS precedes the mipmaps, so it has not moved
+	if let Some(offs) = raw::offs_position(&data) {
+		let length = LittleEndian::read_u32(&data[offs - 4..offs]) as usize;
+
+		for entry in (offs..offs + length).step_by(4) {
+			let offset = LittleEndian::read_u32(&data[entry..entry + 4]);
+
+			if offset as usize > start {
+				let shifted = (i64::from(offset) + payload.len() as i64 - block.payload.len() as i64)
+					.try_into()
+					.context("Mipmap offset overflows a u32")?;
+				LittleEndian::write_u32(&mut data[entry..entry + 4], shifted);
+			};
+		};
+	};
+
+	Ok(data)
+}
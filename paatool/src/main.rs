@@ -1,71 +1,427 @@
 #![allow(unused_variables)]
 
+use std::path::Path;
 use std::process::ExitCode;
 
 use anyhow::{Context, Result as AnyhowResult};
 use tap::prelude::*;
 
+mod batch;
+mod watch;
+mod manifest;
+mod stdio;
+mod report;
 mod encode;
 mod decode;
 mod dds2paa;
+mod paa2dds;
 mod dump_mipmap;
+mod inject_mipmap;
+mod raw;
 mod info;
+mod verify;
+mod compare;
+mod optimize;
+mod fix;
+mod transcode;
+mod tagg;
+mod gen;
+mod thumbnail;
+mod hints;
+mod completions;
+mod manpage;
 
 
 fn construct_app() -> clap::Command<'static> {
 	clap::Command::new("paatool")
 		.version(clap::crate_version!())
+		.about(clap::crate_description!())
+		.long_about("Read and write the Bohemia Interactive PAA image format: encode images to PAA and decode PAA files \
+			back, convert to and from DDS, and inspect, verify, compare, optimize and repair PAA files.")
 		.setting(clap::AppSettings::DeriveDisplayOrder)
-		.arg(clap::arg!(loglevel: -L "Global log verbosity level")
+		.after_help("Exit status is 0 on success, 2 if some inputs failed while others succeeded, and 1 otherwise \
+			(usage or configuration errors, or every input failed).")
+		.arg(clap::arg!(loglevel: -L <LEVEL> "Global log verbosity level")
+			.long_help("Verbosity of the log written to standard error, from Error (least) to Trace (most); case-insensitive")
+			.required(false)
 			.ignore_case(true)
 			.possible_values(["Error", "Warn", "Info", "Debug", "Trace"])
 			.default_value("Info"))
+		.arg(clap::Arg::new("jobs")
+			.short('j')
+			.long("jobs")
+			.value_name("N")
+			.takes_value(true)
+			.global(true)
+			.help("Number of files converted in parallel; defaults to the number of logical CPUs"))
+		.arg(clap::Arg::new("error_report")
+			.long("error-report")
+			.value_name("FILE")
+			.takes_value(true)
+			.global(true)
+			.help("Write a JSON array of failed inputs, with their path, stage (parse, decode, encode or write) and error"))
+		.arg(clap::Arg::new("dry_run")
+			.long("dry-run")
+			.global(true)
+			.help("Read and convert inputs, but only log the outputs and their sizes instead of writing them"))
 		.subcommand(clap::Command::new("encode")
-			.about("Encode an image file to PAA")
-			.arg(clap::arg!(hints: --hints <HINTS> "TexConvert.cfg file with texture hints")
-				.required(false))
+			.about("Encode image files to PAA")
+			.long_about("Encode image files to PAA.  The PAA type, swizzle and other settings of each texture are resolved from TexConvert.cfg texture hints by the texture type suffix of its file name (e.g. \"_co\"), as printed by the hints subcommand; options override them.")
+			.arg(hints_arg())
 			.arg(clap::arg!(suffix: -S --suffix <SUFFIX> "Texture type suffix (e.g. \"CA\"); extracted from PAA if unspecified")
 				.required(false))
-			.arg(clap::arg!(img: <IMG> "IMG input file"))
-			.arg(clap::arg!(paa: <PAA> "PAA output path")))
+			.arg(default_suffix_arg())
+			.arg(clap::arg!(format: --format <PAATYPE> "PAA type overriding texture hints (e.g. \"dxt1\"); textures need no matching hints with it")
+				.required(false))
+			.arg(clap::arg!(swizzle: --swizzle <SWIZZLE> "ARGB swizzle overriding texture hints (e.g. \"1-R, 1-A, G, B\")")
+				.required(false))
+			.arg(clap::Arg::new("no_autoreduce")
+				.long("no-autoreduce")
+				.help("Never reduce solid color textures to 1x1"))
+			.arg(clap::Arg::new("max_mips")
+				.long("max-mips")
+				.value_name("N")
+				.takes_value(true)
+				.help("Keep at most N mipmaps")
+				.long_help("Keep at most N mipmaps, dropping the smallest ones; by default every mipmap down to the smallest size of the PAA type is generated"))
+			.arg(clap::arg!(filter: --filter <FILTER> "Filter used to downscale mipmaps [default: triangle]")
+				.required(false)
+				.possible_values(["nearest", "triangle", "lanczos3"]))
+			.arg(clap::Arg::new("alpha_threshold")
+				.long("alpha-threshold")
+				.value_name("ALPHA")
+				.takes_value(true)
+				.help("Make alpha binary: opaque at or above ALPHA, transparent below"))
+			.arg(clap::arg!(quality: --quality <QUALITY> "DXTn compression quality; the fourth and smaller mipmaps always use fast [default: best]")
+				.required(false)
+				.possible_values(["fast", "normal", "best"]))
+			.arg(clap::arg!(flag: --flag <TRANSPARENCY> "GALFTAGG transparency overriding the one computed from alpha: none, interpolated or noninterpolated")
+				.required(false))
+			.arg(clap::arg!(fit: --fit <MODE> "Fix images that are not a power of two (DXTn) or larger than 16384: stretch to the nearest size, pad with transparency or crop the center [default: fail]")
+				.required(false)
+				.possible_values(["stretch", "pad", "crop"]))
+			.arg(clap::arg!(manifest: --manifest <FILE> "JSON manifest recording the source hash of every encoded PAA; updated after encoding")
+				.required(false))
+			.arg(clap::Arg::new("skip_unchanged")
+				.long("skip-unchanged")
+				.requires("manifest")
+				.help("Skip images whose PAA exists, and whose hash and settings match the manifest"))
+			.arg(clap::arg!(watch: --watch "Keep running, and encode images of a source directory into an output directory whenever they change")
+				.takes_value(false)
+				.conflicts_with_all(&["out_dir", "manifest"]))
+			.arg(out_dir_arg())
+			.arg(clap::arg!(paths: <PATH> ... "IMG input file and PAA output path (\"-\" for standard input/output); with --out-dir, IMG input files and directories; with --watch, source and output directories")))
 		.subcommand(clap::Command::new("decode")
-			.about("Decode a PAA file to PNG")
-			.arg(clap::arg!(mipmap: -m "1-based mipmap index").default_value("1"))
-			.arg(clap::arg!(paa: <PAA> "PAA input file"))
-			.arg(clap::arg!(png: <PNG> "PNG output path")))
+			.about("Decode PAA files to PNG")
+			.long_about("Decode PAA files to PNG or another image format.  The first mipmap is written as stored, with swizzled channels and whatever alpha it has, unless options restore the original image.")
+			.arg(clap::arg!(mipmap: -m <INDEX> "1-based mipmap index [default: 1]").required(false).validator(one_based_index)
+				.long_help("Mipmap to decode, counted from 1 for the first (largest) mipmap"))
+			.arg(clap::arg!(all: --all "Write every mipmap to <OUTPUT stem>_mipNN, skipping unreadable ones")
+				.takes_value(false)
+				.conflicts_with("mipmap"))
+			.arg(clap::arg!(format: --format <FORMAT> "Output image format")
+				.long_help("Format of the output images, which also sets the extension of files written with --all or --out-dir; EXR with --linear unless given")
+				.required(false)
+				.possible_values(["png", "tiff", "bmp", "webp", "exr"])
+				.default_value("png"))
+			.arg(clap::Arg::new("srgb")
+				.long("srgb")
+				.help("Tag PNG output as sRGB, so that other tools do not apply gamma twice; normal maps are left untagged"))
+			.arg(clap::Arg::new("linear")
+				.long("linear")
+				.conflicts_with("srgb")
+				.help("Convert colors from sRGB to linear light, and write 32-bit float EXR (default) or TIFF; normal maps are left unconverted"))
+			.arg(clap::Arg::new("respect_flag")
+				.long("respect-flag")
+				.help("Apply the transparency of GALFTAGG: opaque, or non-interpolated (binary) alpha"))
+			.arg(clap::arg!(unswizzle: --unswizzle "Restore the original channels by inverting SWIZTAGG, where possible"))
+			.arg(clap::arg!(premultiply: --premultiply "Multiply colors by alpha")
+				.long_help("Multiply color channels by alpha, for tools that expect premultiplied images; every PAA type is stored with straight alpha"))
+			.arg(clap::arg!(opaque: --opaque "Make alpha opaque, after the other options"))
+			.arg(clap::Arg::new("raw")
+				.long("raw")
+				.value_name("BIN")
+				.takes_value(true)
+				.conflicts_with_all(&["all", "out_dir"])
+				.help("Write the uncompressed data of the mipmap, as encoded for its PaaType, to BIN instead of decoding it"))
+			.arg(clap::Arg::new("dds")
+				.long("dds")
+				.value_name("DDS")
+				.takes_value(true)
+				.conflicts_with_all(&["all", "out_dir"])
+				.help("Write the mipmap as a single-level DDS to DDS instead of decoding it"))
+			.arg(out_dir_arg())
+			.arg(clap::arg!(paths: <PATH> ... "PAA input file and PNG output path (\"-\" for standard input/output); only the PAA with --raw or --dds; with --out-dir, PAA input files and directories")))
 		.subcommand(clap::Command::new("dds2paa")
 			.about("Convert a DirectX DDS file to PAA")
-			.arg(clap::arg!(layer: -l "1-based array layer index").default_value("1"))
-			.arg(clap::arg!(dds: <DDS> "DDS input file"))
-			.arg(clap::arg!(paa: <PAA> "PAA output path")))
+			.long_about("Convert a DirectX DDS file to PAA.  DXTn blocks and uncompressed pixels are copied as is where PAA has the same format, unless --compress is given; missing mipmaps are generated, and AVGC, MAXC and FLAG taggs computed.")
+			.arg(clap::arg!(layer: -l <INDEX> "1-based array layer index").required(false).validator(one_based_index).default_value("1")
+				.long_help("Array layer or cube map face to convert, counted from 1; the layer of a DDS file without layers is 1"))
+			.arg(clap::arg!(compress: --compress <FORMAT> "Encode uncompressed DDS files to this DXTn format instead of ARGB8888")
+				.required(false)
+				.ignore_case(true)
+				.possible_values(["dxt1", "dxt5"]))
+			.arg(clap::Arg::new("gen_mips")
+				.long("gen-mips")
+				.help("Generate every mipmap from the first one, ignoring smaller DDS mipmaps"))
+			.arg(clap::Arg::new("no_taggs")
+				.long("no-taggs")
+				.help("Do not compute AVGC, MAXC and FLAG taggs"))
+			.arg(clap::Arg::new("all_layers")
+				.long("all-layers")
+				.conflicts_with("layer")
+				.help("Convert every array layer or cube map face to its own PAA, named after PAA by --name-template"))
+			.arg(clap::Arg::new("name_template")
+				.long("name-template")
+				.value_name("TEMPLATE")
+				.takes_value(true)
+				.requires("all_layers")
+				.help("File stem of each layer, from {stem} of PAA, 2-digit {layer} and cube map {face} (px, nx, ..) [default: {stem}_{face} for cube maps, {stem}_{layer} otherwise]"))
+			.arg(clap::arg!(dds: <DDS> "DDS input file, or \"-\" for standard input"))
+			.arg(clap::arg!(paa: <PAA> "PAA output path, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("paa2dds")
+			.about("Convert a PAA file to DirectX DDS, keeping all mipmaps")
+			.long_about("Convert a PAA file to DirectX DDS, keeping all mipmaps.  DXTn blocks are copied as is, as are the pixels of uncompressed ARGB and AI88 types; index palette PAA files cannot be converted.")
+			.arg(clap::arg!(paa: <PAA> "PAA input file")
+				.long_help("PAA file to convert, read as a whole"))
+			.arg(clap::arg!(dds: <DDS> "DDS output path")
+				.long_help("Path the DDS file is written to, or \"-\" for standard output")))
 		.subcommand(clap::Command::new("dump-mipmap")
 			.about("Dump raw mipmap data")
-			.arg(clap::arg!(mipmap: -m "1-based mipmap index").default_value("1"))
-			.arg(clap::arg!(compressed: -z "Dump raw compressed data instead of the uncompressed texture").takes_value(false))
-			.arg(clap::arg!(paa: <PAA> "PAA input file"))
-			.arg(clap::arg!(bin: <BIN> "BIN output path")))
+			.long_about("Dump the data of one mipmap to a file: uncompressed, as encoded for the PAA type, or with -z exactly as stored in the PAA file.")
+			.arg(clap::arg!(mipmap: -m <INDEX> "1-based mipmap index").required(false).validator(one_based_index).default_value("1")
+				.long_help("Mipmap to use, counted from 1 for the first (largest) mipmap"))
+			.arg(clap::arg!(compressed: -z --compressed "Dump raw compressed data instead of the uncompressed texture").takes_value(false))
+			.arg(clap::arg!(paa: <PAA> "PAA input file")
+				.long_help("PAA file to read the mipmap from"))
+			.arg(clap::arg!(bin: <BIN> "BIN output path")
+				.long_help("Path the mipmap data is written to, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("inject-mipmap")
+			.about("Replace raw mipmap data, the inverse of dump-mipmap")
+			.long_about("Replace the data of one mipmap with the contents of a file, as written by dump-mipmap; the data is compressed again unless -z is given.")
+			.arg(clap::arg!(mipmap: -m <INDEX> "1-based mipmap index").required(false).validator(one_based_index).default_value("1")
+				.long_help("Mipmap to use, counted from 1 for the first (largest) mipmap"))
+			.arg(clap::arg!(compressed: -z --compressed "Splice raw compressed data as stored, without recompressing").takes_value(false))
+			.arg(clap::arg!(output: -o <OUT> "PAA output path; PAA is overwritten if unspecified").required(false))
+			.arg(clap::arg!(paa: <PAA> "PAA input file")
+				.long_help("PAA file whose mipmap is replaced; it is overwritten unless -o is given"))
+			.arg(clap::arg!(bin: <BIN> "BIN input file")
+				.long_help("File with the new mipmap data, of the size dump-mipmap writes for the mipmap")))
 		.subcommand(clap::Command::new("info")
 			.about("Parse a PAA file and log details")
-			.arg(clap::arg!(brief: -b --brief "Do not prepend file name to output").takes_value(false))
-			.arg(clap::arg!(serialize_back: -S "Serialize PAA back in memory for debugging").takes_value(false))
-			.arg(clap::arg!(input: <INPUT> ... "PAA file to parse")))
+			.long_about("Parse PAA files and print their type, taggs and mipmaps.  Further options decode every mipmap, print the byte layout of the file, or total the headers of many files.")
+			.arg(clap::arg!(brief: -b --brief "Do not prepend file name to output").takes_value(false)
+				.long_help("Do not prefix each line of output with the path of the input, e.g. to compare the output for two files"))
+			.arg(clap::arg!(serialize_back: -S "Serialize PAA back in memory and compare it with the file").takes_value(false))
+			.arg(clap::arg!(deep: --deep "Decode every mipmap, and print its SHA-256 or decoding error, and the content and header hashes").takes_value(false))
+			.arg(clap::arg!(layout: --layout "Print the offset and length of every tagg and mipmap payload").takes_value(false))
+			.arg(clap::arg!(summary: --summary "Print totals over all inputs, read from their headers only").takes_value(false))
+			.arg(clap::arg!(quiet: -q --quiet "Do not print details of each input; use with --summary").takes_value(false))
+			.arg(clap::arg!(format: --format <FORMAT> "Format of the --summary output")
+				.long_help("Format of the --summary output: lines of text, or a single JSON object of totals")
+				.required(false)
+				.possible_values(["text", "json"])
+				.default_value("text"))
+			.arg(clap::arg!(input: <INPUT> ... "PAA file to parse, or \"-\" for standard input")))
+		.subcommand(clap::Command::new("verify")
+			.about("Validate PAA files, printing PASS/FAIL per check")
+			.long_about("Validate PAA files, printing PASS, FAIL or SKIP for each check: parse, structure, offsets, checksums, decode and colors.  The exit status reports failed files.")
+			.arg(clap::arg!(fast: --fast "Only check structure, without decoding mipmaps").takes_value(false))
+			.arg(clap::arg!(input: <PAA> ... "PAA file to verify")
+				.long_help("PAA files to verify; every file is checked, even after one fails")))
+		.subcommand(clap::Command::new("compare")
+			.about("Compare the decoded mipmaps and taggs of two PAA files")
+			.long_about("Compare two PAA files: print differing taggs, and the mean absolute error and PSNR of each pair of decoded mipmaps.  With thresholds, fail if any mipmap differs too much.")
+			.arg(clap::arg!(mipmap: -m <INDEX> "1-based mipmap index; all common mipmaps if unspecified").required(false).validator(one_based_index))
+			.arg(clap::Arg::new("max_mae")
+				.long("max-mae")
+				.value_name("ERROR")
+				.takes_value(true)
+				.help("Fail if the mean absolute error of any mipmap exceeds ERROR"))
+			.arg(clap::Arg::new("min_psnr")
+				.long("min-psnr")
+				.value_name("DB")
+				.takes_value(true)
+				.help("Fail if the PSNR of any mipmap is below DB decibels"))
+			.arg(clap::arg!(a: <A> "First PAA file")
+				.long_help("First PAA file to compare; mipmaps of the same index in both files are decoded and compared"))
+			.arg(clap::arg!(b: <B> "Second PAA file")
+				.long_help("Second PAA file to compare; if it has a different number of mipmaps, only the common ones are compared")))
+		.subcommand(clap::Command::new("optimize")
+			.about("Rewrite a PAA file smaller, keeping decoded mipmaps identical")
+			.long_about("Rewrite a PAA file smaller, choosing the compression of each mipmap and optionally dropping mipmaps and taggs.  The output is decoded and compared with the input, unless --no-verify is given.")
+			.arg(clap::arg!(lzo: --lzo <POLICY> "Which uncompressed DXTn mipmaps to LZO-compress")
+				.required(false)
+				.possible_values(["smaller", "large", "keep"])
+				.default_value("smaller"))
+			.arg(clap::Arg::new("strip_proc")
+				.long("strip-proc")
+				.help("Remove procedural texture taggs")
+				.long_help("Remove PROCTAGG, the procedural texture expression, keeping the mipmaps"))
+			.arg(clap::Arg::new("max_mips")
+				.long("max-mips")
+				.value_name("N")
+				.takes_value(true)
+				.help("Keep only the N largest mipmaps")
+				.long_help("Keep only the N largest mipmaps, dropping the smallest ones"))
+			.arg(clap::Arg::new("no_verify")
+				.long("no-verify")
+				.help("Do not check that the output decodes identically to the input"))
+			.arg(clap::arg!(input: <IN> "PAA input file")
+				.long_help("PAA file to rewrite; it is left unchanged unless OUT is the same path"))
+			.arg(clap::arg!(output: <OUT> "PAA output path")
+				.long_help("Path the rewritten PAA file is written to, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("fix")
+			.about("Rewrite a PAA file with corrupt mipmaps, regenerating them from the readable ones")
+			.long_about("Rewrite a PAA file with corrupt mipmaps.  Each unreadable mipmap, or one out of the chain of halving dimensions, is regenerated by downscaling the nearest larger readable one; a broken first mipmap is upscaled from the next readable one, losing detail.")
+			.arg(clap::arg!(input: <IN> "PAA input file")
+				.long_help("PAA file to rewrite; it is left unchanged unless OUT is the same path"))
+			.arg(clap::arg!(output: <OUT> "PAA output path")
+				.long_help("Path the rewritten PAA file is written to, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("transcode")
+			.about("Convert a PAA file to another PAA type, keeping its mipmaps and taggs")
+			.long_about("Convert a PAA file to another PAA type, decoding each mipmap and encoding it again.  Taggs are kept, and AVGC, MAXC and FLAG recomputed where the new type changes them; OFFSTAGG is rebuilt.")
+			.arg(clap::arg!(to: --to <FORMAT> "Target PAA type")
+				.long_help("PAA type to convert to; DXTn types drop mipmaps smaller than 4x4")
+				.ignore_case(true)
+				.possible_values(["dxt1", "dxt5", "argb8888", "argb4444", "argb1555", "ai88"]))
+			.arg(clap::Arg::new("alpha_threshold")
+				.long("alpha-threshold")
+				.value_name("ALPHA")
+				.takes_value(true)
+				.help("With DXT1, make pixels with alpha below ALPHA (0-255) transparent and others opaque"))
+			.arg(clap::arg!(input: <IN> "PAA input file, or \"-\" for standard input"))
+			.arg(clap::arg!(output: <OUT> "PAA output path, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("tagg")
+			.about("Set taggs of PAA files in place, rewriting a file only if a tagg is missing or changes size")
+			.long_about("Set taggs of PAA files in place.  A tagg of the same size is overwritten where it is, and the file is only rewritten if a tagg is missing or changes size.")
+			.arg(clap::arg!(avgc: --avgc <COLOR> "AVGCTAGG average color as #RRGGBB or #RRGGBBAA").required(false))
+			.arg(clap::arg!(maxc: --maxc <COLOR> "MAXCTAGG maximum color as #RRGGBB or #RRGGBBAA").required(false))
+			.arg(clap::arg!(flag: --flag <TRANSPARENCY> "GALFTAGG transparency: none, interpolated or noninterpolated").required(false))
+			.arg(clap::arg!(swizzle: --swizzle <SWIZZLE> "SWIZTAGG swizzle (e.g. \"1-R, 1-A, G, B\")").required(false))
+			.arg(clap::arg!(input: <PAA> ... "PAA file to change")
+				.long_help("PAA files whose taggs are set, each changed in place")))
+		.subcommand(clap::Command::new("gen")
+			.about("Generate a synthetic test texture")
+			.long_about("Generate a synthetic test texture of a solid color, checker board, gradient or value noise, encoded to the given PAA type.")
+			.arg(clap::arg!(pattern: --pattern <PATTERN> "Image content")
+				.long_help("Image content: solid color, checker board of the color and black, gradient from black on the left to the color on the right, or tileable value noise")
+				.required(false)
+				.possible_values(["solid", "checker", "gradient", "noise"])
+				.default_value("solid"))
+			.arg(clap::arg!(size: --size <SIZE> "Image size as WxH").required(false).default_value("64x64")
+				.long_help("Image size as WxH in pixels, e.g. 256x128; DXTn types need powers of two"))
+			.arg(clap::arg!(format: --format <FORMAT> "PAA type")
+				.long_help("PAA type the texture is encoded to")
+				.required(false)
+				.ignore_case(true)
+				.possible_values(["dxt1", "dxt5", "argb8888", "argb4444", "argb1555", "ai88"])
+				.default_value("dxt5"))
+			.arg(clap::arg!(color: --color <COLOR> "Pattern color as #RRGGBB or #RRGGBBAA").required(false).default_value("#FFFFFFFF")
+				.long_help("Pattern color as #RRGGBB or #RRGGBBAA; its alpha applies to the whole image"))
+			.arg(clap::arg!(cell: --cell <PIXELS> "Checker square and noise lattice size").required(false).default_value("8")
+				.long_help("Side of a checker square, or spacing of the noise lattice, in pixels"))
+			.arg(clap::arg!(seed: --seed <SEED> "Noise seed").required(false).default_value("0")
+				.long_help("Seed of the noise pattern; the same seed always gives the same texture"))
+			.arg(clap::arg!(png: --png "Also write the source image next to the PAA, with a .png extension").takes_value(false))
+			.arg(clap::arg!(paa: <PAA> "PAA output path")
+				.long_help("Path the PAA file is written to, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("manifest")
+			.about("Record the hashes, types, dimensions and modification times of every PAA in a directory")
+			.long_about("Record the hashes, types, dimensions and modification times of every PAA in a directory to a JSON manifest.")
+			.arg(clap::arg!(output: -o <FILE> "JSON manifest path; source hashes recorded by encode --manifest are kept"))
+			.arg(clap::arg!(dir: <DIR> "Directory searched recursively for PAA files")))
+		.subcommand(clap::Command::new("hints")
+			.about("Print the texture settings resolved from TexConvert.cfg, as used by encode")
+			.long_about("Print the texture settings resolved from TexConvert.cfg for texture type suffixes or file names, as encode would use them, or list every suffix known.")
+			.arg(hints_arg())
+			.arg(default_suffix_arg())
+			.arg(clap::arg!(json: --json "Print settings as JSON")
+				.long_help("Print the settings as JSON, with the name each was resolved for, instead of lines of text"))
+			.arg(clap::arg!(names: [NAME] ... "Texture type suffix or texture file name; lists every suffix if none is given")))
+		.subcommand(clap::Command::new("thumbnail")
+			.about("Write a small PNG preview, decoding only the best-fitting mipmap")
+			.long_about("Write a small PNG preview of a PAA file.  Only the smallest mipmap at least as large as the thumbnail is read and decoded, and then downscaled to fit.")
+			.arg(clap::arg!(size: --size <PIXELS> "Largest thumbnail dimension").required(false).default_value("128")
+				.long_help("Largest thumbnail dimension in pixels; the aspect ratio is kept, and smaller textures are not upscaled"))
+			.arg(clap::arg!(paa: <PAA> "PAA input file")
+				.long_help("PAA file to preview; only its header and one mipmap are read"))
+			.arg(clap::arg!(png: <PNG> "PNG output path")
+				.long_help("Path the PNG thumbnail is written to, or \"-\" for standard output")))
+		.subcommand(clap::Command::new("completions")
+			.about("Print a shell completion script for paatool")
+			.long_about("Print a script completing paatool commands and options in SHELL to standard output, \
+				e.g. for /etc/bash_completion.d/paatool")
+			.arg(clap::arg!(shell: <SHELL> "Shell to complete in")
+				.long_help("Shell whose completion script is printed")
+				.possible_values(["bash", "zsh", "fish", "powershell"])))
+		.subcommand(clap::Command::new("manpage")
+			.about("Print the paatool man page")
+			.long_about("Print the paatool man page, in roff, to standard output, e.g. for /usr/share/man/man1/paatool.1"))
 }
 
 
-fn paatool() -> AnyhowResult<()> {
-	let matches = construct_app().get_matches_from(wild::args());
+/// Validate a 1-based index, such as the mipmap index of -m.
+fn one_based_index(value: &str) -> Result<(), String> {
+	match value.parse::<u32>() {
+		Ok(0) => Err("indices start at 1".into()),
+		Ok(_) => Ok(()),
+		Err(_) => Err("expected a positive integer".into()),
+	}
+}
+
+
+fn hints_arg() -> clap::Arg<'static> {
+	clap::arg!(hints: --hints <HINTS> "TexConvert.cfg file with texture hints; may be repeated, later files override earlier ones")
+		.required(false)
+		.multiple_occurrences(true)
+}
+
+
+fn default_suffix_arg() -> clap::Arg<'static> {
+	clap::Arg::new("default_suffix")
+		.long("default-suffix")
+		.value_name("SUFFIX")
+		.takes_value(true)
+		.help("Texture type suffix used if none is specified or found in the PAA path")
+		.default_value("CO")
+}
+
+
+fn out_dir_arg() -> clap::Arg<'static> {
+	clap::Arg::new("out_dir")
+		.long("out-dir")
+		.value_name("DIR")
+		.takes_value(true)
+		.help("Convert every input into DIR, created if missing, keeping file names")
+}
+
+
+fn paatool(matches: &clap::ArgMatches) -> AnyhowResult<()> {
 	let loglevel_str = matches.value_of("loglevel")
 		.unwrap_or("Info");
 	let loglevel = loglevel_str
 		.parse::<tracing::Level>()
 		.with_context(|| format!("Failed to parse loglevel from -L{loglevel_str}"))?;
 
+	// Standard output may carry binary data when an output path is "-"
 	tracing_subscriber::fmt()
 		.with_max_level(loglevel)
+		.with_writer(std::io::stderr)
 		.init();
 
 	tracing::trace!("Global loglevel set to {loglevel:?}");
 
+	stdio::set_dry_run(matches.is_present("dry_run"));
+
+	// encode --watch stops cleanly on Ctrl-C by itself
+	if !matches.subcommand_matches("encode").map_or(false, |m| m.is_present("watch")) {
+		let _ = ctrlc::set_handler(|| {
+			stdio::remove_temp_files();
+			std::process::exit(130);
+		});
+	};
+
 	match matches.subcommand() {
 		Some(("encode", matches)) => {
 			encode::command_encode(matches)
@@ -79,14 +435,70 @@ fn paatool() -> AnyhowResult<()> {
 			dds2paa::command_dds2paa(matches)
 		},
 
+		Some(("paa2dds", matches)) => {
+			paa2dds::command_paa2dds(matches)
+		},
+
 		Some(("dump-mipmap", matches)) => {
 			dump_mipmap::command_dump_mipmap(matches)
 		},
 
+		Some(("inject-mipmap", matches)) => {
+			inject_mipmap::command_inject_mipmap(matches)
+		},
+
 		Some(("info", matches)) => {
 			info::command_info(matches)
 		},
 
+		Some(("verify", matches)) => {
+			verify::command_verify(matches)
+		},
+
+		Some(("compare", matches)) => {
+			compare::command_compare(matches)
+		},
+
+		Some(("optimize", matches)) => {
+			optimize::command_optimize(matches)
+		},
+
+		Some(("fix", matches)) => {
+			fix::command_fix(matches)
+		},
+
+		Some(("transcode", matches)) => {
+			transcode::command_transcode(matches)
+		},
+
+		Some(("tagg", matches)) => {
+			tagg::command_tagg(matches)
+		},
+
+		Some(("gen", matches)) => {
+			gen::command_gen(matches)
+		},
+
+		Some(("thumbnail", matches)) => {
+			thumbnail::command_thumbnail(matches)
+		},
+
+		Some(("manifest", matches)) => {
+			manifest::command_manifest(matches)
+		},
+
+		Some(("hints", matches)) => {
+			hints::command_hints(matches)
+		},
+
+		Some(("completions", matches)) => {
+			completions::command_completions(matches)
+		},
+
+		Some(("manpage", _)) => {
+			manpage::command_manpage()
+		},
+
 		Some((&_, _)) => unreachable!(),
 
 		None => {
@@ -105,7 +517,74 @@ fn main() -> ExitCode {
 		};
 	};
 
-	crate::paatool()
-		.tap_err(|e| report_chain(e))
-		.map_or(ExitCode::FAILURE, |_| ExitCode::SUCCESS)
+	let matches = match construct_app().try_get_matches_from(wild::args()) {
+		Ok(matches) => matches,
+
+		// --help and --version are not errors
+		Err(e) => {
+			let _ = e.print();
+			return if e.use_stderr() { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+		},
+	};
+
+	let result = crate::paatool(&matches)
+		.tap_err(|e| report_chain(e));
+
+	if let Some(path) = matches.value_of("error_report") {
+		if let Err(e) = report::write_report(Path::new(path), &result) {
+			report_chain(&e);
+			return ExitCode::FAILURE;
+		};
+	};
+
+	ExitCode::from(report::exit_code(&result))
+}
+
+
+#[test]
+fn index_arguments() {
+	let parse = |args: &[&str]| construct_app().try_get_matches_from(args);
+
+	let matches = parse(&["paatool", "-L", "debug", "decode", "-m", "3", "x.paa", "y.png"]).unwrap();
+	assert_eq!(matches.value_of("loglevel"), Some("debug"));
+	let (_, decode) = matches.subcommand().unwrap();
+	assert_eq!(decode.value_of("mipmap"), Some("3"));
+	assert_eq!(decode.values_of("paths").unwrap().collect::<Vec<_>>(), ["x.paa", "y.png"]);
+
+	let matches = parse(&["paatool", "dds2paa", "-l", "2", "x.dds", "y.paa"]).unwrap();
+	assert_eq!(matches.subcommand().unwrap().1.value_of("layer"), Some("2"));
+
+	for args in [
+		["paatool", "decode", "-m", "0", "x.paa", "y.png"],
+		["paatool", "dump-mipmap", "-m", "1.5", "x.paa", "y.bin"],
+		["paatool", "dds2paa", "-l", "0", "x.dds", "y.paa"],
+		["paatool", "compare", "-m", "two", "a.paa", "b.paa"],
+	] {
+		let e = parse(&args).unwrap_err();
+		assert_eq!(e.kind(), clap::ErrorKind::ValueValidation, "{args:?}");
+	};
+}
+
+
+#[test]
+fn arguments_described() {
+	// Short help shorter than this is expanded on in --help
+	const TERSE_HELP_LEN: usize = 40;
+
+	// Completions and the man page are only as good as these
+	fn check(command: &clap::Command) {
+		assert!(command.get_about().is_some(), "{} has no about", command.get_name());
+		assert!(command.get_long_about().is_some(), "{} has no long about", command.get_name());
+
+		for arg in command.get_arguments() {
+			let help = arg.get_help().unwrap_or_else(|| panic!("{} {} has no help", command.get_name(), arg.get_id()));
+			assert!(help.len() >= TERSE_HELP_LEN || arg.get_long_help().is_some(), "{} {} has terse help and no long help", command.get_name(), arg.get_id());
+		};
+
+		for subcommand in command.get_subcommands() {
+			check(subcommand);
+		};
+	}
+
+	check(&construct_app());
 }
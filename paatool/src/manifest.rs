@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+use sha2::{Digest, Sha256};
+
+use crate::report::BatchError;
+use crate::stdio;
+
+
+/// Record of encoded PAAs, keyed by PAA path as given on the command line
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+	pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+
+/// Hashes and properties of a single PAA
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+	/// SHA-256 of the source image file the PAA was encoded from, if known.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub source_sha256: Option<String>,
+	/// SHA-256 of the resolved [`TextureEncodingSettings`] the PAA was
+	/// encoded with, if known.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub settings_sha256: Option<String>,
+	/// SHA-256 of the decoded RGBA pixels of the first mipmap.
+	pub pixels_sha256: String,
+	/// [`PaaImage::content_hash`] of all mipmaps, which does not change with
+	/// recompression.
+	#[serde(default)]
+	pub content_sha256: String,
+	pub paatype: String,
+	pub width: u16,
+	pub height: u16,
+	/// Modification time of the PAA file, in seconds since the Unix epoch.
+	pub mtime: u64,
+	/// Why the PAA could not be read or decoded, in which case the other
+	/// fields, but the source and settings hashes, are empty.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+
+impl Manifest {
+	/// Read a manifest from `path`, or start an empty one if it does not
+	/// exist.
+	pub fn load(path: &Path) -> AnyhowResult<Self> {
+		if !path.exists() {
+			return Ok(Self::default());
+		};
+
+		let file = std::fs::File::open(path).with_context(|| format!("{path:?}: Could not open manifest"))?;
+		serde_json::from_reader(BufReader::new(file)).with_context(|| format!("{path:?}: Could not parse manifest"))
+	}
+
+
+	pub fn save(&self, path: &Path) -> AnyhowResult<()> {
+		let data = serde_json::to_vec_pretty(self).context("Could not serialize manifest")?;
+		stdio::write(path, &data).with_context(|| format!("{path:?}: Could not write manifest"))
+	}
+
+
+	/// Return true if the PAA at `paa_path` exists, was read without error,
+	/// and was encoded from a source image hashing to `source_sha256`, with
+	/// settings hashing to `settings_sha256`.
+	pub fn is_unchanged(&self, paa_path: &Path, source_sha256: &str, settings_sha256: &str) -> bool {
+		paa_path.is_file() && self.entries.get(&key(paa_path))
+			.filter(|e| e.error.is_none())
+			.map_or(false, |e| {
+				e.source_sha256.as_deref() == Some(source_sha256) && e.settings_sha256.as_deref() == Some(settings_sha256)
+			})
+	}
+
+
+	pub fn insert(&mut self, paa_path: &Path, entry: ManifestEntry) {
+		let _ = self.entries.insert(key(paa_path), entry);
+	}
+}
+
+
+impl ManifestEntry {
+	/// Read and hash the PAA at `paa_path`.
+	pub fn from_paa(paa_path: &Path, source_sha256: Option<String>, settings_sha256: Option<String>) -> AnyhowResult<Self> {
+		let file = std::fs::File::open(paa_path).with_context(|| format!("Could not open file: {paa_path:?}"))?;
+		let mtime = file.metadata()?.modified()?.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+		let paa = PaaImage::read_from(&mut BufReader::new(file)).with_context(|| format!("Could not read PAA: {paa_path:?}"))?;
+		let paatype = format!("{:?}", paa.paatype);
+		let mipmap = paa.mipmaps.first().with_context(|| format!("No mipmaps: {paa_path:?}"))?.as_ref().map_err(Clone::clone)
+			.with_context(|| format!("Could not read first mipmap: {paa_path:?}"))?;
+		let (width, height) = (mipmap.width, mipmap.height);
+
+		let content_sha256 = hex(&paa.content_hash().with_context(|| format!("Could not decode mipmaps: {paa_path:?}"))?);
+		let decoded = PaaDecoder::with_paa(paa).decode_first().with_context(|| format!("Could not decode first mipmap: {paa_path:?}"))?;
+		let pixels_sha256 = format!("{:x}", Sha256::digest(decoded.as_raw()));
+
+		Ok(Self { source_sha256, settings_sha256, pixels_sha256, content_sha256, paatype, width, height, mtime, error: None })
+	}
+
+
+	/// Entry of a PAA that [`from_paa`][`Self::from_paa`] failed on.
+	pub fn from_error(source_sha256: Option<String>, settings_sha256: Option<String>, error: &anyhow::Error) -> Self {
+		Self { source_sha256, settings_sha256, error: Some(format!("{error:#}")), ..Default::default() }
+	}
+}
+
+
+fn key(path: &Path) -> String {
+	path.to_string_lossy().replace('\\', "/")
+}
+
+
+/// Lowercase hexadecimal digits of `bytes`, as hashes are printed.
+pub fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+
+/// SHA-256 of the contents of the file at `path`, read in chunks.
+pub fn file_sha256(path: &Path) -> AnyhowResult<String> {
+	let file = std::fs::File::open(path).with_context(|| format!("Could not open file: {path:?}"))?;
+	let mut hasher = Sha256::new();
+	let _ = std::io::copy(&mut BufReader::new(file), &mut hasher).with_context(|| format!("Could not read file: {path:?}"))?;
+	Ok(format!("{:x}", hasher.finalize()))
+}
+
+
+/// SHA-256 of every field of `settings`, which changes with the texture
+/// hints and command line options a PAA is encoded with.
+pub fn settings_sha256(settings: &TextureEncodingSettings) -> String {
+	format!("{:x}", Sha256::digest(format!("{settings:?}").as_bytes()))
+}
+
+
+pub fn command_manifest(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let dir = Path::new(matches.value_of("dir").expect("DIR required"));
+	let out_path = Path::new(matches.value_of("output").expect("OUTPUT required"));
+
+	let mut paths = vec![];
+	collect_paas(dir, &mut paths)?;
+	paths.sort();
+
+	// Source and settings hashes of PAAs recorded by `encode --manifest` are
+	// kept
+	let previous = Manifest::load(out_path)?;
+	let mut manifest = Manifest::default();
+	let mut failures = vec![];
+
+	// Unreadable PAAs are recorded with their error, and fail the run once
+	// the manifest is written
+	for path in &paths {
+		let (source_sha256, settings_sha256) = previous.entries.get(&key(path))
+			.map_or((None, None), |e| (e.source_sha256.clone(), e.settings_sha256.clone()));
+
+		match ManifestEntry::from_paa(path, source_sha256.clone(), settings_sha256.clone()) {
+			Ok(entry) => manifest.insert(path, entry),
+
+			Err(e) => {
+				if paths.len() > 1 {
+					tracing::error!("{path:?}: {e:#}");
+				};
+
+				manifest.insert(path, ManifestEntry::from_error(source_sha256, settings_sha256, &e));
+				failures.push((path.clone(), e));
+			},
+		};
+	};
+
+	manifest.save(out_path)?;
+	println!("{}: {} PAA files, {} errors", out_path.display(), paths.len(), failures.len());
+
+	BatchError::check(failures, paths.len())
+}
+
+
+fn collect_paas(dir: &Path, paths: &mut Vec<PathBuf>) -> AnyhowResult<()> {
+	for entry in std::fs::read_dir(dir).with_context(|| format!("{dir:?}: Could not list directory"))? {
+		let path = entry.with_context(|| format!("{dir:?}: Could not list directory"))?.path();
+
+		if path.is_dir() {
+			collect_paas(&path, paths)?;
+		}
+		else if path.extension().map_or(false, |e| e.eq_ignore_ascii_case("paa")) {
+			paths.push(path);
+		};
+	};
+
+	Ok(())
+}
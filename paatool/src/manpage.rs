@@ -0,0 +1,8 @@
+use anyhow::Result as AnyhowResult;
+
+
+pub fn command_manpage() -> AnyhowResult<()> {
+	clap_mangen::Man::new(crate::construct_app()).render(&mut std::io::stdout())?;
+
+	Ok(())
+}
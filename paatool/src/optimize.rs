@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::stdio;
+
+
+/// Which uncompressed DXTn mipmaps are LZO-compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LzoPolicy {
+	/// Every mipmap that gets smaller.
+	Smaller,
+	/// Mipmaps of at least 256x256 pixels, as BI tools do.
+	Large,
+	/// None; compression is left as is.
+	Keep,
+}
+
+
+pub fn command_optimize(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let in_path = matches.value_of("input").expect("IN required");
+	let out_path = matches.value_of("output").expect("OUT required");
+
+	let policy = match matches.value_of("lzo").expect("POLICY has a default value") {
+		"large" => LzoPolicy::Large,
+		"keep" => LzoPolicy::Keep,
+		_ => LzoPolicy::Smaller,
+	};
+	let max_mips = matches.value_of("max_mips")
+		.map(|m| m.parse::<usize>()
+			.ok()
+			.filter(|n| *n > 0)
+			.with_context(|| format!("Could not parse mipmap count from \"{m}\"")))
+		.transpose()?;
+
+	let in_data = std::fs::read(in_path).with_context(|| format!("Could not read file: {in_path}"))?;
+	let original = PaaImage::from_bytes(&in_data).with_context(|| format!("Could not read PaaImage: {in_path}"))?;
+
+	let mut paa = original.clone();
+
+	// The empty mipmap ending a sequentially read PAA is written back by to_bytes
+	if let Some(Err(PaaError::EmptyMipmap)) = paa.mipmaps.last() {
+		paa.mipmaps.pop();
+	};
+
+	if let Some(max_mips) = max_mips {
+		paa.mipmaps.truncate(max_mips);
+	};
+
+	// Keep the first of each tagg, and let to_bytes regenerate OFFS
+	let mut seen: Vec<&str> = vec![];
+	paa.taggs.retain(|t| {
+		let name = t.as_taggname();
+		let keep = !seen.contains(&name) && !matches!(t, Tagg::Offs { .. });
+		seen.push(name);
+		keep
+	});
+
+	if matches.is_present("strip_proc") {
+		paa.taggs.retain(|t| !matches!(t, Tagg::Proc { .. }));
+	};
+
+	for (index, mipmap) in paa.mipmaps.iter_mut().enumerate() {
+		let mipmap = mipmap.as_mut()
+			.map_err(|e| anyhow::anyhow!("{e}"))
+			.with_context(|| format!("Mipmap #{} could not be read", index + 1))?;
+
+		if !mipmap.paatype.is_dxtn() || mipmap.compression != PaaMipmapCompression::Uncompressed {
+			continue;
+		};
+
+		let compress = match policy {
+			LzoPolicy::Smaller => {
+				let lzo = PaaMipmap { compression: PaaMipmapCompression::Lzo, ..mipmap.clone() };
+				lzo.to_bytes()?.len() < mipmap.to_bytes()?.len()
+			},
+
+			LzoPolicy::Large => PaaMipmap::dxtn_needs_lzo(mipmap.width, mipmap.height),
+			LzoPolicy::Keep => false,
+		};
+
+		if compress {
+			mipmap.compression = PaaMipmapCompression::Lzo;
+		};
+	};
+
+	let out_data = paa.to_bytes().context("Could not serialize PAA")?;
+
+	if !matches.is_present("no_verify") {
+		verify_identical(&original, &out_data).context("Optimized PAA does not decode identically; nothing was written")?;
+	};
+
+	stdio::write(Path::new(out_path), &out_data).with_context(|| format!("{out_path}: Could not write PAA data"))?;
+
+	let (before, after) = (in_data.len(), out_data.len());
+	#[allow(clippy::cast_precision_loss)]
+	let percent = (after as f64 / before.max(1) as f64 - 1.0) * 100.0;
+	let delta = after as i64 - before as i64;
+	println!("{in_path}: {before} -> {after} bytes ({delta:+}, {percent:+.1}%)");
+
+	Ok(())
+}
+
+
+/// Decode every mipmap of `optimized` and compare it with the same mipmap of
+/// `original`.
+fn verify_identical(original: &PaaImage, optimized: &[u8]) -> AnyhowResult<()> {
+	let optimized = PaaImage::from_bytes(optimized).context("Could not read optimized PAA back")?;
+	let count = optimized.mipmaps.iter().take_while(|m| !matches!(m, Err(PaaError::EmptyMipmap))).count();
+
+	let (original, optimized) = (PaaDecoder::with_paa(original.clone()), PaaDecoder::with_paa(optimized));
+
+	for index in 0..count {
+		let mip_idx = index + 1;
+		let before = original.decode_nth(index).with_context(|| format!("Could not decode original mipmap #{mip_idx}"))?;
+		let after = optimized.decode_nth(index).with_context(|| format!("Could not decode optimized mipmap #{mip_idx}"))?;
+
+		if before != after {
+			anyhow::bail!("Mipmap #{mip_idx} differs after optimization");
+		};
+	};
+
+	Ok(())
+}
@@ -0,0 +1,33 @@
+use std::fs::File;
+use std::path::Path;
+
+use a3_paa::PaaImage;
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::stdio;
+
+
+pub fn command_paa2dds(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let paa_path = matches.value_of("paa").expect("PAA required");
+	let dds_path = matches.value_of("dds").expect("DDS required");
+
+	let mut paa_file = File::open(paa_path)
+		.context(format!("{paa_path}: Could not open PAA file"))?;
+	let image = PaaImage::read_from(&mut paa_file)
+		.context(format!("{paa_path}: Could not read PaaImage"))?;
+
+	let dds = image.to_dds()
+		.context(format!("{paa_path}: Could not convert {:?} PAA to DDS", image.paatype))?;
+
+	let format = dds.get_d3d_format().map_or("None".into(), |f| format!("{f:?}"));
+	let (width, height, levels) = (dds.get_width(), dds.get_height(), dds.get_num_mipmap_levels());
+	tracing::info!("{paa_path}: {:?} to {format}, {width}x{height}, {levels} mipmaps", image.paatype);
+
+	let mut data = vec![];
+	dds.write(&mut data)
+		.context(format!("{dds_path}: Could not serialize DDS"))?;
+	stdio::write(Path::new(dds_path), &data)
+		.context(format!("{dds_path}: Could not write DDS data"))?;
+
+	Ok(())
+}
@@ -0,0 +1,74 @@
+use std::io::Cursor;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+use byteorder::{LittleEndian, ByteOrder};
+
+
+/// Size of a mipmap block header: width, height and 3-byte payload length
+pub const MIPMAP_HEADER_SIZE: usize = 7;
+
+
+/// Raw mipmap block as stored in a PAA file
+pub struct MipmapBlock {
+	/// Offset of the block header in the file.
+	pub offset: u32,
+	/// Payload as stored, possibly compressed.
+	pub payload: Vec<u8>,
+}
+
+
+/// Walk the mipmap blocks of a PAA file sequentially, up to the terminating
+/// empty block or the end of file.
+pub fn mipmap_blocks(bytes: &[u8]) -> AnyhowResult<Vec<MipmapBlock>> {
+	let mut cursor = Cursor::new(bytes);
+	cursor.set_position(2);
+	Tagg::read_taggs_from(&mut cursor).context("Could not read taggs")?;
+	PaaPalette::read_from(&mut cursor).context("Could not read palette")?;
+
+	#[allow(clippy::cast_possible_truncation)]
+	let mut position = cursor.position() as usize;
+	let mut blocks = vec![];
+
+	while let Some(header) = bytes.get(position..position + MIPMAP_HEADER_SIZE) {
+		let (width, height) = (LittleEndian::read_u16(&header[0..2]), LittleEndian::read_u16(&header[2..4]));
+
+		if width == 0 || height == 0 {
+			break;
+		};
+
+		#[allow(clippy::cast_possible_truncation)]
+		let size = LittleEndian::read_uint(&header[4..7], 3) as usize;
+		let start = position + MIPMAP_HEADER_SIZE;
+		let payload = bytes.get(start..start + size)
+			.with_context(|| format!("Mipmap block at 0x{position:X} is truncated"))?;
+
+		blocks.push(MipmapBlock {
+			offset: position.try_into().context("Mipmap offset overflows a u32")?,
+			payload: payload.to_owned(),
+		});
+
+		position = start + size;
+	};
+
+	Ok(blocks)
+}
+
+
+/// Find the offsets array of the OFFS tagg in a PAA file, if there is one.
+pub fn offs_position(bytes: &[u8]) -> Option<usize> {
+	let mut position = 2;
+
+	while bytes.get(position..position + 4)? == b"GGAT" {
+		let name = bytes.get(position + 4..position + 8)?;
+		let length = LittleEndian::read_u32(bytes.get(position + 8..position + 12)?) as usize;
+
+		if name == b"SFFO" {
+			return Some(position + 12);
+		};
+
+		position += 12 + length;
+	};
+
+	None
+}
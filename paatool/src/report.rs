@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::stdio;
+
+
+/// Exit code of runs where some inputs failed, but others succeeded
+pub const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+
+/// Step of a conversion at which an input failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+	/// Reading or parsing the input.
+	Parse,
+	Decode,
+	Encode,
+	/// Writing the output.
+	Write,
+}
+
+
+/// Error tagged with the [`Stage`] it occurred at; it displays as the
+/// wrapped error
+#[derive(Debug)]
+pub struct StageError {
+	pub stage: Stage,
+	error: anyhow::Error,
+}
+
+
+impl std::fmt::Display for StageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.error)
+	}
+}
+
+
+impl StageError {
+	/// Tag `error` with `stage`.
+	pub fn wrap(stage: Stage, error: impl Into<anyhow::Error>) -> anyhow::Error {
+		anyhow::Error::new(StageError { stage, error: error.into() })
+	}
+}
+
+
+impl std::error::Error for StageError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		self.error.source()
+	}
+}
+
+
+/// Tag the error of a [`Result`] with a [`Stage`]
+pub trait StageContext<T> {
+	fn stage(self, stage: Stage) -> AnyhowResult<T>;
+}
+
+
+impl<T, E: Into<anyhow::Error>> StageContext<T> for Result<T, E> {
+	fn stage(self, stage: Stage) -> AnyhowResult<T> {
+		self.map_err(|e| StageError::wrap(stage, e))
+	}
+}
+
+
+/// Error of a command run on several inputs, some of which failed.  With a
+/// single input, it displays as the error of that input.
+#[derive(Debug)]
+pub struct BatchError {
+	pub failures: Vec<(PathBuf, anyhow::Error)>,
+	pub total: usize,
+}
+
+
+impl std::fmt::Display for BatchError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match &self.failures[..] {
+			[(_, e)] if self.total == 1 => write!(f, "{e}"),
+			_ => write!(f, "{} of {} inputs failed", self.failures.len(), self.total),
+		}
+	}
+}
+
+
+impl std::error::Error for BatchError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match &self.failures[..] {
+			[(_, e)] if self.total == 1 => e.source(),
+			_ => None,
+		}
+	}
+}
+
+
+impl BatchError {
+	/// Return `Ok(())` if there are no `failures`, or a [`BatchError`].
+	pub fn check(failures: Vec<(PathBuf, anyhow::Error)>, total: usize) -> AnyhowResult<()> {
+		if failures.is_empty() {
+			return Ok(());
+		};
+
+		Err(anyhow::Error::new(BatchError { failures, total }))
+	}
+}
+
+
+/// Failed input, as written by `--error-report`
+#[derive(Debug, serde::Serialize)]
+struct ReportEntry {
+	path: Option<String>,
+	stage: Option<Stage>,
+	error: String,
+}
+
+
+impl ReportEntry {
+	fn new(path: Option<&Path>, error: &anyhow::Error) -> Self {
+		Self {
+			path: path.map(|p| p.display().to_string()),
+			stage: error.downcast_ref::<StageError>().map(|s| s.stage),
+			error: format!("{error:#}"),
+		}
+	}
+}
+
+
+/// Exit code of a run ending with `result`: 0 if it succeeded, 2 if some
+/// inputs failed while others succeeded, and 1 otherwise.
+pub fn exit_code(result: &AnyhowResult<()>) -> u8 {
+	match result {
+		Ok(()) => 0,
+		Err(e) => match e.downcast_ref::<BatchError>() {
+			Some(b) if b.failures.len() < b.total => EXIT_PARTIAL_FAILURE,
+			_ => 1,
+		},
+	}
+}
+
+
+/// Write a JSON array of the inputs that failed in `result` to `path`.  An
+/// error not tied to an input is reported with a null path.
+pub fn write_report(path: &Path, result: &AnyhowResult<()>) -> AnyhowResult<()> {
+	let entries: Vec<ReportEntry> = match result {
+		Ok(()) => vec![],
+		Err(e) => match e.downcast_ref::<BatchError>() {
+			Some(b) => b.failures.iter().map(|(p, e)| ReportEntry::new(Some(p), e)).collect(),
+			None => vec![ReportEntry::new(None, e)],
+		},
+	};
+
+	// Written even with --dry-run, as the outcome of the run
+	let data = serde_json::to_vec_pretty(&entries).context("Could not serialize error report")?;
+	stdio::write_atomically(path, &data).with_context(|| format!("{path:?}: Could not write error report"))
+}
@@ -0,0 +1,107 @@
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use anyhow::{Context, Result as AnyhowResult};
+
+
+/// Set by --dry-run: outputs are reported rather than written
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Temporary files being written, removed on Ctrl-C
+static TEMP_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Tells apart temporary files of concurrent writes
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+
+/// Skip all output writes from now on if `dry_run`, logging them instead.
+pub fn set_dry_run(dry_run: bool) {
+	DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+
+/// Return true if --dry-run was given.
+pub fn is_dry_run() -> bool {
+	DRY_RUN.load(Ordering::Relaxed)
+}
+
+
+/// Remove the temporary files of writes in progress, as far as possible.
+pub fn remove_temp_files() {
+	let temp_files = TEMP_FILES.lock().unwrap_or_else(PoisonError::into_inner);
+
+	for path in temp_files.iter() {
+		let _ = std::fs::remove_file(path);
+	};
+}
+
+
+/// Return true if `path` is "-", standing for standard input or output.
+pub fn is_stdio(path: &Path) -> bool {
+	path.as_os_str() == "-"
+}
+
+
+/// Read the whole file at `path`, or standard input if `path` is "-".
+pub fn read(path: &Path) -> AnyhowResult<Vec<u8>> {
+	if !is_stdio(path) {
+		return std::fs::read(path).with_context(|| format!("Could not read file: {path:?}"));
+	};
+
+	let mut data = vec![];
+	std::io::stdin().lock().read_to_end(&mut data).context("Could not read standard input")?;
+	Ok(data)
+}
+
+
+/// Write `data` to the file at `path` with [`write_atomically`], or standard
+/// output if `path` is "-".  Standard output does no newline translation, on
+/// Windows neither, so binary data is written unchanged.  With --dry-run,
+/// only log what would be written.
+pub fn write(path: &Path, data: &[u8]) -> AnyhowResult<()> {
+	if is_dry_run() {
+		tracing::info!("{path:?}: Dry run, not writing {} bytes", data.len());
+		return Ok(());
+	};
+
+	if !is_stdio(path) {
+		return write_atomically(path, data);
+	};
+
+	let mut stdout = std::io::stdout().lock();
+	stdout.write_all(data).and_then(|_| stdout.flush()).context("Could not write standard output")
+}
+
+
+/// Encode `image` as `format`, and [`write`] it to `path`.
+pub fn write_image(path: &Path, image: &image::RgbaImage, format: image::ImageFormat) -> AnyhowResult<()> {
+	let mut buffer = Cursor::new(vec![]);
+	image.write_to(&mut buffer, format).with_context(|| format!("Could not encode {format:?} image"))?;
+	write(path, buffer.get_ref())
+}
+
+
+/// Write `data` to a temporary file next to `path`, then rename it to `path`,
+/// so that `path` is never left partially written.  The temporary file is
+/// removed if anything fails.
+pub fn write_atomically(path: &Path, data: &[u8]) -> AnyhowResult<()> {
+	let name = path.file_name().with_context(|| format!("Output path has no file name: {path:?}"))?;
+	let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+	let temp_path = path.with_file_name(format!(".{}.{}-{count}.tmp", name.to_string_lossy(), std::process::id()));
+
+	TEMP_FILES.lock().unwrap_or_else(PoisonError::into_inner).push(temp_path.clone());
+
+	let result = std::fs::write(&temp_path, data)
+		.with_context(|| format!("Could not write file: {path:?}"))
+		.and_then(|_| std::fs::rename(&temp_path, path).with_context(|| format!("Could not move {temp_path:?} to {path:?}")));
+
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp_path);
+	};
+
+	TEMP_FILES.lock().unwrap_or_else(PoisonError::into_inner).retain(|p| *p != temp_path);
+
+	result
+}
@@ -0,0 +1,115 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::gen::parse_color;
+use crate::report::{BatchError, Stage, StageContext};
+use crate::stdio;
+
+
+pub fn command_tagg(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let taggs = parse_taggs(matches)?;
+
+	if taggs.is_empty() {
+		anyhow::bail!("No tagg to set; pass --avgc, --maxc, --flag or --swizzle");
+	};
+
+	let paths: Vec<&str> = matches.values_of("input").expect("PAA required").collect();
+	let mut failures = vec![];
+
+	for path in &paths {
+		if let Err(e) = set_taggs(Path::new(path), &taggs) {
+			if paths.len() > 1 {
+				tracing::error!("{path}: {e:#}");
+			};
+
+			failures.push((path.into(), e));
+		};
+	};
+
+	BatchError::check(failures, paths.len())
+}
+
+
+fn parse_taggs(matches: &clap::ArgMatches) -> AnyhowResult<Vec<Tagg>> {
+	let color = |name: &str| matches.value_of(name)
+		.map(|c| parse_color(c).map(Bgra8888Pixel::from).with_context(|| format!("Could not parse --{name} color from \"{c}\"")))
+		.transpose();
+
+	let mut taggs = vec![];
+
+	if let Some(rgba) = color("avgc")? {
+		taggs.push(Tagg::Avgc { rgba });
+	};
+
+	if let Some(rgba) = color("maxc")? {
+		taggs.push(Tagg::Maxc { rgba });
+	};
+
+	if let Some(flag) = matches.value_of("flag") {
+		let transparency = flag.parse::<Transparency>()
+			.map_err(|_| anyhow::anyhow!("Unknown transparency \"{flag}\" (expected none, interpolated or noninterpolated)"))?;
+		taggs.push(Tagg::Flag { transparency });
+	};
+
+	if let Some(s) = matches.value_of("swizzle") {
+		let swizzle = s.parse::<ArgbSwizzle>().with_context(|| format!("Could not parse swizzle from \"{s}\""))?;
+		taggs.push(Tagg::Swiz { swizzle });
+	};
+
+	Ok(taggs)
+}
+
+
+/// Patch each of `taggs` into the PAA at `path` in place, and rewrite the
+/// whole file once for those that cannot be.
+fn set_taggs(path: &Path, taggs: &[Tagg]) -> AnyhowResult<()> {
+	let mut rewrite = vec![];
+
+	for tagg in taggs {
+		let name = tagg.as_taggname();
+
+		// A dry run patches a copy, to tell whether the fast path applies
+		let result = if stdio::is_dry_run() {
+			let mut copy = Cursor::new(stdio::read(path).stage(Stage::Parse)?);
+			patch::replace_tagg_in(&mut copy, tagg)
+		}
+		else {
+			patch::replace_tagg(path, tagg)
+		};
+
+		match result {
+			Ok(()) => println!("{}: {name} patched in place", path.display()),
+
+			Err(e @ (PaaError::TaggNotFound(_) | PaaError::TaggSizeMismatch(..))) => {
+				tracing::debug!("{path:?}: {e}");
+				rewrite.push(tagg);
+			},
+
+			Err(e) => return Err(e).with_context(|| format!("Could not patch {name} in place: {path:?}")).stage(Stage::Write),
+		};
+	};
+
+	if rewrite.is_empty() {
+		return Ok(());
+	};
+
+	let data = stdio::read(path).stage(Stage::Parse)?;
+	let mut paa = PaaImage::from_bytes(&data).with_context(|| format!("Could not read PaaImage: {path:?}")).stage(Stage::Parse)?;
+
+	for tagg in &rewrite {
+		paa.taggs.retain(|t| t.as_taggname() != tagg.as_taggname());
+		paa.taggs.push((*tagg).clone());
+	};
+
+	let out_data = paa.to_bytes_exact(false).context("Could not serialize PAA").stage(Stage::Encode)?;
+	stdio::write(path, &out_data).with_context(|| format!("{path:?}: Could not write PAA data")).stage(Stage::Write)?;
+
+	for tagg in &rewrite {
+		println!("{}: {} set by rewriting the file", path.display(), tagg.as_taggname());
+	};
+
+	Ok(())
+}
@@ -0,0 +1,73 @@
+use std::io::BufReader;
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+use image::imageops::FilterType;
+
+use crate::stdio;
+
+
+pub fn command_thumbnail(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let paa_path = matches.value_of("paa").expect("PAA required");
+	let png_path = matches.value_of("png").expect("PNG required");
+	let size_str = matches.value_of("size").expect("SIZE has a default value");
+	let size = size_str.parse::<u16>().ok().filter(|s| *s > 0)
+		.with_context(|| format!("Could not parse thumbnail size from \"{size_str}\""))?;
+
+	let paa_file = std::fs::File::open(paa_path).with_context(|| format!("Could not open file: {paa_path}"))?;
+	let mut reader = PaaReader::new(BufReader::new(paa_file)).with_context(|| format!("Could not read PAA header: {paa_path}"))?;
+
+	let dimensions = (0..reader.mipmap_count())
+		.map(|i| reader.mipmap_dimensions(i))
+		.collect::<PaaResult<Vec<_>>>()
+		.context("Could not read mipmap dimensions")?;
+
+	let index = choose_mipmap(&dimensions, size).context("PAA has no mipmaps")?;
+	let (width, height) = dimensions[index];
+
+	let mipmap = reader.read_mipmap(index).with_context(|| format!("Could not read mipmap #{}", index + 1))?;
+	let single = PaaImage { paatype: reader.paatype(), taggs: vec![], palette: None, mipmaps: vec![Ok(mipmap)], trailer: vec![] };
+	let image = PaaDecoder::with_paa(single).decode_first().with_context(|| format!("Could not decode mipmap #{}", index + 1))?;
+
+	let (thumb_width, thumb_height) = fit((width, height), size);
+	let thumbnail = if (thumb_width, thumb_height) == (width, height) {
+		image
+	}
+	else {
+		image::imageops::resize(&image, thumb_width.into(), thumb_height.into(), FilterType::Triangle)
+	};
+
+	stdio::write_image(Path::new(png_path), &thumbnail, image::ImageFormat::Png)
+		.with_context(|| format!("{png_path}: Could not write thumbnail"))?;
+	println!("{paa_path}: mipmap #{} ({width}x{height}) -> {thumb_width}x{thumb_height}", index + 1);
+
+	Ok(())
+}
+
+
+/// Index of the smallest mipmap whose larger dimension reaches `size`, or of
+/// the largest mipmap if none does.
+fn choose_mipmap(dimensions: &[(u16, u16)], size: u16) -> Option<usize> {
+	let larger = |i: &usize| dimensions[i].0.max(dimensions[i].1);
+
+	(0..dimensions.len())
+		.filter(|i| larger(i) >= size)
+		.min_by_key(larger)
+		.or_else(|| (0..dimensions.len()).max_by_key(larger))
+}
+
+
+/// Largest dimensions fitting in a `size`x`size` box, keeping the aspect
+/// ratio of `(width, height)`; smaller images are not enlarged.
+#[allow(clippy::cast_possible_truncation)]
+fn fit((width, height): (u16, u16), size: u16) -> (u16, u16) {
+	let larger = width.max(height);
+
+	if larger <= size {
+		return (width, height);
+	};
+
+	let scale = |d: u16| ((u32::from(d) * u32::from(size) + u32::from(larger) / 2) / u32::from(larger)).max(1);
+	(scale(width) as u16, scale(height) as u16)
+}
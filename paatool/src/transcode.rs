@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::stdio;
+
+
+pub fn command_transcode(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let in_path = matches.value_of("input").expect("IN required");
+	let out_path = matches.value_of("output").expect("OUT required");
+
+	let to_str = matches.value_of("to").expect("FORMAT required");
+	let paatype = to_str.parse::<PaaType>().ok().with_context(|| format!("Could not parse PAA type: {to_str}"))?;
+	let alpha_threshold = matches.value_of("alpha_threshold")
+		.map(|t| t.parse::<u8>().with_context(|| format!("Could not parse alpha threshold from \"{t}\"")))
+		.transpose()?;
+
+	if alpha_threshold.is_some() && paatype != PaaType::Dxt1 {
+		tracing::warn!("--alpha-threshold only applies to DXT1; ignoring it");
+	};
+
+	let in_data = stdio::read(Path::new(in_path))?;
+	let image = PaaImage::from_bytes(&in_data).with_context(|| format!("Could not read PaaImage: {in_path}"))?;
+
+	let transcoded = image.transcode(paatype, alpha_threshold)
+		.with_context(|| format!("Could not transcode {:?} to {paatype:?}", image.paatype))?;
+	tracing::info!("{in_path}: {:?} to {paatype:?}, {} mipmaps", image.paatype, transcoded.mipmaps.len());
+
+	let data = transcoded.to_bytes().context("Could not serialize PAA")?;
+	stdio::write(Path::new(out_path), &data).with_context(|| format!("{out_path}: Could not write PAA data"))?;
+
+	Ok(())
+}
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+
+use a3_paa::*;
+use anyhow::{Context, Result as AnyhowResult};
+use byteorder::{LittleEndian, ByteOrder};
+
+use crate::raw::{self, MipmapBlock};
+use crate::report::{BatchError, Stage, StageError};
+
+
+/// Largest difference per channel between stored and recomputed AVGC/MAXC
+/// colors; DXTn compression shifts colors slightly.
+const COLOR_TOLERANCE: u8 = 8;
+
+
+/// Outcome of a single check
+enum Check {
+	Pass,
+	Fail(String),
+	Skip(&'static str),
+}
+
+
+impl<E: std::fmt::Display> From<Result<(), E>> for Check {
+	fn from(result: Result<(), E>) -> Self {
+		result.map_or_else(|e| Check::Fail(e.to_string()), |_| Check::Pass)
+	}
+}
+
+
+pub fn command_verify(matches: &clap::ArgMatches) -> AnyhowResult<()> {
+	let fast = matches.is_present("fast");
+	let paths: Vec<&str> = matches.values_of("input").expect("INPUT required").collect();
+
+	let failures: Vec<(PathBuf, anyhow::Error)> = paths.iter()
+		.filter_map(|p| verify_file(p, fast).map(|(name, reason)| {
+			let stage = if matches!(name, "decode" | "colors") { Stage::Decode } else { Stage::Parse };
+			(p.into(), StageError::wrap(stage, anyhow::anyhow!("Failed {name} check: {reason}")))
+		}))
+		.collect();
+
+	BatchError::check(failures, paths.len())
+}
+
+
+/// Run every check on `path`, printing one line per check, and return the
+/// name and reason of the first failed check, if any.
+fn verify_file(path: &str, fast: bool) -> Option<(&'static str, String)> {
+	let mut failed: Option<(&'static str, String)> = None;
+
+	let mut report = |name: &'static str, check: Check| {
+		match check {
+			Check::Pass => println!("{path}: PASS {name}"),
+			Check::Fail(reason) => {
+				println!("{path}: FAIL {name}: {reason}");
+				let _ = failed.get_or_insert((name, reason));
+			},
+			Check::Skip(reason) => println!("{path}: SKIP {name}: {reason}"),
+		};
+	};
+
+	let parsed = std::fs::read(path)
+		.context("Could not read file")
+		.and_then(|bytes| PaaImage::from_bytes(&bytes).context("Could not parse PAA").map(|image| (bytes, image)));
+
+	let (bytes, image) = match parsed {
+		Ok(p) => p,
+		Err(e) => {
+			report("parse", Check::Fail(format!("{e:#}")));
+			return failed;
+		},
+	};
+
+	report("parse", Check::Pass);
+	report("structure", image.validate().into());
+
+	match raw::mipmap_blocks(&bytes) {
+		Ok(blocks) => {
+			report("offsets", check_offsets(&image, &blocks));
+			report("checksums", check_checksums(&image, &blocks));
+		},
+
+		Err(e) => report("offsets", Check::Fail(format!("{e:#}"))),
+	};
+
+	if fast {
+		report("decode", Check::Skip("--fast"));
+		report("colors", Check::Skip("--fast"));
+		return failed;
+	};
+
+	// A sequentially read PAA ends with an empty mipmap
+	let mip_count = match image.mipmaps.last() {
+		Some(Err(PaaError::EmptyMipmap)) => image.mipmaps.len() - 1,
+		_ => image.mipmaps.len(),
+	};
+
+	let taggs = image.taggs.clone();
+	let decoder = PaaDecoder::with_paa(image);
+	let mut first: Option<image::RgbaImage> = None;
+	let mut decode = Ok(());
+
+	for index in 0..mip_count {
+		match decoder.decode_nth(index) {
+			Ok(decoded) => { first.get_or_insert(decoded); },
+			Err(e) => {
+				decode = Err(format!("Mipmap #{}: {e}", index + 1));
+				break;
+			},
+		};
+	};
+
+	report("decode", decode.into());
+
+	match first {
+		Some(first) => report("colors", check_colors(&taggs, &first)),
+		None => report("colors", Check::Skip("no decodable mipmap")),
+	};
+
+	failed
+}
+
+
+/// Compare the OFFS tagg against the actual mipmap block offsets.
+fn check_offsets(image: &PaaImage, blocks: &[MipmapBlock]) -> Check {
+	let stored = image.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets) } else { None });
+
+	let stored = match stored {
+		Some(s) => s,
+		None => return Check::Skip("no OFFS tagg"),
+	};
+
+	let actual: Vec<u32> = blocks.iter().map(|b| b.offset).collect();
+
+	if *stored != actual {
+		return Check::Fail(format!("OFFS is {stored:X?}, but mipmaps are at {actual:X?}"));
+	};
+
+	Check::Pass
+}
+
+
+/// Compare the additive checksums stored after LZSS-compressed mipmap data
+/// against the decompressed data.
+fn check_checksums(image: &PaaImage, blocks: &[MipmapBlock]) -> Check {
+	let mut checked = 0usize;
+
+	for (index, (mipmap, block)) in image.mipmaps.iter().zip(blocks).enumerate() {
+		let mipmap = match mipmap {
+			Ok(m) if m.compression == PaaMipmapCompression::Lzss && block.payload.len() >= 4 => m,
+			_ => continue,
+		};
+
+		let stored = LittleEndian::read_i32(&block.payload[block.payload.len() - 4..]);
+
+		// Both signed and unsigned byte sums are found in the wild
+		let unsigned = mipmap.data.iter().fold(0i32, |sum, b| sum.wrapping_add(i32::from(*b)));
+		let signed = mipmap.data.iter().fold(0i32, |sum, b| sum.wrapping_add(i32::from(*b as i8)));
+
+		if stored != unsigned && stored != signed {
+			return Check::Fail(format!("Mipmap #{}: {} (stored 0x{stored:08X}, computed 0x{unsigned:08X})", index + 1, PaaError::LzssWrongChecksum));
+		};
+
+		checked += 1;
+	};
+
+	if checked == 0 {
+		return Check::Skip("no LZSS-compressed mipmaps");
+	};
+
+	Check::Pass
+}
+
+
+/// Compare the AVGC and MAXC taggs against colors recomputed from the first
+/// mipmap.
+fn check_colors(taggs: &[Tagg], first: &image::RgbaImage) -> Check {
+	let (avgc, maxc) = imageops::average_and_max_color(first);
+
+	let close = |a: &Bgra8888Pixel, b: &Bgra8888Pixel| {
+		[(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)].iter().all(|(x, y)| x.abs_diff(*y) <= COLOR_TOLERANCE)
+	};
+
+	let mut checked = 0usize;
+
+	for tagg in taggs {
+		let (name, stored, computed) = match tagg {
+			Tagg::Avgc { rgba } => ("AVGC", rgba, &avgc),
+			Tagg::Maxc { rgba } => ("MAXC", rgba, &maxc),
+			_ => continue,
+		};
+
+		if !close(stored, computed) {
+			return Check::Fail(format!("{name} is {stored}, but the first mipmap gives {computed}"));
+		};
+
+		checked += 1;
+	};
+
+	if checked == 0 {
+		return Check::Skip("no AVGC or MAXC tagg");
+	};
+
+	Check::Pass
+}
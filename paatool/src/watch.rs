@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result as AnyhowResult};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{batch, encode};
+
+
+/// Time without further changes after which a changed file is encoded, so
+/// that an editor saving in several writes triggers a single encode
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+
+enum Message {
+	Event(notify::Result<notify::Event>),
+	Stop,
+}
+
+
+/// Images changed in a watched directory, waiting to be encoded into an
+/// output directory
+#[derive(Debug)]
+pub struct WatchState {
+	out_dir: PathBuf,
+	debounce: Duration,
+	pending: HashMap<PathBuf, Instant>,
+	pub encoded: usize,
+	pub failed: usize,
+}
+
+
+impl WatchState {
+	pub fn new(out_dir: &Path, debounce: Duration) -> Self {
+		WatchState { out_dir: out_dir.to_owned(), debounce, pending: HashMap::new(), encoded: 0, failed: 0 }
+	}
+
+
+	/// Record a change of `path` at `now`.  Files without an image extension
+	/// are ignored.
+	pub fn touch(&mut self, path: &Path, now: Instant) {
+		if encode::is_image(path) {
+			let _ = self.pending.insert(path.to_owned(), now);
+		};
+	}
+
+
+	/// Encode, in path order, every pending file that has not changed for the
+	/// debounce time at `now`, and print its timing.  Failures are logged and
+	/// counted, but do not stop the others.
+	pub fn flush<F>(&mut self, now: Instant, encode: F)
+	where
+		F: Fn(&Path, &Path) -> AnyhowResult<()>,
+	{
+		let mut ready: Vec<PathBuf> = self.pending.iter()
+			.filter(|(_, changed)| now.saturating_duration_since(**changed) >= self.debounce)
+			.map(|(path, _)| path.clone())
+			.collect();
+		ready.sort();
+
+		for input in ready {
+			let _ = self.pending.remove(&input);
+			let start = Instant::now();
+
+			let result = batch::output_path(&self.out_dir, &input, "paa")
+				.and_then(|output| batch::run_job(&input, &output, &encode));
+
+			match result {
+				Ok(()) => {
+					self.encoded += 1;
+					println!("{input:?}: encoded in {} ms", start.elapsed().as_millis());
+				},
+
+				Err(e) => {
+					self.failed += 1;
+					batch::log_failure(&input, &e);
+				},
+			};
+		};
+	}
+}
+
+
+/// Watch `src_dir`, and encode images into `out_dir` with `encode` whenever
+/// they are created or modified, until Ctrl-C is pressed.
+pub fn watch<F>(src_dir: &Path, out_dir: &Path, encode: F) -> AnyhowResult<()>
+where
+	F: Fn(&Path, &Path) -> AnyhowResult<()>,
+{
+	if !src_dir.is_dir() {
+		return Err(anyhow::anyhow!("{src_dir:?}: Not a directory"));
+	};
+
+	let (sender, receiver) = mpsc::channel();
+
+	let stop_sender = sender.clone();
+	ctrlc::set_handler(move || { let _ = stop_sender.send(Message::Stop); })
+		.context("Could not set Ctrl-C handler")?;
+
+	let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| { let _ = sender.send(Message::Event(event)); })
+		.context("Could not create file watcher")?;
+	watcher.watch(src_dir, RecursiveMode::NonRecursive)
+		.with_context(|| format!("{src_dir:?}: Could not watch directory"))?;
+
+	tracing::info!("Watching {src_dir:?}, encoding into {out_dir:?}; press Ctrl-C to stop");
+
+	let mut state = WatchState::new(out_dir, DEBOUNCE);
+
+	loop {
+		match receiver.recv_timeout(DEBOUNCE / 2) {
+			Ok(Message::Event(Ok(event))) => {
+				if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+					for path in &event.paths {
+						state.touch(path, Instant::now());
+					};
+				};
+			},
+
+			Ok(Message::Event(Err(e))) => tracing::warn!("File watcher error: {e}"),
+			Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+			Err(RecvTimeoutError::Timeout) => (),
+		};
+
+		state.flush(Instant::now(), &encode);
+	};
+
+	println!("{} encoded, {} failed", state.encoded, state.failed);
+
+	Ok(())
+}
+
+
+#[test]
+fn watch_state() {
+	use std::sync::Mutex;
+
+	let out_dir = std::env::temp_dir().join(format!("paatool-watch-state-{}", std::process::id()));
+	let encoded = Mutex::new(vec![]);
+	let encode = |input: &Path, output: &Path| {
+		encoded.lock().unwrap().push((input.to_owned(), output.to_owned()));
+
+		if input.ends_with("broken_co.png") {
+			return Err(anyhow::anyhow!("Broken image"));
+		};
+
+		Ok(())
+	};
+
+	let mut state = WatchState::new(&out_dir, Duration::from_millis(100));
+	let start = Instant::now();
+
+	// Rapid saves are encoded once, after the debounce time
+	state.touch(Path::new("src/a_co.png"), start);
+	state.touch(Path::new("src/notes.txt"), start);
+	state.touch(Path::new("src/a_co.png"), start + Duration::from_millis(50));
+	state.flush(start + Duration::from_millis(100), encode);
+	assert!(encoded.lock().unwrap().is_empty());
+
+	state.flush(start + Duration::from_millis(150), encode);
+	assert_eq!(*encoded.lock().unwrap(), vec![(PathBuf::from("src/a_co.png"), out_dir.join("a_co.paa"))]);
+
+	// A failure is counted, and later changes are still encoded
+	state.touch(Path::new("src/broken_co.png"), start);
+	state.touch(Path::new("src/b_ca.png"), start);
+	state.flush(start + Duration::from_millis(200), encode);
+	assert_eq!(encoded.lock().unwrap().len(), 3);
+	assert_eq!((state.encoded, state.failed), (2, 1));
+
+	state.flush(start + Duration::from_millis(300), encode);
+	assert_eq!(encoded.lock().unwrap().len(), 3);
+
+	let _ = std::fs::remove_dir_all(&out_dir);
+}
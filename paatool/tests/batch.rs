@@ -0,0 +1,103 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn batch_encode_decode() {
+	let dir = temp_dir("batch");
+	let input = dir.join("input");
+	std::fs::create_dir_all(&input).unwrap();
+
+	// Not solid color, so that `_co` autoreduce keeps the dimensions
+	for (name, color) in [("red_co.png", [0xFF, 0, 0, 0xFF]), ("green_co.png", [0, 0xFF, 0, 0xFF])] {
+		let image = image::RgbaImage::from_fn(16, 16, |x, _| image::Rgba(if x < 8 { color } else { [0, 0, 0, 0xFF] }));
+		image.save(input.join(name)).unwrap();
+	};
+
+	std::fs::write(input.join("broken_co.png"), b"not a PNG").unwrap();
+
+	// One invalid input fails the run, but the others are still encoded
+	assert!(!paatool(&dir, &["encode", "--out-dir", "paa/nested", "input"]));
+	assert!(dir.join("paa/nested/red_co.paa").is_file());
+	assert!(dir.join("paa/nested/green_co.paa").is_file());
+	assert!(!dir.join("paa/nested/broken_co.paa").exists());
+
+	assert!(paatool(&dir, &["decode", "--out-dir", "png", "paa/nested"]));
+	let decoded = image::open(dir.join("png/red_co.png")).unwrap().into_rgba8();
+	assert_eq!(decoded.dimensions(), (16, 16));
+	assert_eq!(decoded.get_pixel(0, 0).0, [0xFF, 0, 0, 0xFF]);
+	assert!(dir.join("png/green_co.png").is_file());
+
+	// A single input and output path still work without --out-dir
+	assert!(paatool(&dir, &["encode", "input/green_co.png", "single/green_co.paa"]));
+	assert!(dir.join("single/green_co.paa").is_file());
+	assert!(!paatool(&dir, &["encode", "input/red_co.png", "input/green_co.png", "out.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn parallel_batch_encode() {
+	let dir = temp_dir("parallel");
+	let input = dir.join("input");
+	std::fs::create_dir_all(&input).unwrap();
+
+	for i in 0..8u8 {
+		let image = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([i * 30, x as u8 * 8, y as u8 * 8, 0xFF]));
+		image.save(input.join(format!("texture{i}_co.png"))).unwrap();
+	};
+
+	assert!(paatool(&dir, &["-j", "4", "encode", "--out-dir", "paa", "input"]));
+
+	// Encoding is deterministic, whatever the number of threads
+	assert!(paatool(&dir, &["-j", "1", "encode", "--out-dir", "serial", "input"]));
+
+	for i in 0..8 {
+		let parallel = std::fs::read(dir.join(format!("paa/texture{i}_co.paa"))).unwrap();
+		assert_eq!(parallel, std::fs::read(dir.join(format!("serial/texture{i}_co.paa"))).unwrap(), "texture{i}_co.paa");
+	};
+
+	// Inputs from different directories must not be written to the same output
+	std::fs::create_dir_all(dir.join("other")).unwrap();
+	std::fs::copy(input.join("texture0_co.png"), dir.join("other/texture0_co.png")).unwrap();
+	assert!(!paatool(&dir, &["-j", "4", "encode", "--out-dir", "clash", "input", "other"]));
+	assert!(!dir.join("clash").exists());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn encode_quality() {
+	let dir = temp_dir("quality");
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8, 0xFF]));
+	image.save(dir.join("noise_co.png")).unwrap();
+
+	assert!(paatool(&dir, &["encode", "--format", "dxt1", "--quality", "fast", "noise_co.png", "fast.paa"]));
+	assert!(paatool(&dir, &["encode", "--format", "dxt1", "noise_co.png", "best.paa"]));
+	assert_ne!(std::fs::read(dir.join("fast.paa")).unwrap(), std::fs::read(dir.join("best.paa")).unwrap());
+	assert!(!paatool(&dir, &["encode", "--quality", "ultra", "noise_co.png", "ultra.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn encode_fit() {
+	let dir = temp_dir("fit");
+	image::RgbaImage::from_pixel(24, 12, image::Rgba([0x40, 0x80, 0xC0, 0xFF])).save(dir.join("banner_co.png")).unwrap();
+
+	assert!(!paatool(&dir, &["encode", "--format", "dxt1", "banner_co.png", "fail.paa"]));
+
+	for (mode, dimensions) in [("stretch", (32, 16)), ("pad", (32, 16)), ("crop", (16, 8))] {
+		let paa_name = format!("{mode}.paa");
+		assert!(paatool(&dir, &["encode", "--format", "dxt1", "--fit", mode, "banner_co.png", &paa_name]));
+		let paa = a3_paa::PaaImage::from_bytes(&std::fs::read(dir.join(&paa_name)).unwrap()).unwrap();
+		let mipmap = paa.mipmaps[0].as_ref().unwrap();
+		assert_eq!((mipmap.width, mipmap.height), dimensions);
+	};
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+
+pub fn temp_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("paatool-{name}-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+
+pub fn paatool(dir: &Path, args: &[&str]) -> bool {
+	Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(dir)
+		.args(args)
+		.status()
+		.unwrap()
+		.success()
+}
+
+
+/// Run paatool in `dir` and return its standard output, or [`None`] if it
+/// failed.
+#[allow(dead_code)]
+pub fn paatool_stdout(dir: &Path, args: &[&str]) -> Option<String> {
+	let output = Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(dir)
+		.args(args)
+		.output()
+		.unwrap();
+
+	output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn compare_reencoded() {
+	let dir = temp_dir("compare");
+
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, (x * y) as u8, 0xFF]));
+	image.save(dir.join("original_co.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "original_co.png", "original_co.paa"]));
+
+	// Decode and encode again, compressing twice
+	assert!(paatool(&dir, &["decode", "original_co.paa", "decoded_co.png"]));
+	assert!(paatool(&dir, &["encode", "decoded_co.png", "reencoded_co.paa"]));
+
+	assert!(paatool(&dir, &["compare", "--min-psnr", "1000", "original_co.paa", "original_co.paa"]));
+	assert!(paatool(&dir, &["compare", "original_co.paa", "reencoded_co.paa"]));
+	assert!(paatool(&dir, &["compare", "-m", "2", "--max-mae", "32", "--min-psnr", "20", "original_co.paa", "reencoded_co.paa"]));
+	assert!(!paatool(&dir, &["compare", "--min-psnr", "1000", "original_co.paa", "reencoded_co.paa"]));
+	assert!(!paatool(&dir, &["compare", "--max-mae", "0", "original_co.paa", "reencoded_co.paa"]));
+	assert!(!paatool(&dir, &["compare", "-m", "99", "original_co.paa", "reencoded_co.paa"]));
+
+	// Only the common mipmaps of a smaller texture are compared, and they differ in size
+	image::imageops::resize(&image, 32, 32, image::imageops::FilterType::Triangle).save(dir.join("small_co.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "small_co.png", "small_co.paa"]));
+	assert!(!paatool(&dir, &["compare", "original_co.paa", "small_co.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
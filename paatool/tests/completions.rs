@@ -0,0 +1,48 @@
+mod common;
+
+use common::{paatool_stdout, temp_dir};
+
+
+/// Names of the subcommands listed by `paatool --help`, but `help`.
+fn subcommand_names(help: &str) -> Vec<String> {
+	help.lines()
+		.skip_while(|l| !l.starts_with("SUBCOMMANDS:"))
+		.skip(1)
+		.take_while(|l| l.starts_with("    "))
+		.filter(|l| !l.starts_with("     "))
+		.filter_map(|l| l.split_whitespace().next())
+		.filter(|name| *name != "help")
+		.map(String::from)
+		.collect()
+}
+
+
+#[test]
+fn completions_and_manpage() {
+	let dir = temp_dir("completions");
+
+	let names = subcommand_names(&paatool_stdout(&dir, &["--help"]).unwrap());
+	assert!(names.len() > 15);
+	assert!(names.iter().any(|n| n == "dump-mipmap"));
+	assert!(names.iter().any(|n| n == "completions") && names.iter().any(|n| n == "manpage"));
+
+	for shell in ["bash", "zsh", "fish", "powershell"] {
+		let script = paatool_stdout(&dir, &["completions", shell]).unwrap();
+
+		for name in &names {
+			assert!(script.contains(name.as_str()), "{shell} completion does not mention {name}");
+		};
+	};
+
+	// roff escapes hyphens
+	let manpage = paatool_stdout(&dir, &["manpage"]).unwrap().replace("\\-", "-");
+	assert!(manpage.contains(".TH ") && manpage.contains(".SH NAME"));
+
+	for name in &names {
+		assert!(manpage.contains(name.as_str()), "Man page does not mention {name}");
+	};
+
+	assert!(paatool_stdout(&dir, &["completions", "tcsh"]).is_none());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::{paatool, temp_dir};
+use ddsfile::{Dds, D3DFormat, DxgiFormat, NewD3dParams, NewDxgiParams, D3D10ResourceDimension, AlphaMode};
+
+
+fn dxgi_params(width: u32, height: u32, format: DxgiFormat, mipmap_levels: u32) -> NewDxgiParams {
+	NewDxgiParams {
+		height,
+		width,
+		depth: None,
+		format,
+		mipmap_levels: Some(mipmap_levels),
+		array_layers: None,
+		caps2: None,
+		is_cubemap: false,
+		resource_dimension: D3D10ResourceDimension::Texture2D,
+		alpha_mode: AlphaMode::Unknown,
+	}
+}
+
+
+fn decode_png(dir: &std::path::Path, paa: &str) -> image::RgbaImage {
+	assert!(paatool(dir, &["decode", paa, "decoded.png"]));
+	image::open(dir.join("decoded.png")).unwrap().into_rgba8()
+}
+
+
+#[test]
+fn dds_roundtrip() {
+	let dir = temp_dir("dds");
+
+	let params = NewD3dParams { height: 16, width: 16, depth: None, format: D3DFormat::DXT1, mipmap_levels: Some(3), caps2: None };
+	let mut dds = Dds::new_d3d(params).unwrap();
+	assert_eq!(dds.data.len(), 128 + 32 + 8);
+
+	for (i, b) in dds.data.iter_mut().enumerate() {
+		*b = (i * 7 % 251) as u8;
+	};
+
+	dds.write(&mut std::fs::File::create(dir.join("fixture.dds")).unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["dds2paa", "fixture.dds", "fixture.paa"]));
+	assert!(paatool(&dir, &["paa2dds", "fixture.paa", "roundtrip.dds"]));
+
+	let roundtrip = Dds::read(std::fs::File::open(dir.join("roundtrip.dds")).unwrap()).unwrap();
+	assert_eq!(roundtrip.get_d3d_format(), Some(D3DFormat::DXT1));
+	assert_eq!((roundtrip.get_width(), roundtrip.get_height(), roundtrip.get_num_mipmap_levels()), (16, 16, 3));
+	assert_eq!(roundtrip.data, dds.data);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn dds2paa_formats() {
+	let dir = temp_dir("dds-formats");
+	let write = |name: &str, dds: &Dds| dds.write(&mut std::fs::File::create(dir.join(name)).unwrap()).unwrap();
+
+	// Non-sRGB BC1 passes through like DXT1
+	let mut bc1 = Dds::new_dxgi(dxgi_params(8, 8, DxgiFormat::BC1_UNorm, 2)).unwrap();
+	bc1.data.iter_mut().enumerate().for_each(|(i, b)| *b = (i * 13 % 256) as u8);
+	write("bc1.dds", &bc1);
+	assert!(paatool(&dir, &["dds2paa", "bc1.dds", "bc1.paa"]));
+	assert!(paatool(&dir, &["paa2dds", "bc1.paa", "bc1_roundtrip.dds"]));
+	let roundtrip = Dds::read(std::fs::File::open(dir.join("bc1_roundtrip.dds")).unwrap()).unwrap();
+	assert_eq!(roundtrip.data, bc1.data);
+
+	// BGRA with a full mipmap chain converts to ARGB8888
+	let params = NewD3dParams { height: 4, width: 4, depth: None, format: D3DFormat::A8R8G8B8, mipmap_levels: Some(3), caps2: None };
+	let mut bgra = Dds::new_d3d(params).unwrap();
+	assert_eq!(bgra.data.len(), (16 + 4 + 1) * 4);
+	bgra.data.chunks_exact_mut(4).for_each(|p| p.copy_from_slice(&[0x30, 0x20, 0x10, 0x80]));
+	write("bgra.dds", &bgra);
+	assert!(paatool(&dir, &["dds2paa", "bgra.dds", "bgra.paa"]));
+	let decoded = decode_png(&dir, "bgra.paa");
+	assert_eq!(decoded.dimensions(), (4, 4));
+	assert!(decoded.pixels().all(|p| p.0 == [0x10, 0x20, 0x30, 0x80]));
+	assert!(paatool(&dir, &["decode", "--all", "bgra.paa", "bgra/level.png"]));
+	assert_eq!(std::fs::read_dir(dir.join("bgra")).unwrap().count(), 3);
+
+	// RGBA rows padded to 16 bytes
+	let params = NewD3dParams { height: 2, width: 3, depth: None, format: D3DFormat::A8B8G8R8, mipmap_levels: None, caps2: None };
+	let mut padded = Dds::new_d3d(params).unwrap();
+	padded.header.pitch = Some(16);
+	padded.data = (0..2u8).flat_map(|y| (0..4u8).flat_map(move |x| [x * 0x40, y * 0x40, 0, 0xFF])).collect();
+	write("padded.dds", &padded);
+	assert!(paatool(&dir, &["dds2paa", "padded.dds", "padded.paa"]));
+	let decoded = decode_png(&dir, "padded.paa");
+	assert_eq!(decoded.dimensions(), (3, 2));
+	assert_eq!(decoded.get_pixel(2, 1).0, [0x80, 0x40, 0, 0xFF]);
+
+	// RGBA re-encoded to DXT5
+	let mut rgba = Dds::new_dxgi(dxgi_params(8, 8, DxgiFormat::R8G8B8A8_UNorm, 1)).unwrap();
+	rgba.data.chunks_exact_mut(4).for_each(|p| p.copy_from_slice(&[0xFF, 0, 0, 0x80]));
+	write("rgba.dds", &rgba);
+	assert!(paatool(&dir, &["dds2paa", "--compress", "dxt5", "rgba.dds", "rgba.paa"]));
+	let decoded = decode_png(&dir, "rgba.paa");
+	assert_eq!(decoded.dimensions(), (8, 8));
+	assert!(decoded.pixels().all(|p| p.0 == [0xFF, 0, 0, 0x80]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn dds2paa_gen_mips() {
+	let dir = temp_dir("dds-gen-mips");
+
+	let params = NewD3dParams { height: 256, width: 256, depth: None, format: D3DFormat::DXT1, mipmap_levels: None, caps2: None };
+	let dds = Dds::new_d3d(params).unwrap();
+	dds.write(&mut std::fs::File::create(dir.join("single.dds")).unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["dds2paa", "--gen-mips", "single.dds", "single.paa"]));
+	let paa = a3_paa::PaaImage::from_bytes(&std::fs::read(dir.join("single.paa")).unwrap()).unwrap();
+	assert_eq!(paa.mipmaps.len(), 7);
+	assert!(paa.mipmaps.iter().all(Result::is_ok));
+
+	let taggs = paa.taggs.iter().filter(|t| !matches!(t, a3_paa::Tagg::Offs { .. })).collect::<Vec<_>>();
+	assert_eq!(taggs.len(), 3);
+	assert!(taggs.contains(&&a3_paa::Tagg::Flag { transparency: a3_paa::Transparency::None }));
+
+	assert!(paatool(&dir, &["dds2paa", "--no-taggs", "single.dds", "bare.paa"]));
+	let paa = a3_paa::PaaImage::from_bytes(&std::fs::read(dir.join("bare.paa")).unwrap()).unwrap();
+	assert!(paa.taggs.iter().all(|t| matches!(t, a3_paa::Tagg::Offs { .. })));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn dds2paa_all_layers() {
+	let dir = temp_dir("dds-layers");
+
+	// Two 8x8 layers of a single mipmap, each a solid color
+	let params = NewDxgiParams { array_layers: Some(2), ..dxgi_params(8, 8, DxgiFormat::R8G8B8A8_UNorm, 1) };
+	let mut dds = Dds::new_dxgi(params).unwrap();
+	assert_eq!(dds.data.len(), 2 * 8 * 8 * 4);
+
+	for (i, pixel) in dds.data.chunks_mut(4).enumerate() {
+		pixel.copy_from_slice(if i < 64 { &[0xFF, 0x00, 0x00, 0xFF] } else { &[0x00, 0x00, 0xFF, 0xFF] });
+	};
+
+	dds.write(&mut std::fs::File::create(dir.join("array.dds")).unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["dds2paa", "--all-layers", "array.dds", "array.paa"]));
+	assert!(decode_png(&dir, "array_01.paa").pixels().all(|p| p.0 == [0xFF, 0x00, 0x00, 0xFF]));
+	assert!(decode_png(&dir, "array_02.paa").pixels().all(|p| p.0 == [0x00, 0x00, 0xFF, 0xFF]));
+	assert!(!dir.join("array.paa").exists());
+
+	assert!(paatool(&dir, &["dds2paa", "--all-layers", "--name-template", "layer{layer}_{stem}", "array.dds", "array.paa"]));
+	assert!(dir.join("layer01_array.paa").exists() && dir.join("layer02_array.paa").exists());
+	assert!(!paatool(&dir, &["dds2paa", "--all-layers", "--name-template", "{stem}", "array.dds", "array.paa"]));
+	assert!(!paatool(&dir, &["dds2paa", "--all-layers", "--name-template", "{stem}_{face}", "array.dds", "array.paa"]));
+
+	// Cube map faces are named after their direction
+	let params = NewDxgiParams { array_layers: Some(6), is_cubemap: true, ..dxgi_params(8, 8, DxgiFormat::R8G8B8A8_UNorm, 1) };
+	Dds::new_dxgi(params).unwrap().write(&mut std::fs::File::create(dir.join("sky.dds")).unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["dds2paa", "--all-layers", "sky.dds", "sky.paa"]));
+	assert!(["px", "nx", "py", "ny", "pz", "nz"].iter().all(|f| dir.join(format!("sky_{f}.paa")).exists()));
+
+	// A truncated layer fails, without stopping the others
+	let mut bytes = std::fs::read(dir.join("array.dds")).unwrap();
+	bytes.truncate(bytes.len() - 4);
+	std::fs::write(dir.join("truncated.dds"), &bytes).unwrap();
+
+	assert!(!paatool(&dir, &["dds2paa", "--all-layers", "truncated.dds", "truncated.paa"]));
+	assert!(dir.join("truncated_01.paa").exists());
+	assert!(!dir.join("truncated_02.paa").exists());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
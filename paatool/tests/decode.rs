@@ -0,0 +1,127 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn decode_all_mipmaps() {
+	let dir = temp_dir("decode-all");
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+	image.save(dir.join("fixture_co.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "fixture_co.png", "fixture_co.paa"]));
+
+	// DXT5 mipmaps from 64x64 down to 4x4
+	assert!(paatool(&dir, &["decode", "--all", "--format", "tiff", "fixture_co.paa", "out/fixture.tiff"]));
+	let mut written = std::fs::read_dir(dir.join("out")).unwrap()
+		.map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+		.collect::<Vec<_>>();
+	written.sort();
+	assert_eq!(written, ["fixture_mip01.tiff", "fixture_mip02.tiff", "fixture_mip03.tiff", "fixture_mip04.tiff", "fixture_mip05.tiff"]);
+	assert_eq!(image::open(dir.join("out/fixture_mip05.tiff")).unwrap().into_rgba8().dimensions(), (4, 4));
+
+	assert!(paatool(&dir, &["decode", "-m", "3", "fixture_co.paa", "third.png"]));
+	assert_eq!(image::open(dir.join("third.png")).unwrap().into_rgba8().dimensions(), (16, 16));
+
+	assert!(!paatool(&dir, &["decode", "--all", "-m", "2", "fixture_co.paa", "conflict.png"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn decode_mipmap_zero() {
+	let dir = temp_dir("decode-zero");
+	assert!(paatool(&dir, &["gen", "--size", "16x16", "x.paa"]));
+
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(&dir)
+		.args(["decode", "-m", "0", "x.paa", "x.png"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("indices start at 1"));
+	assert!(!dir.join("x.png").exists());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn mipmap_index_selects_mipmap() {
+	let dir = temp_dir("decode-index");
+	assert!(paatool(&dir, &["gen", "--format", "argb8888", "--size", "64x64", "x.paa"]));
+
+	assert!(paatool(&dir, &["decode", "-m", "3", "x.paa", "x.png"]));
+	assert_eq!(image::open(dir.join("x.png")).unwrap().into_rgba8().dimensions(), (16, 16));
+
+	assert!(paatool(&dir, &["dump-mipmap", "-m", "2", "x.paa", "x.bin"]));
+	assert_eq!(std::fs::read(dir.join("x.bin")).unwrap().len(), a3_paa::PaaType::Argb8888.predict_size(32, 32));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn decode_raw_and_dds() {
+	let dir = temp_dir("decode-raw");
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--size", "64x64", "--format", "dxt5", "x.paa"]));
+
+	assert!(paatool(&dir, &["decode", "-m", "2", "--raw", "x.bin", "--dds", "x.dds", "x.paa"]));
+	let raw = std::fs::read(dir.join("x.bin")).unwrap();
+	assert_eq!(raw.len(), a3_paa::PaaType::Dxt5.predict_size(32, 32));
+
+	let dds = ddsfile::Dds::read(std::fs::File::open(dir.join("x.dds")).unwrap()).unwrap();
+	assert_eq!(dds.get_d3d_format(), Some(ddsfile::D3DFormat::DXT5));
+	assert_eq!((dds.get_width(), dds.get_height(), dds.get_num_mipmap_levels()), (32, 32, 1));
+	assert_eq!(dds.data, raw);
+
+	// Either replaces the output image
+	assert!(!paatool(&dir, &["decode", "--raw", "y.bin", "x.paa", "y.png"]));
+	assert!(!paatool(&dir, &["decode", "--all", "--dds", "y.dds", "x.paa"]));
+	assert!(!dir.join("y.bin").exists() && !dir.join("y.png").exists());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn decode_color_managed() {
+	use a3_paa::imageops::color::srgb_to_linear;
+
+	let dir = temp_dir("decode-color");
+	let image = image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, y as u8 * 16, 0x80, 0xFF]));
+	image.save(dir.join("x_co.png")).unwrap();
+	image.save(dir.join("x_nohq.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "x_co.png", "x_co.paa"]));
+	assert!(paatool(&dir, &["encode", "x_nohq.png", "x_nohq.paa"]));
+
+	let is_srgb = |name: &str| {
+		let decoder = png::Decoder::new(std::fs::File::open(dir.join(name)).unwrap());
+		decoder.read_info().unwrap().info().srgb.is_some()
+	};
+
+	assert!(paatool(&dir, &["decode", "x_co.paa", "plain.png"]));
+	assert!(paatool(&dir, &["decode", "--srgb", "x_co.paa", "tagged.png"]));
+	assert!(paatool(&dir, &["decode", "--srgb", "x_nohq.paa", "normal.png"]));
+	assert!(!is_srgb("plain.png") && is_srgb("tagged.png") && !is_srgb("normal.png"));
+	assert_eq!(image::open(dir.join("tagged.png")).unwrap().into_rgba8(), image::open(dir.join("plain.png")).unwrap().into_rgba8());
+
+	// Linear output is EXR unless another format is given
+	assert!(paatool(&dir, &["decode", "--linear", "x_co.paa", "x_nohq.paa", "--out-dir", "linear"]));
+	let plain = image::open(dir.join("plain.png")).unwrap().into_rgba8();
+	let linear = image::open(dir.join("linear/x_co.exr")).unwrap().into_rgba32f();
+	let normal = image::open(dir.join("linear/x_nohq.exr")).unwrap().into_rgba32f();
+	assert!(paatool(&dir, &["decode", "x_nohq.paa", "normal_plain.png"]));
+	let normal_plain = image::open(dir.join("normal_plain.png")).unwrap().into_rgba8();
+
+	for ((p, l), (np, n)) in plain.pixels().zip(linear.pixels()).zip(normal_plain.pixels().zip(normal.pixels())) {
+		assert!((srgb_to_linear(f32::from(p.0[0]) / 255.0) - l.0[0]).abs() < 1e-5);
+		assert!((f32::from(np.0[0]) / 255.0 - n.0[0]).abs() < 1e-5);
+	};
+
+	assert!(!paatool(&dir, &["decode", "--srgb", "--format", "tiff", "x_co.paa", "x.tiff"]));
+	assert!(!paatool(&dir, &["decode", "--linear", "--format", "png", "x_co.paa", "x.png"]));
+	assert!(!paatool(&dir, &["decode", "--linear", "--srgb", "x_co.paa", "x.exr"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
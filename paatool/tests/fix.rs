@@ -0,0 +1,44 @@
+mod common;
+
+use a3_paa::{PaaEncoder, PaaImage, PaaType, Tagg, TextureEncodingSettings};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn fix_corrupt_mipmap() {
+	let dir = temp_dir("fix");
+
+	let image = image::RgbaImage::from_fn(128, 128, |x, y| image::Rgba([x as u8 * 2, y as u8 * 2, 0x40, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let mut data = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap().to_bytes().unwrap();
+
+	// Flag the uncompressed third mipmap as LZO-compressed, making its data
+	// an invalid LZO stream
+	let paa = PaaImage::from_bytes(&data).unwrap();
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+	data[offsets[2] as usize + 1] |= 0x80;
+	std::fs::write(dir.join("corrupt_co.paa"), &data).unwrap();
+
+	let corrupt = PaaImage::from_bytes(&data).unwrap();
+	assert!(corrupt.mipmaps[2].is_err());
+	assert!(corrupt.validate().is_err());
+
+	assert!(paatool(&dir, &["fix", "corrupt_co.paa", "fixed_co.paa"]));
+
+	let fixed = PaaImage::from_bytes(&std::fs::read(dir.join("fixed_co.paa")).unwrap()).unwrap();
+	assert!(fixed.validate().is_ok());
+	assert_eq!(fixed.mipmaps.iter().filter(|m| m.is_ok()).count(), corrupt.mipmaps.iter().filter(|m| m.is_ok()).count() + 1);
+	assert!(fixed.mipmaps.iter().flatten().all(|m| m.decode().is_ok()));
+
+	// A readable mipmap out of the chain is regenerated too (the last mipmap
+	// read is the terminator)
+	let mut misplaced = PaaImage::from_bytes(&std::fs::read(dir.join("fixed_co.paa")).unwrap()).unwrap();
+	misplaced.mipmaps[1] = misplaced.mipmaps[3].clone();
+	std::fs::write(dir.join("misplaced_co.paa"), misplaced.to_bytes().unwrap()).unwrap();
+	let output = paatool_stdout(&dir, &["fix", "misplaced_co.paa", "fixed_co.paa"]).unwrap();
+	assert!(output.ends_with(&format!(": 1 of {} mipmaps regenerated\n", misplaced.mipmaps.len() - 1)), "{output}");
+
+	assert!(!paatool(&dir, &["fix", "missing_co.paa", "fixed_co.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
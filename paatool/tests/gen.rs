@@ -0,0 +1,32 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn gen_checker_dxt1() {
+	let dir = temp_dir("gen");
+
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--size", "64x64", "--format", "dxt1", "--color", "#E04020", "--png", "checker.paa"]));
+	assert!(paatool(&dir, &["decode", "checker.paa", "decoded.png"]));
+
+	let source = image::open(dir.join("checker.png")).unwrap().into_rgba8();
+	let decoded = image::open(dir.join("decoded.png")).unwrap().into_rgba8();
+	assert_eq!(decoded.dimensions(), (64, 64));
+
+	for (x, y, pixel) in decoded.enumerate_pixels() {
+		let expected = if (x / 8 + y / 8) % 2 == 0 { [0xE0, 0x40, 0x20, 0xFF] } else { [0, 0, 0, 0xFF] };
+		assert_eq!(source.get_pixel(x, y).0, expected);
+		assert!(pixel.0.iter().zip(expected).all(|(a, b)| a.abs_diff(b) <= 8), "{x},{y}: {pixel:?}");
+	};
+
+	// Noise is deterministic for a given seed
+	for name in ["a.paa", "b.paa"] {
+		assert!(paatool(&dir, &["gen", "--pattern", "noise", "--seed", "7", "--format", "argb8888", name]));
+	};
+
+	assert_eq!(std::fs::read(dir.join("a.paa")).unwrap(), std::fs::read(dir.join("b.paa")).unwrap());
+	assert!(!paatool(&dir, &["gen", "--size", "64", "bad.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn hints_builtin() {
+	let dir = temp_dir("hints");
+
+	let nohq = paatool_stdout(&dir, &["hints", "NOHQ"]).unwrap();
+	assert!(nohq.starts_with("NOHQ: format=Dxt5, swizzle=<"));
+	assert!(nohq.contains("dynRange=false, mipmapFilter=NormalizeNormalMapAlpha, errorMetrics=Distance"));
+
+	// File names resolve as encode does, falling back to the default suffix
+	let co = paatool_stdout(&dir, &["hints", "co", "wall_co.paa", "detailmap.paa"]).unwrap();
+	let lines: Vec<&str> = co.lines().collect();
+	assert_eq!(lines.len(), 3);
+	assert!(lines.iter().all(|l| l.contains(": format=Dxt5, ") && l.contains("autoreduce") && l.ends_with("dynRange=unset, mipmapFilter=unset, errorMetrics=unset")));
+
+	let all = paatool_stdout(&dir, &["hints"]).unwrap();
+	assert!(all.lines().any(|l| l.starts_with("CO: ")));
+	assert!(all.lines().any(|l| l.starts_with("NOHQ: ")));
+
+	let json: serde_json::Value = serde_json::from_str(&paatool_stdout(&dir, &["hints", "--json", "CO", "x_nohq.png"]).unwrap()).unwrap();
+	assert_eq!(json[0]["name"], "CO");
+	assert_eq!(json[0]["settings"]["format"], "Dxt5");
+	assert_eq!(json[0]["settings"]["autoreduce"], true);
+	assert_eq!(json[1]["settings"]["format"], "Dxt5");
+
+	assert!(!paatool(&dir, &["hints", "NOSUCHSUFFIX"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn hints_missing_suffix_default_format() {
+	let dir = temp_dir("hints-default-format");
+	let config = "class TextureHints {\n\tclass normalmap {\n\t\tname = \"*_nohq.*\";\n\t\tformat = \"DXT5\";\n\t};\n};\n";
+	std::fs::write(dir.join("TexConvert.cfg"), config).unwrap();
+	image::RgbaImage::from_pixel(8, 8, image::Rgba([0x40, 0x80, 0xC0, 0xFF])).save(dir.join("light_lco.png")).unwrap();
+	image::RgbaImage::from_pixel(8, 8, image::Rgba([0x40, 0x80, 0xC0, 0xFF])).save(dir.join("plain.png")).unwrap();
+
+	// LCO is not in the config, but has a usual format
+	assert!(paatool(&dir, &["encode", "--hints", "TexConvert.cfg", "light_lco.png", "light_lco.paa"]));
+	let paa = a3_paa::PaaImage::from_bytes(&std::fs::read(dir.join("light_lco.paa")).unwrap()).unwrap();
+	assert_eq!(paa.paatype, a3_paa::PaaType::Argb8888);
+
+	assert!(!paatool(&dir, &["encode", "--hints", "TexConvert.cfg", "plain.png", "plain.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
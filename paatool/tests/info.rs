@@ -0,0 +1,143 @@
+mod common;
+
+use a3_paa::{Bgr888Pixel, PaaImage, PaaMipmap, PaaMipmapCompression, PaaPalette, PaaType, Tagg, Transparency};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn info_deep() {
+	let dir = temp_dir("info");
+
+	// The 256x256 mipmap is LZO-compressed
+	assert!(paatool(&dir, &["gen", "--pattern", "noise", "--size", "256x256", "--format", "dxt5", "good.paa"]));
+
+	let info = paatool_stdout(&dir, &["info", "-b", "--deep", "-S", "good.paa"]).unwrap();
+	let mipmaps: Vec<&str> = info.lines().filter(|l| l.starts_with("Mipmap #")).collect();
+	assert_eq!(mipmaps.len(), 7);
+	assert!(mipmaps.iter().all(|l| l.split(" sha256=").nth(1).map_or(false, |h| h.len() == 8) && l.contains("decode=OK")));
+	assert!(info.contains("Serialized back: identical"));
+	assert!(info.lines().any(|l| l.starts_with("Summary: DXT5 256x256, 7 mipmaps (lzo")), "{info}");
+
+	// Hashes cover decoded pixels, so they do not change with compression
+	assert!(paatool(&dir, &["optimize", "--lzo", "keep", "good.paa", "same.paa"]));
+	let same = paatool_stdout(&dir, &["info", "-b", "--deep", "same.paa"]).unwrap();
+	let hashes = |info: &str| info.lines().filter_map(|l| l.split_once("sha256=")).map(|(_, h)| h.to_owned()).collect::<Vec<_>>();
+	assert_eq!(hashes(&info), hashes(&same));
+
+	// Garble the middle of the LZO-compressed first mipmap
+	let mut bytes = std::fs::read(dir.join("good.paa")).unwrap();
+	let paa = PaaImage::from_bytes(&bytes).unwrap();
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+	let middle = (offsets[0] as usize + offsets[1] as usize) / 2;
+	bytes[middle..middle + 32].fill(0xFF);
+	std::fs::write(dir.join("corrupted.paa"), &bytes).unwrap();
+
+	let corrupted = paatool_stdout(&dir, &["info", "-b", "--deep", "corrupted.paa"]).unwrap();
+	let first = corrupted.lines().find(|l| l.starts_with("Mipmap #1")).unwrap();
+	assert!(first.contains("decode=ERROR"));
+	assert!(corrupted.lines().filter(|l| l.contains("decode=OK")).count() == 6);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn info_layout() {
+	let dir = temp_dir("info-layout");
+
+	assert!(paatool(&dir, &["gen", "--size", "8x8", "--format", "dxt1", "small.paa"]));
+	let bytes = std::fs::read(dir.join("small.paa")).unwrap();
+	let paa = PaaImage::from_bytes(&bytes).unwrap();
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+
+	let info = paatool_stdout(&dir, &["info", "-b", "--layout", "small.paa"]).unwrap();
+	let layout: Vec<&str> = info.lines().filter(|l| l.starts_with("Layout: ")).collect();
+	assert_eq!(layout.len(), paa.taggs.len() + 2);
+
+	// Mipmap data follows the 7-byte header at the OFFSTAGG offset
+	let first = offsets[0] + 7;
+	assert!(layout.iter().any(|l| l.starts_with("Layout: mipmap #1 ") && l.contains(&format!("offset={first} ")) && l.ends_with("length=32")));
+	assert!(layout.iter().any(|l| l.contains("tagg SFFO") && l.ends_with("length=64")));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn info_summary() {
+	let dir = temp_dir("info-summary");
+
+	assert!(paatool(&dir, &["gen", "--format", "dxt1", "--size", "64x64", "a.paa"]));
+	assert!(paatool(&dir, &["gen", "--format", "dxt1", "--size", "128x64", "b.paa"]));
+	assert!(paatool(&dir, &["gen", "--format", "argb8888", "--size", "64x64", "c.paa"]));
+
+	let mut flagged = PaaImage::from_bytes(&std::fs::read(dir.join("c.paa")).unwrap()).unwrap();
+	flagged.taggs.push(Tagg::Flag { transparency: Transparency::AlphaInterpolated });
+	std::fs::write(dir.join("c.paa"), flagged.to_bytes().unwrap()).unwrap();
+	std::fs::write(dir.join("garbage.paa"), b"not a PAA").unwrap();
+
+	// A parse failure still fails the batch, after the summary is printed
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(&dir)
+		.args(["info", "--summary", "--quiet", "--format", "json", "a.paa", "b.paa", "c.paa", "garbage.paa"])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(2));
+
+	let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+	assert_eq!(summary["files"], 4);
+	assert_eq!(summary["parse_failures"], 1);
+	assert_eq!(summary["paatypes"]["Dxt1"], 2);
+	assert_eq!(summary["paatypes"]["Argb8888"], 1);
+	assert_eq!(summary["first_mipmap_dimensions"]["64x64"], 2);
+	assert_eq!(summary["first_mipmap_dimensions"]["128x64"], 1);
+	assert_eq!(summary["missing_flag"], 2);
+
+	let text = paatool_stdout(&dir, &["info", "--summary", "a.paa", "b.paa"]).unwrap();
+	assert!(text.lines().any(|l| l.starts_with("a.paa: PaaType: Dxt1")));
+	assert!(text.contains("Files: 2 (0 failed to parse)"));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn info_unexpected_compression() {
+	let dir = temp_dir("info-compression");
+	let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/../a3-paa/tests/reference/fixtures/unpaired/lzss_dxt5.paa");
+	std::fs::copy(fixture, dir.join("lzss.paa")).unwrap();
+
+	// LZSS on DXTn is read, but flagged
+	let info = paatool_stdout(&dir, &["info", "-b", "--deep", "lzss.paa"]).unwrap();
+	let first = info.lines().find(|l| l.starts_with("Mipmap #1")).unwrap();
+	assert!(first.starts_with("Mipmap #1, 8x8 [Lzss] (expected Uncompressed), size=64") && first.contains("decode=OK"), "{first}");
+
+	assert!(paatool(&dir, &["gen", "--size", "16x16", "--format", "dxt5", "good.paa"]));
+	let good = paatool_stdout(&dir, &["info", "-b", "good.paa"]).unwrap();
+	assert!(!good.contains("expected"), "{good}");
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn info_index_palette() {
+	let dir = temp_dir("info-palette");
+
+	let palette = PaaImage {
+		paatype: PaaType::IndexPalette,
+		palette: Some(PaaPalette::with_pixels(&[Bgr888Pixel::default(); 2]).unwrap()),
+		mipmaps: vec![Ok(PaaMipmap { width: 4, height: 4, paatype: PaaType::IndexPalette, compression: PaaMipmapCompression::RleBlocks, data: vec![1; 16], ..Default::default() })],
+		..Default::default()
+	};
+	std::fs::write(dir.join("palette.paa"), palette.to_bytes().unwrap()).unwrap();
+
+	// Palette mipmaps cannot be decoded, which only --deep reports
+	let info = paatool_stdout(&dir, &["info", "-b", "palette.paa"]).unwrap();
+	assert!(info.lines().any(|l| l.starts_with("Summary: INDEXPALETTE 4x4, 1 mipmaps") && l.contains(", opaque")), "{info}");
+
+	let deep = paatool_stdout(&dir, &["info", "-b", "--deep", "palette.paa"]).unwrap();
+	assert!(deep.lines().any(|l| l.starts_with("Mipmap #1") && l.contains("decode=ERROR")), "{deep}");
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
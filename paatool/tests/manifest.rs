@@ -0,0 +1,79 @@
+mod common;
+
+use a3_paa::{PaaImage, Tagg};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn manifest_skip_unchanged() {
+	let dir = temp_dir("manifest");
+	std::fs::create_dir_all(dir.join("src")).unwrap();
+
+	for (name, shade) in [("a_co.png", 0x20), ("b_co.png", 0x80)] {
+		let image = image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([shade, x as u8 * 16, y as u8 * 16, 0xFF]));
+		image.save(dir.join("src").join(name)).unwrap();
+	};
+
+	let encode = ["encode", "--manifest", "manifest.json", "--skip-unchanged", "--out-dir", "out", "src"];
+	assert_eq!(paatool_stdout(&dir, &encode).unwrap().trim(), "2 encoded, 0 skipped");
+	assert_eq!(paatool_stdout(&dir, &encode).unwrap().trim(), "0 encoded, 2 skipped");
+
+	// Changed sources and missing PAAs are encoded again
+	image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([0, x as u8 * 16, y as u8 * 16, 0xFF])).save(dir.join("src/a_co.png")).unwrap();
+	std::fs::remove_file(dir.join("out/b_co.paa")).unwrap();
+	assert_eq!(paatool_stdout(&dir, &encode).unwrap().trim(), "2 encoded, 0 skipped");
+
+	let manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+	let entry = &manifest["entries"]["out/a_co.paa"];
+	assert_eq!(entry["paatype"], "Dxt5");
+	assert_eq!((entry["width"].as_u64(), entry["height"].as_u64()), (Some(16), Some(16)));
+	assert_eq!(entry["source_sha256"].as_str().map(str::len), Some(64));
+	assert_eq!(entry["content_sha256"].as_str().map(str::len), Some(64));
+
+	// Regenerating the manifest keeps source hashes, so nothing is re-encoded
+	assert!(paatool(&dir, &["manifest", "-o", "manifest.json", "out"]));
+	let regenerated: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+	assert_eq!(regenerated["entries"]["out/b_co.paa"]["pixels_sha256"], manifest["entries"]["out/b_co.paa"]["pixels_sha256"]);
+	assert_eq!(paatool_stdout(&dir, &encode).unwrap().trim(), "0 encoded, 2 skipped");
+
+	// Changed settings are encoded again, even with unchanged sources
+	let dxt1 = ["encode", "--manifest", "manifest.json", "--skip-unchanged", "--format", "dxt1", "--out-dir", "out", "src"];
+	assert_eq!(paatool_stdout(&dir, &dxt1).unwrap().trim(), "2 encoded, 0 skipped");
+	assert_eq!(paatool_stdout(&dir, &dxt1).unwrap().trim(), "0 encoded, 2 skipped");
+	assert_eq!(paatool_stdout(&dir, &encode).unwrap().trim(), "2 encoded, 0 skipped");
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn manifest_broken_mipmap() {
+	let dir = temp_dir("manifest-broken");
+	std::fs::create_dir_all(dir.join("paas")).unwrap();
+
+	assert!(paatool(&dir, &["gen", "--format", "dxt1", "--size", "128x128", "paas/good.paa"]));
+	assert!(paatool(&dir, &["gen", "--format", "dxt1", "--size", "128x128", "paas/broken.paa"]));
+
+	// Flag the uncompressed third mipmap as LZO-compressed, making its data
+	// an invalid LZO stream
+	let mut data = std::fs::read(dir.join("paas/broken.paa")).unwrap();
+	let paa = PaaImage::from_bytes(&data).unwrap();
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+	data[offsets[2] as usize + 1] |= 0x80;
+	std::fs::write(dir.join("paas/broken.paa"), &data).unwrap();
+
+	// The broken PAA is recorded with its error, and fails the run
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(&dir)
+		.args(["manifest", "-o", "manifest.json", "paas"])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(2));
+
+	let manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+	assert_eq!(manifest["entries"]["paas/good.paa"]["content_sha256"].as_str().map(str::len), Some(64));
+	assert!(manifest["entries"]["paas/good.paa"].get("error").is_none());
+	assert!(manifest["entries"]["paas/broken.paa"]["error"].as_str().map_or(false, |e| !e.is_empty()));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
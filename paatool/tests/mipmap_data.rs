@@ -0,0 +1,45 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn dump_and_inject_mipmap() {
+	let dir = temp_dir("mipmap-data");
+	let read = |name: &str| std::fs::read(dir.join(name)).unwrap();
+
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--format", "dxt1", "small.paa"]));
+
+	// Injecting dumped data changes nothing
+	assert!(paatool(&dir, &["dump-mipmap", "-m", "2", "small.paa", "mip2.bin"]));
+	assert_eq!(read("mip2.bin").len(), 32 * 32 / 2);
+	assert!(paatool(&dir, &["inject-mipmap", "-m", "2", "-o", "same.paa", "small.paa", "mip2.bin"]));
+	assert_eq!(read("same.paa"), read("small.paa"));
+
+	// All-zero DXT1 blocks decode to opaque black
+	std::fs::write(dir.join("black.bin"), vec![0u8; 32 * 32 / 2]).unwrap();
+	assert!(paatool(&dir, &["inject-mipmap", "-m", "2", "-o", "black.paa", "small.paa", "black.bin"]));
+	assert!(paatool(&dir, &["decode", "-m", "2", "black.paa", "black.png"]));
+	assert!(image::open(dir.join("black.png")).unwrap().into_rgba8().pixels().all(|p| p.0 == [0, 0, 0, 0xFF]));
+	assert!(!paatool(&dir, &["inject-mipmap", "-m", "1", "-o", "short.paa", "small.paa", "black.bin"]));
+
+	// Raw LZO payloads of another texture are spliced as is
+	assert!(paatool(&dir, &["gen", "--pattern", "noise", "--size", "256x256", "--seed", "1", "a.paa"]));
+	assert!(paatool(&dir, &["gen", "--pattern", "noise", "--size", "256x256", "--seed", "2", "b.paa"]));
+	assert!(paatool(&dir, &["dump-mipmap", "--compressed", "a.paa", "a1.bin"]));
+	assert!(paatool(&dir, &["inject-mipmap", "--compressed", "-o", "a_same.paa", "a.paa", "a1.bin"]));
+	assert_eq!(read("a_same.paa"), read("a.paa"));
+
+	assert!(paatool(&dir, &["dump-mipmap", "--compressed", "b.paa", "b1.bin"]));
+	assert!(paatool(&dir, &["inject-mipmap", "--compressed", "-o", "mixed.paa", "a.paa", "b1.bin"]));
+	assert!(paatool(&dir, &["verify", "mixed.paa"]));
+
+	for (paa, mip, png) in [("mixed.paa", "1", "mixed1.png"), ("b.paa", "1", "b1.png"), ("mixed.paa", "2", "mixed2.png"), ("a.paa", "2", "a2.png")] {
+		assert!(paatool(&dir, &["decode", "-m", mip, paa, png]));
+	};
+
+	assert_eq!(read("mixed1.png"), read("b1.png"));
+	assert_eq!(read("mixed2.png"), read("a2.png"));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
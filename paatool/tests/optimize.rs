@@ -0,0 +1,78 @@
+mod common;
+
+use a3_paa::{PaaDecoder, PaaEncoder, PaaImage, PaaMipmapCompression, PaaType, Tagg, TextureEncodingSettings};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn optimize_uncompressed_dxt() {
+	let dir = temp_dir("optimize");
+
+	let image = image::RgbaImage::from_fn(256, 256, |x, _| image::Rgba([if x < 128 { 0x20 } else { 0xE0 }, 0x80, 0x40, 0xFF]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Dxt1).build();
+	let mut paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+
+	for mipmap in paa.mipmaps.iter_mut().flatten() {
+		mipmap.compression = PaaMipmapCompression::Uncompressed;
+	};
+
+	let duplicate = paa.taggs.iter().find(|t| !matches!(t, Tagg::Offs { .. })).unwrap().clone();
+	paa.taggs.push(duplicate);
+	paa.trailer = PaaImage::TERMINATOR.to_vec();
+	std::fs::write(dir.join("bloated_co.paa"), paa.to_bytes_exact(true).unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["optimize", "bloated_co.paa", "optimized_co.paa"]));
+
+	let before = std::fs::read(dir.join("bloated_co.paa")).unwrap();
+	let after = std::fs::read(dir.join("optimized_co.paa")).unwrap();
+	assert!(after.len() < before.len());
+
+	let (before, after) = (PaaImage::from_bytes(&before).unwrap(), PaaImage::from_bytes(&after).unwrap());
+	assert_eq!(after.taggs.len(), before.taggs.len() - 1);
+	assert_eq!(after.mipmaps.len(), before.mipmaps.len());
+
+	let (before, after) = (PaaDecoder::with_paa(before), PaaDecoder::with_paa(after));
+
+	for index in 0..7 {
+		assert_eq!(before.decode_nth(index).unwrap(), after.decode_nth(index).unwrap());
+	};
+
+	assert!(paatool(&dir, &["optimize", "--max-mips", "2", "--lzo", "keep", "bloated_co.paa", "truncated_co.paa"]));
+	let truncated = PaaImage::from_bytes(&std::fs::read(dir.join("truncated_co.paa")).unwrap()).unwrap();
+	assert_eq!(truncated.mipmaps.iter().filter(|m| m.is_ok()).count(), 2);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn optimize_keeps_content_hash() {
+	let dir = temp_dir("optimize-hash");
+
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--size", "256x256", "--format", "dxt1", "lzo.paa"]));
+	let mut paa = PaaImage::from_bytes(&std::fs::read(dir.join("lzo.paa")).unwrap()).unwrap();
+
+	for mipmap in paa.mipmaps.iter_mut().flatten() {
+		mipmap.compression = PaaMipmapCompression::Uncompressed;
+	};
+
+	std::fs::write(dir.join("uncompressed.paa"), paa.to_bytes().unwrap()).unwrap();
+	assert!(paatool(&dir, &["optimize", "uncompressed.paa", "optimized.paa"]));
+
+	let before = std::fs::read(dir.join("uncompressed.paa")).unwrap();
+	let after = std::fs::read(dir.join("optimized.paa")).unwrap();
+	assert_ne!(before, after);
+
+	let (before, after) = (PaaImage::from_bytes(&before).unwrap(), PaaImage::from_bytes(&after).unwrap());
+	assert_eq!(before.content_hash().unwrap(), after.content_hash().unwrap());
+
+	let content_hash = |name: &str| {
+		let info = paatool_stdout(&dir, &["info", "-b", "--deep", name]).unwrap();
+		info.lines().find_map(|l| l.strip_prefix("Content hash: ")).map(str::to_owned)
+	};
+
+	assert_eq!(content_hash("uncompressed.paa").map(|h| h.len()), Some(64));
+	assert_eq!(content_hash("uncompressed.paa"), content_hash("optimized.paa"));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
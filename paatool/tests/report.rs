@@ -0,0 +1,64 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::temp_dir;
+
+
+fn exit_code(dir: &Path, args: &[&str]) -> i32 {
+	Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(dir)
+		.args(args)
+		.status()
+		.unwrap()
+		.code()
+		.unwrap()
+}
+
+
+fn read_report(dir: &Path, name: &str) -> Vec<serde_json::Value> {
+	serde_json::from_slice(&std::fs::read(dir.join(name)).unwrap()).unwrap()
+}
+
+
+#[test]
+fn exit_codes_and_error_report() {
+	let dir = temp_dir("report");
+
+	image::RgbaImage::from_pixel(8, 8, image::Rgba([0x40, 0x80, 0xC0, 0xFF])).save(dir.join("good_co.png")).unwrap();
+	std::fs::write(dir.join("broken_co.png"), b"not a PNG").unwrap();
+	std::fs::write(dir.join("broken.paa"), b"not a PAA").unwrap();
+
+	assert_eq!(exit_code(&dir, &["encode", "--error-report", "ok.json", "good_co.png", "good_co.paa"]), 0);
+	assert!(read_report(&dir, "ok.json").is_empty());
+
+	// Some inputs failed, others succeeded
+	assert_eq!(exit_code(&dir, &["encode", "--error-report", "mixed.json", "--out-dir", "out", "good_co.png", "broken_co.png"]), 2);
+	let report = read_report(&dir, "mixed.json");
+	assert_eq!(report.len(), 1);
+	assert_eq!(report[0]["path"], "broken_co.png");
+	assert_eq!(report[0]["stage"], "parse");
+	assert!(report[0]["error"].as_str().unwrap().contains("Failed to open input IMG"));
+	assert!(dir.join("out/good_co.paa").exists());
+
+	assert_eq!(exit_code(&dir, &["decode", "--error-report", "decode.json", "--out-dir", "out", "good_co.paa", "broken.paa"]), 2);
+	assert_eq!(read_report(&dir, "decode.json")[0]["path"], "broken.paa");
+
+	assert_eq!(exit_code(&dir, &["info", "--error-report", "info.json", "good_co.paa", "broken.paa"]), 2);
+	assert_eq!(read_report(&dir, "info.json")[0]["stage"], "parse");
+
+	// Every input failed
+	assert_eq!(exit_code(&dir, &["decode", "--error-report", "failed.json", "broken.paa", "broken.png"]), 1);
+	assert_eq!(read_report(&dir, "failed.json")[0]["path"], "broken.paa");
+
+	// Usage and configuration errors
+	assert_eq!(exit_code(&dir, &["encode", "--no-such-flag", "good_co.png", "good_co.paa"]), 1);
+	assert_eq!(exit_code(&dir, &["encode", "--error-report", "config.json", "--hints", "missing.cfg", "good_co.png", "good_co.paa"]), 1);
+	let report = read_report(&dir, "config.json");
+	assert!(report[0]["path"].is_null() && report[0]["stage"].is_null());
+
+	assert_eq!(exit_code(&dir, &["--help"]), 0);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::temp_dir;
+
+
+/// Run paatool in `dir` with `input` on standard input, and return its
+/// standard output, or [`None`] if it failed.
+fn pipe(dir: &std::path::Path, args: &[&str], input: &[u8]) -> Option<Vec<u8>> {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(dir)
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.spawn()
+		.unwrap();
+
+	child.stdin.take().unwrap().write_all(input).unwrap();
+	let output = child.wait_with_output().unwrap();
+	output.status.success().then(|| output.stdout)
+}
+
+
+#[test]
+fn pipe_encode_decode() {
+	let dir = temp_dir("stdio");
+
+	let image = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 0x80, 0xFF]));
+	image.save(dir.join("source.png")).unwrap();
+	let png = std::fs::read(dir.join("source.png")).unwrap();
+
+	let paa = pipe(&dir, &["encode", "-S", "CA", "-", "-"], &png).unwrap();
+	assert_eq!(&paa[..2], &[0x05, 0xFF]);
+
+	let decoded_png = pipe(&dir, &["decode", "-", "-"], &paa).unwrap();
+	let decoded = image::load_from_memory(&decoded_png).unwrap().into_rgba8();
+	assert_eq!(decoded.dimensions(), (32, 32));
+
+	for (a, b) in decoded.pixels().zip(image.pixels()) {
+		assert!(a.0.iter().zip(b.0).all(|(a, b)| a.abs_diff(b) <= 8));
+	};
+
+	let info = pipe(&dir, &["info", "-b", "-"], &paa).unwrap();
+	assert!(String::from_utf8(info).unwrap().contains("PaaType: Dxt5"));
+
+	assert!(pipe(&dir, &["decode", "--all", "-", "-"], &paa).is_none());
+	assert!(pipe(&dir, &["decode", "--out-dir", "out", "-"], &paa).is_none());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use a3_paa::{Bgra8888Pixel, PaaImage, Tagg, Transparency};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn tagg_in_place() {
+	let dir = temp_dir("tagg");
+
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--size", "64x64", "checker.paa"]));
+	let original = std::fs::read(dir.join("checker.paa")).unwrap();
+
+	// AVGC is always 4 bytes, so only its payload changes
+	let out = paatool_stdout(&dir, &["tagg", "--avgc", "#102030", "checker.paa"]).unwrap();
+	assert!(out.contains("CGVA patched in place"));
+
+	let patched = std::fs::read(dir.join("checker.paa")).unwrap();
+	let changed: Vec<usize> = original.iter().zip(&patched).enumerate().filter(|(_, (a, b))| a != b).map(|(i, _)| i).collect();
+	assert_eq!(patched.len(), original.len());
+	assert!(!changed.is_empty() && changed.iter().all(|i| (changed[0]..changed[0] + 4).contains(i)));
+
+	let avgc = Bgra8888Pixel { r: 0x10, g: 0x20, b: 0x30, a: 0xFF };
+	assert!(PaaImage::from_bytes(&patched).unwrap().taggs.contains(&Tagg::Avgc { rgba: avgc }));
+
+	// gen writes no FLAG, so adding one rewrites the file
+	assert!(paatool(&dir, &["--dry-run", "tagg", "--flag", "not-interpolated", "checker.paa"]));
+	assert_eq!(std::fs::read(dir.join("checker.paa")).unwrap(), patched);
+
+	let out = paatool_stdout(&dir, &["tagg", "--flag", "not-interpolated", "checker.paa"]).unwrap();
+	assert!(out.contains("GALF set by rewriting the file"));
+
+	let rewritten = PaaImage::from_bytes(&std::fs::read(dir.join("checker.paa")).unwrap()).unwrap();
+	assert!(rewritten.taggs.contains(&Tagg::Flag { transparency: Transparency::AlphaNotInterpolated }));
+	assert!(rewritten.taggs.contains(&Tagg::Avgc { rgba: avgc }));
+
+	let out = paatool_stdout(&dir, &["tagg", "--flag", "none", "checker.paa"]).unwrap();
+	assert!(out.contains("GALF patched in place"));
+
+	assert!(!paatool(&dir, &["tagg", "checker.paa"]));
+	assert!(!paatool(&dir, &["tagg", "--maxc", "#GGGGGG", "checker.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn thumbnail_level() {
+	let dir = temp_dir("thumbnail");
+	assert!(paatool(&dir, &["gen", "--pattern", "gradient", "--size", "512x256", "--format", "dxt1", "wide.paa"]));
+
+	// 512x256, 256x128, 128x64, ...: the third mipmap is the smallest reaching 100
+	let output = paatool_stdout(&dir, &["thumbnail", "--size", "100", "wide.paa", "thumb.png"]).unwrap();
+	assert!(output.contains("mipmap #3 (128x64) -> 100x50"));
+	assert_eq!(image::open(dir.join("thumb.png")).unwrap().into_rgba8().dimensions(), (100, 50));
+
+	let output = paatool_stdout(&dir, &["thumbnail", "--size", "128", "wide.paa", "exact.png"]).unwrap();
+	assert!(output.contains("mipmap #3 (128x64) -> 128x64"));
+
+	// No mipmap is large enough, so the largest one is used as is
+	let output = paatool_stdout(&dir, &["thumbnail", "--size", "1024", "wide.paa", "large.png"]).unwrap();
+	assert!(output.contains("mipmap #1 (512x256) -> 512x256"));
+	assert_eq!(image::open(dir.join("large.png")).unwrap().into_rgba8().dimensions(), (512, 256));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use a3_paa::{PaaEncoder, PaaType, TextureEncodingSettings};
+use common::{paatool, paatool_stdout, temp_dir};
+
+
+#[test]
+fn transcode_argb4444_to_dxt5() {
+	let dir = temp_dir("transcode");
+
+	let image = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 0x80, if x < 16 { 0x80 } else { 0xFF }]));
+	let settings = TextureEncodingSettings::builder().format(PaaType::Argb4444).build();
+	let paa = PaaEncoder::with_image_and_settings(image, settings).encode().unwrap();
+	std::fs::write(dir.join("ui_ca.paa"), paa.to_bytes().unwrap()).unwrap();
+
+	assert!(paatool(&dir, &["transcode", "--to", "dxt5", "ui_ca.paa", "ui_dxt5_ca.paa"]));
+
+	let info = paatool_stdout(&dir, &["info", "-b", "ui_dxt5_ca.paa"]).unwrap();
+	assert!(info.contains("PaaType: Dxt5"));
+	assert!(info.contains("Avgc"));
+	assert!(info.contains("Flag"));
+	assert_eq!(info.lines().filter(|l| l.starts_with("Mipmap #")).count(), 4);
+	assert!(!info.contains("ERROR"));
+
+	assert!(paatool(&dir, &["transcode", "--to", "DXT1", "--alpha-threshold", "128", "ui_ca.paa", "ui_dxt1_ca.paa"]));
+	assert!(paatool(&dir, &["verify", "ui_dxt1_ca.paa"]));
+	assert!(!paatool(&dir, &["transcode", "--to", "indexpalette", "ui_ca.paa", "palette_ca.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use a3_paa::imageops::image_difference;
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn decode_unswizzle_nohq() {
+	let dir = temp_dir("unswizzle");
+
+	// A normal map, with a specular alpha channel
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([0x40 + x as u8 * 2, 0x40 + y as u8 * 2, 0xF0, 0xC0]));
+	image.save(dir.join("wall_nohq.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "wall_nohq.png", "wall_nohq.paa"]));
+
+	assert!(paatool(&dir, &["decode", "wall_nohq.paa", "swizzled.png"]));
+	assert!(paatool(&dir, &["decode", "--unswizzle", "wall_nohq.paa", "restored.png"]));
+	assert!(paatool(&dir, &["decode", "--unswizzle", "--opaque", "wall_nohq.paa", "opaque.png"]));
+
+	let swizzled = image::open(dir.join("swizzled.png")).unwrap().into_rgba8();
+	let restored = image::open(dir.join("restored.png")).unwrap().into_rgba8();
+	let opaque = image::open(dir.join("opaque.png")).unwrap().into_rgba8();
+
+	// Every NOHQ channel is invertible, up to DXT5 compression error
+	let diff = image_difference(&image, &restored).unwrap();
+	assert!(diff.max_error.iter().all(|e| *e <= 16), "{diff:?}");
+	assert!(image_difference(&image, &swizzled).unwrap().max_error[3] > 0x80);
+	assert!(opaque.pixels().all(|p| p.0[3] == 0xFF));
+
+	// Unswizzling a texture without SWIZTAGG only warns
+	image.save(dir.join("wall_co.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "wall_co.png", "wall_co.paa"]));
+	assert!(paatool(&dir, &["decode", "--unswizzle", "wall_co.paa", "plain.png"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use a3_paa::{PaaError, PaaImage, PaaMipmapCompression, Tagg};
+use common::{paatool, temp_dir};
+
+
+#[test]
+fn verify_good_and_corrupted() {
+	let dir = temp_dir("verify");
+
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+	image.save(dir.join("gradient_co.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "gradient_co.png", "good_co.paa"]));
+
+	assert!(paatool(&dir, &["verify", "good_co.paa"]));
+	assert!(paatool(&dir, &["verify", "--fast", "good_co.paa"]));
+
+	// Widen the second mipmap, so it no longer follows the first one
+	let mut bytes = std::fs::read(dir.join("good_co.paa")).unwrap();
+	let paa = PaaImage::from_bytes(&bytes).unwrap();
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+	let offset = offsets[1] as usize;
+	bytes[offset] = bytes[offset].wrapping_add(4);
+	std::fs::write(dir.join("corrupted_co.paa"), &bytes).unwrap();
+
+	assert!(!paatool(&dir, &["verify", "--fast", "corrupted_co.paa"]));
+	assert!(!paatool(&dir, &["verify", "good_co.paa", "corrupted_co.paa"]));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn verify_lzss_checksum() {
+	let dir = temp_dir("verify-checksum");
+
+	let image = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([x as u8 * 4, y as u8 * 4, 0x80, 0xFF]));
+	image.save(dir.join("gradient_ca.png")).unwrap();
+	assert!(paatool(&dir, &["encode", "--format", "argb4444", "gradient_ca.png", "good_ca.paa"]));
+	assert!(paatool(&dir, &["verify", "--fast", "good_ca.paa"]));
+
+	// Flip the first literal of the first mipmap's LZSS data, after its
+	// block header and first flag byte; the data keeps its length
+	let mut bytes = std::fs::read(dir.join("good_ca.paa")).unwrap();
+	let paa = PaaImage::from_bytes(&bytes).unwrap();
+	assert_eq!(paa.mipmaps[0].as_ref().unwrap().compression, PaaMipmapCompression::Lzss);
+	let offsets = paa.taggs.iter().find_map(|t| if let Tagg::Offs { offsets } = t { Some(offsets.clone()) } else { None }).unwrap();
+	let offset = offsets[0] as usize + 8;
+	bytes[offset] = !bytes[offset];
+	std::fs::write(dir.join("corrupted_ca.paa"), &bytes).unwrap();
+
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_paatool"))
+		.current_dir(&dir)
+		.args(["verify", "--fast", "corrupted_ca.paa"])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(1));
+
+	let expected = format!("corrupted_ca.paa: FAIL checksums: Mipmap #1: {}", PaaError::LzssWrongChecksum);
+	assert!(String::from_utf8_lossy(&output.stdout).lines().any(|l| l.starts_with(&expected)));
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{paatool, temp_dir};
+
+
+/// Files in `dir` other than `keep`
+fn leftovers(dir: &std::path::Path, keep: &[&str]) -> Vec<String> {
+	std::fs::read_dir(dir).unwrap()
+		.map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+		.filter(|name| !keep.contains(&name.as_str()))
+		.collect()
+}
+
+
+#[test]
+fn dry_run() {
+	let dir = temp_dir("dry-run");
+
+	assert!(paatool(&dir, &["--dry-run", "gen", "--pattern", "checker", "--size", "64x64", "--png", "checker.paa"]));
+	assert!(leftovers(&dir, &[]).is_empty());
+
+	assert!(paatool(&dir, &["gen", "--pattern", "checker", "--size", "64x64", "checker.paa"]));
+	let original = std::fs::read(dir.join("checker.paa")).unwrap();
+	assert!(paatool(&dir, &["transcode", "--dry-run", "--to", "argb8888", "checker.paa", "checker.paa"]));
+	assert_eq!(std::fs::read(dir.join("checker.paa")).unwrap(), original);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn failed_write_leaves_nothing() {
+	let dir = temp_dir("failed-write");
+
+	// The PAA cannot replace a directory, so the final rename fails
+	std::fs::create_dir(dir.join("taken.paa")).unwrap();
+	assert!(!paatool(&dir, &["gen", "--pattern", "checker", "--size", "64x64", "taken.paa"]));
+	assert!(dir.join("taken.paa").is_dir());
+	assert_eq!(leftovers(&dir, &["taken.paa"]), Vec::<String>::new());
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+
+		let readonly = dir.join("readonly");
+		std::fs::create_dir(&readonly).unwrap();
+		std::fs::set_permissions(&readonly, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+		// Permissions do not stop root
+		if std::fs::write(readonly.join("probe"), b"").is_err() {
+			assert!(!paatool(&readonly, &["gen", "--pattern", "checker", "--size", "64x64", "checker.paa"]));
+			assert!(leftovers(&readonly, &[]).is_empty());
+		};
+
+		std::fs::set_permissions(&readonly, std::fs::Permissions::from_mode(0o755)).unwrap();
+	};
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}